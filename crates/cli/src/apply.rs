@@ -1,10 +1,18 @@
+use crate::dirs;
 use crate::fs_apply;
+use crate::journal;
 use crate::paths;
+use crate::preflight;
+use crate::safety;
+use crate::sessions;
+use crate::trash_store;
+use crate::undo;
 use anyhow::Result;
 use organizer_core::config::SafetyConfig;
+use organizer_core::fts;
 use serde::Serialize;
 use serde_json::Value;
-use sqlx::Row;
+use sqlx::{Row, SqlitePool};
 use std::io::{self, Write};
 use std::path::PathBuf;
 use storage;
@@ -19,6 +27,8 @@ pub struct ActionView {
     pub rule: Option<String>,
     pub error: Option<String>,
     pub backup: Option<String>,
+    /// The `apply_sessions` row of the run that processed this action.
+    pub session_id: Option<i64>,
 }
 
 fn extract_dest(payload: &str) -> Option<String> {
@@ -35,11 +45,42 @@ pub async fn apply_actions(
     safety: &SafetyConfig,
     conflict: &str,
 ) -> Result<Vec<ActionView>> {
+    safety::check_conflict_policy(safety, conflict)?;
     let pool = storage::connect(db_path).await?;
+    if dry_run {
+        return run_actions(pool, true, force, ids, safety, conflict).await;
+    }
+    // One apply at a time: recovery must not touch the journal of a run still in progress.
+    let lock = journal::lock(&pool).await?;
+    let trash_dir = safety.trash_dir.as_deref().map(std::path::Path::new);
+    let result = match journal::recover(&pool, trash_dir).await {
+        Ok(recovered) => {
+            if recovered.total() > 0 {
+                println!(
+                    "recovered interrupted apply: completed={}, rolled_back={}, failed={}",
+                    recovered.completed, recovered.rolled_back, recovered.failed
+                );
+            }
+            run_actions(pool, false, force, ids, safety, conflict).await
+        }
+        Err(e) => Err(e),
+    };
+    lock.release().await?;
+    result
+}
+
+async fn run_actions(
+    pool: SqlitePool,
+    dry_run: bool,
+    force: bool,
+    ids: Option<&str>,
+    safety: &SafetyConfig,
+    conflict: &str,
+) -> Result<Vec<ActionView>> {
     let rows = if let Some(id_list) = ids {
         let placeholders: Vec<String> = id_list.split(',').map(|_| "?".into()).collect();
         let sql = format!(
-            "SELECT actions.id, files.path, actions.kind, actions.payload_json, actions.status, actions.backup_path FROM actions JOIN files ON files.id = actions.file_id WHERE actions.status = 'planned' AND actions.id IN ({})",
+            "SELECT actions.id, COALESCE(files.path, directories.path), actions.kind, actions.payload_json, actions.status, actions.backup_path FROM actions LEFT JOIN files ON files.id = actions.file_id LEFT JOIN directories ON directories.id = actions.dir_id WHERE actions.status = 'planned' AND actions.id IN ({}) ORDER BY actions.id",
             placeholders.join(",")
         );
         let mut query = sqlx::query(&sql);
@@ -48,7 +89,7 @@ pub async fn apply_actions(
         }
         query.fetch_all(&pool).await?
     } else {
-        sqlx::query("SELECT actions.id, files.path, actions.kind, actions.payload_json, actions.status, actions.backup_path FROM actions JOIN files ON files.id = actions.file_id WHERE actions.status = 'planned'")
+        sqlx::query("SELECT actions.id, COALESCE(files.path, directories.path), actions.kind, actions.payload_json, actions.status, actions.backup_path FROM actions LEFT JOIN files ON files.id = actions.file_id LEFT JOIN directories ON directories.id = actions.dir_id WHERE actions.status = 'planned' ORDER BY actions.id")
             .fetch_all(&pool)
             .await?
     };
@@ -68,7 +109,59 @@ pub async fn apply_actions(
         }
     }
 
+    let session_id = if !dry_run && !rows.is_empty() {
+        Some(sessions::start(&pool).await?)
+    } else {
+        None
+    };
+    let (views, success, failed) =
+        match execute_rows(&pool, rows, session_id, dry_run, safety, conflict).await {
+            Ok(done) => done,
+            Err(e) => {
+                // Leave no session `running` behind.
+                if let Some(sid) = session_id {
+                    let _ = sessions::fail(&pool, sid).await;
+                }
+                return Err(e);
+            }
+        };
+    if let Some(sid) = session_id {
+        sessions::finish(&pool, sid).await?;
+        println!("apply session {}", sid);
+    }
+    if !dry_run {
+        println!("apply summary: success={}, failed={}", success, failed);
+    } else {
+        println!("dry-run: {} actions listed", views.len());
+    }
+
+    Ok(views)
+}
+
+/// Runs the selected actions of a session; the caller closes the session either way.
+async fn execute_rows(
+    pool: &SqlitePool,
+    rows: Vec<sqlx::sqlite::SqliteRow>,
+    session_id: Option<i64>,
+    dry_run: bool,
+    safety: &SafetyConfig,
+    conflict: &str,
+) -> Result<(Vec<ActionView>, usize, usize)> {
+    let os_trash = if safety.use_os_trash && !dry_run {
+        Some(fs_apply::xdg_trash_dir().ok_or_else(|| {
+            anyhow::anyhow!("use_os_trash is set but neither XDG_DATA_HOME nor HOME is available")
+        })?)
+    } else {
+        None
+    };
+    let policy = paths::PathPolicy::new(&safety.allow_paths, &safety.deny_paths)?;
+    // Where merges put the removed copy, which needs room for it.
+    let merge_backup_dir = os_trash
+        .clone()
+        .or_else(|| safety.trash_dir.as_ref().map(PathBuf::from));
     let mut views = Vec::new();
+    // Parents of the files and folders moved away, for the empty-directory cleanup.
+    let mut vacated: Vec<PathBuf> = Vec::new();
 
     let mut success = 0usize;
     let mut failed = 0usize;
@@ -87,17 +180,20 @@ pub async fn apply_actions(
             dirty_paths.push(dest);
         }
 
+        if let Some(sid) = session_id {
+            sqlx::query("UPDATE actions SET session_id = ?2 WHERE id = ?1")
+                .bind(id)
+                .bind(sid)
+                .execute(pool)
+                .await?;
+        }
         if !dry_run {
             // Allow/deny enforcement
-            if !paths::is_allowed(
-                std::path::Path::new(&path),
-                &safety.allow_paths,
-                &safety.deny_paths,
-            ) {
-                error = Some("path denied".to_string());
+            if let Err(reason) = policy.check("source", std::path::Path::new(&path)) {
+                error = Some(reason);
                 sqlx::query("UPDATE actions SET status='error' WHERE id = ?1")
                     .bind(id)
-                    .execute(&pool)
+                    .execute(pool)
                     .await?;
                 views.push(ActionView {
                     id,
@@ -108,6 +204,7 @@ pub async fn apply_actions(
                     rule,
                     error,
                     backup: backup_path.clone(),
+                    session_id,
                 });
                 continue;
             }
@@ -118,23 +215,24 @@ pub async fn apply_actions(
                     if let Some(tag) = extract_tag(&payload) {
                         sqlx::query("INSERT OR IGNORE INTO tags(name) VALUES (?1)")
                             .bind(&tag)
-                            .execute(&pool)
+                            .execute(pool)
                             .await?;
                         sqlx::query("INSERT OR IGNORE INTO file_tags(file_id, tag_id, confidence, source) VALUES ((SELECT id FROM files WHERE path = ?1),(SELECT id FROM tags WHERE name = ?2),1.0,'apply')")
                             .bind(&path)
                             .bind(&tag)
-                            .execute(&pool)
+                            .execute(pool)
                             .await?;
+                        refresh_fts(pool, &[&path]).await;
                         sqlx::query("UPDATE actions SET status='executed', executed_at=strftime('%s','now') WHERE id = ?1")
                                 .bind(id)
-                                .execute(&pool)
+                                .execute(pool)
                                 .await?;
                         status = "executed".to_string();
                     } else {
                         error = Some("invalid tag payload".to_string());
                         sqlx::query("UPDATE actions SET status='error' WHERE id = ?1")
                             .bind(id)
-                            .execute(&pool)
+                            .execute(pool)
                             .await?;
                     }
                 }
@@ -143,66 +241,153 @@ pub async fn apply_actions(
                         let tag_name = format!("duplicate_of:{}", dup_path);
                         sqlx::query("INSERT OR IGNORE INTO tags(name) VALUES (?1)")
                             .bind(&tag_name)
-                            .execute(&pool)
+                            .execute(pool)
                             .await?;
                         sqlx::query("INSERT OR IGNORE INTO file_tags(file_id, tag_id, confidence, source) VALUES ((SELECT id FROM files WHERE path = ?1),(SELECT id FROM tags WHERE name = ?2),1.0,'dedupe')")
                             .bind(&path)
                             .bind(&tag_name)
-                            .execute(&pool)
+                            .execute(pool)
                             .await?;
+                        refresh_fts(pool, &[&path]).await;
                         sqlx::query("UPDATE actions SET status='executed', executed_at=strftime('%s','now') WHERE id = ?1")
                             .bind(id)
-                            .execute(&pool)
+                            .execute(pool)
                             .await?;
                         status = "executed".to_string();
                     } else {
                         error = Some("invalid duplicate_of payload".to_string());
                         sqlx::query("UPDATE actions SET status='error' WHERE id = ?1")
                             .bind(id)
-                            .execute(&pool)
+                            .execute(pool)
                             .await?;
+                        failed += 1;
                     }
                 }
                 "merge_duplicate" => {
-                    if let Some(dup) = extract_duplicate_of(&payload) {
+                    let verified = match extract_duplicate_of(&payload) {
+                        Some(dup) => {
+                            // The survivor may be overwritten or relinked, so it is held to the same rules.
+                            match safety::check_action(safety, &kind, &payload).and_then(|()| {
+                                policy.check("destination", std::path::Path::new(&dup))
+                            }) {
+                                Err(reason) => {
+                                    error = Some(reason);
+                                    None
+                                }
+                                Ok(()) => match verify_true_duplicate(
+                                    pool,
+                                    &path,
+                                    &dup,
+                                    safety.byte_compare_duplicates,
+                                )
+                                .await
+                                {
+                                    Ok(true) => match preflight::Scanned::load(pool, &path)
+                                        .await
+                                        .map_err(|e| e.to_string())
+                                        .and_then(|scanned| {
+                                            preflight::check(
+                                                std::path::Path::new(&path),
+                                                None,
+                                                merge_backup_dir.as_deref(),
+                                                &scanned,
+                                            )
+                                        }) {
+                                        Ok(()) => Some(dup),
+                                        Err(reason) => {
+                                            error = Some(reason);
+                                            None
+                                        }
+                                    },
+                                    Ok(false) => {
+                                        error = Some("not a true duplicate".to_string());
+                                        None
+                                    }
+                                    Err(e) => {
+                                        error = Some(format!("duplicate check failed: {}", e));
+                                        None
+                                    }
+                                },
+                            }
+                        }
+                        None => None,
+                    };
+                    if let Some(err) = &error {
+                        sqlx::query("UPDATE actions SET status='error' WHERE id = ?1")
+                            .bind(id)
+                            .execute(pool)
+                            .await?;
+                        let _ = sqlx::query("INSERT INTO audit(action_id, event, detail) VALUES (?1,'merge_duplicate',?2)")
+                            .bind(id)
+                            .bind(err)
+                            .execute(pool)
+                            .await;
+                        failed += 1;
+                    } else if let Some(dup) = verified {
+                        let strategy = extract_strategy(&payload);
+                        let jid =
+                            journal::begin(pool, id, &kind, &path, Some(&dup), Some(&strategy))
+                                .await?;
                         // Treat merge as removing or replacing duplicate; if strategy is replace, let fs layer move it.
                         match fs_apply::apply_action(
                             action.clone(),
                             safety.trash_dir.as_deref().map(PathBuf::from).as_deref(),
+                            os_trash.as_deref(),
                             safety.copy_then_delete,
                             conflict,
                         ) {
                             Ok(bp) => {
+                                // Files sent to the OS trash are tracked by their .trashinfo, not the trash store.
+                                let trash_info =
+                                    bp.as_deref().and_then(fs_apply::os_trash_info_path);
+                                if let (Some(b), None) = (&bp, &trash_info) {
+                                    let _ = trash_store::record(pool, id, &path, b).await;
+                                }
                                 backup_path = bp.map(|p| p.to_string_lossy().into_owned());
-                                sqlx::query("UPDATE actions SET status='executed', executed_at=strftime('%s','now'), backup_path=?2 WHERE id = ?1")
+                                let mut tx = pool.begin().await?;
+                                journal::finish(&mut tx, jid, "done", None).await?;
+                                sqlx::query("UPDATE actions SET status='executed', executed_at=strftime('%s','now'), backup_path=?2, undo_token=?3 WHERE id = ?1")
                                     .bind(id)
                                     .bind(backup_path.clone())
-                                    .execute(&pool)
+                                    .bind(trash_info.map(|p| p.to_string_lossy().into_owned()))
+                                    .execute(&mut *tx)
                                     .await?;
+                                tx.commit().await?;
                                 status = "executed".to_string();
                                 success += 1;
                                 // Tag the surviving file with duplicate_of for audit
                                 let tag_name = format!("duplicate_of:{}", dup);
                                 let _ = sqlx::query("INSERT OR IGNORE INTO tags(name) VALUES (?1)")
                                     .bind(&tag_name)
-                                    .execute(&pool)
-                                    .await;
-                                let _ = copy_tags(&pool, &path, &dup).await;
-                                let _ = sqlx::query("DELETE FROM files WHERE path = ?1")
-                                    .bind(&path)
-                                    .execute(&pool)
+                                    .execute(pool)
                                     .await;
+                                let _ = copy_tags(pool, &path, &dup).await;
+                                // Linked duplicates keep their path, so their row stays.
+                                // Deleting the row cascades to this action, so snapshot both for undo.
+                                if !fs_apply::is_link_strategy(&strategy) {
+                                    let mut tx = pool.begin().await?;
+                                    undo::snapshot_merged(&mut tx, id).await?;
+                                    sqlx::query("DELETE FROM files WHERE path = ?1")
+                                        .bind(&path)
+                                        .execute(&mut *tx)
+                                        .await?;
+                                    tx.commit().await?;
+                                }
+                                refresh_fts(pool, &[&path, &dup]).await;
                                 let _ = sqlx::query("INSERT INTO audit(action_id, event, detail) VALUES (?1,'merge_duplicate','executed')")
                                     .bind(id)
-                                    .execute(&pool)
+                                    .execute(pool)
                                     .await;
                             }
                             Err(e) => {
                                 error = Some(e.to_string());
+                                let mut tx = pool.begin().await?;
+                                journal::finish(&mut tx, jid, "failed", error.as_deref()).await?;
                                 sqlx::query("UPDATE actions SET status='error' WHERE id = ?1")
                                     .bind(id)
-                                    .execute(&pool)
+                                    .execute(&mut *tx)
                                     .await?;
+                                tx.commit().await?;
                                 failed += 1;
                             }
                         }
@@ -210,19 +395,134 @@ pub async fn apply_actions(
                         error = Some("invalid duplicate_of payload".to_string());
                         sqlx::query("UPDATE actions SET status='error' WHERE id = ?1")
                             .bind(id)
-                            .execute(&pool)
+                            .execute(pool)
                             .await?;
+                        failed += 1;
+                    }
+                }
+                "move_dir" => {
+                    let from = PathBuf::from(&path);
+                    let checked = match extract_dest(&payload) {
+                        Some(to) => {
+                            let to = PathBuf::from(to);
+                            policy
+                                .check("destination", &to)
+                                .and_then(|()| preflight::check_dir(&from, &to))
+                                .map(|()| to)
+                        }
+                        None => Err("invalid move_dir payload".to_string()),
+                    };
+                    match checked {
+                        Err(reason) => {
+                            error = Some(reason);
+                            sqlx::query("UPDATE actions SET status='error' WHERE id = ?1")
+                                .bind(id)
+                                .execute(pool)
+                                .await?;
+                            failed += 1;
+                        }
+                        Ok(to) => {
+                            let dest = to.to_string_lossy().into_owned();
+                            let jid =
+                                journal::begin(pool, id, &kind, &path, Some(&dest), None).await?;
+                            match dirs::move_dir(&from, &to, conflict) {
+                                Ok(moved) => {
+                                    let mut tx = pool.begin().await?;
+                                    let files = dirs::record_dir_move(
+                                        &mut tx,
+                                        Some(id),
+                                        &path,
+                                        &dest,
+                                        &moved,
+                                    )
+                                    .await?;
+                                    // The keyword index and vector payloads of every file below
+                                    // still carry its old path.
+                                    dirty_paths.extend(
+                                        files.into_iter().flat_map(|(src, dest)| [src, dest]),
+                                    );
+                                    journal::finish(&mut tx, jid, "done", None).await?;
+                                    sqlx::query("UPDATE actions SET status='executed', executed_at=strftime('%s','now'), undo_token=?2 WHERE id = ?1")
+                                        .bind(id)
+                                        .bind(serde_json::to_string(&moved)?)
+                                        .execute(&mut *tx)
+                                        .await?;
+                                    tx.commit().await?;
+                                    status = "executed".to_string();
+                                    success += 1;
+                                }
+                                Err(e) => {
+                                    error = Some(e.to_string());
+                                    let mut tx = pool.begin().await?;
+                                    journal::finish(&mut tx, jid, "failed", error.as_deref())
+                                        .await?;
+                                    sqlx::query("UPDATE actions SET status='error' WHERE id = ?1")
+                                        .bind(id)
+                                        .execute(&mut *tx)
+                                        .await?;
+                                    tx.commit().await?;
+                                    failed += 1;
+                                }
+                            }
+                        }
                     }
                 }
                 _ => {
                     let trash_dir = safety.trash_dir.as_ref().map(|p| PathBuf::from(p));
-                    match fs_apply::apply_action(
-                        action,
-                        trash_dir.as_deref(),
-                        safety.copy_then_delete,
-                        conflict,
-                    ) {
+                    // Moves and renames resolve their final destination up front so the journal
+                    // records exactly where the file is going.
+                    let mut journal_entry = None;
+                    let outcome = match resolve_move(action, conflict) {
+                        Ok(Some((action, dest))) => {
+                            let scanned = preflight::Scanned::load(pool, &path).await?;
+                            let checked = policy.check("destination", &dest).and_then(|()| {
+                                preflight::check(
+                                    std::path::Path::new(&path),
+                                    Some(&dest),
+                                    trash_dir.as_deref(),
+                                    &scanned,
+                                )
+                            });
+                            match checked {
+                                Err(reason) => Err(anyhow::anyhow!(reason)),
+                                Ok(()) => {
+                                    let dest_str = dest.to_string_lossy().into_owned();
+                                    let jid = journal::begin(
+                                        pool,
+                                        id,
+                                        &kind,
+                                        &path,
+                                        Some(&dest_str),
+                                        None,
+                                    )
+                                    .await?;
+                                    journal_entry = Some((jid, dest_str.clone()));
+                                    dirty_paths.push(dest_str);
+                                    // The journal holds `dest`: replace it only under `overwrite`,
+                                    // and fail rather than pick another name if it was taken since.
+                                    let no_clobber = if conflict == "overwrite" {
+                                        "overwrite"
+                                    } else {
+                                        "fail"
+                                    };
+                                    fs_apply::apply_action(
+                                        action,
+                                        trash_dir.as_deref(),
+                                        os_trash.as_deref(),
+                                        safety.copy_then_delete,
+                                        no_clobber,
+                                    )
+                                }
+                            }
+                        }
+                        Ok(None) => Ok(None),
+                        Err(e) => Err(e),
+                    };
+                    match outcome {
                         Ok(bp) => {
+                            if let Some(b) = &bp {
+                                let _ = trash_store::record(pool, id, &path, b).await;
+                            }
                             if let Some(td) = &trash_dir {
                                 backup_path = bp
                                     .map(|p| p.to_string_lossy().into_owned())
@@ -230,30 +530,46 @@ pub async fn apply_actions(
                             } else {
                                 backup_path = bp.map(|p| p.to_string_lossy().into_owned());
                             }
+                            let mut tx = pool.begin().await?;
+                            if let Some((jid, dest)) = &journal_entry {
+                                journal::record_move(&mut tx, &path, dest).await?;
+                                journal::finish(&mut tx, *jid, "done", None).await?;
+                            }
                             sqlx::query("UPDATE actions SET status='executed', executed_at=strftime('%s','now'), backup_path=?2 WHERE id = ?1")
                                 .bind(id)
                                 .bind(backup_path.clone())
-                                .execute(&pool)
+                                .execute(&mut *tx)
                                 .await?;
+                            tx.commit().await?;
                             status = "executed".to_string();
                             success += 1;
                         }
                         Err(e) => {
                             error = Some(e.to_string());
+                            let mut tx = pool.begin().await?;
+                            if let Some((jid, _)) = &journal_entry {
+                                journal::finish(&mut tx, *jid, "failed", error.as_deref()).await?;
+                            }
                             sqlx::query("UPDATE actions SET status='error' WHERE id = ?1")
                                 .bind(id)
-                                .execute(&pool)
+                                .execute(&mut *tx)
                                 .await?;
+                            tx.commit().await?;
                             failed += 1;
                         }
                     }
                 }
             }
+            if status == "executed" && kind != "tag" && kind != "dedupe" {
+                if let Some(parent) = std::path::Path::new(&path).parent() {
+                    vacated.push(parent.to_path_buf());
+                }
+            }
             // mark dirty for downstream indexing
             for d in dirty_paths {
                 let _ = sqlx::query("INSERT OR REPLACE INTO dirty(path, reason, updated_at) VALUES (?1,'apply', strftime('%s','now'))")
                     .bind(d)
-                    .execute(&pool)
+                    .execute(pool)
                     .await;
             }
         }
@@ -267,16 +583,102 @@ pub async fn apply_actions(
             rule,
             error,
             backup: backup_path,
+            session_id,
         });
     }
 
-    if !dry_run {
-        println!("apply summary: success={}, failed={}", success, failed);
-    } else {
-        println!("dry-run: {} actions listed", views.len());
+    if safety.cleanup_empty_dirs && !vacated.is_empty() {
+        let removed = dirs::cleanup_empty_dirs(pool, &vacated, &policy).await?;
+        if !removed.is_empty() {
+            println!("removed {} empty directories", removed.len());
+        }
     }
+    Ok((views, success, failed))
+}
 
-    Ok(views)
+/// For moves and renames, the action retargeted at its conflict-resolved destination plus that
+/// destination; `None` for other kinds and for moves skipped by the conflict policy.
+fn resolve_move(
+    action: fs_apply::ActionKind,
+    conflict: &str,
+) -> Result<Option<(fs_apply::ActionKind, PathBuf)>> {
+    use fs_apply::ActionKind;
+    Ok(match action {
+        ActionKind::Move { from, to } => {
+            fs_apply::resolve_destination(&to, conflict)?.map(|dest| {
+                (
+                    ActionKind::Move {
+                        from,
+                        to: dest.clone(),
+                    },
+                    dest,
+                )
+            })
+        }
+        ActionKind::Rename { from, to } => {
+            fs_apply::resolve_destination(&to, conflict)?.map(|dest| {
+                (
+                    ActionKind::Rename {
+                        from,
+                        to: dest.clone(),
+                    },
+                    dest,
+                )
+            })
+        }
+        _ => None,
+    })
+}
+
+/// A merge may only remove `path` when it is really a copy of `survivor`: equal size on disk and
+/// equal full blake3 hash (hashed now and stored when `full_hash` is missing), plus an optional
+/// byte-by-byte comparison.
+async fn verify_true_duplicate(
+    pool: &sqlx::SqlitePool,
+    path: &str,
+    survivor: &str,
+    byte_compare: bool,
+) -> Result<bool> {
+    let (a, b) = (std::path::Path::new(path), std::path::Path::new(survivor));
+    if std::fs::metadata(a)?.len() != std::fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+    if stored_or_computed_full_hash(pool, path).await?
+        != stored_or_computed_full_hash(pool, survivor).await?
+    {
+        return Ok(false);
+    }
+    if byte_compare {
+        return fs_apply::same_contents(a, b);
+    }
+    Ok(true)
+}
+
+/// The stored full hash while the file still has the scanned size and mtime; otherwise it is
+/// re-hashed. A fresh hash is only stored when the row describes the file as it is now.
+async fn stored_or_computed_full_hash(pool: &sqlx::SqlitePool, path: &str) -> Result<String> {
+    let meta = std::fs::metadata(path)?;
+    let stored: Option<(Option<String>, i64, i64)> =
+        sqlx::query_as("SELECT full_hash, size, mtime FROM files WHERE path = ?1")
+            .bind(path)
+            .fetch_optional(pool)
+            .await?;
+    let unchanged = |size: i64, mtime: i64| {
+        size == meta.len() as i64 && mtime == organizer_core::scanner::mtime(&meta)
+    };
+    match stored {
+        Some((Some(h), size, mtime)) if unchanged(size, mtime) => Ok(h),
+        Some((None, size, mtime)) if unchanged(size, mtime) => {
+            let h = fs_apply::full_hash(std::path::Path::new(path))?;
+            sqlx::query("UPDATE files SET full_hash = ?2 WHERE path = ?1")
+                .bind(path)
+                .bind(&h)
+                .execute(pool)
+                .await?;
+            Ok(h)
+        }
+        _ => fs_apply::full_hash(std::path::Path::new(path)),
+    }
 }
 
 fn extract_tag(payload: &str) -> Option<String> {
@@ -285,7 +687,7 @@ fn extract_tag(payload: &str) -> Option<String> {
         .and_then(|v| v.get("tag").and_then(|t| t.as_str()).map(|s| s.to_string()))
 }
 
-fn extract_duplicate_of(payload: &str) -> Option<String> {
+pub(crate) fn extract_duplicate_of(payload: &str) -> Option<String> {
     serde_json::from_str::<Value>(payload).ok().and_then(|v| {
         v.get("duplicate_of")
             .and_then(|t| t.as_str())
@@ -321,6 +723,27 @@ async fn copy_tags(pool: &sqlx::SqlitePool, from_path: &str, to_path: &str) -> R
     Ok(())
 }
 
+/// Best-effort refresh of the full-text rows after an action changed tags or file rows.
+async fn refresh_fts(pool: &sqlx::SqlitePool, paths: &[&str]) {
+    let Ok(mut conn) = pool.acquire().await else {
+        return;
+    };
+    for p in paths {
+        let _ = fts::refresh_path(&mut conn, p).await;
+    }
+}
+
+fn extract_strategy(payload: &str) -> String {
+    serde_json::from_str::<Value>(payload)
+        .ok()
+        .and_then(|v| {
+            v.get("strategy")
+                .and_then(|t| t.as_str())
+                .map(|s| s.to_string())
+        })
+        .unwrap_or_default()
+}
+
 fn extract_rule(payload: &str) -> Option<String> {
     serde_json::from_str::<Value>(payload).ok().and_then(|v| {
         v.get("rule")
@@ -0,0 +1,213 @@
+//! Directory-level actions: `move_dir` moves a whole folder, merging it file by file into a
+//! folder that already exists, and `cleanup_empty_dirs` removes the directories `apply` emptied.
+
+use crate::fs_apply;
+use crate::journal;
+use crate::paths::PathPolicy;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sqlx::{SqliteConnection, SqlitePool};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// What a `move_dir` did; stored as the action's `undo_token`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DirMove {
+    /// The directory was renamed in one step.
+    pub renamed: bool,
+    /// Otherwise, the files moved one by one into the existing destination, as (from, to).
+    #[serde(default)]
+    pub files: Vec<(String, String)>,
+}
+
+/// Moves the directory `from` to `to`. A free destination on the same filesystem is a single
+/// rename; otherwise every file is moved to the same relative path under `to`, resolving clashes
+/// with `conflict_policy`, and the emptied source folders are removed. A failed merge moves the
+/// files already merged back before returning the error.
+pub fn move_dir(from: &Path, to: &Path, conflict_policy: &str) -> Result<DirMove> {
+    if to.starts_with(from) {
+        anyhow::bail!("cannot move {} into itself", from.display());
+    }
+    if !to.exists() {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        match fs::rename(from, to) {
+            Ok(()) => {
+                return Ok(DirMove {
+                    renamed: true,
+                    files: Vec::new(),
+                })
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    fs::create_dir_all(to)?;
+    let mut moved = DirMove::default();
+    if let Err(e) = merge_into(from, to, conflict_policy, &mut moved) {
+        for (src, dest) in moved.files.iter().rev() {
+            let _ = fs_apply::move_across(Path::new(dest), Path::new(src));
+        }
+        return Err(e);
+    }
+    remove_empty_tree(from);
+    Ok(moved)
+}
+
+fn merge_into(from: &Path, to: &Path, conflict_policy: &str, moved: &mut DirMove) -> Result<()> {
+    for entry in WalkDir::new(from).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let rel = entry.path().strip_prefix(from)?;
+        let Some(dest) = fs_apply::resolve_destination(&to.join(rel), conflict_policy)? else {
+            continue;
+        };
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs_apply::move_across(entry.path(), &dest)?;
+        moved.files.push((
+            entry.path().to_string_lossy().into_owned(),
+            dest.to_string_lossy().into_owned(),
+        ));
+    }
+    Ok(())
+}
+
+/// Removes `dir` and the folders below it that are empty, deepest first. Folders still holding
+/// files (skipped by the conflict policy) stay.
+fn remove_empty_tree(dir: &Path) {
+    for entry in WalkDir::new(dir).contents_first(true).into_iter().flatten() {
+        if entry.file_type().is_dir() {
+            let _ = fs::remove_dir(entry.path());
+        }
+    }
+}
+
+/// Points the DB at a moved directory: each `files` row below it follows its file, and the
+/// `directories` rows are renamed along (one-step rename) or, after a merge, the action is
+/// re-pointed at the destination folder and rows of source folders that are gone are dropped.
+/// Returns the files that moved, as (from, to), for the caller to mark dirty.
+pub async fn record_dir_move(
+    conn: &mut SqliteConnection,
+    action_id: Option<i64>,
+    from: &str,
+    to: &str,
+    moved: &DirMove,
+) -> Result<Vec<(String, String)>> {
+    if moved.renamed {
+        let below = format!("{}/", from);
+        let paths: Vec<String> =
+            sqlx::query_scalar("SELECT path FROM files WHERE substr(path, 1, length(?1)) = ?1")
+                .bind(&below)
+                .fetch_all(&mut *conn)
+                .await?;
+        let mut files = Vec::new();
+        for path in paths {
+            if let Some(rest) = path.strip_prefix(from) {
+                let dest = format!("{}{}", to, rest);
+                journal::record_move(conn, &path, &dest).await?;
+                files.push((path, dest));
+            }
+        }
+        sqlx::query(
+            "UPDATE OR REPLACE directories SET path = ?2 || substr(path, length(?1) + 1)
+             WHERE path = ?1 OR substr(path, 1, length(?1) + 1) = ?1 || '/'",
+        )
+        .bind(from)
+        .bind(to)
+        .execute(&mut *conn)
+        .await?;
+        return Ok(files);
+    }
+    for (src, dest) in &moved.files {
+        journal::record_move(conn, src, dest).await?;
+    }
+    sqlx::query("INSERT OR IGNORE INTO directories (path, mtime) VALUES (?1, 0)")
+        .bind(to)
+        .execute(&mut *conn)
+        .await?;
+    if let Some(id) = action_id {
+        sqlx::query("UPDATE actions SET dir_id = (SELECT id FROM directories WHERE path = ?2) WHERE id = ?1")
+            .bind(id)
+            .bind(to)
+            .execute(&mut *conn)
+            .await?;
+    }
+    let known: Vec<String> = sqlx::query_scalar(
+        "SELECT path FROM directories WHERE path = ?1 OR substr(path, 1, length(?1) + 1) = ?1 || '/'",
+    )
+    .bind(from)
+    .fetch_all(&mut *conn)
+    .await?;
+    for path in known.iter().filter(|p| !Path::new(p).exists()) {
+        sqlx::query("DELETE FROM directories WHERE path = ?1")
+            .bind(path)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(moved.files.clone())
+}
+
+/// Removes each of `dirs` and then its parents while they are empty. Only scanned folders (rows
+/// in `directories`, so never a scan root) that the allow/deny paths cover are removed; each
+/// removal is audited. Returns the removed directories.
+pub async fn cleanup_empty_dirs(
+    pool: &SqlitePool,
+    dirs: &[PathBuf],
+    policy: &PathPolicy,
+) -> Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    for start in dirs {
+        let mut dir = start.as_path();
+        loop {
+            let path = dir.to_string_lossy();
+            let known: bool =
+                sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM directories WHERE path = ?1)")
+                    .bind(path.as_ref())
+                    .fetch_one(pool)
+                    .await?;
+            if !known || policy.check("directory", dir).is_err() || !is_empty(dir) {
+                break;
+            }
+            if fs::remove_dir(dir).is_err() {
+                break;
+            }
+            sqlx::query("DELETE FROM directories WHERE path = ?1")
+                .bind(path.as_ref())
+                .execute(pool)
+                .await?;
+            sqlx::query("INSERT INTO audit (action_id, event, detail) VALUES (NULL, 'cleanup_empty_dirs', ?1)")
+                .bind(format!("removed empty directory {}", path))
+                .execute(pool)
+                .await?;
+            removed.push(dir.to_path_buf());
+            match dir.parent() {
+                Some(parent) => dir = parent,
+                None => break,
+            }
+        }
+    }
+    Ok(removed)
+}
+
+fn is_empty(dir: &Path) -> bool {
+    fs::read_dir(dir)
+        .map(|mut entries| entries.next().is_none())
+        .unwrap_or(false)
+}
+
+/// Total size of the files below `dir`.
+pub fn dir_size(dir: &Path) -> u64 {
+    WalkDir::new(dir)
+        .into_iter()
+        .flatten()
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
@@ -26,6 +26,11 @@ pub enum ActionKind {
         target: PathBuf,
         strategy: String,
     },
+    /// A whole directory; `to` is its new path.
+    MoveDir {
+        from: PathBuf,
+        to: PathBuf,
+    },
     Unsupported,
 }
 
@@ -71,6 +76,14 @@ pub fn parse_action(path: &str, kind: &str, payload: &str) -> ActionKind {
                 _duplicate_of: dupe,
             }
         }
+        "move_dir" => {
+            let to = parsed
+                .get("to")
+                .and_then(|v| v.as_str())
+                .map(PathBuf::from)
+                .unwrap_or_else(|| from.clone());
+            ActionKind::MoveDir { from, to }
+        }
         "merge_duplicate" => {
             let dupe = parsed
                 .get("duplicate_of")
@@ -94,19 +107,14 @@ pub fn parse_action(path: &str, kind: &str, payload: &str) -> ActionKind {
 pub fn apply_action(
     action: ActionKind,
     trash_dir: Option<&Path>,
+    os_trash: Option<&Path>,
     copy_then_delete: bool,
     conflict_policy: &str,
 ) -> Result<Option<PathBuf>> {
     match action {
         ActionKind::Move { from, to } | ActionKind::Rename { from, to } => {
-            let target = if to.exists() {
-                match conflict_policy {
-                    "skip" => return Ok(None),
-                    "overwrite" => to,
-                    _ => resolve_conflict(&to)?,
-                }
-            } else {
-                to
+            let Some(target) = resolve_destination(&to, conflict_policy)? else {
+                return Ok(None);
             };
             let backup = apply_move(from, target, trash_dir, copy_then_delete)?;
             Ok(backup)
@@ -127,26 +135,173 @@ pub fn apply_action(
                 "keep_duplicate" | "replace" => apply_action(
                     ActionKind::Move { from, to: target },
                     trash_dir,
+                    os_trash,
                     copy_then_delete,
                     "overwrite",
                 ),
+                "hardlink" => link_duplicate(&from, &target, trash_dir, false),
+                "reflink" => link_duplicate(&from, &target, trash_dir, true),
                 _ => {
+                    // The OS trash takes the file itself; nothing is left to back up.
+                    if let Some(os) = os_trash {
+                        return move_to_os_trash(&from, os).map(|t| Some(t.file));
+                    }
                     if let Some(trash) = trash_dir {
                         let backup = backup_to_trash(&from, trash)?;
-                        let _ = fs::remove_file(&from);
+                        fs::remove_file(&from)?;
                         Ok(Some(backup))
                     } else {
-                        let _ = fs::remove_file(&from);
+                        fs::remove_file(&from)?;
                         Ok(None)
                     }
                 }
             }
         }
+        ActionKind::MoveDir { from, to } => {
+            crate::dirs::move_dir(&from, &to, conflict_policy)?;
+            Ok(None)
+        }
         ActionKind::Unsupported => Ok(None),
     }
 }
 
-fn choose_strategy_by_mtime(from: &Path, target: &Path, requested: &str) -> String {
+/// Where a move or rename to `to` will land under `conflict_policy`; `None` when the policy is
+/// `skip` and `to` is taken. The internal `fail` policy errors instead, for a destination that
+/// was already resolved and must not be replaced.
+pub fn resolve_destination(to: &Path, conflict_policy: &str) -> Result<Option<PathBuf>> {
+    resolve_destination_with(to, conflict_policy, |p| p.exists())
+}
+
+/// [`resolve_destination`] against an arbitrary view of which paths exist, so a plan can be
+/// simulated before anything is moved.
+pub fn resolve_destination_with(
+    to: &Path,
+    conflict_policy: &str,
+    exists: impl Fn(&Path) -> bool,
+) -> Result<Option<PathBuf>> {
+    if !exists(to) {
+        return Ok(Some(to.to_path_buf()));
+    }
+    match conflict_policy {
+        "skip" => Ok(None),
+        "overwrite" => Ok(Some(to.to_path_buf())),
+        "fail" => anyhow::bail!("destination {} already exists", to.display()),
+        _ => resolve_conflict(to, &exists).map(Some),
+    }
+}
+
+/// True for merge strategies that leave the duplicate's path in place as a link to the survivor.
+pub fn is_link_strategy(strategy: &str) -> bool {
+    matches!(strategy, "hardlink" | "reflink")
+}
+
+/// Replaces `dup` with a copy-on-write clone of `survivor` (when `reflink` is set and the
+/// filesystem supports it) or a hardlink to it, so both paths stay valid. The duplicate is backed
+/// up to the trash first, so link merges are refused without a trash directory; the swap goes
+/// through a sibling temp file and a rename, so `dup` is never missing.
+fn link_duplicate(
+    dup: &Path,
+    survivor: &Path,
+    trash_dir: Option<&Path>,
+    reflink: bool,
+) -> Result<Option<PathBuf>> {
+    let Some(trash) = trash_dir else {
+        anyhow::bail!(
+            "refusing to link {} without a trash directory to back it up to",
+            dup.display()
+        );
+    };
+    let backup = Some(backup_to_trash(dup, trash)?);
+    let tmp = link_temp_path(dup);
+    let _ = fs::remove_file(&tmp);
+    let cloned = reflink && reflink_file(survivor, &tmp).is_ok();
+    if !cloned {
+        fs::hard_link(survivor, &tmp).map_err(|e| {
+            anyhow::anyhow!(
+                "cannot link {} to {}: {} (hardlinks need both files on the same filesystem)",
+                dup.display(),
+                survivor.display(),
+                e
+            )
+        })?;
+    }
+    if let Err(e) = fs::rename(&tmp, dup) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e.into());
+    }
+    Ok(backup)
+}
+
+/// Sibling temp file a link is built in before it replaces `dup`.
+pub fn link_temp_path(dup: &Path) -> PathBuf {
+    let name = dup
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "duplicate".to_string());
+    dup.with_file_name(format!(".{}.organizer-link", name))
+}
+
+/// Copy-on-write clone via the FICLONE ioctl (Btrfs, XFS and other reflink-capable filesystems).
+#[cfg(target_os = "linux")]
+fn reflink_file(src: &Path, dest: &Path) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    const FICLONE: libc::c_ulong = 0x4004_9409;
+    let src_file = fs::File::open(src)?;
+    let dest_file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dest)?;
+    // SAFETY: both descriptors are open for the duration of the call.
+    let rc = unsafe { libc::ioctl(dest_file.as_raw_fd(), FICLONE as _, src_file.as_raw_fd()) };
+    if rc == -1 {
+        let err = std::io::Error::last_os_error();
+        drop(dest_file);
+        let _ = fs::remove_file(dest);
+        return Err(err);
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink_file(_src: &Path, _dest: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "reflink is not supported on this platform",
+    ))
+}
+
+/// Full-content blake3 hash (hex), matching the scanner's `full_hash`.
+pub fn full_hash(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Byte-by-byte comparison of two files.
+pub fn same_contents(a: &Path, b: &Path) -> Result<bool> {
+    use std::io::{BufReader, Read};
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+    let mut ra = BufReader::new(fs::File::open(a)?);
+    let mut rb = BufReader::new(fs::File::open(b)?);
+    let mut ba = [0u8; 8192];
+    let mut bb = [0u8; 8192];
+    loop {
+        let n = ra.read(&mut ba)?;
+        if n == 0 {
+            return Ok(rb.read(&mut bb[..1])? == 0);
+        }
+        rb.read_exact(&mut bb[..n])?;
+        if ba[..n] != bb[..n] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Resolves `keep_newest` / `keep_oldest` to `keep_duplicate` or `keep_original` by mtime.
+pub fn choose_strategy_by_mtime(from: &Path, target: &Path, requested: &str) -> String {
     let from_mtime = from.metadata().ok().and_then(|m| m.modified().ok());
     let target_mtime = target.metadata().ok().and_then(|m| m.modified().ok());
     match (from_mtime, target_mtime) {
@@ -161,43 +316,298 @@ fn choose_strategy_by_mtime(from: &Path, target: &Path, requested: &str) -> Stri
     }
 }
 
+/// Stores `src`'s bytes in the trash under their blake3 hash, so backing up the same content
+/// again (a file moved twice, several copies of one duplicate) costs the disk only once. Returns
+/// the object path.
 fn backup_to_trash(src: &Path, trash_dir: &Path) -> Result<PathBuf> {
-    fs::create_dir_all(trash_dir)?;
-    let file_name = src
+    let hash = full_hash(src)?;
+    let object = trash_object_path(trash_dir, &hash);
+    if object.exists() {
+        return Ok(object);
+    }
+    if let Some(parent) = object.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = object.with_extension("partial");
+    fs::copy(src, &tmp)?;
+    fs::rename(&tmp, &object)?;
+    Ok(object)
+}
+
+/// The freedesktop.org home trash: `$XDG_DATA_HOME/Trash`, falling back to
+/// `~/.local/share/Trash`.
+pub fn xdg_trash_dir() -> Option<PathBuf> {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| {
+            std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local").join("share"))
+        })?;
+    Some(data_home.join("Trash"))
+}
+
+/// A file moved into an OS trash: its new location and the `.trashinfo` describing it.
+#[derive(Debug, Clone)]
+pub struct OsTrashed {
+    pub file: PathBuf,
+    pub info: PathBuf,
+}
+
+/// Moves `path` into the freedesktop trash at `trash` (`files/` plus an `info/*.trashinfo` with
+/// the original path and deletion date), so file managers can show and restore it. The info file
+/// is created first and exclusively, which reserves the name.
+pub fn move_to_os_trash(path: &Path, trash: &Path) -> Result<OsTrashed> {
+    let files_dir = trash.join("files");
+    let info_dir = trash.join("info");
+    fs::create_dir_all(&files_dir)?;
+    fs::create_dir_all(&info_dir)?;
+    let original = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path)
+    };
+    let name = path
         .file_name()
-        .map(|n| n.to_os_string())
-        .unwrap_or_else(|| "backup".into());
-    let mut candidate = trash_dir.join(&file_name);
-    if candidate.exists() {
-        let stem = candidate
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("backup")
-            .to_string();
-        let ext = candidate
-            .extension()
-            .and_then(|e| e.to_str())
-            .unwrap_or("")
-            .to_string();
-        let mut counter = 1;
-        loop {
-            let name = if ext.is_empty() {
-                format!("{}_{}", stem, counter)
-            } else {
-                format!("{}_{}.{}", stem, counter, ext)
-            };
-            candidate = trash_dir.join(name);
-            if !candidate.exists() {
-                break;
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "file".to_string());
+    let contents = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        percent_encode_path(&original.to_string_lossy()),
+        chrono::Local::now().format("%Y-%m-%dT%H:%M:%S")
+    );
+    let mut counter = 1;
+    let (file, info) = loop {
+        let candidate = if counter == 1 {
+            name.clone()
+        } else {
+            match name.rsplit_once('.') {
+                Some((stem, ext)) if !stem.is_empty() => format!("{}.{}.{}", stem, counter, ext),
+                _ => format!("{}.{}", name, counter),
+            }
+        };
+        let info = info_dir.join(format!("{}.trashinfo", candidate));
+        let file = files_dir.join(&candidate);
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info)
+        {
+            Ok(mut f) if !file.exists() => {
+                use std::io::Write;
+                f.write_all(contents.as_bytes())?;
+                break (file, info);
+            }
+            Ok(_) => {
+                let _ = fs::remove_file(&info);
             }
-            counter += 1;
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e.into()),
+        }
+        counter += 1;
+    };
+    if let Err(e) = move_across(path, &file) {
+        let _ = fs::remove_file(&info);
+        return Err(e);
+    }
+    Ok(OsTrashed { file, info })
+}
+
+/// The `.trashinfo` of a file inside an OS trash's `files/` directory, if it has one.
+pub fn os_trash_info_path(file: &Path) -> Option<PathBuf> {
+    let files_dir = file.parent()?;
+    if files_dir.file_name()? != "files" {
+        return None;
+    }
+    let info = files_dir
+        .parent()?
+        .join("info")
+        .join(format!("{}.trashinfo", file.file_name()?.to_string_lossy()));
+    info.exists().then_some(info)
+}
+
+/// Moves a file out of the OS trash back to `dest` and drops its `.trashinfo`.
+pub fn restore_from_os_trash(file: &Path, dest: &Path) -> Result<()> {
+    if dest.exists() {
+        anyhow::bail!("{} already exists", dest.display());
+    }
+    let info = os_trash_info_path(file);
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    move_across(file, dest)?;
+    if let Some(info) = info {
+        let _ = fs::remove_file(info);
+    }
+    Ok(())
+}
+
+/// Rename, or a verified copy and delete when the paths are on different filesystems.
+pub fn move_across(from: &Path, to: &Path) -> Result<()> {
+    match fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            copy_verified_then_delete(from, to)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Copies `from` to a temporary file next to `to`, carries over timestamps, extended attributes
+/// and permissions, checks the copy's blake3 against the source, then renames it into place and
+/// only then deletes `from`. A failed copy leaves `from` untouched and no partial file behind.
+pub fn copy_verified_then_delete(from: &Path, to: &Path) -> Result<()> {
+    let name = to
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "file".to_string());
+    let tmp = to.with_file_name(format!(".{}.partial", name));
+    let copied = (|| -> Result<()> {
+        fs::copy(from, &tmp)?;
+        preserve_metadata(from, &tmp)?;
+        if full_hash(from)? != full_hash(&tmp)? {
+            anyhow::bail!(
+                "copy of {} to {} did not verify",
+                from.display(),
+                to.display()
+            );
         }
+        fs::rename(&tmp, to)?;
+        Ok(())
+    })();
+    if copied.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    copied?;
+    fs::remove_file(from)?;
+    Ok(())
+}
+
+/// Gives `to` the access/modification times, extended attributes and permissions of `from`.
+/// `fs::copy` already gave `to` the source's mode, so it is made owner-writable while times and
+/// attributes are set, and the source's permissions are applied last.
+fn preserve_metadata(from: &Path, to: &Path) -> Result<()> {
+    let meta = fs::metadata(from)?;
+    fs::set_permissions(to, owner_writable(meta.permissions()))?;
+    let mut times = fs::FileTimes::new().set_modified(meta.modified()?);
+    if let Ok(accessed) = meta.accessed() {
+        times = times.set_accessed(accessed);
+    }
+    fs::OpenOptions::new()
+        .write(true)
+        .open(to)?
+        .set_times(times)?;
+    copy_xattrs(from, to);
+    fs::set_permissions(to, meta.permissions())?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn owner_writable(mut perms: fs::Permissions) -> fs::Permissions {
+    use std::os::unix::fs::PermissionsExt;
+    perms.set_mode(perms.mode() | 0o200);
+    perms
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::permissions_set_readonly_false)]
+fn owner_writable(mut perms: fs::Permissions) -> fs::Permissions {
+    perms.set_readonly(false);
+    perms
+}
+
+/// Best effort: attributes the target filesystem rejects are skipped with a warning.
+#[cfg(target_os = "linux")]
+fn copy_xattrs(from: &Path, to: &Path) {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    let (Ok(src), Ok(dst)) = (
+        CString::new(from.as_os_str().as_bytes()),
+        CString::new(to.as_os_str().as_bytes()),
+    ) else {
+        return;
+    };
+    // SAFETY: `src` is a NUL-terminated path; a null buffer of size 0 only asks for the length.
+    let len = unsafe { libc::listxattr(src.as_ptr(), std::ptr::null_mut(), 0) };
+    if len <= 0 {
+        return;
     }
-    let _ = fs::copy(src, &candidate);
-    Ok(candidate)
+    let mut names = vec![0u8; len as usize];
+    // SAFETY: the kernel writes at most `names.len()` bytes into `names`.
+    let len = unsafe { libc::listxattr(src.as_ptr(), names.as_mut_ptr().cast(), names.len()) };
+    if len <= 0 {
+        return;
+    }
+    for name in names[..len as usize]
+        .split(|b| *b == 0)
+        .filter(|n| !n.is_empty())
+    {
+        let Ok(name) = CString::new(name) else {
+            continue;
+        };
+        // SAFETY: both strings are NUL-terminated; a null buffer of size 0 only asks for the size.
+        let size = unsafe { libc::getxattr(src.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0) };
+        if size < 0 {
+            continue;
+        }
+        let mut value = vec![0u8; size as usize];
+        // SAFETY: the kernel writes at most `value.len()` bytes into `value`.
+        let size = unsafe {
+            libc::getxattr(
+                src.as_ptr(),
+                name.as_ptr(),
+                value.as_mut_ptr().cast(),
+                value.len(),
+            )
+        };
+        if size < 0 {
+            continue;
+        }
+        // SAFETY: `size` bytes of `value` were filled by getxattr and are only read here.
+        let rc = unsafe {
+            libc::setxattr(
+                dst.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr().cast(),
+                size as usize,
+                0,
+            )
+        };
+        if rc != 0 {
+            tracing::warn!(
+                "could not copy extended attribute {} to {}: {}",
+                name.to_string_lossy(),
+                to.display(),
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn copy_xattrs(_from: &Path, _to: &Path) {}
+
+/// Percent-encodes a path for the `Path=` key of a `.trashinfo` (RFC 2396, `/` kept).
+fn percent_encode_path(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for b in path.bytes() {
+        if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+/// `trash_dir/objects/ab/abcdef…` for a blake3 hex digest.
+pub fn trash_object_path(trash_dir: &Path, hash: &str) -> PathBuf {
+    trash_dir
+        .join("objects")
+        .join(hash.get(..2).unwrap_or("00"))
+        .join(hash)
 }
 
-fn resolve_conflict(dest: &Path) -> Result<PathBuf> {
+fn resolve_conflict(dest: &Path, exists: &impl Fn(&Path) -> bool) -> Result<PathBuf> {
     let mut candidate = dest.to_path_buf();
     let stem = candidate
         .file_stem()
@@ -220,7 +630,7 @@ fn resolve_conflict(dest: &Path) -> Result<PathBuf> {
             .parent()
             .unwrap_or_else(|| Path::new("."))
             .join(name);
-        if !candidate.exists() {
+        if !exists(&candidate) {
             return Ok(candidate);
         }
         counter += 1;
@@ -241,10 +651,9 @@ fn apply_move(
         fs::create_dir_all(parent)?;
     }
     if copy_then_delete {
-        fs::copy(&from, &to)?;
-        fs::remove_file(&from)?;
+        copy_verified_then_delete(&from, &to)?;
     } else {
-        fs::rename(&from, &to)?;
+        move_across(&from, &to)?;
     }
     Ok(backup_path)
 }
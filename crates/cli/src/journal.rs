@@ -0,0 +1,344 @@
+//! Write-ahead journal for `apply`: intent rows before filesystem operations, completion after,
+//! and recovery of operations a crash left half-finished.
+
+use crate::fs_apply;
+use anyhow::Result;
+use organizer_core::fts;
+use serde::Serialize;
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use std::path::Path;
+
+/// Records the intent to run a filesystem operation for `action_id`; returns the journal id.
+pub async fn begin(
+    pool: &SqlitePool,
+    action_id: i64,
+    kind: &str,
+    src: &str,
+    dest: Option<&str>,
+    strategy: Option<&str>,
+) -> Result<i64> {
+    let id = sqlx::query_scalar(
+        "INSERT INTO apply_journal (action_id, kind, src, dest, strategy, pid, state) VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'pending') RETURNING id",
+    )
+    .bind(action_id)
+    .bind(kind)
+    .bind(src)
+    .bind(dest)
+    .bind(strategy)
+    .bind(std::process::id() as i64)
+    .fetch_one(pool)
+    .await?;
+    Ok(id)
+}
+
+/// Moves an entry out of `pending`.
+pub async fn finish(
+    conn: &mut SqliteConnection,
+    journal_id: i64,
+    state: &str,
+    detail: Option<&str>,
+) -> Result<()> {
+    sqlx::query("UPDATE apply_journal SET state = ?2, detail = ?3, updated_at = strftime('%s','now') WHERE id = ?1")
+        .bind(journal_id)
+        .bind(state)
+        .bind(detail)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Points the `files` row (and its full-text rows) at the file's new location. A row already at
+/// `dest` belonged to a file the move overwrote and is dropped.
+pub async fn record_move(conn: &mut SqliteConnection, src: &str, dest: &str) -> Result<()> {
+    if src == dest {
+        return Ok(());
+    }
+    fts::remove_path(conn, dest).await?;
+    sqlx::query("DELETE FROM files WHERE path = ?1")
+        .bind(dest)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("UPDATE files SET path = ?2 WHERE path = ?1")
+        .bind(src)
+        .bind(dest)
+        .execute(&mut *conn)
+        .await?;
+    fts::refresh_path(conn, dest).await?;
+    Ok(())
+}
+
+/// Where the file of `action_id` was before it was applied, from its completed journal entry.
+pub async fn original_path(pool: &SqlitePool, action_id: i64) -> Result<Option<String>> {
+    let src = sqlx::query_scalar(
+        "SELECT src FROM apply_journal WHERE action_id = ?1 AND state = 'done' ORDER BY id DESC LIMIT 1",
+    )
+    .bind(action_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(src)
+}
+
+#[derive(Debug, Default, Clone, Serialize, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// The filesystem side had finished; the DB side was completed now.
+    pub completed: usize,
+    /// The filesystem was untouched; the action stays planned.
+    pub rolled_back: usize,
+    /// Both or neither path exists; the action is marked `error` for manual review.
+    pub failed: usize,
+}
+
+impl RecoveryReport {
+    pub fn total(&self) -> usize {
+        self.completed + self.rolled_back + self.failed
+    }
+}
+
+/// Where a lock's owner cannot be checked, it is trusted for this long.
+#[cfg(not(unix))]
+const LOCK_STALE_SECS: i64 = 12 * 60 * 60;
+
+/// Exclusive claim on applying to a database, held in `apply_lock` until [`ApplyLock::release`].
+#[derive(Debug)]
+pub struct ApplyLock {
+    pool: SqlitePool,
+    pid: i64,
+}
+
+/// Takes the apply lock, replacing a stale one left by a process that is gone. Fails while
+/// another live process (or another run in this one) holds it.
+pub async fn lock(pool: &SqlitePool) -> Result<ApplyLock> {
+    let pid = std::process::id() as i64;
+    let mut conn = pool.acquire().await?;
+    // IMMEDIATE takes the write lock up front, so two processes cannot both see the row free.
+    sqlx::query("BEGIN IMMEDIATE").execute(&mut *conn).await?;
+    let claimed = claim(&mut conn, pid).await;
+    let end = if claimed.is_ok() {
+        "COMMIT"
+    } else {
+        "ROLLBACK"
+    };
+    sqlx::query(end).execute(&mut *conn).await?;
+    claimed?;
+    Ok(ApplyLock {
+        pool: pool.clone(),
+        pid,
+    })
+}
+
+async fn claim(conn: &mut SqliteConnection, pid: i64) -> Result<()> {
+    let holder: Option<(i64, i64)> =
+        sqlx::query_as("SELECT pid, acquired_at FROM apply_lock WHERE id = 1")
+            .fetch_optional(&mut *conn)
+            .await?;
+    if let Some((owner, acquired_at)) = holder {
+        if owner == pid || process_alive(owner, acquired_at) {
+            anyhow::bail!(
+                "another apply (pid {}) is running against this database",
+                owner
+            );
+        }
+    }
+    sqlx::query("INSERT OR REPLACE INTO apply_lock (id, pid, acquired_at) VALUES (1, ?1, strftime('%s','now'))")
+        .bind(pid)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+impl ApplyLock {
+    pub async fn release(self) -> Result<()> {
+        sqlx::query("DELETE FROM apply_lock WHERE id = 1 AND pid = ?1")
+            .bind(self.pid)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn process_alive(pid: i64, _acquired_at: i64) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: signal 0 only checks that the process exists and may be signalled; nothing is sent.
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn process_alive(_pid: i64, acquired_at: i64) -> bool {
+    chrono::Utc::now().timestamp() - acquired_at < LOCK_STALE_SECS
+}
+
+/// Reconciles the `pending` journal entries of processes that are gone with the filesystem;
+/// entries of a live process (another apply that skipped the lock) are left to it. A no-op on
+/// databases that predate the journal. A finished trash merge gets its backup back from
+/// `trash_dir`, where the content-addressed copy was written before the duplicate went away.
+pub async fn recover(pool: &SqlitePool, trash_dir: Option<&Path>) -> Result<RecoveryReport> {
+    let mut report = RecoveryReport::default();
+    let has_journal: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'apply_journal')",
+    )
+    .fetch_one(pool)
+    .await?;
+    if !has_journal {
+        return Ok(report);
+    }
+    let rows = sqlx::query(
+        "SELECT id, action_id, kind, src, dest, strategy, pid, created_at FROM apply_journal WHERE state = 'pending' ORDER BY id",
+    )
+    .fetch_all(pool)
+    .await?;
+    let own_pid = std::process::id() as i64;
+    for row in rows {
+        if let Some(pid) = row.get::<Option<i64>, _>(6) {
+            if pid != own_pid && process_alive(pid, row.get(7)) {
+                continue;
+            }
+        }
+        let journal_id: i64 = row.get(0);
+        let action_id: Option<i64> = row.get(1);
+        let kind: String = row.get(2);
+        let src: String = row.get(3);
+        let dest: Option<String> = row.get(4);
+        let strategy: String = row.get::<Option<String>, _>(5).unwrap_or_default();
+        let src_exists = Path::new(&src).exists();
+        let dest_exists = dest
+            .as_deref()
+            .map(|d| Path::new(d).exists())
+            .unwrap_or(false);
+
+        let mut tx = pool.begin().await?;
+        let state = if kind == "merge_duplicate" {
+            if fs_apply::is_link_strategy(&strategy) {
+                // The duplicate's path stays valid either way; drop a half-made link and let
+                // the action run again.
+                let _ = std::fs::remove_file(fs_apply::link_temp_path(Path::new(&src)));
+                "rolled_back"
+            } else if src_exists {
+                "rolled_back"
+            } else {
+                if let (Some(aid), Some(trash)) = (action_id, trash_dir) {
+                    if let Some(object) =
+                        merge_backup(&mut tx, &src, dest.as_deref(), trash).await?
+                    {
+                        sqlx::query("UPDATE actions SET backup_path = ?2 WHERE id = ?1")
+                            .bind(aid)
+                            .bind(object.to_string_lossy())
+                            .execute(&mut *tx)
+                            .await?;
+                        crate::trash_store::record(&mut *tx, aid, &src, &object).await?;
+                    }
+                }
+                if let Some(survivor) = dest.as_deref() {
+                    sqlx::query(
+                        "INSERT OR IGNORE INTO file_tags (file_id, tag_id, confidence, source)
+                         SELECT (SELECT id FROM files WHERE path = ?2), ft.tag_id, 1.0, 'merge'
+                         FROM file_tags ft WHERE ft.file_id = (SELECT id FROM files WHERE path = ?1)
+                           AND EXISTS(SELECT 1 FROM files WHERE path = ?2)",
+                    )
+                    .bind(&src)
+                    .bind(survivor)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                mark_executed(&mut tx, action_id).await?;
+                if let Some(aid) = action_id {
+                    crate::undo::snapshot_merged(&mut tx, aid).await?;
+                }
+                fts::remove_path(&mut tx, &src).await?;
+                sqlx::query("DELETE FROM files WHERE path = ?1")
+                    .bind(&src)
+                    .execute(&mut *tx)
+                    .await?;
+                "done"
+            }
+        } else {
+            match (src_exists, dest_exists, dest.as_deref()) {
+                (false, true, Some(d)) if kind == "move_dir" => {
+                    // Finished renames and finished merges both leave only the destination; the
+                    // rows below follow by prefix, as after a rename.
+                    let renamed = crate::dirs::DirMove {
+                        renamed: true,
+                        files: Vec::new(),
+                    };
+                    crate::dirs::record_dir_move(&mut tx, action_id, &src, d, &renamed).await?;
+                    mark_executed(&mut tx, action_id).await?;
+                    "done"
+                }
+                (false, true, Some(d)) => {
+                    record_move(&mut tx, &src, d).await?;
+                    mark_executed(&mut tx, action_id).await?;
+                    "done"
+                }
+                (true, false, _) => "rolled_back",
+                _ => {
+                    if let Some(aid) = action_id {
+                        sqlx::query("UPDATE actions SET status = 'error' WHERE id = ?1")
+                            .bind(aid)
+                            .execute(&mut *tx)
+                            .await?;
+                    }
+                    "failed"
+                }
+            }
+        };
+        let detail = match state {
+            "done" => "recovered: filesystem change had completed",
+            "rolled_back" => "recovered: filesystem untouched",
+            _ => "recovered: source and destination both present or both missing",
+        };
+        finish(&mut tx, journal_id, state, Some(detail)).await?;
+        // A completed merge took its action with the deleted files row.
+        sqlx::query("INSERT INTO audit (action_id, event, detail) VALUES ((SELECT id FROM actions WHERE id = ?1), 'recover', ?2)")
+            .bind(action_id)
+            .bind(detail)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        match state {
+            "done" => report.completed += 1,
+            "rolled_back" => report.rolled_back += 1,
+            _ => report.failed += 1,
+        }
+    }
+    Ok(report)
+}
+
+/// The trash object a merged-away duplicate was backed up to: addressed by its stored full hash
+/// or, failing that, by the hash of the survivor it was verified identical to.
+async fn merge_backup(
+    conn: &mut SqliteConnection,
+    src: &str,
+    survivor: Option<&str>,
+    trash_dir: &Path,
+) -> Result<Option<std::path::PathBuf>> {
+    let stored: Option<String> = sqlx::query_scalar("SELECT full_hash FROM files WHERE path = ?1")
+        .bind(src)
+        .fetch_optional(&mut *conn)
+        .await?
+        .flatten();
+    let hash = match (stored, survivor) {
+        (Some(h), _) => h,
+        (None, Some(s)) => match fs_apply::full_hash(Path::new(s)) {
+            Ok(h) => h,
+            Err(_) => return Ok(None),
+        },
+        (None, None) => return Ok(None),
+    };
+    let object = fs_apply::trash_object_path(trash_dir, &hash);
+    Ok(object.is_file().then_some(object))
+}
+
+async fn mark_executed(conn: &mut SqliteConnection, action_id: Option<i64>) -> Result<()> {
+    if let Some(aid) = action_id {
+        sqlx::query("UPDATE actions SET status = 'executed', executed_at = strftime('%s','now') WHERE id = ?1")
+            .bind(aid)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
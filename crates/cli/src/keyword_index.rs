@@ -1,53 +1,256 @@
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Bump whenever the schema or document layout changes; indexes written with another
+/// version are rebuilt from the DB instead of being opened.
+pub const SCHEMA_VERSION: u32 = 2;
+const VERSION_FILE: &str = "organizer_schema_version";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexState {
+    Missing,
+    Current,
+    /// Written by another build; carries the version found on disk, if any.
+    Stale(Option<u32>),
+}
+
+/// Index directory next to the SQLite database (temp dir for in-memory databases).
+pub fn index_dir(db_path: &str) -> PathBuf {
+    let stripped = db_path.strip_prefix("sqlite://").unwrap_or(db_path);
+    let db = PathBuf::from(stripped);
+    let base = if stripped == ":memory:" {
+        std::env::temp_dir()
+    } else {
+        db.parent().unwrap_or_else(|| Path::new(".")).to_path_buf()
+    };
+    let dir = base.join(".organizer_keyword_index");
+    let _ = std::fs::create_dir_all(&dir);
+    dir
+}
+
+pub fn index_state(path: &Path) -> IndexState {
+    if !path.join("meta.json").exists() {
+        return IndexState::Missing;
+    }
+    let found = std::fs::read_to_string(path.join(VERSION_FILE))
+        .ok()
+        .and_then(|s| s.trim().parse::<u32>().ok());
+    match found {
+        Some(v) if v == SCHEMA_VERSION => IndexState::Current,
+        other => IndexState::Stale(other),
+    }
+}
+
+/// Refuses to touch an index this build did not write.
+pub fn ensure_current(path: &Path) -> Result<()> {
+    match index_state(path) {
+        IndexState::Current => Ok(()),
+        IndexState::Missing => bail!(
+            "keyword index not found at {}; run `rebuild-keyword-index --full`",
+            path.display()
+        ),
+        IndexState::Stale(found) => bail!(
+            "keyword index at {} has schema version {} but this build expects {}; run `rebuild-keyword-index --full`",
+            path.display(),
+            found.map_or_else(|| "unknown".to_string(), |v| v.to_string()),
+            SCHEMA_VERSION
+        ),
+    }
+}
+
+/// Removes every index file and leaves an empty directory behind.
+pub fn wipe(path: &Path) -> Result<()> {
+    if path.exists() {
+        std::fs::remove_dir_all(path)?;
+    }
+    std::fs::create_dir_all(path)?;
+    Ok(())
+}
+
+pub fn write_version(path: &Path) -> Result<()> {
+    std::fs::write(path.join(VERSION_FILE), SCHEMA_VERSION.to_string())?;
+    Ok(())
+}
+
+/// One keyword-index document: a chunk of a file, or the file itself when it has no chunks.
+/// Every document carries the file-level fields so name/tag matches hit any chunk of the file.
+#[derive(Debug, Clone, Default)]
+pub struct KeywordDoc {
+    pub path: String,
+    pub file_id: i64,
+    pub chunk_id: Option<i64>,
+    pub name: String,
+    pub ext: String,
+    pub mime: String,
+    pub mtime: i64,
+    pub size: i64,
+    pub tags: Vec<String>,
+    pub meta: Vec<String>,
+    pub content: String,
+}
+
+/// Best-scoring hit per file with a highlighted snippet of the matching chunk.
+#[derive(Debug, Clone, Serialize)]
+pub struct KeywordHit {
+    pub path: String,
+    pub score: f32,
+    pub chunk_id: Option<i64>,
+    pub snippet: Option<String>,
+}
+
+/// Wraps highlighted byte ranges of `fragment` in `**` markers.
+pub fn render_highlight(fragment: &str, ranges: &[std::ops::Range<usize>]) -> String {
+    let mut out = String::with_capacity(fragment.len() + ranges.len() * 4);
+    let mut last = 0;
+    for r in ranges {
+        if r.start < last || r.end > fragment.len() {
+            continue;
+        }
+        out.push_str(&fragment[last..r.start]);
+        out.push_str("**");
+        out.push_str(&fragment[r.start..r.end]);
+        out.push_str("**");
+        last = r.end;
+    }
+    out.push_str(&fragment[last..]);
+    out
+}
+
 #[cfg(feature = "keyword-index")]
 pub mod enabled {
+    use super::{ensure_current, render_highlight, wipe, write_version, KeywordDoc, KeywordHit};
     use anyhow::{anyhow, Result};
+    use std::collections::HashSet;
     use std::path::Path;
     use tantivy::collector::TopDocs;
-    use tantivy::doc;
-    use tantivy::schema::{Schema, STORED, TEXT};
-    use tantivy::{Index, IndexWriter, ReloadPolicy, Term};
-
-    pub fn build_index(path: &Path, docs: &[(String, String)]) -> Result<()> {
-        std::fs::create_dir_all(path)?;
-        let index = Index::open_in_dir(path).or_else(|_| {
-            let mut schema_builder = Schema::builder();
-            schema_builder.add_text_field("path", STORED);
-            schema_builder.add_text_field("text", TEXT);
-            let schema = schema_builder.build();
-            Index::create_in_dir(path, schema)
-        })?;
-        let schema = index.schema();
-        let path_field = schema
-            .get_field("path")
-            .map_err(|_| anyhow!("path field missing in index schema"))?;
-        let text_field = schema
-            .get_field("text")
-            .map_err(|_| anyhow!("text field missing in index schema"))?;
+    use tantivy::schema::{Field, Schema, FAST, INDEXED, STORED, STRING, TEXT};
+    use tantivy::{Document, Index, IndexWriter, ReloadPolicy, SnippetGenerator, Term};
+
+    // Query-time boosts: a hit in the file name or tags outranks a passing mention in the body.
+    const NAME_BOOST: f32 = 3.0;
+    const TAGS_BOOST: f32 = 2.0;
+    const PATH_BOOST: f32 = 1.5;
+    const SNIPPET_CHARS: usize = 200;
+
+    struct Fields {
+        path: Field,
+        path_text: Field,
+        file_id: Field,
+        chunk_id: Field,
+        name: Field,
+        ext: Field,
+        mime: Field,
+        mtime: Field,
+        size: Field,
+        tags: Field,
+        meta: Field,
+        content: Field,
+    }
+
+    impl Fields {
+        fn from_schema(schema: &Schema) -> Result<Self> {
+            let get = |name: &str| {
+                schema
+                    .get_field(name)
+                    .map_err(|_| anyhow!("{} field missing in index schema", name))
+            };
+            Ok(Self {
+                path: get("path")?,
+                path_text: get("path_text")?,
+                file_id: get("file_id")?,
+                chunk_id: get("chunk_id")?,
+                name: get("name")?,
+                ext: get("ext")?,
+                mime: get("mime")?,
+                mtime: get("mtime")?,
+                size: get("size")?,
+                tags: get("tags")?,
+                meta: get("meta")?,
+                content: get("content")?,
+            })
+        }
+    }
+
+    fn build_schema() -> Schema {
+        let mut schema_builder = Schema::builder();
+        // Raw path term so whole files can be deleted/replaced by path.
+        schema_builder.add_text_field("path", STRING | STORED);
+        schema_builder.add_text_field("path_text", TEXT);
+        schema_builder.add_i64_field("file_id", INDEXED | STORED);
+        schema_builder.add_i64_field("chunk_id", STORED);
+        schema_builder.add_text_field("name", TEXT | STORED);
+        schema_builder.add_text_field("ext", TEXT);
+        schema_builder.add_text_field("mime", TEXT);
+        // Numeric fields back the `modified:` and `size:` range filters.
+        schema_builder.add_i64_field("mtime", INDEXED | FAST);
+        schema_builder.add_i64_field("size", INDEXED | FAST);
+        schema_builder.add_text_field("tags", TEXT);
+        schema_builder.add_text_field("meta", TEXT);
+        schema_builder.add_text_field("content", TEXT | STORED);
+        schema_builder.build()
+    }
+
+    fn to_document(f: &Fields, d: &KeywordDoc) -> Document {
+        let mut doc = Document::default();
+        doc.add_text(f.path, &d.path);
+        doc.add_text(f.path_text, &d.path);
+        doc.add_i64(f.file_id, d.file_id);
+        if let Some(cid) = d.chunk_id {
+            doc.add_i64(f.chunk_id, cid);
+        }
+        doc.add_text(f.name, &d.name);
+        doc.add_text(f.ext, &d.ext);
+        doc.add_text(f.mime, &d.mime);
+        doc.add_i64(f.mtime, d.mtime);
+        doc.add_i64(f.size, d.size);
+        for t in &d.tags {
+            doc.add_text(f.tags, t);
+        }
+        for m in &d.meta {
+            doc.add_text(f.meta, m);
+        }
+        doc.add_text(f.content, &d.content);
+        doc
+    }
+
+    /// Opens an index written by this build; mismatched versions or schemas are an error.
+    fn open(path: &Path) -> Result<(Index, Fields)> {
+        ensure_current(path)?;
+        let index = Index::open_in_dir(path)?;
+        let fields = Fields::from_schema(&index.schema())?;
+        Ok((index, fields))
+    }
+
+    /// Wipes the directory and writes a fresh index with the current schema.
+    pub fn build_index(path: &Path, docs: &[KeywordDoc]) -> Result<()> {
+        wipe(path)?;
+        let index = Index::create_in_dir(path, build_schema())?;
+        let fields = Fields::from_schema(&index.schema())?;
         let mut writer: IndexWriter = index.writer(50_000_000)?;
-        // Reset before refresh to keep the index in sync with the DB snapshot.
-        let _ = writer.delete_all_documents();
-        for (p, t) in docs {
-            let _ = writer.add_document(doc!(path_field=>p.as_str(), text_field=>t.as_str()));
+        for d in docs {
+            writer.add_document(to_document(&fields, d))?;
         }
         writer.commit()?;
         use tantivy::Directory;
         index.directory().sync_directory()?;
+        // Written last so a crash mid-build leaves an index that reads as stale.
+        write_version(path)?;
         Ok(())
     }
 
-    pub fn upsert_docs(path: &Path, docs: &[(String, String)]) -> Result<()> {
-        let index = Index::open_in_dir(path)?;
-        let schema = index.schema();
-        let path_field = schema
-            .get_field("path")
-            .map_err(|_| anyhow!("path field missing in index schema"))?;
-        let text_field = schema
-            .get_field("text")
-            .map_err(|_| anyhow!("text field missing in index schema"))?;
+    pub fn upsert_docs(path: &Path, docs: &[KeywordDoc]) -> Result<()> {
+        let (index, fields) = open(path)?;
         let mut writer: IndexWriter = index.writer(50_000_000)?;
-        for (p, t) in docs {
-            writer.delete_term(Term::from_field_text(path_field, p));
-            let _ = writer.add_document(doc!(path_field=>p.as_str(), text_field=>t.as_str()));
+        // Drop every chunk of a touched file once, then re-add the fresh chunk docs.
+        let mut cleared = HashSet::new();
+        for d in docs {
+            if cleared.insert(d.path.as_str()) {
+                writer.delete_term(Term::from_field_text(fields.path, &d.path));
+            }
+        }
+        for d in docs {
+            writer.add_document(to_document(&fields, d))?;
         }
         writer.commit()?;
         use tantivy::Directory;
@@ -56,14 +259,10 @@ pub mod enabled {
     }
 
     pub fn delete_docs(path: &Path, paths: &[String]) -> Result<()> {
-        let index = Index::open_in_dir(path)?;
-        let schema = index.schema();
-        let path_field = schema
-            .get_field("path")
-            .map_err(|_| anyhow!("path field missing in index schema"))?;
+        let (index, fields) = open(path)?;
         let mut writer: IndexWriter = index.writer(50_000_000)?;
         for p in paths {
-            writer.delete_term(Term::from_field_text(path_field, p));
+            writer.delete_term(Term::from_field_text(fields.path, p));
         }
         writer.commit()?;
         use tantivy::Directory;
@@ -71,26 +270,57 @@ pub mod enabled {
         Ok(())
     }
 
-    pub fn search(path: &Path, query_str: &str, limit: usize) -> Result<Vec<String>> {
-        let index = Index::open_in_dir(path)?;
-        let schema = index.schema();
-        let path_field = schema.get_field("path").unwrap();
-        let text_field = schema.get_field("text").unwrap();
+    pub fn search(path: &Path, query_str: &str, limit: usize) -> Result<Vec<KeywordHit>> {
+        let (index, fields) = open(path)?;
         let reader = index
             .reader_builder()
             .reload_policy(ReloadPolicy::OnCommit)
             .try_into()?;
         let searcher = reader.searcher();
-        let parser = tantivy::query::QueryParser::for_index(&index, vec![text_field]);
+        let mut parser = tantivy::query::QueryParser::for_index(
+            &index,
+            vec![
+                fields.content,
+                fields.name,
+                fields.path_text,
+                fields.tags,
+                fields.meta,
+                fields.ext,
+                fields.mime,
+            ],
+        );
+        parser.set_field_boost(fields.name, NAME_BOOST);
+        parser.set_field_boost(fields.tags, TAGS_BOOST);
+        parser.set_field_boost(fields.path_text, PATH_BOOST);
         let query = parser.parse_query(query_str)?;
-        let top_docs = searcher.search(&query, &TopDocs::with_limit(limit))?;
+        // Several chunks of one file can match; over-fetch so `limit` distinct files survive.
+        let top_docs = searcher.search(&query, &TopDocs::with_limit(limit.max(1) * 8))?;
+        let mut snippets = SnippetGenerator::create(&searcher, &*query, fields.content)?;
+        snippets.set_max_num_chars(SNIPPET_CHARS);
+        let mut seen = HashSet::new();
         let mut results = Vec::new();
-        for (_score, addr) in top_docs {
+        for (score, addr) in top_docs {
             let doc = searcher.doc(addr)?;
-            if let Some(val) = doc.get_first(path_field) {
-                if let Some(text) = val.as_text() {
-                    results.push(text.to_string());
-                }
+            let Some(doc_path) = doc.get_first(fields.path).and_then(|v| v.as_text()) else {
+                continue;
+            };
+            if !seen.insert(doc_path.to_string()) {
+                continue;
+            }
+            let snippet = snippets.snippet_from_doc(&doc);
+            let snippet = if snippet.fragment().is_empty() {
+                None
+            } else {
+                Some(render_highlight(snippet.fragment(), snippet.highlighted()))
+            };
+            results.push(KeywordHit {
+                path: doc_path.to_string(),
+                score,
+                chunk_id: doc.get_first(fields.chunk_id).and_then(|v| v.as_i64()),
+                snippet,
+            });
+            if results.len() >= limit {
+                break;
             }
         }
         Ok(results)
@@ -99,15 +329,16 @@ pub mod enabled {
 
 #[cfg(not(feature = "keyword-index"))]
 pub mod enabled {
+    use super::{KeywordDoc, KeywordHit};
     use anyhow::Result;
     use std::path::Path;
-    pub fn build_index(_path: &Path, _docs: &[(String, String)]) -> Result<()> {
+    pub fn build_index(_path: &Path, _docs: &[KeywordDoc]) -> Result<()> {
         Ok(())
     }
-    pub fn search(_path: &Path, _query: &str, _limit: usize) -> Result<Vec<String>> {
+    pub fn search(_path: &Path, _query: &str, _limit: usize) -> Result<Vec<KeywordHit>> {
         Ok(vec![])
     }
-    pub fn upsert_docs(_path: &Path, _docs: &[(String, String)]) -> Result<()> {
+    pub fn upsert_docs(_path: &Path, _docs: &[KeywordDoc]) -> Result<()> {
         Ok(())
     }
     pub fn delete_docs(_path: &Path, _paths: &[String]) -> Result<()> {
@@ -1,7 +1,18 @@
 //! Public library modules for the CLI crate
 pub mod apply;
+pub mod dirs;
 pub mod fs_apply;
+pub mod journal;
 pub mod keyword_index;
 pub mod paths;
+pub mod plan;
+pub mod plan_file;
+pub mod preflight;
+pub mod progress;
+pub mod query;
+pub mod report;
+pub mod safety;
+pub mod sessions;
+pub mod trash_store;
 pub mod undo;
 pub mod watch;
\ No newline at end of file
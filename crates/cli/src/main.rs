@@ -2,29 +2,42 @@ use anyhow::Result;
 use clap::{Parser, Subcommand};
 use organizer_core::config;
 use organizer_core::config::AppConfig;
+use organizer_core::dedupe;
 use organizer_core::embeddings;
+use organizer_core::fts;
 use organizer_core::pipeline;
 use organizer_core::pipeline::PipelineMode;
 use organizer_core::search;
 use organizer_core::vectorstore::AsQdrant;
 use sqlx::{QueryBuilder, Row};
 use std::collections::{HashMap, HashSet};
-use std::path::{Path, PathBuf};
-use storage;
+use std::path::PathBuf;
 
 // Import modules from the crate's own library
 use cli::apply;
+use cli::journal;
 use cli::keyword_index;
+use cli::plan;
+use cli::plan_file;
+use cli::progress;
+use cli::query::SearchQuery;
+use cli::report;
+use cli::safety;
+use cli::sessions;
+use cli::trash_store;
 use cli::undo;
 use cli::watch;
 
 
+const SIMILAR_SNIPPET_CHARS: usize = 200;
+
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
 
     let cli = Cli::parse();
     let cfg = config::load(cli.config.as_deref())?;
+    recover_interrupted_applies(&cfg).await;
 
     match cli.command {
         Commands::Scan { json } => run_pipeline(cfg, PipelineMode::Scan, json).await,
@@ -33,15 +46,17 @@ async fn main() -> Result<()> {
             if list {
                 run_actions(
                     cfg,
-                    "planned",
-                    None,
-                    None,
-                    false,
-                    false,
-                    &tags,
-                    &[],
-                    false,
-                    json,
+                    ActionsArgs {
+                        status: "planned",
+                        rule: None,
+                        kind: None,
+                        has_backup: false,
+                        duplicates_only: false,
+                        tags: &tags,
+                        fields: &[],
+                        summary: false,
+                        json,
+                    },
                 )
                 .await
             } else {
@@ -50,8 +65,10 @@ async fn main() -> Result<()> {
         }
         Commands::Apply {
             dry_run,
+            execute,
             force,
             ids,
+            plan,
             json,
             fields,
             summary,
@@ -60,20 +77,28 @@ async fn main() -> Result<()> {
             deny_paths,
             trash_dir,
             conflict,
+            byte_compare,
+            plan_format,
         } => {
             run_apply(
                 cfg,
-                dry_run,
-                force,
-                ids.as_deref(),
-                json,
-                summary,
-                verbose,
-                allow_paths,
-                deny_paths,
-                trash_dir,
-                conflict,
-                Some(fields),
+                ApplyArgs {
+                    dry_run,
+                    execute,
+                    force,
+                    ids,
+                    plan_path: plan,
+                    json,
+                    summary,
+                    verbose,
+                    allow_override: allow_paths,
+                    deny_override: deny_paths,
+                    trash_override: trash_dir,
+                    conflict,
+                    byte_compare,
+                    plan_format,
+                    fields: Some(fields),
+                },
             )
             .await
         }
@@ -92,17 +117,48 @@ async fn main() -> Result<()> {
         } => {
             run_search(
                 cfg,
-                query,
-                topk,
-                path_prefix,
-                mime,
-                hybrid,
-                after,
-                before,
-                keyword_index,
-                keyword_index_refresh,
-                tags,
-                fields,
+                SearchArgs {
+                    query,
+                    topk,
+                    path_prefix,
+                    mime,
+                    hybrid,
+                    after,
+                    before,
+                    keyword_index,
+                    keyword_index_refresh,
+                    tags,
+                    fields,
+                },
+            )
+            .await
+        }
+        Commands::Similar {
+            path,
+            topk,
+            mode,
+            filter,
+            path_prefix,
+            mime,
+            after,
+            before,
+            tags,
+            fields,
+        } => {
+            run_similar(
+                cfg,
+                SimilarArgs {
+                    path,
+                    topk,
+                    mode,
+                    filter,
+                    path_prefix,
+                    mime,
+                    after,
+                    before,
+                    tags,
+                    fields,
+                },
             )
             .await
         }
@@ -118,44 +174,42 @@ async fn main() -> Result<()> {
             summary,
             json,
         } => {
-            let dedupe_default_fields = vec![
-                "id".to_string(),
-                "path".to_string(),
-                "kind".to_string(),
-                "status".to_string(),
-                "duplicate_of".to_string(),
-                "snippet".to_string(),
-            ];
-            let mut effective_fields = fields;
-            if show_duplicates && effective_fields.is_empty() {
-                effective_fields = dedupe_default_fields;
+            if show_duplicates {
+                return run_duplicate_groups(cfg, &status, summary, json).await;
             }
             run_actions(
                 cfg,
-                &status,
-                rule.as_deref(),
-                kind.as_deref(),
-                has_backup,
-                duplicates_only || show_duplicates,
-                &tags,
-                &effective_fields,
-                summary,
-                json,
+                ActionsArgs {
+                    status: &status,
+                    rule: rule.as_deref(),
+                    kind: kind.as_deref(),
+                    has_backup,
+                    duplicates_only,
+                    tags: &tags,
+                    fields: &fields,
+                    summary,
+                    json,
+                },
             )
             .await
         }
-        Commands::Undo { ids, backup_path } => {
-            run_undo(cfg, ids.as_deref(), backup_path.as_deref()).await
-        }
+        Commands::Undo {
+            ids,
+            session,
+            last,
+            backup_path,
+        } => run_undo(cfg, ids.as_deref(), session, last, backup_path.as_deref()).await,
         Commands::Watch {
             paths,
             debounce_ms,
             quiet,
         } => run_watch(cfg, paths, debounce_ms, quiet).await,
         Commands::RebuildVectors { batch, paths, dirty_only } => run_rebuild_vectors(cfg, batch, paths, dirty_only).await,
-        Commands::RebuildKeywordIndex { force, dirty_only } => {
-            run_rebuild_keyword_index(cfg, force, dirty_only).await
-        }
+        Commands::RebuildKeywordIndex {
+            force,
+            dirty_only,
+            full,
+        } => run_rebuild_keyword_index(cfg, force, dirty_only, full).await,
         Commands::BackfillFullHashes { paths, exclude } => {
             run_backfill_full_hashes(cfg, paths, exclude).await
         }
@@ -164,6 +218,34 @@ async fn main() -> Result<()> {
             exclude,
             batch,
         } => run_maintain(cfg, paths, exclude, batch).await,
+        Commands::Report { command } => match command {
+            ReportCommand::Space { top, json } => run_report_space(cfg, top, json).await,
+        },
+        Commands::Plan { command } => match command {
+            PlanCommand::Export { ids } => run_plan_export(cfg, ids.as_deref()).await,
+        },
+        Commands::Sessions { command } => match command {
+            SessionsCommand::List { limit, json } => run_sessions_list(cfg, limit, json).await,
+            SessionsCommand::Show { id, json } => run_sessions_show(cfg, id, json).await,
+        },
+        Commands::Trash { command } => match command {
+            TrashCommand::List { json } => run_trash_list(cfg, json).await,
+            TrashCommand::Restore { id, to } => run_trash_restore(cfg, id, to).await,
+            TrashCommand::Purge {
+                older_than_days,
+                max_bytes,
+                all,
+                dry_run,
+                json,
+            } => {
+                let policy = trash_store::RetentionPolicy {
+                    all,
+                    max_age_days: older_than_days,
+                    max_bytes,
+                };
+                run_trash_purge(cfg, policy, dry_run, json).await
+            }
+        },
     }
 }
 
@@ -207,15 +289,22 @@ enum Commands {
     },
     /// Apply planned actions (move/tag/rename) from the database
     Apply {
-        /// Do not actually perform changes, only print what would happen
-        #[arg(long, default_value_t = true)]
+        /// Do not actually perform changes, only print what would happen (the default while
+        /// `safety.dry_run` is set)
+        #[arg(long, default_value_t = false, conflicts_with = "execute")]
         dry_run: bool,
+        /// Perform the changes even though `safety.dry_run` is set
+        #[arg(long, default_value_t = false)]
+        execute: bool,
         /// Bypass the confirmation prompt
         #[arg(long, short, default_value_t = false)]
         force: bool,
         /// Comma-separated action IDs to apply; if omitted, apply all planned
         #[arg(long)]
         ids: Option<String>,
+        /// Apply exactly the actions of a reviewed plan file (see `plan export`)
+        #[arg(long, conflicts_with = "ids")]
+        plan: Option<PathBuf>,
         /// Output JSON
         #[arg(long)]
         json: bool,
@@ -240,10 +329,17 @@ enum Commands {
         /// Conflict policy: rename|skip|overwrite
         #[arg(long, default_value = "rename")]
         conflict: String,
+        /// Compare duplicates byte by byte before merge_duplicate (on top of size + full hash)
+        #[arg(long, default_value_t = false)]
+        byte_compare: bool,
+        /// Dry-run output: text (tree diff), json or shell (a script for review)
+        #[arg(long, default_value = "text", value_parser = ["text", "json", "shell"])]
+        plan_format: String,
     },
     /// Semantic search against vector store
     Search {
-        /// Query text to embed and search
+        /// Query text, optionally with filters: tag:, ext:, mime:, path:, modified:, size:
+        /// (e.g. `invoice tag:finance ext:pdf modified:>2024-01-01 size:>1MB -tag:archive`)
         query: String,
         /// Number of results
         #[arg(short, long, default_value_t = 5)]
@@ -276,6 +372,38 @@ enum Commands {
         #[arg(long, value_delimiter = ',', num_args = 1.., default_values_t = Vec::<String>::new())]
         fields: Vec<String>,
     },
+    /// Find files similar to a given file using its chunk vectors
+    Similar {
+        /// Indexed file to find neighbours for
+        path: String,
+        /// Number of results
+        #[arg(short, long, default_value_t = 5)]
+        topk: u64,
+        /// Query strategy: average (one query, mean of chunk vectors) | multi (one query per chunk)
+        #[arg(long, default_value = "average")]
+        mode: String,
+        /// Filters in search query syntax, e.g. "tag:finance ext:pdf -path:~/Archive"
+        #[arg(long)]
+        filter: Option<String>,
+        /// Optional path prefix filter
+        #[arg(long)]
+        path_prefix: Option<String>,
+        /// Optional MIME filter
+        #[arg(long)]
+        mime: Option<String>,
+        /// Only include files modified after this RFC3339 timestamp
+        #[arg(long)]
+        after: Option<String>,
+        /// Only include files modified before this RFC3339 timestamp
+        #[arg(long)]
+        before: Option<String>,
+        /// Filter by tag names (comma-separated)
+        #[arg(long, value_delimiter = ',', num_args = 1.., default_values_t = Vec::<String>::new())]
+        tags: Vec<String>,
+        /// Restrict output fields (comma-separated), e.g. path,score,snippet
+        #[arg(long, value_delimiter = ',', num_args = 1.., default_values_t = Vec::<String>::new())]
+        fields: Vec<String>,
+    },
     /// List actions from the database
     Actions {
         /// Status filter (planned|executed|error)
@@ -293,7 +421,7 @@ enum Commands {
         /// Show only duplicate-related actions
         #[arg(long, default_value_t = false)]
         duplicates_only: bool,
-        /// Convenience flag: show duplicates with id/path/kind/status/duplicate_of/snippet
+        /// Show duplicate groups (survivor plus merge candidates) instead of individual actions
         #[arg(long, default_value_t = false)]
         show_duplicates: bool,
         /// Print a brief summary (non-JSON)
@@ -312,8 +440,14 @@ enum Commands {
     /// Undo executed actions (placeholder)
     Undo {
         /// Comma-separated action IDs; if omitted, reset all executed
-        #[arg(long)]
+        #[arg(long, conflicts_with_all = ["session", "last"])]
         ids: Option<String>,
+        /// Undo every executed action of one apply session, newest first
+        #[arg(long, conflicts_with = "last")]
+        session: Option<i64>,
+        /// Undo the most recent apply session that still has executed actions
+        #[arg(long)]
+        last: bool,
         /// Provide backup path to restore from if not recorded
         #[arg(long)]
         backup_path: Option<String>,
@@ -350,6 +484,9 @@ enum Commands {
         /// Only rebuild dirty paths
         #[arg(long, default_value_t = false)]
         dirty_only: bool,
+        /// Wipe and recreate the index (and the SQLite full-text table) from the DB
+        #[arg(long, default_value_t = false, conflicts_with = "dirty_only")]
+        full: bool,
     },
     /// Rescan to backfill full hashes into the DB (uses hash_mode=full temporarily)
     BackfillFullHashes {
@@ -372,6 +509,209 @@ enum Commands {
         #[arg(long, default_value_t = 500)]
         batch: usize,
     },
+    /// Reports over the indexed files
+    Report {
+        #[command(subcommand)]
+        command: ReportCommand,
+    },
+    /// Export planned actions to an editable plan file for `apply --plan`
+    Plan {
+        #[command(subcommand)]
+        command: PlanCommand,
+    },
+    /// Apply sessions: one per `apply` run
+    Sessions {
+        #[command(subcommand)]
+        command: SessionsCommand,
+    },
+    /// Backups kept in `safety.trash_dir`
+    Trash {
+        #[command(subcommand)]
+        command: TrashCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum TrashCommand {
+    /// Trash entries, newest first
+    List {
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Copy an entry back to its original path
+    Restore {
+        /// Trash entry ID (see `trash list`)
+        id: i64,
+        /// Restore here instead of the original path
+        #[arg(long)]
+        to: Option<String>,
+    },
+    /// Delete backups by age and/or total size (defaults to the [trash] config)
+    Purge {
+        /// Purge entries older than this many days
+        #[arg(long)]
+        older_than_days: Option<u64>,
+        /// Purge oldest entries until the trash holds at most this many bytes
+        #[arg(long)]
+        max_bytes: Option<u64>,
+        /// Purge everything
+        #[arg(long)]
+        all: bool,
+        /// Report what would be purged without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum PlanCommand {
+    /// Print planned actions as TOML, one per line (`plan export > plan.toml`)
+    Export {
+        /// Comma-separated action IDs; if omitted, every planned action
+        #[arg(long)]
+        ids: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SessionsCommand {
+    /// Recent sessions, newest first
+    List {
+        /// Maximum sessions to show
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// One session and the actions it applied
+    Show {
+        /// Session ID
+        id: i64,
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReportCommand {
+    /// Disk usage by duplicate group, directory, tag, mime and age, with reclaimable bytes
+    Space {
+        /// Rows per section
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Reconciles apply operations a crash left half-finished. Best effort: a missing or
+/// unmigrated database is left for the command itself to report, and while another process
+/// holds the apply lock its journal is left alone.
+async fn recover_interrupted_applies(cfg: &AppConfig) {
+    let db = &cfg.database.path;
+    if !db.starts_with("sqlite:") && !std::path::Path::new(db).exists() {
+        return;
+    }
+    let Ok(pool) = storage::connect(db).await else {
+        return;
+    };
+    let Ok(lock) = journal::lock(&pool).await else {
+        return;
+    };
+    let trash_dir = cfg.safety.trash_dir.as_deref().map(std::path::Path::new);
+    match journal::recover(&pool, trash_dir).await {
+        Ok(r) if r.total() > 0 => tracing::warn!(
+            completed = r.completed,
+            rolled_back = r.rolled_back,
+            failed = r.failed,
+            "recovered interrupted apply"
+        ),
+        Ok(_) => {}
+        Err(e) => tracing::warn!("apply journal recovery failed: {}", e),
+    }
+    if let Err(e) = lock.release().await {
+        tracing::warn!("releasing the apply lock failed: {}", e);
+    }
+}
+
+async fn run_report_space(cfg: AppConfig, top: usize, json: bool) -> Result<()> {
+    let pool = storage::connect(&cfg.database.path).await?;
+    let now = chrono::Utc::now().timestamp();
+    let r = report::space_report(&pool, &cfg.scan.include, now, top).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&r)?);
+        return Ok(());
+    }
+    let hb = report::human_bytes;
+    println!(
+        "space: files={}, total={}, reclaimable={} (planned merge_duplicate actions)",
+        r.total_files,
+        hb(r.total_bytes),
+        hb(r.reclaimable_bytes)
+    );
+    if !r.duplicate_groups.is_empty() {
+        println!("\nduplicate groups");
+        println!(
+            "  {:>6}  {:>6}  {:>10}  {:>12}  survivor",
+            "group", "copies", "size", "reclaimable"
+        );
+        for g in &r.duplicate_groups {
+            println!(
+                "  {:>6}  {:>6}  {:>10}  {:>12}  {}",
+                g.group_id,
+                g.copies,
+                hb(g.size),
+                hb(g.reclaimable),
+                g.survivor.as_deref().unwrap_or("-")
+            );
+            for m in &g.merges {
+                println!("  {:>40}  action {} {}", hb(m.bytes), m.action_id, m.path);
+            }
+        }
+    }
+    for (title, buckets) in [
+        ("directory", &r.by_directory),
+        ("tag", &r.by_tag),
+        ("mime", &r.by_mime),
+        ("age", &r.by_age),
+    ] {
+        if buckets.iter().all(|b| b.files == 0) {
+            continue;
+        }
+        println!("\nby {}", title);
+        println!(
+            "  {:>8}  {:>10}  {:>12}  {}",
+            "files", "size", "reclaimable", title
+        );
+        for b in buckets.iter().filter(|b| b.files > 0) {
+            println!(
+                "  {:>8}  {:>10}  {:>12}  {}",
+                b.files,
+                hb(b.bytes),
+                hb(b.reclaimable),
+                b.key
+            );
+        }
+    }
+    println!("\ncleanup candidates");
+    println!(
+        "  {}: {} files, {}",
+        r.stale_downloads.key,
+        r.stale_downloads.files,
+        hb(r.stale_downloads.bytes)
+    );
+    println!("  largest files:");
+    for f in &r.largest_files {
+        println!("  {:>10}  {}", hb(f.bytes), f.path);
+    }
+    Ok(())
 }
 
 async fn run_pipeline(cfg: AppConfig, mode: PipelineMode, json: bool) -> Result<()> {
@@ -382,7 +722,17 @@ async fn run_pipeline(cfg: AppConfig, mode: PipelineMode, json: bool) -> Result<
         PipelineMode::Suggest => "suggest",
         PipelineMode::All => "all",
     };
-    let summary = pipeline::run_with_mode_summary(cfg.clone(), mode).await?;
+    let scans = matches!(mode, PipelineMode::Scan | PipelineMode::All);
+    let (bar, bar_handle) = if scans && !json {
+        progress::spawn_bar()
+    } else {
+        (None, None)
+    };
+    let summary = pipeline::run_with_progress(cfg.clone(), mode, bar).await;
+    if let Some(handle) = bar_handle {
+        let _ = handle.await;
+    }
+    let summary = summary?;
     if json {
         let mut summary_json = serde_json::json!({
             "status": "ok",
@@ -390,6 +740,8 @@ async fn run_pipeline(cfg: AppConfig, mode: PipelineMode, json: bool) -> Result<
             "discovered": summary.discovered,
             "processed_files": summary.processed_files,
             "embedded_chunks": summary.embedded_chunks,
+            "moved_files": summary.moved_files,
+            "missing_files": summary.missing_files,
         });
         if is_suggest {
             if let Ok(actions) = fetch_actions(&cfg.database.path).await {
@@ -405,6 +757,12 @@ async fn run_pipeline(cfg: AppConfig, mode: PipelineMode, json: bool) -> Result<
             "{}: discovered {}, processed {}, embedded chunks {}",
             mode_label, summary.discovered, summary.processed_files, summary.embedded_chunks
         );
+        if summary.moved_files + summary.missing_files > 0 {
+            println!(
+                "moved files: {}, missing files: {}",
+                summary.moved_files, summary.missing_files
+            );
+        }
         if is_suggest {
             if let Ok(actions) = fetch_actions(&cfg.database.path).await {
                 println!("planned actions: {}", actions.len());
@@ -414,8 +772,8 @@ async fn run_pipeline(cfg: AppConfig, mode: PipelineMode, json: bool) -> Result<
     Ok(())
 }
 
-async fn run_search(
-    cfg: AppConfig,
+/// Arguments of `search`, as given on the command line.
+struct SearchArgs {
     query: String,
     topk: u64,
     path_prefix: Option<String>,
@@ -427,45 +785,58 @@ async fn run_search(
     keyword_index_refresh: bool,
     tags: Vec<String>,
     fields: Vec<String>,
-) -> Result<()> {
+}
+
+async fn run_search(cfg: AppConfig, args: SearchArgs) -> Result<()> {
+    let SearchArgs {
+        query,
+        topk,
+        path_prefix,
+        mime,
+        hybrid,
+        after,
+        before,
+        keyword_index,
+        keyword_index_refresh,
+        tags,
+        fields,
+    } = args;
     // Build provider registry for embeddings and vector store.
     let registry = pipeline::build_registry(&cfg);
     let vector_store = pipeline::build_vector_store(&cfg);
-    let tag_filter = if tags.is_empty() { None } else { Some(tags) };
+    let query = SearchQuery::parse(&query)?.with_flags(
+        path_prefix,
+        mime,
+        after.as_deref(),
+        before.as_deref(),
+        &tags,
+    )?;
     let fields = if fields.is_empty() {
         vec![
             "path".to_string(),
             "score".to_string(),
             "tags".to_string(),
             "snippet".to_string(),
+            "bundle".to_string(),
             "payload".to_string(),
         ]
     } else {
         fields
     };
-    let tagged_paths: Option<HashSet<String>> = if let Some(t) = &tag_filter {
-        Some(fetch_paths_with_tags(&cfg.database.path, t).await?)
-    } else {
-        None
-    };
     let use_keyword_index = keyword_index && cfg!(feature = "keyword-index");
-    if let Some(qdrant) = vector_store.downcast_qdrant() {
+    let qdrant = vector_store.downcast_qdrant();
+    // A filter-only query (e.g. `tag:finance ext:pdf`) has nothing to embed.
+    if let Some(qdrant) = qdrant.as_ref().filter(|_| query.has_text()) {
         let embed = embeddings::embed(
             embeddings::EmbeddingRequest {
-                texts: vec![query.clone()],
+                texts: vec![query.text_query()],
                 provider: Some(cfg.embeddings.provider.clone()),
             },
             &registry,
         )
         .await?;
         let vector = embed.vectors.into_iter().next().unwrap_or_default();
-        let filter = build_qdrant_filter(
-            path_prefix.clone(),
-            mime.clone(),
-            after.as_deref(),
-            before.as_deref(),
-        );
-        let results = search::vector_search(&qdrant, vector, topk, filter).await?;
+        let results = search::vector_search(qdrant, vector, topk, query.to_qdrant_filter()).await?;
         let mut results_json: Vec<serde_json::Value> = results
             .iter()
             .map(|r| {
@@ -476,20 +847,19 @@ async fn run_search(
                 })
             })
             .collect();
-        if let Some(pref) = &path_prefix {
-            results_json.retain(|r| {
-                extract_path(r)
-                    .map(|p| p.starts_with(pref))
-                    .unwrap_or(false)
-            });
-        }
-        if let Some(allowed) = &tagged_paths {
-            results_json.retain(|r| {
-                extract_path(r)
-                    .map(|p| allowed.contains(&p))
-                    .unwrap_or(false)
-            });
-        }
+        // Tags, exact path prefixes and exclusions are not all in the Qdrant payload;
+        // re-check every hit against the DB with the same filters.
+        let hit_paths: Vec<String> = results_json.iter().filter_map(extract_path).collect();
+        let allowed: HashSet<String> = enrich_paths(&cfg.database.path, &hit_paths, &query)
+            .await?
+            .into_iter()
+            .filter_map(|v| extract_path(&v))
+            .collect();
+        results_json.retain(|r| {
+            extract_path(r)
+                .map(|p| allowed.contains(&p))
+                .unwrap_or(false)
+        });
         if hybrid {
             let mut seen = std::collections::HashSet::new();
             for r in &results_json {
@@ -502,30 +872,10 @@ async fn run_search(
                 }
             }
             let fallback = if use_keyword_index {
-                refresh_keyword_index_if_dirty(&cfg, tag_filter.as_deref()).await?;
-                keyword_index_search(
-                    &cfg,
-                    &query,
-                    topk,
-                    path_prefix.clone(),
-                    mime.clone(),
-                    after.as_deref(),
-                    before.as_deref(),
-                    tag_filter.as_deref(),
-                    keyword_index_refresh,
-                )
-                .await?
+                watch::refresh_keyword_index_if_dirty(&cfg.database.path).await?;
+                keyword_index_search(&cfg, &query, topk, keyword_index_refresh).await?
             } else {
-                keyword_search(
-                    &cfg.database.path,
-                    &query,
-                    path_prefix.clone(),
-                    mime.clone(),
-                    after.as_deref(),
-                    before.as_deref(),
-                    tag_filter.as_deref(),
-                )
-                .await?
+                keyword_search(&cfg.database.path, &query, topk as usize).await?
             };
             for f in fallback {
                 if let Some(p) = f.get("path").and_then(|v| v.as_str()) {
@@ -537,65 +887,171 @@ async fn run_search(
         }
         attach_tags(&cfg.database.path, &mut results_json).await?;
         attach_snippets(&cfg.database.path, &mut results_json).await?;
+        attach_bundles(&cfg.database.path, &mut results_json).await?;
         let filtered = filter_fields(results_json, &fields);
         let out = serde_json::to_string_pretty(&filtered)?;
         println!("{}", out);
         return Ok(());
     }
 
-    // Fallback: simple DB LIKE search if vector store is unavailable.
-    eprintln!("Vector store not configured; using fallback path search.");
+    // Fallback: keyword search (Tantivy or SQLite FTS) if vector store is unavailable.
+    if qdrant.is_none() {
+        eprintln!("Vector store not configured; using fallback keyword search.");
+    }
     let mut results = if use_keyword_index {
-        refresh_keyword_index_if_dirty(&cfg, tag_filter.as_deref()).await?;
-        keyword_index_search(
-            &cfg,
-            &query,
-            topk,
-            path_prefix.clone(),
-            mime.clone(),
-            after.as_deref(),
-            before.as_deref(),
-            tag_filter.as_deref(),
-            keyword_index_refresh,
-        )
-        .await?
+        watch::refresh_keyword_index_if_dirty(&cfg.database.path).await?;
+        keyword_index_search(&cfg, &query, topk, keyword_index_refresh).await?
     } else {
-        keyword_search(
-            &cfg.database.path,
-            &query,
-            path_prefix.clone(),
-            mime.clone(),
-            after.as_deref(),
-            before.as_deref(),
-            tag_filter.as_deref(),
-        )
-        .await?
+        keyword_search(&cfg.database.path, &query, topk as usize).await?
     };
-    if let Some(allowed) = &tagged_paths {
-        results.retain(|r| {
-            extract_path(r)
-                .map(|p| allowed.contains(&p))
-                .unwrap_or(false)
-        });
-    }
     attach_tags(&cfg.database.path, &mut results).await?;
     attach_snippets(&cfg.database.path, &mut results).await?;
+    attach_bundles(&cfg.database.path, &mut results).await?;
     let filtered = filter_fields(results, &fields);
     println!("{}", serde_json::to_string_pretty(&filtered)?);
     Ok(())
 }
 
-fn keyword_index_dir(db_path: &str) -> PathBuf {
-    let stripped = db_path.strip_prefix("sqlite://").unwrap_or(db_path);
-    let db = PathBuf::from(stripped);
-    let base = if stripped == ":memory:" {
-        std::env::temp_dir()
+/// Arguments of `similar`, as given on the command line.
+struct SimilarArgs {
+    path: String,
+    topk: u64,
+    mode: String,
+    filter: Option<String>,
+    path_prefix: Option<String>,
+    mime: Option<String>,
+    after: Option<String>,
+    before: Option<String>,
+    tags: Vec<String>,
+    fields: Vec<String>,
+}
+
+async fn run_similar(cfg: AppConfig, args: SimilarArgs) -> Result<()> {
+    let SimilarArgs {
+        path,
+        topk,
+        mode,
+        filter,
+        path_prefix,
+        mime,
+        after,
+        before,
+        tags,
+        fields,
+    } = args;
+    let query = SearchQuery::parse(filter.as_deref().unwrap_or_default())?.with_flags(
+        path_prefix,
+        mime,
+        after.as_deref(),
+        before.as_deref(),
+        &tags,
+    )?;
+    if query.has_text() {
+        anyhow::bail!(
+            "--filter only takes field filters (tag:, ext:, mime:, path:, modified:, size:), got text {:?}",
+            query.text_query()
+        );
+    }
+    let fields = if fields.is_empty() {
+        vec![
+            "path".to_string(),
+            "score".to_string(),
+            "tags".to_string(),
+            "snippet".to_string(),
+        ]
     } else {
-        db.parent().unwrap_or_else(|| Path::new(".")).to_path_buf()
+        fields
+    };
+    let vector_store = pipeline::build_vector_store(&cfg);
+    let Some(qdrant) = vector_store.downcast_qdrant() else {
+        anyhow::bail!("similar requires a configured vector store");
     };
-    let dir = base.join(".organizer_keyword_index");
-    let _ = std::fs::create_dir_all(&dir);
-    dir
+    let pool = storage::connect(&cfg.database.path).await?;
+    let Some(file_id) = sqlx::query_scalar::<_, i64>("SELECT id FROM files WHERE path = ?1")
+        .bind(&path)
+        .fetch_optional(&pool)
+        .await?
+    else {
+        anyhow::bail!("{} is not indexed; run scan first", path);
+    };
+    let hashes: Vec<String> =
+        sqlx::query_scalar("SELECT hash FROM chunks WHERE file_id = ?1 ORDER BY start")
+            .bind(file_id)
+            .fetch_all(&pool)
+            .await?;
+    let vectors: Vec<Vec<f32>> = if hashes.is_empty() {
+        Vec::new()
+    } else {
+        qdrant
+            .retrieve(hashes)
+            .await?
+            .result
+            .into_iter()
+            .map(|p| p.vector)
+            .filter(|v| !v.is_empty())
+            .collect()
+    };
+    if vectors.is_empty() {
+        anyhow::bail!("{} has no embedded chunks; run classify to embed it", path);
+    }
+    let neighbours = search::similar_files(
+        &qdrant,
+        file_id,
+        vectors,
+        search::SimilarMode::from(mode.as_str()),
+        topk,
+        query.to_qdrant_filter(),
+    )
+    .await?;
+    // Same post-filter as search: tags and exact path prefixes are checked against the DB.
+    let hit_paths: Vec<String> = neighbours.iter().map(|n| n.path.clone()).collect();
+    let allowed: HashSet<String> = enrich_paths(&cfg.database.path, &hit_paths, &query)
+        .await?
+        .into_iter()
+        .filter_map(|v| extract_path(&v))
+        .collect();
+    let neighbours: Vec<search::SimilarFile> = neighbours
+        .into_iter()
+        .filter(|n| allowed.contains(&n.path))
+        .take(topk as usize)
+        .collect();
+    let chunk_ids: Vec<i64> = neighbours.iter().filter_map(|n| n.chunk_id).collect();
+    let mut chunk_text: HashMap<i64, String> = HashMap::new();
+    if !chunk_ids.is_empty() {
+        let mut qb = QueryBuilder::new("SELECT id, text_preview FROM chunks WHERE id IN (");
+        let mut separated = qb.separated(", ");
+        for id in &chunk_ids {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(")");
+        for row in qb.build().fetch_all(&pool).await? {
+            let id: i64 = row.get(0);
+            if let Ok(Some(text)) = row.try_get::<Option<String>, _>(1) {
+                chunk_text.insert(id, text);
+            }
+        }
+    }
+    let mut results: Vec<serde_json::Value> = neighbours
+        .into_iter()
+        .map(|n| {
+            // The best-matching chunk is the snippet, not the file's first chunk.
+            let snippet = n
+                .chunk_id
+                .and_then(|id| chunk_text.get(&id))
+                .map(|t| t.chars().take(SIMILAR_SNIPPET_CHARS).collect::<String>());
+            serde_json::json!({
+                "path": n.path,
+                "score": n.score,
+                "chunk_id": n.chunk_id,
+                "snippet": snippet,
+                "payload": n.payload,
+            })
+        })
+        .collect();
+    attach_tags(&cfg.database.path, &mut results).await?;
+    let filtered = filter_fields(results, &fields);
+    println!("{}", serde_json::to_string_pretty(&filtered)?);
+    Ok(())
 }
 
 fn extract_path(val: &serde_json::Value) -> Option<String> {
@@ -611,31 +1067,6 @@ fn extract_path(val: &serde_json::Value) -> Option<String> {
         .map(|s| s.to_string())
 }
 
-async fn fetch_paths_with_tags(db_path: &str, tags: &[String]) -> Result<HashSet<String>> {
-    if tags.is_empty() {
-        return Ok(HashSet::new());
-    }
-    let pool = storage::connect(db_path).await?;
-    let mut query =
-        QueryBuilder::new("SELECT DISTINCT files.path FROM files JOIN file_tags ft ON ft.file_id = files.id JOIN tags t ON t.id = ft.tag_id WHERE t.name IN (");
-    let mut first = true;
-    for tag in tags {
-        if !first {
-            query.push(", ");
-        }
-        first = false;
-        query.push_bind(tag);
-    }
-    query.push(")");
-    let rows = query.build().fetch_all(&pool).await?;
-    let mut paths = HashSet::new();
-    for row in rows {
-        let path: String = row.get(0);
-        paths.insert(path);
-    }
-    Ok(paths)
-}
-
 async fn fetch_tags_for_paths(
     db_path: &str,
     paths: &[String],
@@ -669,8 +1100,8 @@ async fn fetch_tags_for_paths(
     Ok(map)
 }
 
-async fn attach_tags(db_path: &str, results: &mut Vec<serde_json::Value>) -> Result<()> {
-    let paths: Vec<String> = results.iter().filter_map(|r| extract_path(r)).collect();
+async fn attach_tags(db_path: &str, results: &mut [serde_json::Value]) -> Result<()> {
+    let paths: Vec<String> = results.iter().filter_map(extract_path).collect();
     if paths.is_empty() {
         return Ok(());
     }
@@ -731,8 +1162,8 @@ async fn fetch_snippets_for_paths(
     Ok(map)
 }
 
-async fn attach_snippets(db_path: &str, results: &mut Vec<serde_json::Value>) -> Result<()> {
-    let paths: Vec<String> = results.iter().filter_map(|r| extract_path(r)).collect();
+async fn attach_snippets(db_path: &str, results: &mut [serde_json::Value]) -> Result<()> {
+    let paths: Vec<String> = results.iter().filter_map(extract_path).collect();
     if paths.is_empty() {
         return Ok(());
     }
@@ -740,6 +1171,10 @@ async fn attach_snippets(db_path: &str, results: &mut Vec<serde_json::Value>) ->
     for r in results.iter_mut() {
         if let Some(path) = extract_path(r) {
             if let Some(snippet) = snippets.get(&path) {
+                // Keyword hits already carry a highlighted snippet of the matching chunk.
+                if r.get("snippet").is_some() {
+                    continue;
+                }
                 let snippet_val = serde_json::Value::String(snippet.clone());
                 if let Some(payload) = r.get_mut("payload") {
                     if payload.is_object() {
@@ -760,6 +1195,26 @@ async fn attach_snippets(db_path: &str, results: &mut Vec<serde_json::Value>) ->
     Ok(())
 }
 
+/// Adds `bundle` (kind, root and member count) to hits whose file belongs to one, so a result
+/// inside a repo or a photo with sidecars shows what it travels with.
+async fn attach_bundles(db_path: &str, results: &mut [serde_json::Value]) -> Result<()> {
+    let paths: Vec<String> = results.iter().filter_map(extract_path).collect();
+    if paths.is_empty() {
+        return Ok(());
+    }
+    let pool = storage::connect(db_path).await?;
+    let bundles = organizer_core::bundles::bundles_for_paths(&pool, &paths).await?;
+    for r in results.iter_mut() {
+        let Some(bundle) = extract_path(r).and_then(|p| bundles.get(&p)) else {
+            continue;
+        };
+        if let Some(obj) = r.as_object_mut() {
+            obj.insert("bundle".into(), serde_json::to_value(bundle)?);
+        }
+    }
+    Ok(())
+}
+
 fn filter_fields(mut results: Vec<serde_json::Value>, fields: &[String]) -> Vec<serde_json::Value> {
     if fields.is_empty() {
         return results;
@@ -780,95 +1235,94 @@ fn filter_fields(mut results: Vec<serde_json::Value>, fields: &[String]) -> Vec<
     results
 }
 
+/// Keyword search over the SQLite FTS table (BM25-ranked, one hit per file with a snippet of
+/// the best chunk). Filter-only queries list matching files, newest first.
 async fn keyword_search(
     db_path: &str,
-    query: &str,
-    path_prefix: Option<String>,
-    mime: Option<String>,
-    after: Option<&str>,
-    before: Option<&str>,
-    tags: Option<&[String]>,
+    query: &SearchQuery,
+    limit: usize,
 ) -> Result<Vec<serde_json::Value>> {
-    let tags = tags.unwrap_or(&[]);
     let pool = storage::connect(db_path).await?;
-    let mut qb = QueryBuilder::new("SELECT DISTINCT files.path, files.mime FROM files");
-    if !tags.is_empty() {
-        qb.push(" JOIN file_tags ft ON ft.file_id = files.id JOIN tags t ON t.id = ft.tag_id");
-    }
-    qb.push(" WHERE files.path LIKE ");
-    qb.push_bind(format!("%{}%", query));
-    if let Some(pref) = path_prefix {
-        qb.push(" AND files.path LIKE ");
-        qb.push_bind(format!("{}%", pref));
-    }
-    if let Some(m) = mime {
-        qb.push(" AND files.mime = ");
-        qb.push_bind(m);
-    }
-    if let Some(a) = after.and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok()) {
-        qb.push(" AND files.mtime >= ");
-        qb.push_bind(a.timestamp());
-    }
-    if let Some(b) = before.and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok()) {
-        qb.push(" AND files.mtime <= ");
-        qb.push_bind(b.timestamp());
-    }
-    if !tags.is_empty() {
-        qb.push(" AND t.name IN (");
-        let mut first = true;
-        for tag in tags {
-            if !first {
-                qb.push(", ");
-            }
-            first = false;
-            qb.push_bind(tag);
+    let Some(fts_query) = query.to_fts_query() else {
+        let mut qb = QueryBuilder::new("SELECT files.path, files.mime FROM files WHERE 1 = 1");
+        query.push_sql_filters(&mut qb, true);
+        qb.push(" ORDER BY files.mtime DESC LIMIT ");
+        qb.push_bind(limit as i64);
+        let rows = qb.build().fetch_all(&pool).await?;
+        let mut results = Vec::new();
+        for row in rows {
+            let path: String = row.get(0);
+            let mime: Option<String> = row.try_get(1).ok();
+            results.push(serde_json::json!({ "path": path, "mime": mime }));
         }
-        qb.push(")");
-    }
+        return Ok(results);
+    };
+    // Weights follow the FTS columns: name, tags, path, content.
+    let mut qb = QueryBuilder::new(
+        "SELECT files.path, files.mime, hits.chunk_id, hits.rank, hits.snippet FROM (\
+         SELECT file_id, chunk_id, bm25(search_fts, 3.0, 2.0, 1.5, 1.0) AS rank, \
+         snippet(search_fts, 3, '**', '**', '...', 24) AS snippet \
+         FROM search_fts WHERE search_fts MATCH ",
+    );
+    qb.push_bind(fts_query);
+    qb.push(") hits JOIN files ON files.id = hits.file_id WHERE 1 = 1");
+    query.push_sql_filters(&mut qb, false);
+    // Several chunks of one file can match; over-fetch so `limit` distinct files survive.
+    qb.push(" ORDER BY hits.rank LIMIT ");
+    qb.push_bind((limit.max(1) * 8) as i64);
     let rows = qb.build().fetch_all(&pool).await?;
+    let mut seen = HashSet::new();
     let mut results = Vec::new();
     for row in rows {
         let path: String = row.get(0);
+        if !seen.insert(path.clone()) {
+            continue;
+        }
         let mime: Option<String> = row.try_get(1).ok();
-        results.push(serde_json::json!({ "path": path, "mime": mime }));
+        let chunk_id: Option<i64> = row.try_get(2).ok().flatten();
+        let rank: f64 = row.try_get(3).unwrap_or_default();
+        let snippet: Option<String> = row
+            .try_get::<Option<String>, _>(4)
+            .ok()
+            .flatten()
+            .filter(|s| !s.is_empty());
+        let mut hit = serde_json::json!({
+            "path": path,
+            "mime": mime,
+            // bm25() is lower-is-better; flip it so larger scores rank higher like elsewhere.
+            "score": -rank,
+            "chunk_id": chunk_id,
+        });
+        if let Some(s) = snippet {
+            hit["snippet"] = serde_json::Value::String(s);
+        }
+        results.push(hit);
+        if results.len() >= limit {
+            break;
+        }
     }
     Ok(results)
 }
 
+/// Looks up `paths` in the DB, keeping only those that pass the query's field filters.
 async fn enrich_paths(
     db_path: &str,
     paths: &[String],
-    path_prefix: Option<String>,
-    mime: Option<String>,
-    after: Option<&str>,
-    before: Option<&str>,
+    query: &SearchQuery,
 ) -> Result<Vec<serde_json::Value>> {
     if paths.is_empty() {
         return Ok(Vec::new());
     }
     let pool = storage::connect(db_path).await?;
-    let mut qb = QueryBuilder::new("SELECT path, mime, mtime FROM files WHERE path IN (");
+    let mut qb = QueryBuilder::new(
+        "SELECT files.path, files.mime, files.mtime FROM files WHERE files.path IN (",
+    );
     let mut separated = qb.separated(", ");
     for p in paths {
         separated.push_bind(p);
     }
     separated.push_unseparated(")");
-    if let Some(pref) = path_prefix {
-        qb.push(" AND path LIKE ");
-        qb.push_bind(format!("{}%", pref));
-    }
-    if let Some(m) = mime {
-        qb.push(" AND mime = ");
-        qb.push_bind(m);
-    }
-    if let Some(a) = after.and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok()) {
-        qb.push(" AND mtime >= ");
-        qb.push_bind(a.timestamp());
-    }
-    if let Some(b) = before.and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok()) {
-        qb.push(" AND mtime <= ");
-        qb.push_bind(b.timestamp());
-    }
+    query.push_sql_filters(&mut qb, false);
     let rows = qb.build().fetch_all(&pool).await?;
     let mut results = Vec::new();
     for row in rows {
@@ -879,158 +1333,58 @@ async fn enrich_paths(
     Ok(results)
 }
 
-async fn enrich_paths_for_index(
-    db_path: &str,
-    paths: &[String],
-    tags: Option<&[String]>,
-) -> Result<(Vec<(String, String)>, Vec<String>)> {
-    if paths.is_empty() {
-        return Ok((Vec::new(), Vec::new()));
-    }
-    let pool = storage::connect(db_path).await?;
-    let mut qb = QueryBuilder::new("SELECT path, mime FROM files WHERE path IN (");
-    let mut separated = qb.separated(", ");
-    for p in paths {
-        separated.push_bind(p);
-    }
-    separated.push_unseparated(")");
-    if let Some(tag_list) = tags {
-        if !tag_list.is_empty() {
-            qb.push(" AND EXISTS (SELECT 1 FROM file_tags ft JOIN tags t ON t.id = ft.tag_id WHERE ft.file_id = files.id AND t.name IN (");
-            let mut sep = qb.separated(", ");
-            for t in tag_list {
-                sep.push_bind(t);
-            }
-            sep.push_unseparated("))");
-        }
-    }
-    let rows = qb.build().fetch_all(&pool).await?;
-    let mut docs = Vec::new();
-    let mut found = std::collections::HashSet::new();
-    for row in rows {
-        let path: String = row.get(0);
-        let mime: Option<String> = row.try_get(1).ok();
-        let mime_text = mime.as_deref().unwrap_or("");
-        docs.push((path.clone(), format!("{} {}", path, mime_text)));
-        found.insert(path);
-    }
-    let missing: Vec<String> = paths
-        .iter()
-        .filter(|p| !found.contains(*p))
-        .cloned()
-        .collect();
-    Ok((docs, missing))
-}
-
 async fn keyword_index_search(
     cfg: &AppConfig,
-    query: &str,
+    query: &SearchQuery,
     topk: u64,
-    path_prefix: Option<String>,
-    mime: Option<String>,
-    after: Option<&str>,
-    before: Option<&str>,
-    tags: Option<&[String]>,
     refresh: bool,
 ) -> Result<Vec<serde_json::Value>> {
     if !cfg!(feature = "keyword-index") {
         return Ok(Vec::new());
     }
-    let dir = keyword_index_dir(&cfg.database.path);
-    let meta_exists = dir.join("meta.json").exists();
-    if refresh || !meta_exists {
-        let docs = keyword_search(&cfg.database.path, "", None, None, None, None, tags)
-            .await?
-            .into_iter()
-            .filter_map(|v| {
-                let path = v.get("path")?.as_str()?;
-                // Use path + mime text as a minimal keyword corpus.
-                let mime_text = v.get("mime").and_then(|m| m.as_str()).unwrap_or("");
-                Some((path.to_string(), format!("{} {}", path, mime_text)))
-            })
-            .collect::<Vec<_>>();
-        let _ = keyword_index::enabled::build_index(&dir, &docs);
-    }
-    let hits = keyword_index::enabled::search(&dir, query, topk as usize).unwrap_or_default();
-    enrich_paths(&cfg.database.path, &hits, path_prefix, mime, after, before).await
-}
-
-async fn refresh_keyword_index_if_dirty(cfg: &AppConfig, tags: Option<&[String]>) -> Result<()> {
-    if !cfg!(feature = "keyword-index") {
-        return Ok(());
+    let dir = keyword_index::index_dir(&cfg.database.path);
+    if refresh || keyword_index::index_state(&dir) != keyword_index::IndexState::Current {
+        // Index every file; tag and other filters are applied at query time.
+        watch::rebuild_keyword_index(&cfg.database.path).await?;
     }
-    let pool = storage::connect(&cfg.database.path).await?;
-    let rows = sqlx::query("SELECT path FROM dirty")
-        .fetch_all(&pool)
-        .await?;
-    if rows.is_empty() {
-        return Ok(());
-    }
-    let paths: Vec<String> = rows.into_iter().filter_map(|r| r.try_get(0).ok()).collect();
-    let dir = keyword_index_dir(&cfg.database.path);
-    let (docs, missing) = enrich_paths_for_index(&cfg.database.path, &paths, tags).await?;
-    if dir.join("meta.json").exists() {
-        let _ = keyword_index::enabled::upsert_docs(&dir, &docs);
-        if !missing.is_empty() {
-            let _ = keyword_index::enabled::delete_docs(&dir, &missing);
+    let hits = keyword_index::enabled::search(&dir, &query.to_tantivy_query(), topk as usize)?;
+    let hit_paths: Vec<String> = hits.iter().map(|h| h.path.clone()).collect();
+    let mut results = enrich_paths(&cfg.database.path, &hit_paths, query).await?;
+    let by_path: HashMap<&str, &keyword_index::KeywordHit> =
+        hits.iter().map(|h| (h.path.as_str(), h)).collect();
+    for r in results.iter_mut() {
+        let Some(hit) = r
+            .get("path")
+            .and_then(|p| p.as_str())
+            .and_then(|p| by_path.get(p))
+        else {
+            continue;
+        };
+        let (score, chunk_id, snippet) = (hit.score, hit.chunk_id, hit.snippet.clone());
+        if let Some(obj) = r.as_object_mut() {
+            obj.insert("score".into(), serde_json::json!(score));
+            obj.insert("chunk_id".into(), serde_json::json!(chunk_id));
+            if let Some(s) = snippet {
+                obj.insert("snippet".into(), serde_json::Value::String(s));
+            }
         }
-    } else {
-        let _ = keyword_index::enabled::build_index(&dir, &docs);
-    }
-    let mut qb = QueryBuilder::new("DELETE FROM dirty WHERE path IN (");
-    let mut separated = qb.separated(", ");
-    for p in &paths {
-        separated.push_bind(p);
-    }
-    separated.push_unseparated(")");
-    let _ = qb.build().execute(&pool).await;
-    Ok(())
-}
-
-fn build_qdrant_filter(
-    path_prefix: Option<String>,
-    mime: Option<String>,
-    after: Option<&str>,
-    before: Option<&str>,
-) -> Option<serde_json::Value> {
-    let mut must = Vec::new();
-    if let Some(p) = path_prefix {
-        let pref = p.to_lowercase();
-        must.push(serde_json::json!({
-            "key": "path_prefixes",
-            "match": { "any": [pref] }
-        }));
-    }
-    if let Some(m) = mime {
-        must.push(serde_json::json!({
-            "key": "mime",
-            "match": { "value": m }
-        }));
-    }
-    if let Some(a) = after.and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok()) {
-        must.push(serde_json::json!({
-            "key": "mtime",
-            "range": { "gte": a.timestamp() }
-        }));
-    }
-    if let Some(b) = before.and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok()) {
-        must.push(serde_json::json!({
-            "key": "mtime",
-            "range": { "lte": b.timestamp() }
-        }));
-    }
-    if must.is_empty() {
-        None
-    } else {
-        Some(serde_json::json!({ "must": must }))
     }
+    // Keep Tantivy's ranking rather than the DB's row order.
+    results.sort_by(|a, b| {
+        let sa = a.get("score").and_then(|v| v.as_f64()).unwrap_or(0.0);
+        let sb = b.get("score").and_then(|v| v.as_f64()).unwrap_or(0.0);
+        sb.partial_cmp(&sa).unwrap_or(std::cmp::Ordering::Equal)
+    });
+    Ok(results)
 }
 
-async fn run_apply(
-    cfg: AppConfig,
+/// Arguments of `apply`, as given on the command line.
+struct ApplyArgs {
     dry_run: bool,
+    execute: bool,
     force: bool,
-    ids: Option<&str>,
+    ids: Option<String>,
+    plan_path: Option<PathBuf>,
     json: bool,
     summary: bool,
     verbose: bool,
@@ -1038,9 +1392,31 @@ async fn run_apply(
     deny_override: Option<String>,
     trash_override: Option<String>,
     conflict: String,
+    byte_compare: bool,
+    plan_format: String,
     fields: Option<Vec<String>>,
-) -> Result<()> {
+}
+
+async fn run_apply(cfg: AppConfig, args: ApplyArgs) -> Result<()> {
+    let ApplyArgs {
+        dry_run,
+        execute,
+        force,
+        ids,
+        plan_path,
+        json,
+        summary,
+        verbose,
+        allow_override,
+        deny_override,
+        trash_override,
+        conflict,
+        byte_compare,
+        plan_format,
+        fields,
+    } = args;
     let mut safety = cfg.safety.clone();
+    safety.byte_compare_duplicates |= byte_compare;
     if let Some(allow) = allow_override {
         safety.allow_paths = allow
             .split(',')
@@ -1058,9 +1434,65 @@ async fn run_apply(
     if let Some(trash) = trash_override {
         safety.trash_dir = Some(trash);
     }
+    let dry_run = safety::resolve_dry_run(&safety, dry_run, execute);
+    safety.dry_run = dry_run;
+    // A reviewed plan narrows the run to its checked actions; refused ones are reported as errors.
+    let mut refused = Vec::new();
+    let reviewed_ids;
+    let ids = match plan_path {
+        Some(file) => {
+            let pool = storage::connect(&cfg.database.path).await?;
+            let reviewed = plan_file::parse(&std::fs::read_to_string(&file)?)?;
+            let (ready, rejected) = plan_file::prepare(&pool, &reviewed).await?;
+            for v in &rejected {
+                eprintln!(
+                    "plan: action {} refused: {}",
+                    v.id,
+                    v.error.as_deref().unwrap_or("")
+                );
+            }
+            refused = rejected;
+            reviewed_ids = ready
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            Some(reviewed_ids.as_str())
+        }
+        None => ids.as_deref(),
+    };
+    if dry_run && !json && !summary {
+        let pool = storage::connect(&cfg.database.path).await?;
+        let plan = plan::simulate(&pool, ids, &safety, &conflict).await?;
+        match plan_format.as_str() {
+            "json" => println!("{}", serde_json::to_string_pretty(&plan)?),
+            "shell" => print!("{}", plan::render_shell(&plan)),
+            _ => print!("{}", plan::render_text(&plan)),
+        }
+        return Ok(());
+    }
 
-    let actions =
+    let mut actions =
         apply::apply_actions(&cfg.database.path, dry_run, force, ids, &safety, &conflict).await?;
+    actions.extend(refused);
+    if !dry_run && (cfg.trash.retention_days.is_some() || cfg.trash.max_bytes.is_some()) {
+        let pool = storage::connect(&cfg.database.path).await?;
+        let policy = trash_store::RetentionPolicy {
+            all: false,
+            max_age_days: cfg.trash.retention_days,
+            max_bytes: cfg.trash.max_bytes,
+        };
+        let purged =
+            trash_store::purge(&pool, &policy, chrono::Utc::now().timestamp(), false).await?;
+        if purged.entries > 0 {
+            tracing::info!(
+                entries = purged.entries,
+                bytes = purged.bytes_freed,
+                backups_cleared = purged.backups_cleared,
+                "trash retention purged backups"
+            );
+        }
+    }
     let mut vals: Vec<serde_json::Value> = actions
         .iter()
         .filter_map(|a| serde_json::to_value(a).ok())
@@ -1106,20 +1538,33 @@ async fn run_apply(
     Ok(())
 }
 
-async fn run_actions(
-    cfg: AppConfig,
-    status: &str,
-    rule: Option<&str>,
-    kind: Option<&str>,
+/// Filters and output options of `actions` (and `suggest --list`).
+struct ActionsArgs<'a> {
+    status: &'a str,
+    rule: Option<&'a str>,
+    kind: Option<&'a str>,
     has_backup: bool,
     duplicates_only: bool,
-    tags: &[String],
-    fields: &[String],
+    tags: &'a [String],
+    fields: &'a [String],
     summary: bool,
     json: bool,
-) -> Result<()> {
+}
+
+async fn run_actions(cfg: AppConfig, args: ActionsArgs<'_>) -> Result<()> {
+    let ActionsArgs {
+        status,
+        rule,
+        kind,
+        has_backup,
+        duplicates_only,
+        tags,
+        fields,
+        summary,
+        json,
+    } = args;
     let pool = storage::connect(&cfg.database.path).await?;
-    let mut query = QueryBuilder::new("SELECT actions.id, files.path, actions.kind, actions.payload_json, actions.status, actions.backup_path, GROUP_CONCAT(t.name, ',') as tags FROM actions JOIN files ON files.id = actions.file_id LEFT JOIN file_tags ft ON ft.file_id = files.id LEFT JOIN tags t ON t.id = ft.tag_id");
+    let mut query = QueryBuilder::new("SELECT actions.id, COALESCE(files.path, directories.path), actions.kind, actions.payload_json, actions.status, actions.backup_path, GROUP_CONCAT(t.name, ',') as tags FROM actions LEFT JOIN files ON files.id = actions.file_id LEFT JOIN directories ON directories.id = actions.dir_id LEFT JOIN file_tags ft ON ft.file_id = files.id LEFT JOIN tags t ON t.id = ft.tag_id");
     query.push(" WHERE actions.status = ");
     query.push_bind(status);
     if let Some(r) = rule {
@@ -1145,7 +1590,7 @@ async fn run_actions(
         separated.push_unseparated("");
         query.push(")");
     }
-    query.push(" GROUP BY actions.id, files.path, actions.kind, actions.payload_json, actions.status, actions.backup_path ORDER BY actions.id");
+    query.push(" GROUP BY actions.id, COALESCE(files.path, directories.path), actions.kind, actions.payload_json, actions.status, actions.backup_path ORDER BY actions.id");
     let rows = query.build().fetch_all(&pool).await?;
     let mut vals = Vec::new();
     for row in rows {
@@ -1163,6 +1608,9 @@ async fn run_actions(
                     .and_then(|d| d.as_str())
                     .map(|s| s.to_string())
             });
+        let score = serde_json::from_str::<serde_json::Value>(&payload)
+            .ok()
+            .and_then(|v| v.get("score").and_then(|s| s.as_f64()));
         let tags_vec: Vec<String> = tags_col
             .unwrap_or_default()
             .split(',')
@@ -1185,6 +1633,7 @@ async fn run_actions(
             "rule": rule,
             "backup_path": backup,
             "duplicate_of": duplicate_of,
+            "score": score,
             "tags": tags_vec,
         }));
     }
@@ -1201,7 +1650,7 @@ async fn run_actions(
             "snippet".to_string(),
         ]
     } else {
-        fields.iter().cloned().collect()
+        fields.to_vec()
     };
     if json {
         let mut enriched = vals;
@@ -1237,9 +1686,158 @@ async fn run_actions(
     Ok(())
 }
 
+/// Duplicate groups for `actions --show-duplicates`: exact groups from `duplicate_groups`, plus
+/// near-duplicate `dedupe` suggestions clustered by the file they point at. A group is listed
+/// when one of its member actions has `status`.
+async fn run_duplicate_groups(
+    cfg: AppConfig,
+    status: &str,
+    summary: bool,
+    json: bool,
+) -> Result<()> {
+    let pool = storage::connect(&cfg.database.path).await?;
+    let mut groups = Vec::new();
+    for g in dedupe::list_duplicate_groups(&pool).await? {
+        if !g
+            .members
+            .iter()
+            .any(|m| m.action_status.as_deref() == Some(status))
+        {
+            continue;
+        }
+        let members: Vec<serde_json::Value> = g
+            .members
+            .iter()
+            .map(|m| {
+                serde_json::json!({
+                    "path": m.path,
+                    "role": if m.survivor { "survivor" } else { "duplicate" },
+                    "action_id": m.action_id,
+                    "status": m.action_status,
+                })
+            })
+            .collect();
+        groups.push(serde_json::json!({
+            "group_id": g.id,
+            "kind": "exact",
+            "hash": g.full_hash,
+            "size": g.size,
+            "policy": g.policy,
+            "survivor": g.survivor,
+            "members": members,
+        }));
+    }
+
+    let rows = sqlx::query(
+        "SELECT a.id, f.path, a.payload_json, a.status FROM actions a JOIN files f ON f.id = a.file_id
+         WHERE a.kind = 'dedupe' AND a.status = ?1 ORDER BY a.id",
+    )
+    .bind(status)
+    .fetch_all(&pool)
+    .await?;
+    let mut near: Vec<(String, Vec<serde_json::Value>)> = Vec::new();
+    for row in rows {
+        let payload: serde_json::Value =
+            serde_json::from_str(&row.get::<String, _>(2)).unwrap_or_default();
+        let Some(original) = payload.get("duplicate_of").and_then(|v| v.as_str()) else {
+            continue;
+        };
+        let member = serde_json::json!({
+            "path": row.get::<String, _>(1),
+            "role": "duplicate",
+            "action_id": row.get::<i64, _>(0),
+            "status": row.get::<String, _>(3),
+            "score": payload.get("score"),
+            "method": payload.get("method"),
+        });
+        match near.iter_mut().find(|(o, _)| o == original) {
+            Some((_, members)) => members.push(member),
+            None => near.push((original.to_string(), vec![member])),
+        }
+    }
+    for (original, mut members) in near {
+        members.insert(
+            0,
+            serde_json::json!({ "path": original, "role": "survivor" }),
+        );
+        groups.push(serde_json::json!({
+            "group_id": null,
+            "kind": "near",
+            "survivor": original,
+            "members": members,
+        }));
+    }
+
+    let duplicates = |g: &serde_json::Value| {
+        g["members"]
+            .as_array()
+            .map(|m| m.iter().filter(|m| m["role"] == "duplicate").count())
+            .unwrap_or(0)
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&groups)?);
+    } else if summary {
+        let exact = groups.iter().filter(|g| g["kind"] == "exact").count();
+        let files: usize = groups.iter().map(duplicates).sum();
+        let reclaimable: i64 = groups
+            .iter()
+            .map(|g| g["size"].as_i64().unwrap_or(0) * duplicates(g) as i64)
+            .sum();
+        println!(
+            "duplicate groups: groups={}, exact={}, near={}, duplicates={}, reclaimable_bytes={}, status={}",
+            groups.len(),
+            exact,
+            groups.len() - exact,
+            files,
+            reclaimable,
+            status
+        );
+    } else {
+        for g in &groups {
+            match g["group_id"].as_i64() {
+                Some(id) => println!(
+                    "group {} (exact, {} bytes, policy {})",
+                    id,
+                    g["size"].as_i64().unwrap_or(0),
+                    g["policy"].as_str().unwrap_or_default()
+                ),
+                None => println!(
+                    "near-duplicates of {}",
+                    g["survivor"].as_str().unwrap_or_default()
+                ),
+            }
+            let verb = if g["kind"] == "exact" {
+                "merge "
+            } else {
+                "dedupe"
+            };
+            for m in g["members"].as_array().into_iter().flatten() {
+                let path = m["path"].as_str().unwrap_or_default();
+                if m["role"] == "survivor" {
+                    println!("  keep   {}", path);
+                } else {
+                    let score = m["score"]
+                        .as_f64()
+                        .map(|s| format!(" score={:.2}", s))
+                        .unwrap_or_default();
+                    println!(
+                        "  {} {} [action {} {}{}]",
+                        verb,
+                        path,
+                        m["action_id"].as_i64().unwrap_or_default(),
+                        m["status"].as_str().unwrap_or_default(),
+                        score
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 async fn fetch_actions(db_path: &str) -> Result<Vec<serde_json::Value>> {
     let pool = storage::connect(db_path).await?;
-    let rows = sqlx::query("SELECT actions.id, files.path, actions.kind, actions.payload_json, actions.status, actions.backup_path, GROUP_CONCAT(t.name, ',') as tags FROM actions JOIN files ON files.id = actions.file_id LEFT JOIN file_tags ft ON ft.file_id = files.id LEFT JOIN tags t ON t.id = ft.tag_id GROUP BY actions.id, files.path, actions.kind, actions.payload_json, actions.status, actions.backup_path ORDER BY actions.id")
+    let rows = sqlx::query("SELECT actions.id, COALESCE(files.path, directories.path), actions.kind, actions.payload_json, actions.status, actions.backup_path, GROUP_CONCAT(t.name, ',') as tags FROM actions LEFT JOIN files ON files.id = actions.file_id LEFT JOIN directories ON directories.id = actions.dir_id LEFT JOIN file_tags ft ON ft.file_id = files.id LEFT JOIN tags t ON t.id = ft.tag_id GROUP BY actions.id, COALESCE(files.path, directories.path), actions.kind, actions.payload_json, actions.status, actions.backup_path ORDER BY actions.id")
         .fetch_all(&pool)
         .await?;
     let mut vals = Vec::new();
@@ -1278,11 +1876,194 @@ async fn fetch_actions(db_path: &str) -> Result<Vec<serde_json::Value>> {
     Ok(vals)
 }
 
-async fn run_undo(cfg: AppConfig, ids: Option<&str>, backup_override: Option<&str>) -> Result<()> {
+async fn run_undo(
+    cfg: AppConfig,
+    ids: Option<&str>,
+    session: Option<i64>,
+    last: bool,
+    backup_override: Option<&str>,
+) -> Result<()> {
+    let session = if last {
+        let pool = storage::connect(&cfg.database.path).await?;
+        match sessions::last_undoable(&pool).await? {
+            Some(id) => Some(id),
+            None => {
+                println!("no apply session to undo");
+                return Ok(());
+            }
+        }
+    } else {
+        session
+    };
+    if let Some(id) = session {
+        let n = undo::undo_session(&cfg.database.path, id, backup_override).await?;
+        println!("undo session {}: {} actions reverted", id, n);
+        return Ok(());
+    }
     undo::undo_actions(&cfg.database.path, ids, backup_override).await?;
     Ok(())
 }
 
+async fn run_plan_export(cfg: AppConfig, ids: Option<&str>) -> Result<()> {
+    let pool = storage::connect(&cfg.database.path).await?;
+    print!("{}", plan_file::export(&pool, ids).await?);
+    Ok(())
+}
+
+async fn run_sessions_list(cfg: AppConfig, limit: usize, json: bool) -> Result<()> {
+    let pool = storage::connect(&cfg.database.path).await?;
+    let list = sessions::list_sessions(&pool, limit).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&list)?);
+        return Ok(());
+    }
+    if list.is_empty() {
+        println!("no apply sessions");
+        return Ok(());
+    }
+    println!(
+        "{:>6}  {:<20}  {:<9}  {:>9}  {:>6}  {:>8}",
+        "id", "started", "status", "succeeded", "failed", "executed"
+    );
+    for s in list {
+        println!(
+            "{:>6}  {:<20}  {:<9}  {:>9}  {:>6}  {:>8}",
+            s.id,
+            format_ts(s.started_at),
+            s.status,
+            s.succeeded,
+            s.failed,
+            s.executed
+        );
+    }
+    Ok(())
+}
+
+async fn run_sessions_show(cfg: AppConfig, id: i64, json: bool) -> Result<()> {
+    let pool = storage::connect(&cfg.database.path).await?;
+    let Some(detail) = sessions::show_session(&pool, id).await? else {
+        anyhow::bail!("no apply session {}", id);
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&detail)?);
+        return Ok(());
+    }
+    let s = &detail.session;
+    println!(
+        "session {}: {} (started {}, succeeded={}, failed={}, still executed={})",
+        s.id,
+        s.status,
+        format_ts(s.started_at),
+        s.succeeded,
+        s.failed,
+        s.executed
+    );
+    for a in &detail.actions {
+        println!(
+            "{:>6}  {:<15}  {:<8}  {}",
+            a.id,
+            a.kind,
+            a.status,
+            a.path.as_deref().unwrap_or("-")
+        );
+    }
+    Ok(())
+}
+
+async fn run_trash_list(cfg: AppConfig, json: bool) -> Result<()> {
+    let pool = storage::connect(&cfg.database.path).await?;
+    let entries = trash_store::list_entries(&pool).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+    if entries.is_empty() {
+        println!("trash is empty");
+        return Ok(());
+    }
+    for e in &entries {
+        println!(
+            "{:>6}  {:<20}  {:>10}  {}{}{}",
+            e.id,
+            format_ts(e.created_at),
+            report::human_bytes(e.size),
+            e.original_path,
+            e.action_id
+                .map(|a| format!("  (action {})", a))
+                .unwrap_or_default(),
+            if e.restored_at.is_some() {
+                "  [restored]"
+            } else {
+                ""
+            }
+        );
+    }
+    let mut seen = HashSet::new();
+    let stored: i64 = entries
+        .iter()
+        .filter(|e| seen.insert(e.hash.as_str()))
+        .map(|e| e.size)
+        .sum();
+    println!(
+        "{} entries, {} stored in {} objects",
+        entries.len(),
+        report::human_bytes(stored),
+        seen.len()
+    );
+    Ok(())
+}
+
+async fn run_trash_restore(cfg: AppConfig, id: i64, to: Option<String>) -> Result<()> {
+    let pool = storage::connect(&cfg.database.path).await?;
+    let dest = trash_store::restore(&pool, id, to.as_deref().map(std::path::Path::new)).await?;
+    println!("restored trash entry {} to {}", id, dest.display());
+    Ok(())
+}
+
+async fn run_trash_purge(
+    cfg: AppConfig,
+    mut policy: trash_store::RetentionPolicy,
+    dry_run: bool,
+    json: bool,
+) -> Result<()> {
+    if !policy.all && policy.max_age_days.is_none() && policy.max_bytes.is_none() {
+        policy.max_age_days = cfg.trash.retention_days;
+        policy.max_bytes = cfg.trash.max_bytes;
+    }
+    if !policy.all && policy.max_age_days.is_none() && policy.max_bytes.is_none() {
+        println!("no retention configured; pass --older-than-days, --max-bytes or --all");
+        return Ok(());
+    }
+    let pool = storage::connect(&cfg.database.path).await?;
+    let now = chrono::Utc::now().timestamp();
+    let r = trash_store::purge(&pool, &policy, now, dry_run).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&r)?);
+    } else {
+        println!(
+            "{}purged {} entries, {} objects, {} freed",
+            if dry_run { "dry-run: would have " } else { "" },
+            r.entries,
+            r.objects,
+            report::human_bytes(r.bytes_freed)
+        );
+        if r.backups_cleared > 0 {
+            println!(
+                "{} executed actions {} their backup and can no longer be restored by undo",
+                r.backups_cleared,
+                if dry_run { "would lose" } else { "lost" }
+            );
+        }
+    }
+    Ok(())
+}
+
+fn format_ts(ts: i64) -> String {
+    chrono::DateTime::from_timestamp(ts, 0)
+        .map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| ts.to_string())
+}
+
 async fn run_watch(
     cfg: AppConfig,
     paths: Vec<String>,
@@ -1345,13 +2126,16 @@ async fn run_rebuild_vectors(
     Ok(())
 }
 
-async fn run_rebuild_keyword_index(cfg: AppConfig, force: bool, dirty_only: bool) -> Result<()> {
-    if !cfg!(feature = "keyword-index") && !force {
-        println!("keyword-index feature not enabled; rerun with --force to ignore.");
-        return Ok(());
-    }
+async fn run_rebuild_keyword_index(
+    cfg: AppConfig,
+    force: bool,
+    dirty_only: bool,
+    full: bool,
+) -> Result<()> {
     let pool = storage::connect(&cfg.database.path).await?;
-    let paths = if dirty_only {
+    let paths = if full {
+        Vec::new()
+    } else if dirty_only {
         sqlx::query_scalar::<_, String>("SELECT path FROM dirty")
             .fetch_all(&pool)
             .await
@@ -1362,26 +2146,36 @@ async fn run_rebuild_keyword_index(cfg: AppConfig, force: bool, dirty_only: bool
             .await
             .unwrap_or_default()
     };
-    if paths.is_empty() {
+    if !full && paths.is_empty() {
         println!("keyword index rebuild: no paths found (dirty_only={})", dirty_only);
         return Ok(());
     }
-    let dir = keyword_index_dir(&cfg.database.path);
-    let (docs, missing) =
-        crate::watch::keyword_index_docs_for_paths(&cfg.database.path, &paths, None).await?;
-    if dir.join("meta.json").exists() {
-        let _ = keyword_index::enabled::upsert_docs(&dir, &docs);
-        if !missing.is_empty() {
-            let _ = keyword_index::enabled::delete_docs(&dir, &missing);
-        }
+    // The SQLite FTS table backs keyword search in every build.
+    if full {
+        let files = fts::rebuild(&pool).await?;
+        println!("full-text table recreated: {} files", files);
     } else {
-        let _ = keyword_index::enabled::build_index(&dir, &docs);
+        let mut conn = pool.acquire().await?;
+        for p in &paths {
+            fts::refresh_path(&mut conn, p).await?;
+        }
+        println!("full-text table refreshed: {} paths", paths.len());
     }
-    println!(
-        "keyword index rebuilt: {} docs, {} missing",
-        docs.len(),
-        missing.len()
-    );
+    if !cfg!(feature = "keyword-index") && !force {
+        println!("keyword-index feature not enabled; skipping Tantivy index (rerun with --force to ignore).");
+        return Ok(());
+    }
+    if full {
+        let docs = watch::rebuild_keyword_index(&cfg.database.path).await?;
+        println!(
+            "keyword index recreated: {} docs (schema v{})",
+            docs,
+            keyword_index::SCHEMA_VERSION
+        );
+        return Ok(());
+    }
+    let (docs, missing) = watch::update_keyword_index(&cfg.database.path, &paths).await?;
+    println!("keyword index rebuilt: {} docs, {} missing", docs, missing);
     Ok(())
 }
 
@@ -1403,8 +2197,19 @@ async fn run_backfill_full_hashes(
     let hash_mode = organizer_core::scanner::HashMode::Full;
     let pool = storage::connect(&cfg.database.path).await?;
     storage::migrate(&pool).await?;
+    let (bar, bar_handle) = progress::spawn_bar();
+    let options = organizer_core::scanner::ScanOptions {
+        progress: bar,
+        ..organizer_core::scanner::ScanOptions::from_config(&cfg.scan)
+    };
     let discovered =
-        organizer_core::scanner::scan(&roots, &excludes, &hash_mode, &pool).await?;
+        organizer_core::scanner::scan_with_options(&roots, &excludes, &hash_mode, &options, &pool)
+            .await;
+    drop(options);
+    if let Some(handle) = bar_handle {
+        let _ = handle.await;
+    }
+    let discovered = discovered?.files;
     println!(
         "backfill full hashes: scanned {} files for full_hash population",
         discovered
@@ -1423,6 +2228,6 @@ async fn run_maintain(
     // 2) Rebuild vectors for dirty paths
     run_rebuild_vectors(cfg.clone(), batch, Vec::new(), true).await?;
     // 3) Rebuild keyword index for dirty paths
-    run_rebuild_keyword_index(cfg, false, true).await?;
+    run_rebuild_keyword_index(cfg, false, true, false).await?;
     Ok(())
 }
@@ -1,17 +1,148 @@
-use std::path::Path;
+use anyhow::Result;
+use globset::{GlobBuilder, GlobMatcher};
+use std::path::{Component, Path, PathBuf};
 
 /// Returns true if path is allowed given allow/deny lists.
-/// If allow list is non-empty, path must match at least one allowed prefix.
-/// Deny list always overrides.
+/// If allow list is non-empty, path must match at least one allowed entry.
+/// Deny list always overrides. See [`PathPolicy`] for how entries match.
 pub fn is_allowed(path: &Path, allow: &[String], deny: &[String]) -> bool {
-    let path_str = path.to_string_lossy();
-    for prefix in deny {
-        if path_str.starts_with(prefix) {
-            return false;
+    PathPolicy::new(allow, deny)
+        .map(|policy| policy.check("path", path).is_ok())
+        .unwrap_or(false)
+}
+
+/// `safety.allow_paths` / `safety.deny_paths`, compiled once per apply run.
+///
+/// Plain entries are canonicalised and match whole path components, so `/home/a` covers
+/// `/home/a/x` but not `/home/abc`. Entries containing `*`, `?`, `[` or `{` are globs
+/// (`*` stays within one component, `**` crosses them) and cover a path when the path or
+/// one of its ancestors matches. Checked paths are resolved through `..` and symlinks first,
+/// including paths that do not exist yet, such as move destinations.
+#[derive(Debug, Clone)]
+pub struct PathPolicy {
+    allow: Vec<Entry>,
+    deny: Vec<Entry>,
+}
+
+#[derive(Debug, Clone)]
+enum Entry {
+    Prefix(PathBuf),
+    Glob(GlobMatcher),
+}
+
+impl Entry {
+    fn parse(raw: &str) -> Result<Self> {
+        if raw.contains(['*', '?', '[', '{']) {
+            let glob = GlobBuilder::new(raw).literal_separator(true).build()?;
+            Ok(Entry::Glob(glob.compile_matcher()))
+        } else {
+            Ok(Entry::Prefix(resolve(Path::new(raw))))
+        }
+    }
+
+    fn covers(&self, path: &Path) -> bool {
+        match self {
+            Entry::Prefix(prefix) => path.starts_with(prefix),
+            Entry::Glob(glob) => path.ancestors().any(|a| glob.is_match(a)),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Entry::Prefix(prefix) => prefix.display().to_string(),
+            Entry::Glob(glob) => glob.glob().to_string(),
+        }
+    }
+}
+
+impl PathPolicy {
+    pub fn new(allow: &[String], deny: &[String]) -> Result<Self> {
+        let parse = |list: &[String]| {
+            list.iter()
+                .filter(|s| !s.trim().is_empty())
+                .map(|s| Entry::parse(s))
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            allow: parse(allow)?,
+            deny: parse(deny)?,
+        })
+    }
+
+    /// Checks `path` and returns why it is refused. `role` ("source", "destination") only
+    /// labels the reason.
+    pub fn check(&self, role: &str, path: &Path) -> std::result::Result<(), String> {
+        let lexical = absolute(path);
+        let resolved = resolve(path);
+        if let Some(entry) = self
+            .deny
+            .iter()
+            .find(|e| e.covers(&resolved) || e.covers(&lexical))
+        {
+            return Err(format!(
+                "{} {} is under denied path {}",
+                role,
+                path.display(),
+                entry.describe()
+            ));
+        }
+        if self.allow.is_empty() || self.allow.iter().any(|e| e.covers(&resolved)) {
+            return Ok(());
+        }
+        if self.allow.iter().any(|e| e.covers(&lexical)) {
+            return Err(format!(
+                "{} {} resolves to {} through a symlink, outside allowed paths",
+                role,
+                path.display(),
+                resolved.display()
+            ));
+        }
+        Err(format!(
+            "{} {} is outside allowed paths",
+            role,
+            path.display()
+        ))
+    }
+}
+
+/// `path` made absolute with `.` and `..` removed, without touching the filesystem.
+fn absolute(path: &Path) -> PathBuf {
+    let joined = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+    let mut out = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
         }
     }
-    if allow.is_empty() {
-        return true;
+    out
+}
+
+/// Where `path` really points: the deepest existing ancestor is canonicalised (following
+/// symlinks) and the not-yet-existing rest is appended.
+fn resolve(path: &Path) -> PathBuf {
+    let lexical = absolute(path);
+    let mut existing = lexical.as_path();
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(real) = existing.canonicalize() {
+            return rest.iter().rev().fold(real, |acc, part| acc.join(part));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return lexical,
+        }
     }
-    allow.iter().any(|p| path_str.starts_with(p))
 }
@@ -0,0 +1,440 @@
+//! Dry-run plans: planned actions simulated in apply order against a virtual view of the tree,
+//! so conflicts resolve exactly as `apply` would resolve them, then rendered as a before/after
+//! tree diff, JSON or a reviewable shell script.
+
+use crate::dirs;
+use crate::fs_apply::{self, ActionKind};
+use crate::paths::PathPolicy;
+use crate::report::human_bytes;
+use crate::safety;
+use anyhow::Result;
+use organizer_core::config::SafetyConfig;
+use serde::Serialize;
+use sqlx::{Row, SqlitePool};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PlanStep {
+    pub action_id: i64,
+    pub kind: String,
+    pub source: String,
+    /// `move`, `merge` (a folder into an existing one), `overwrite`, `remove`, `link`, `tag`,
+    /// `skip` (conflict policy), `blocked` or `none`.
+    pub effect: String,
+    /// The merge strategy after `keep_newest` / `keep_oldest` are decided.
+    pub strategy: Option<String>,
+    /// Where the file ends up, or the survivor a link points at.
+    pub destination: Option<String>,
+    /// The destination the action asked for, when conflict handling picked another one.
+    pub requested: Option<String>,
+    pub bytes: i64,
+    /// Why `apply` would refuse the action (allow/deny paths, `allow_delete`).
+    pub blocked: Option<String>,
+}
+
+/// Two or more actions asking for the same destination.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Collision {
+    pub destination: String,
+    pub action_ids: Vec<i64>,
+}
+
+/// One path whose presence or contents the plan changes: `added`, `removed` or `replaced`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TreeChange {
+    pub path: String,
+    pub change: String,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Plan {
+    pub steps: Vec<PlanStep>,
+    pub collisions: Vec<Collision>,
+    pub changes: Vec<TreeChange>,
+    pub bytes_moved: i64,
+    pub bytes_removed: i64,
+}
+
+/// The tree as the plan leaves it: disk state plus the paths earlier steps filled or emptied.
+#[derive(Default)]
+struct VirtualTree {
+    added: HashMap<PathBuf, i64>,
+    removed: HashSet<PathBuf>,
+    touched: BTreeSet<PathBuf>,
+    written: HashSet<PathBuf>,
+}
+
+impl VirtualTree {
+    /// A path under a removed or moved-away folder is gone with it, unless a later step wrote it.
+    fn exists(&self, path: &Path) -> bool {
+        self.added.contains_key(path)
+            || (!path.ancestors().any(|p| self.removed.contains(p)) && path.exists())
+    }
+
+    fn size(&self, path: &Path, fallback: i64) -> i64 {
+        self.added
+            .get(path)
+            .copied()
+            .or_else(|| path.metadata().ok().map(|m| m.len() as i64))
+            .unwrap_or(fallback)
+    }
+
+    fn remove(&mut self, path: &Path) {
+        self.added.remove(path);
+        self.removed.insert(path.to_path_buf());
+        self.touched.insert(path.to_path_buf());
+    }
+
+    fn write(&mut self, path: &Path, size: i64) {
+        self.removed.remove(path);
+        self.added.insert(path.to_path_buf(), size);
+        self.touched.insert(path.to_path_buf());
+        self.written.insert(path.to_path_buf());
+    }
+
+    fn changes(&self) -> Vec<TreeChange> {
+        self.touched
+            .iter()
+            .filter_map(|p| {
+                let change = match (p.exists(), self.exists(p)) {
+                    (true, false) => "removed",
+                    (false, true) => "added",
+                    (true, true) if self.written.contains(p) => "replaced",
+                    _ => return None,
+                };
+                Some(TreeChange {
+                    path: p.to_string_lossy().into_owned(),
+                    change: change.to_string(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Simulates the planned actions `apply` would run (all of them, or `ids`) in the same order.
+pub async fn simulate(
+    pool: &SqlitePool,
+    ids: Option<&str>,
+    safety: &SafetyConfig,
+    conflict: &str,
+) -> Result<Plan> {
+    let mut sql = "SELECT actions.id, COALESCE(files.path, directories.path), actions.kind, actions.payload_json, files.size FROM actions LEFT JOIN files ON files.id = actions.file_id LEFT JOIN directories ON directories.id = actions.dir_id WHERE actions.status = 'planned'".to_string();
+    let id_list: Vec<&str> = ids
+        .map(|s| s.split(',').map(str::trim).collect())
+        .unwrap_or_default();
+    if !id_list.is_empty() {
+        let placeholders: Vec<&str> = id_list.iter().map(|_| "?").collect();
+        sql.push_str(&format!(" AND actions.id IN ({})", placeholders.join(",")));
+    }
+    sql.push_str(" ORDER BY actions.id");
+    let mut query = sqlx::query(&sql);
+    for id in &id_list {
+        query = query.bind(*id);
+    }
+    let rows = query.fetch_all(pool).await?;
+
+    let policy = PathPolicy::new(&safety.allow_paths, &safety.deny_paths)?;
+    let overwrite_refused = safety::check_conflict_policy(safety, conflict)
+        .err()
+        .map(|e| e.to_string());
+    let mut tree = VirtualTree::default();
+    let mut requested_by: BTreeMap<PathBuf, Vec<i64>> = BTreeMap::new();
+    let mut steps = Vec::new();
+    let (mut bytes_moved, mut bytes_removed) = (0i64, 0i64);
+
+    for row in rows {
+        let id: i64 = row.get(0);
+        let path: String = row.get(1);
+        let kind: String = row.get(2);
+        let payload: String = row.get(3);
+        let size: i64 = row.try_get::<Option<i64>, _>(4).ok().flatten().unwrap_or(0);
+        let src = PathBuf::from(&path);
+        let mut step = PlanStep {
+            action_id: id,
+            kind: kind.clone(),
+            source: path.clone(),
+            effect: if kind == "tag" || kind == "dedupe" {
+                "tag"
+            } else {
+                "none"
+            }
+            .to_string(),
+            strategy: None,
+            destination: None,
+            requested: None,
+            bytes: if kind == "move_dir" {
+                dirs::dir_size(&src) as i64
+            } else {
+                tree.size(&src, size)
+            },
+            blocked: None,
+        };
+
+        let (target, strategy) = match fs_apply::parse_action(&path, &kind, &payload) {
+            ActionKind::Move { to, .. }
+            | ActionKind::Rename { to, .. }
+            | ActionKind::MoveDir { to, .. } => (Some(to), None),
+            ActionKind::MergeDuplicate {
+                target, strategy, ..
+            } => {
+                let strategy = if strategy == "keep_newest" || strategy == "keep_oldest" {
+                    fs_apply::choose_strategy_by_mtime(&src, &target, &strategy)
+                } else {
+                    strategy
+                };
+                (Some(target), Some(strategy))
+            }
+            _ => (None, None),
+        };
+        step.strategy = strategy.clone();
+        // Many merges naming one survivor is normal; only steps that write there can collide.
+        let writes_target = matches!(
+            strategy.as_deref(),
+            None | Some("keep_duplicate") | Some("replace")
+        );
+        if let (Some(target), true) = (&target, writes_target) {
+            requested_by.entry(target.clone()).or_default().push(id);
+        }
+        step.blocked = policy
+            .check("source", &src)
+            .and_then(|()| match &target {
+                Some(t) => policy.check("destination", t),
+                None => Ok(()),
+            })
+            .and_then(|()| safety::check_action(safety, &kind, &payload))
+            .err()
+            .or_else(|| overwrite_refused.clone());
+
+        if step.blocked.is_some() {
+            step.effect = "blocked".to_string();
+            step.destination = target.map(|t| t.to_string_lossy().into_owned());
+        } else if let Some(target) = target {
+            match strategy.as_deref() {
+                // A folder moving onto an existing one is merged into it file by file.
+                None if kind == "move_dir" => {
+                    if tree.exists(&target) {
+                        step.effect = "merge".to_string();
+                    } else {
+                        step.effect = "move".to_string();
+                        tree.write(&target, step.bytes);
+                    }
+                    tree.remove(&src);
+                    bytes_moved += step.bytes;
+                    step.destination = Some(target.to_string_lossy().into_owned());
+                }
+                None => {
+                    match fs_apply::resolve_destination_with(&target, conflict, |p| tree.exists(p))?
+                    {
+                        None => {
+                            step.effect = "skip".to_string();
+                            step.requested = Some(target.to_string_lossy().into_owned());
+                        }
+                        Some(dest) => {
+                            if tree.exists(&dest) {
+                                step.effect = "overwrite".to_string();
+                                bytes_removed += tree.size(&dest, 0);
+                            } else {
+                                step.effect = "move".to_string();
+                            }
+                            if dest != target {
+                                step.requested = Some(target.to_string_lossy().into_owned());
+                            }
+                            tree.remove(&src);
+                            tree.write(&dest, step.bytes);
+                            bytes_moved += step.bytes;
+                            step.destination = Some(dest.to_string_lossy().into_owned());
+                        }
+                    }
+                }
+                Some(s) if fs_apply::is_link_strategy(s) => {
+                    step.effect = "link".to_string();
+                    step.destination = Some(target.to_string_lossy().into_owned());
+                }
+                Some("keep_duplicate") | Some("replace") => {
+                    step.effect = "overwrite".to_string();
+                    bytes_removed += tree.size(&target, 0);
+                    tree.remove(&src);
+                    tree.write(&target, step.bytes);
+                    step.destination = Some(target.to_string_lossy().into_owned());
+                }
+                Some(_) => {
+                    step.effect = "remove".to_string();
+                    bytes_removed += step.bytes;
+                    tree.remove(&src);
+                }
+            }
+        }
+        steps.push(step);
+    }
+
+    let collisions = requested_by
+        .into_iter()
+        .filter(|(_, ids)| ids.len() > 1)
+        .map(|(dest, action_ids)| Collision {
+            destination: dest.to_string_lossy().into_owned(),
+            action_ids,
+        })
+        .collect();
+    Ok(Plan {
+        steps,
+        collisions,
+        changes: tree.changes(),
+        bytes_moved,
+        bytes_removed,
+    })
+}
+
+/// Summary, per-step lines, collisions and the before/after tree diff (`-` removed, `+` added,
+/// `~` replaced; `+` on a directory means apply creates it).
+pub fn render_text(plan: &Plan) -> String {
+    let mut out = String::new();
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for step in &plan.steps {
+        *counts.entry(step.effect.as_str()).or_default() += 1;
+    }
+    let counts: Vec<String> = counts.iter().map(|(e, n)| format!("{}={}", e, n)).collect();
+    let _ = writeln!(
+        out,
+        "dry-run plan: {} actions ({}), {} moved, {} removed",
+        plan.steps.len(),
+        counts.join(", "),
+        human_bytes(plan.bytes_moved),
+        human_bytes(plan.bytes_removed)
+    );
+    for step in &plan.steps {
+        let _ = write!(out, "  #{} {} {}", step.action_id, step.effect, step.source);
+        if let Some(dest) = &step.destination {
+            let _ = write!(out, " -> {}", dest);
+        }
+        if let Some(requested) = &step.requested {
+            let _ = write!(out, " (requested {})", requested);
+        }
+        if let Some(reason) = &step.blocked {
+            let _ = write!(out, ": {}", reason);
+        }
+        out.push('\n');
+    }
+    if !plan.collisions.is_empty() {
+        out.push_str("collisions:\n");
+        for c in &plan.collisions {
+            let ids: Vec<String> = c.action_ids.iter().map(|i| format!("#{}", i)).collect();
+            let _ = writeln!(out, "  {} <- {}", c.destination, ids.join(", "));
+        }
+    }
+    if plan.changes.is_empty() {
+        out.push_str("no filesystem changes\n");
+        return out;
+    }
+    out.push_str(&render_tree(&plan.changes));
+    out
+}
+
+fn render_tree(changes: &[TreeChange]) -> String {
+    let paths: Vec<PathBuf> = changes.iter().map(|c| PathBuf::from(&c.path)).collect();
+    let mut root = paths[0].parent().map(Path::to_path_buf).unwrap_or_default();
+    while !paths.iter().all(|p| p.starts_with(&root)) {
+        if !root.pop() {
+            break;
+        }
+    }
+    let mut out = format!("  {}\n", root.display());
+    let mut open: Vec<PathBuf> = Vec::new();
+    for (change, path) in changes.iter().zip(&paths) {
+        let rel = path.strip_prefix(&root).unwrap_or(path);
+        let parts: Vec<_> = rel.components().collect();
+        let dirs = &parts[..parts.len().saturating_sub(1)];
+        let mut dir = root.clone();
+        for (depth, part) in dirs.iter().enumerate() {
+            dir.push(part);
+            if open.get(depth) == Some(&dir) {
+                continue;
+            }
+            open.truncate(depth);
+            open.push(dir.clone());
+            let marker = if dir.exists() { ' ' } else { '+' };
+            let _ = writeln!(
+                out,
+                "{} {}{}/",
+                marker,
+                "  ".repeat(depth + 1),
+                part.as_os_str().to_string_lossy()
+            );
+        }
+        open.truncate(dirs.len());
+        let marker = match change.change.as_str() {
+            "added" => '+',
+            "removed" => '-',
+            _ => '~',
+        };
+        let _ = writeln!(
+            out,
+            "{} {}{}",
+            marker,
+            "  ".repeat(dirs.len() + 1),
+            path.file_name().unwrap_or_default().to_string_lossy()
+        );
+    }
+    out
+}
+
+/// A POSIX shell script doing what the plan does, for review. It is not what `apply` runs:
+/// apply also backs files up, journals and records each step.
+pub fn render_shell(plan: &Plan) -> String {
+    let mut out =
+        String::from("#!/bin/sh\n# Generated by `apply --dry-run --plan-format shell`.\nset -e\n");
+    for step in &plan.steps {
+        let _ = writeln!(
+            out,
+            "\n# #{} {} ({})",
+            step.action_id, step.kind, step.effect
+        );
+        let src = shell_quote(&step.source);
+        let dest = step.destination.as_deref().map(shell_quote);
+        match (step.effect.as_str(), dest) {
+            ("move", Some(dest)) | ("overwrite", Some(dest)) => {
+                if let Some(parent) = step
+                    .destination
+                    .as_deref()
+                    .and_then(|d| Path::new(d).parent())
+                {
+                    let _ = writeln!(
+                        out,
+                        "mkdir -p -- {}",
+                        shell_quote(&parent.to_string_lossy())
+                    );
+                }
+                let flag = if step.effect == "move" { "-n" } else { "-f" };
+                let _ = writeln!(out, "mv {} -- {} {}", flag, src, dest);
+            }
+            ("link", Some(dest)) if step.strategy.as_deref() == Some("reflink") => {
+                // Cloning has no POSIX spelling; the hardlink is what apply falls back to.
+                out.push_str("# apply clones copy-on-write where the filesystem supports it\n");
+                let _ = writeln!(out, "ln -f -- {} {}", dest, src);
+            }
+            ("link", Some(dest)) => {
+                let _ = writeln!(out, "ln -f -- {} {}", dest, src);
+            }
+            ("merge", Some(dest)) => {
+                let _ = writeln!(
+                    out,
+                    "# merged into {} file by file, clashes resolved by --conflict",
+                    dest
+                );
+            }
+            ("remove", _) => {
+                let _ = writeln!(out, "rm -- {}", src);
+            }
+            ("blocked", _) => {
+                let _ = writeln!(out, "# skipped: {}", step.blocked.as_deref().unwrap_or(""));
+            }
+            _ => out.push_str(": # no filesystem change\n"),
+        }
+    }
+    out
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
@@ -0,0 +1,246 @@
+//! Reviewed plans: `plan export` writes the planned actions to an editable TOML file, one action
+//! per line, and `apply --plan` runs exactly the actions left in it after checking that none of
+//! their files changed since the export.
+
+use crate::apply::{self, ActionView};
+use crate::fs_apply;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use sqlx::{Row, SqlitePool};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Payload keys a reviewer may change in the file; everything else must still match the DB.
+const EDITABLE: [&str; 3] = ["to", "tag", "strategy"];
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct PlanFile {
+    #[serde(default)]
+    pub actions: Vec<PlanEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct PlanEntry {
+    pub id: i64,
+    pub kind: String,
+    pub path: String,
+    pub to: Option<String>,
+    pub tag: Option<String>,
+    pub strategy: Option<String>,
+    pub duplicate_of: Option<String>,
+    /// The file as exported; `apply --plan` refuses the action if it no longer matches.
+    pub size: Option<i64>,
+    pub mtime: Option<i64>,
+    pub hash: Option<String>,
+}
+
+/// Writes the planned actions (all of them, or `ids`) as a plan file.
+pub async fn export(pool: &SqlitePool, ids: Option<&str>) -> Result<String> {
+    let mut sql = "SELECT actions.id, COALESCE(files.path, directories.path), actions.kind, actions.payload_json, files.size, files.mtime, files.full_hash FROM actions LEFT JOIN files ON files.id = actions.file_id LEFT JOIN directories ON directories.id = actions.dir_id WHERE actions.status = 'planned'".to_string();
+    let id_list: Vec<&str> = ids
+        .map(|s| s.split(',').map(str::trim).collect())
+        .unwrap_or_default();
+    if !id_list.is_empty() {
+        let placeholders: Vec<&str> = id_list.iter().map(|_| "?").collect();
+        sql.push_str(&format!(" AND actions.id IN ({})", placeholders.join(",")));
+    }
+    sql.push_str(" ORDER BY actions.id");
+    let mut query = sqlx::query(&sql);
+    for id in &id_list {
+        query = query.bind(*id);
+    }
+    let rows = query.fetch_all(pool).await?;
+
+    let mut out = String::from(
+        "# Reviewed plan for `apply --plan <file>`: only the actions listed here run.\n\
+         # Delete a line to drop an action; `to`, `tag` and `strategy` may be edited.\n\
+         # size/mtime/hash pin each file: an action whose file changed since export is refused.\n\
+         actions = [\n",
+    );
+    for row in rows {
+        let path: String = row.get(1);
+        let payload: Value = serde_json::from_str(&row.get::<String, _>(3)).unwrap_or(Value::Null);
+        let mut fields = vec![
+            ("id", toml::Value::Integer(row.get(0))),
+            ("kind", toml::Value::String(row.get(2))),
+            ("path", toml::Value::String(path.clone())),
+        ];
+        for key in EDITABLE {
+            if let Some(v) = payload.get(key).and_then(|v| v.as_str()) {
+                fields.push((key, toml::Value::String(v.to_string())));
+            }
+        }
+        if let Some(survivor) = apply::extract_duplicate_of(&payload.to_string()) {
+            fields.push(("duplicate_of", toml::Value::String(survivor)));
+        }
+        if let Some((size, mtime)) = stat(Path::new(&path)) {
+            let stored: Option<String> = row.get(6);
+            let unchanged = row.get::<Option<i64>, _>(4) == Some(size)
+                && row.get::<Option<i64>, _>(5) == Some(mtime);
+            let hash = match stored {
+                Some(h) if unchanged => Some(h),
+                _ => hash_file(&path).await,
+            };
+            fields.push(("size", toml::Value::Integer(size)));
+            fields.push(("mtime", toml::Value::Integer(mtime)));
+            if let Some(h) = hash {
+                fields.push(("hash", toml::Value::String(h)));
+            }
+        }
+        let inline: Vec<String> = fields
+            .iter()
+            .map(|(k, v)| format!("{} = {}", k, v))
+            .collect();
+        let _ = writeln!(out, "  {{ {} }},", inline.join(", "));
+    }
+    out.push_str("]\n");
+    Ok(out)
+}
+
+pub fn parse(text: &str) -> Result<PlanFile> {
+    toml::from_str(text).context("invalid plan file")
+}
+
+/// Checks every entry against the DB and the disk and saves edited payload keys, with an audit
+/// row per edited key. Returns the ids that may run and a view for each refused entry.
+pub async fn prepare(pool: &SqlitePool, plan: &PlanFile) -> Result<(Vec<i64>, Vec<ActionView>)> {
+    let mut ready = Vec::new();
+    let mut refused = Vec::new();
+    for entry in &plan.actions {
+        let row = sqlx::query("SELECT COALESCE(files.path, directories.path), actions.kind, actions.payload_json, actions.status FROM actions LEFT JOIN files ON files.id = actions.file_id LEFT JOIN directories ON directories.id = actions.dir_id WHERE actions.id = ?1")
+            .bind(entry.id)
+            .fetch_optional(pool)
+            .await?;
+        let Some(row) = row else {
+            refused.push(view(entry, "planned", "no such action".to_string(), None));
+            continue;
+        };
+        let status: String = row.get(3);
+        let mut payload: Value =
+            serde_json::from_str(&row.get::<String, _>(2)).unwrap_or(Value::Null);
+        let problem = if status != "planned" {
+            Some(format!("action is {}, not planned", status))
+        } else if row.get::<String, _>(1) != entry.kind || row.get::<String, _>(0) != entry.path {
+            Some("action changed since the plan was exported".to_string())
+        } else if apply::extract_duplicate_of(&payload.to_string()) != entry.duplicate_of {
+            Some("duplicate_of cannot be edited; plan the merge again instead".to_string())
+        } else {
+            changed_since_export(pool, entry).await?
+        };
+        if let Some(reason) = problem {
+            refused.push(view(entry, &status, reason, Some(payload.to_string())));
+            continue;
+        }
+        let values = [&entry.to, &entry.tag, &entry.strategy];
+        let mut edits = Vec::new();
+        if let Some(obj) = payload.as_object_mut() {
+            for (key, value) in EDITABLE.iter().zip(values) {
+                if let Some(v) = value {
+                    let old = obj.get(*key).and_then(|c| c.as_str());
+                    if old != Some(v.as_str()) {
+                        edits.push(format!("{}: {} -> {}", key, old.unwrap_or("(unset)"), v));
+                        obj.insert(key.to_string(), Value::String(v.clone()));
+                    }
+                }
+            }
+        }
+        if !edits.is_empty() {
+            let mut tx = pool.begin().await?;
+            sqlx::query("UPDATE actions SET payload_json = ?2 WHERE id = ?1")
+                .bind(entry.id)
+                .bind(payload.to_string())
+                .execute(&mut *tx)
+                .await?;
+            for edit in &edits {
+                sqlx::query(
+                    "INSERT INTO audit(action_id, event, detail) VALUES (?1, 'plan_edit', ?2)",
+                )
+                .bind(entry.id)
+                .bind(edit)
+                .execute(&mut *tx)
+                .await?;
+            }
+            tx.commit().await?;
+        }
+        ready.push(entry.id);
+    }
+    Ok((ready, refused))
+}
+
+/// Why the entry's file no longer matches what was exported, if it doesn't. The scanned full
+/// hash stands in for the file's while its size and mtime still match the `files` row.
+async fn changed_since_export(pool: &SqlitePool, entry: &PlanEntry) -> Result<Option<String>> {
+    let path = Path::new(&entry.path);
+    let Some((size, mtime)) = stat(path) else {
+        return Ok(Some(format!("{} is missing", entry.path)));
+    };
+    if entry.size.is_some_and(|s| s != size) {
+        return Ok(Some(format!(
+            "{} changed size since the plan was exported",
+            entry.path
+        )));
+    }
+    if entry.mtime.is_some_and(|m| m != mtime) {
+        return Ok(Some(format!(
+            "{} was modified since the plan was exported",
+            entry.path
+        )));
+    }
+    let Some(hash) = &entry.hash else {
+        return Ok(None);
+    };
+    let stored: Option<String> = sqlx::query_scalar(
+        "SELECT full_hash FROM files WHERE path = ?1 AND size = ?2 AND mtime = ?3",
+    )
+    .bind(&entry.path)
+    .bind(size)
+    .bind(mtime)
+    .fetch_optional(pool)
+    .await?
+    .flatten();
+    let current = match stored {
+        Some(h) => Some(h),
+        None => hash_file(&entry.path).await,
+    };
+    Ok((current.as_ref() != Some(hash)).then(|| {
+        format!(
+            "{} has different contents than when the plan was exported",
+            entry.path
+        )
+    }))
+}
+
+/// Full blake3 of a file, computed off the async runtime.
+async fn hash_file(path: &str) -> Option<String> {
+    let path = PathBuf::from(path);
+    tokio::task::spawn_blocking(move || fs_apply::full_hash(&path).ok())
+        .await
+        .ok()
+        .flatten()
+}
+
+fn stat(path: &Path) -> Option<(i64, i64)> {
+    let meta = path.metadata().ok()?;
+    let mtime = meta
+        .modified()
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs() as i64;
+    Some((meta.len() as i64, mtime))
+}
+
+fn view(entry: &PlanEntry, status: &str, error: String, payload: Option<String>) -> ActionView {
+    ActionView {
+        id: entry.id,
+        path: entry.path.clone(),
+        kind: entry.kind.clone(),
+        payload: payload.unwrap_or_default(),
+        status: status.to_string(),
+        rule: None,
+        error: Some(error),
+        backup: None,
+        session_id: None,
+    }
+}
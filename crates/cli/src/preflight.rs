@@ -0,0 +1,181 @@
+//! Pre-flight checks `apply` runs before touching a file, so an operation that is bound to fail
+//! or to act on stale data is refused up front instead of failing halfway.
+
+use crate::fs_apply;
+use crate::report::human_bytes;
+use organizer_core::scanner;
+use sqlx::SqlitePool;
+use std::path::Path;
+
+/// The hashes the scanner stored for a file; the fast hash is preferred as it is cheap to redo.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Scanned {
+    pub fast_hash: Option<String>,
+    pub full_hash: Option<String>,
+}
+
+impl Scanned {
+    pub async fn load(pool: &SqlitePool, path: &str) -> anyhow::Result<Self> {
+        let row: Option<(Option<String>, Option<String>)> =
+            sqlx::query_as("SELECT fast_hash, full_hash FROM files WHERE path = ?1")
+                .bind(path)
+                .fetch_optional(pool)
+                .await?;
+        let (fast_hash, full_hash) = row.unwrap_or_default();
+        Ok(Self {
+            fast_hash,
+            full_hash,
+        })
+    }
+}
+
+/// Checks that `src` is still the file that was scanned and can be removed from its directory,
+/// that `dest` (when the file moves) can be created and, across filesystems, has room for it,
+/// and that `backup_dir` (when a backup copy is written first) has room for that copy.
+pub fn check(
+    src: &Path,
+    dest: Option<&Path>,
+    backup_dir: Option<&Path>,
+    scanned: &Scanned,
+) -> Result<(), String> {
+    let meta = match src.metadata() {
+        Ok(m) if m.is_file() => m,
+        _ => return Err(format!("source {} no longer exists", src.display())),
+    };
+    let current = match (&scanned.fast_hash, &scanned.full_hash) {
+        (Some(h), _) => Some((h, scanner::fast_hash(src))),
+        (None, Some(h)) => Some((h, fs_apply::full_hash(src))),
+        _ => None,
+    };
+    if let Some((stored, now)) = current {
+        if now.ok().as_ref() != Some(stored) {
+            return Err(format!(
+                "source {} changed since it was scanned",
+                src.display()
+            ));
+        }
+    }
+    if let Some(dir) = src.parent() {
+        if !writable(dir) {
+            return Err(format!(
+                "cannot remove {}: {} is not writable",
+                src.display(),
+                dir.display()
+            ));
+        }
+    }
+    let size = meta.len();
+    if let Some(dest) = dest {
+        let anchor = existing_ancestor(dest.parent().unwrap_or(dest));
+        if !writable(anchor) {
+            return Err(format!(
+                "destination directory {} is not writable",
+                anchor.display()
+            ));
+        }
+        if !same_device(src, anchor) {
+            room_for(anchor, size)?;
+        }
+    }
+    if let Some(dir) = backup_dir {
+        room_for(existing_ancestor(dir), size)?;
+    }
+    Ok(())
+}
+
+/// [`check`] for a `move_dir`: `src` is still a directory that can be removed from its parent,
+/// `dest` can be created and, across filesystems, has room for everything below `src`.
+pub fn check_dir(src: &Path, dest: &Path) -> Result<(), String> {
+    if !src.is_dir() {
+        return Err(format!("source {} no longer exists", src.display()));
+    }
+    if let Some(dir) = src.parent() {
+        if !writable(dir) {
+            return Err(format!(
+                "cannot remove {}: {} is not writable",
+                src.display(),
+                dir.display()
+            ));
+        }
+    }
+    let anchor = existing_ancestor(dest);
+    if !writable(anchor) {
+        return Err(format!(
+            "destination directory {} is not writable",
+            anchor.display()
+        ));
+    }
+    if !same_device(src, anchor) {
+        room_for(anchor, crate::dirs::dir_size(src))?;
+    }
+    Ok(())
+}
+
+fn room_for(dir: &Path, size: u64) -> Result<(), String> {
+    match free_space(dir) {
+        Some(free) if free < size => Err(format!(
+            "not enough space on {}: needs {}, {} free",
+            dir.display(),
+            human_bytes(size as i64),
+            human_bytes(free as i64)
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// The deepest ancestor of `path` (or `path` itself) that exists; directories below it are
+/// created by the move.
+fn existing_ancestor(path: &Path) -> &Path {
+    path.ancestors().find(|a| a.exists()).unwrap_or(path)
+}
+
+#[cfg(unix)]
+fn same_device(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (a.metadata(), b.metadata()) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_device(_a: &Path, _b: &Path) -> bool {
+    false
+}
+
+#[cfg(target_os = "linux")]
+fn free_space(dir: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    let path = CString::new(dir.as_os_str().as_bytes()).ok()?;
+    // SAFETY: statvfs is plain old data, so all zeroes is a valid value to fill in.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stat` outlives the call.
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn free_space(_dir: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn writable(dir: &Path) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    match CString::new(dir.as_os_str().as_bytes()) {
+        // SAFETY: `path` is NUL-terminated and outlives the call.
+        Ok(path) => unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 },
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn writable(dir: &Path) -> bool {
+    dir.metadata()
+        .map(|m| !m.permissions().readonly())
+        .unwrap_or(false)
+}
@@ -0,0 +1,75 @@
+//! Scan progress bar: one line on stderr, redrawn for every `ScanProgress` the scanner sends.
+
+use crate::report::human_bytes;
+use organizer_core::scanner::ScanProgress;
+use std::io::{IsTerminal, Write};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+const BAR_WIDTH: usize = 24;
+
+/// Starts drawing the bar if stderr is a terminal. Pass the sender to the scan and await the
+/// handle once the scan is done so the bar's last line is finished.
+pub fn spawn_bar() -> (
+    Option<mpsc::UnboundedSender<ScanProgress>>,
+    Option<JoinHandle<()>>,
+) {
+    if !std::io::stderr().is_terminal() {
+        return (None, None);
+    }
+    let (tx, mut rx) = mpsc::unbounded_channel::<ScanProgress>();
+    let handle = tokio::spawn(async move {
+        let mut drawn = false;
+        while let Some(progress) = rx.recv().await {
+            let mut err = std::io::stderr().lock();
+            let _ = write!(err, "\r\x1b[2K{}", render(&progress));
+            let _ = err.flush();
+            drawn = true;
+        }
+        if drawn {
+            eprintln!();
+        }
+    });
+    (Some(tx), Some(handle))
+}
+
+/// `[#####-----]  42% 1200/2857 files, 3.1 GiB hashed, 850 files/s, ETA 2s`. Until the walk is
+/// over the total is unknown, so the bar is left out.
+pub fn render(p: &ScanProgress) -> String {
+    let hashing = p.files_hashed > 0;
+    let (done, total) = if hashing {
+        (p.bytes_hashed, p.bytes_found)
+    } else {
+        (p.files_stored, p.files_found)
+    };
+    let mut line = String::new();
+    if p.walk_done && total > 0 {
+        let ratio = (done as f64 / total as f64).min(1.0);
+        let filled = (ratio * BAR_WIDTH as f64).round() as usize;
+        line.push_str(&format!(
+            "[{}{}] {:>3}% ",
+            "#".repeat(filled),
+            "-".repeat(BAR_WIDTH - filled),
+            (ratio * 100.0).round() as u64
+        ));
+    }
+    line.push_str(&format!("{}/{} files", p.files_stored, p.files_found));
+    if hashing {
+        line.push_str(&format!(", {} hashed", human_bytes(p.bytes_hashed as i64)));
+    }
+    line.push_str(&format!(", {:.0} files/s", p.files_per_sec));
+    match (p.done, p.eta_secs) {
+        (true, _) => line.push_str(&format!(", done in {:.1}s", p.elapsed_ms as f64 / 1000.0)),
+        (false, Some(eta)) => line.push_str(&format!(", ETA {}", eta_label(eta))),
+        (false, None) => line.push_str(", walking..."),
+    }
+    line
+}
+
+fn eta_label(secs: u64) -> String {
+    match secs {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m{:02}s", s / 60, s % 60),
+        s => format!("{}h{:02}m", s / 3600, (s % 3600) / 60),
+    }
+}
@@ -0,0 +1,575 @@
+//! Small search query language for `cli search`.
+//!
+//! `invoice tag:finance ext:pdf modified:>2024-01-01 size:>1MB path:~/Docs -tag:archive`
+//! is parsed once into a [`SearchQuery`] and then translated into a Qdrant filter, a Tantivy
+//! query string and SQLite predicates so every search backend applies the same filters.
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{NaiveDate, NaiveTime};
+use sqlx::{QueryBuilder, Sqlite};
+
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    /// Free-text terms and quoted phrases.
+    pub text: Vec<String>,
+    /// Free-text terms prefixed with `-`.
+    pub exclude_text: Vec<String>,
+    /// Each group must match at least one tag; groups are ANDed (`tag:a tag:b` = both).
+    pub tag_groups: Vec<Vec<String>>,
+    pub exclude_tags: Vec<String>,
+    pub exts: Vec<String>,
+    pub exclude_exts: Vec<String>,
+    pub mimes: Vec<String>,
+    pub exclude_mimes: Vec<String>,
+    pub path_prefixes: Vec<String>,
+    pub exclude_path_prefixes: Vec<String>,
+    /// Inclusive lower/upper bounds on `files.mtime` (unix seconds).
+    pub modified_after: Option<i64>,
+    pub modified_before: Option<i64>,
+    /// Inclusive lower/upper bounds on `files.size` (bytes).
+    pub size_min: Option<i64>,
+    pub size_max: Option<i64>,
+}
+
+impl SearchQuery {
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut q = SearchQuery::default();
+        for token in tokenize(input) {
+            let (negated, body) = match token.strip_prefix('-') {
+                Some(rest) if !rest.is_empty() => (true, rest.to_string()),
+                _ => (false, token),
+            };
+            let Some((field, value)) = split_field(&body) else {
+                if negated {
+                    q.exclude_text.push(body);
+                } else {
+                    q.text.push(body);
+                }
+                continue;
+            };
+            if value.is_empty() {
+                bail!("missing value for `{}:`", field);
+            }
+            match field.as_str() {
+                "tag" | "tags" => {
+                    if negated {
+                        q.exclude_tags.push(value);
+                    } else {
+                        q.tag_groups.push(vec![value]);
+                    }
+                }
+                "ext" => {
+                    let ext = value.trim_start_matches('.').to_lowercase();
+                    if negated {
+                        q.exclude_exts.push(ext);
+                    } else {
+                        q.exts.push(ext);
+                    }
+                }
+                "mime" | "type" => {
+                    if negated {
+                        q.exclude_mimes.push(value);
+                    } else {
+                        q.mimes.push(value);
+                    }
+                }
+                "path" | "in" => {
+                    let prefix = expand_home(&value);
+                    if negated {
+                        q.exclude_path_prefixes.push(prefix);
+                    } else {
+                        q.path_prefixes.push(prefix);
+                    }
+                }
+                "modified" | "mtime" => {
+                    if negated {
+                        bail!("`-{}:` is not supported; use `<` or `>` instead", field);
+                    }
+                    let (op, rest) = split_op(&value);
+                    let (start, end) = parse_time_range(rest)?;
+                    apply_range(
+                        op,
+                        start,
+                        end,
+                        &mut q.modified_after,
+                        &mut q.modified_before,
+                    );
+                }
+                "size" => {
+                    if negated {
+                        bail!("`-{}:` is not supported; use `<` or `>` instead", field);
+                    }
+                    let (op, rest) = split_op(&value);
+                    let bytes = parse_size(rest)?;
+                    apply_range(op, bytes, bytes, &mut q.size_min, &mut q.size_max);
+                }
+                _ => {
+                    // Unknown prefixes (e.g. `http://...`) are plain text.
+                    if negated {
+                        q.exclude_text.push(body);
+                    } else {
+                        q.text.push(body);
+                    }
+                }
+            }
+        }
+        Ok(q)
+    }
+
+    /// Folds the legacy `--path-prefix/--mime/--after/--before/--tags` flags into the query.
+    pub fn with_flags(
+        mut self,
+        path_prefix: Option<String>,
+        mime: Option<String>,
+        after: Option<&str>,
+        before: Option<&str>,
+        tags: &[String],
+    ) -> Result<Self> {
+        if let Some(p) = path_prefix {
+            self.path_prefixes.push(p);
+        }
+        if let Some(m) = mime {
+            self.mimes.push(m);
+        }
+        if let Some(a) = after {
+            let ts = chrono::DateTime::parse_from_rfc3339(a)
+                .map_err(|e| anyhow!("invalid --after {:?}: {}", a, e))?
+                .timestamp();
+            self.modified_after = Some(self.modified_after.map_or(ts, |cur| cur.max(ts)));
+        }
+        if let Some(b) = before {
+            let ts = chrono::DateTime::parse_from_rfc3339(b)
+                .map_err(|e| anyhow!("invalid --before {:?}: {}", b, e))?
+                .timestamp();
+            self.modified_before = Some(self.modified_before.map_or(ts, |cur| cur.min(ts)));
+        }
+        if !tags.is_empty() {
+            self.tag_groups.push(tags.to_vec());
+        }
+        Ok(self)
+    }
+
+    /// Text used for embedding / LIKE matching, without any field filters.
+    pub fn text_query(&self) -> String {
+        self.text.join(" ")
+    }
+
+    pub fn has_text(&self) -> bool {
+        !self.text.is_empty()
+    }
+
+    /// Qdrant payload filter. Tags and text exclusions are not in the payload; callers
+    /// post-filter vector hits through [`SearchQuery::push_sql_filters`].
+    pub fn to_qdrant_filter(&self) -> Option<serde_json::Value> {
+        let mut must = Vec::new();
+        let mut must_not = Vec::new();
+        if !self.path_prefixes.is_empty() {
+            must.push(serde_json::json!({
+                "key": "path_prefixes",
+                "match": { "any": normalize_prefixes(&self.path_prefixes) }
+            }));
+        }
+        if !self.exclude_path_prefixes.is_empty() {
+            must_not.push(serde_json::json!({
+                "key": "path_prefixes",
+                "match": { "any": normalize_prefixes(&self.exclude_path_prefixes) }
+            }));
+        }
+        if !self.mimes.is_empty() {
+            must.push(serde_json::json!({ "key": "mime", "match": { "any": self.mimes } }));
+        }
+        if !self.exclude_mimes.is_empty() {
+            must_not
+                .push(serde_json::json!({ "key": "mime", "match": { "any": self.exclude_mimes } }));
+        }
+        if !self.exts.is_empty() {
+            must.push(serde_json::json!({ "key": "ext", "match": { "any": self.exts } }));
+        }
+        if !self.exclude_exts.is_empty() {
+            must_not
+                .push(serde_json::json!({ "key": "ext", "match": { "any": self.exclude_exts } }));
+        }
+        if self.modified_after.is_some() || self.modified_before.is_some() {
+            must.push(serde_json::json!({
+                "key": "mtime",
+                "range": range_json(self.modified_after, self.modified_before)
+            }));
+        }
+        if self.size_min.is_some() || self.size_max.is_some() {
+            must.push(serde_json::json!({
+                "key": "size",
+                "range": range_json(self.size_min, self.size_max)
+            }));
+        }
+        if must.is_empty() && must_not.is_empty() {
+            return None;
+        }
+        let mut filter = serde_json::Map::new();
+        if !must.is_empty() {
+            filter.insert("must".into(), serde_json::Value::Array(must));
+        }
+        if !must_not.is_empty() {
+            filter.insert("must_not".into(), serde_json::Value::Array(must_not));
+        }
+        Some(serde_json::Value::Object(filter))
+    }
+
+    /// Tantivy query-parser string over the keyword index fields. Path prefixes have no
+    /// Tantivy equivalent and are applied by the SQL post-filter.
+    pub fn to_tantivy_query(&self) -> String {
+        let mut parts: Vec<String> = Vec::new();
+        for t in &self.text {
+            parts.push(tantivy_phrase(t));
+        }
+        for t in &self.exclude_text {
+            parts.push(format!("-{}", tantivy_phrase(t)));
+        }
+        for group in &self.tag_groups {
+            let any: Vec<String> = group
+                .iter()
+                .map(|t| format!("tags:{}", tantivy_phrase(t)))
+                .collect();
+            parts.push(format!("+({})", any.join(" OR ")));
+        }
+        for t in &self.exclude_tags {
+            parts.push(format!("-tags:{}", tantivy_phrase(t)));
+        }
+        if !self.exts.is_empty() {
+            let any: Vec<String> = self
+                .exts
+                .iter()
+                .map(|e| format!("ext:{}", tantivy_phrase(e)))
+                .collect();
+            parts.push(format!("+({})", any.join(" OR ")));
+        }
+        for e in &self.exclude_exts {
+            parts.push(format!("-ext:{}", tantivy_phrase(e)));
+        }
+        if !self.mimes.is_empty() {
+            let any: Vec<String> = self
+                .mimes
+                .iter()
+                .map(|m| format!("mime:{}", tantivy_phrase(m)))
+                .collect();
+            parts.push(format!("+({})", any.join(" OR ")));
+        }
+        for m in &self.exclude_mimes {
+            parts.push(format!("-mime:{}", tantivy_phrase(m)));
+        }
+        if self.modified_after.is_some() || self.modified_before.is_some() {
+            parts.push(format!(
+                "+mtime:{}",
+                tantivy_range(self.modified_after, self.modified_before)
+            ));
+        }
+        if self.size_min.is_some() || self.size_max.is_some() {
+            parts.push(format!(
+                "+size:{}",
+                tantivy_range(self.size_min, self.size_max)
+            ));
+        }
+        if self.text.is_empty() && !parts.is_empty() && parts.iter().all(|p| p.starts_with('-')) {
+            // Pure exclusions match nothing in Tantivy; anchor on every document.
+            parts.insert(0, "*".to_string());
+        }
+        parts.join(" ")
+    }
+
+    /// FTS5 MATCH expression for the free text (`None` when there is none). Single words
+    /// match as prefixes, quoted phrases exactly.
+    pub fn to_fts_query(&self) -> Option<String> {
+        if self.text.is_empty() {
+            return None;
+        }
+        let terms: Vec<String> = self.text.iter().map(|t| fts_term(t)).collect();
+        let mut expr = terms.join(" AND ");
+        for t in &self.exclude_text {
+            expr = format!("({}) NOT {}", expr, fts_term(t));
+        }
+        Some(expr)
+    }
+
+    /// Appends ` AND ...` predicates over the `files` table. With `include_text` the free-text
+    /// terms are matched against the path; without it only the field filters apply, which is
+    /// how vector, Tantivy and FTS hits are post-filtered. Tombstoned files never match.
+    pub fn push_sql_filters(&self, qb: &mut QueryBuilder<'_, Sqlite>, include_text: bool) {
+        qb.push(" AND files.status != 'missing'");
+        if include_text {
+            for t in &self.text {
+                qb.push(" AND files.path LIKE ");
+                qb.push_bind(format!("%{}%", t));
+            }
+            for t in &self.exclude_text {
+                qb.push(" AND files.path NOT LIKE ");
+                qb.push_bind(format!("%{}%", t));
+            }
+        }
+        if !self.path_prefixes.is_empty() {
+            qb.push(" AND (");
+            for (i, p) in self.path_prefixes.iter().enumerate() {
+                if i > 0 {
+                    qb.push(" OR ");
+                }
+                push_path_under(qb, p);
+            }
+            qb.push(")");
+        }
+        for p in &self.exclude_path_prefixes {
+            qb.push(" AND NOT ");
+            push_path_under(qb, p);
+        }
+        push_in(qb, "LOWER(COALESCE(files.ext, ''))", &self.exts, false);
+        push_in(
+            qb,
+            "LOWER(COALESCE(files.ext, ''))",
+            &self.exclude_exts,
+            true,
+        );
+        push_in(qb, "COALESCE(files.mime, '')", &self.mimes, false);
+        push_in(qb, "COALESCE(files.mime, '')", &self.exclude_mimes, true);
+        if let Some(a) = self.modified_after {
+            qb.push(" AND files.mtime >= ");
+            qb.push_bind(a);
+        }
+        if let Some(b) = self.modified_before {
+            qb.push(" AND files.mtime <= ");
+            qb.push_bind(b);
+        }
+        if let Some(min) = self.size_min {
+            qb.push(" AND files.size >= ");
+            qb.push_bind(min);
+        }
+        if let Some(max) = self.size_max {
+            qb.push(" AND files.size <= ");
+            qb.push_bind(max);
+        }
+        for group in &self.tag_groups {
+            qb.push(" AND EXISTS (SELECT 1 FROM file_tags ft JOIN tags t ON t.id = ft.tag_id WHERE ft.file_id = files.id AND t.name IN (");
+            let mut sep = qb.separated(", ");
+            for t in group {
+                sep.push_bind(t.clone());
+            }
+            sep.push_unseparated("))");
+        }
+        if !self.exclude_tags.is_empty() {
+            qb.push(" AND NOT EXISTS (SELECT 1 FROM file_tags ft JOIN tags t ON t.id = ft.tag_id WHERE ft.file_id = files.id AND t.name IN (");
+            let mut sep = qb.separated(", ");
+            for t in &self.exclude_tags {
+                sep.push_bind(t.clone());
+            }
+            sep.push_unseparated("))");
+        }
+    }
+}
+
+fn push_in(qb: &mut QueryBuilder<'_, Sqlite>, column: &str, values: &[String], negate: bool) {
+    if values.is_empty() {
+        return;
+    }
+    qb.push(format!(
+        " AND {} {}IN (",
+        column,
+        if negate { "NOT " } else { "" }
+    ));
+    let mut sep = qb.separated(", ");
+    for v in values {
+        sep.push_bind(v.clone());
+    }
+    sep.push_unseparated(")");
+}
+
+/// Splits on whitespace while keeping `"quoted phrases"` (also after `field:` or `-`) together.
+fn tokenize(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    for c in input.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn split_field(token: &str) -> Option<(String, String)> {
+    let (field, value) = token.split_once(':')?;
+    if field.is_empty() || !field.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some((field.to_lowercase(), value.to_string()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RangeOp {
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Eq,
+}
+
+fn split_op(value: &str) -> (RangeOp, &str) {
+    if let Some(rest) = value.strip_prefix(">=") {
+        (RangeOp::Ge, rest)
+    } else if let Some(rest) = value.strip_prefix("<=") {
+        (RangeOp::Le, rest)
+    } else if let Some(rest) = value.strip_prefix('>') {
+        (RangeOp::Gt, rest)
+    } else if let Some(rest) = value.strip_prefix('<') {
+        (RangeOp::Lt, rest)
+    } else {
+        (RangeOp::Eq, value.strip_prefix('=').unwrap_or(value))
+    }
+}
+
+/// `start..=end` is the inclusive span the value denotes (a whole day for dates).
+fn apply_range(
+    op: RangeOp,
+    start: i64,
+    end: i64,
+    lower: &mut Option<i64>,
+    upper: &mut Option<i64>,
+) {
+    let tighten_lower =
+        |lower: &mut Option<i64>, v: i64| *lower = Some(lower.map_or(v, |cur| cur.max(v)));
+    let tighten_upper =
+        |upper: &mut Option<i64>, v: i64| *upper = Some(upper.map_or(v, |cur| cur.min(v)));
+    match op {
+        RangeOp::Gt => tighten_lower(lower, end + 1),
+        RangeOp::Ge => tighten_lower(lower, start),
+        RangeOp::Lt => tighten_upper(upper, start - 1),
+        RangeOp::Le => tighten_upper(upper, end),
+        RangeOp::Eq => {
+            tighten_lower(lower, start);
+            tighten_upper(upper, end);
+        }
+    }
+}
+
+fn parse_time_range(value: &str) -> Result<(i64, i64)> {
+    if let Ok(ts) = value.parse::<i64>() {
+        return Ok((ts, ts));
+    }
+    if let Ok(ts) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok((ts.timestamp(), ts.timestamp()));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+        anyhow!(
+            "invalid date {:?}; expected YYYY-MM-DD, RFC3339 or unix seconds",
+            value
+        )
+    })?;
+    let start = date.and_time(NaiveTime::MIN).and_utc().timestamp();
+    Ok((start, start + DAY_SECS - 1))
+}
+
+fn parse_size(value: &str) -> Result<i64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (num, unit) = value.split_at(split);
+    let num: f64 = num
+        .parse()
+        .map_err(|_| anyhow!("invalid size {:?}; expected e.g. 500KB or 1.5GB", value))?;
+    let multiplier: f64 = match unit.trim().to_uppercase().as_str() {
+        "" | "B" => 1.0,
+        "K" | "KB" | "KIB" => 1024.0,
+        "M" | "MB" | "MIB" => 1024.0 * 1024.0,
+        "G" | "GB" | "GIB" => 1024.0 * 1024.0 * 1024.0,
+        "T" | "TB" | "TIB" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        other => bail!("unknown size unit {:?}", other),
+    };
+    Ok((num * multiplier).round() as i64)
+}
+
+fn expand_home(path: &str) -> String {
+    if let Some(rest) = path.strip_prefix('~') {
+        if let Some(home) = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")) {
+            return format!("{}{}", home.to_string_lossy(), rest);
+        }
+    }
+    path.to_string()
+}
+
+/// `files.path` is `prefix` or lies below it: `path:/data/a` matches `/data/a/x` but not
+/// `/data/ab`. Compared with `substr` rather than `LIKE`, so `%` and `_` in the prefix are literal.
+fn push_path_under(qb: &mut QueryBuilder<'_, Sqlite>, prefix: &str) {
+    let prefix = match prefix.trim_end_matches('/') {
+        "" => "/",
+        p => p,
+    };
+    let dir = if prefix.ends_with('/') {
+        prefix.to_string()
+    } else {
+        format!("{}/", prefix)
+    };
+    qb.push("(files.path = ");
+    qb.push_bind(prefix.to_string());
+    qb.push(" OR substr(files.path, 1, ");
+    qb.push_bind(dir.chars().count() as i64);
+    qb.push(") = ");
+    qb.push_bind(dir);
+    qb.push(")");
+}
+
+/// Qdrant stores lowercase `/`-separated prefixes (see `embeddings::path_prefixes`).
+fn normalize_prefixes(prefixes: &[String]) -> Vec<String> {
+    prefixes
+        .iter()
+        .map(|p| {
+            let norm = p.replace('\\', "/").to_lowercase();
+            let trimmed = norm.trim_end_matches('/');
+            if trimmed.starts_with('/') {
+                trimmed.to_string()
+            } else {
+                format!("/{}", trimmed)
+            }
+        })
+        .collect()
+}
+
+fn range_json(lower: Option<i64>, upper: Option<i64>) -> serde_json::Value {
+    let mut range = serde_json::Map::new();
+    if let Some(l) = lower {
+        range.insert("gte".into(), serde_json::json!(l));
+    }
+    if let Some(u) = upper {
+        range.insert("lte".into(), serde_json::json!(u));
+    }
+    serde_json::Value::Object(range)
+}
+
+fn tantivy_range(lower: Option<i64>, upper: Option<i64>) -> String {
+    format!(
+        "[{} TO {}]",
+        lower.map_or("*".to_string(), |v| v.to_string()),
+        upper.map_or("*".to_string(), |v| v.to_string())
+    )
+}
+
+fn fts_term(text: &str) -> String {
+    let quoted = format!("\"{}\"", text.replace('"', "\"\""));
+    if text.contains(char::is_whitespace) {
+        quoted
+    } else {
+        format!("{}*", quoted)
+    }
+}
+
+/// Quotes a value so characters the query parser treats as syntax (`:`, `+`, `(`, `*`, ...)
+/// are matched as text.
+fn tantivy_phrase(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', ""))
+}
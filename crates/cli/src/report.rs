@@ -0,0 +1,264 @@
+//! Disk-space report: where the bytes are and how much planned duplicate merges would reclaim.
+
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::{Row, SqlitePool};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Downloads untouched for this long are listed as cleanup candidates.
+pub const STALE_DOWNLOAD_DAYS: i64 = 90;
+const DAY: i64 = 24 * 60 * 60;
+/// Age buckets as (label, upper bound in days); the last bucket is open-ended.
+const AGE_BUCKETS: [(&str, i64); 5] = [
+    ("< 30 days", 30),
+    ("30-180 days", 180),
+    ("180 days-1 year", 365),
+    ("1-3 years", 3 * 365),
+    ("> 3 years", i64::MAX),
+];
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct SpaceBucket {
+    pub key: String,
+    pub files: u64,
+    pub bytes: i64,
+    /// Bytes freed by this bucket's planned `merge_duplicate` actions.
+    pub reclaimable: i64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct MergeSpace {
+    pub action_id: i64,
+    pub path: String,
+    pub bytes: i64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct DuplicateSpace {
+    pub group_id: i64,
+    pub survivor: Option<String>,
+    pub copies: u64,
+    pub size: i64,
+    pub reclaimable: i64,
+    pub merges: Vec<MergeSpace>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FileSpace {
+    pub path: String,
+    pub bytes: i64,
+    pub mtime: i64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SpaceReport {
+    pub total_files: u64,
+    pub total_bytes: i64,
+    pub reclaimable_bytes: i64,
+    pub duplicate_groups: Vec<DuplicateSpace>,
+    pub by_directory: Vec<SpaceBucket>,
+    pub by_tag: Vec<SpaceBucket>,
+    pub by_mime: Vec<SpaceBucket>,
+    pub by_age: Vec<SpaceBucket>,
+    pub stale_downloads: SpaceBucket,
+    pub largest_files: Vec<FileSpace>,
+}
+
+/// Builds the report from the `files` table. Directories are the first level below whichever
+/// scan root contains the file; `top` caps every ranked list.
+pub async fn space_report(
+    pool: &SqlitePool,
+    roots: &[String],
+    now: i64,
+    top: usize,
+) -> Result<SpaceReport> {
+    let rows = sqlx::query(
+        "SELECT f.id, f.path, f.size, COALESCE(f.mime, 'unknown'), f.mtime,
+            (SELECT GROUP_CONCAT(t.name, char(31)) FROM file_tags ft JOIN tags t ON t.id = ft.tag_id
+             WHERE ft.file_id = f.id AND t.name NOT LIKE 'duplicate_of:%'),
+            EXISTS(SELECT 1 FROM actions a WHERE a.file_id = f.id AND a.kind = 'merge_duplicate'
+                   AND a.status = 'planned')
+         FROM files f WHERE f.status != 'missing'",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut report = SpaceReport {
+        total_files: 0,
+        total_bytes: 0,
+        reclaimable_bytes: 0,
+        duplicate_groups: Vec::new(),
+        by_directory: Vec::new(),
+        by_tag: Vec::new(),
+        by_mime: Vec::new(),
+        by_age: AGE_BUCKETS
+            .iter()
+            .map(|(label, _)| SpaceBucket {
+                key: label.to_string(),
+                ..Default::default()
+            })
+            .collect(),
+        stale_downloads: SpaceBucket {
+            key: format!("downloads older than {} days", STALE_DOWNLOAD_DAYS),
+            ..Default::default()
+        },
+        largest_files: Vec::new(),
+    };
+    let mut dirs: HashMap<String, SpaceBucket> = HashMap::new();
+    let mut tags: HashMap<String, SpaceBucket> = HashMap::new();
+    let mut mimes: HashMap<String, SpaceBucket> = HashMap::new();
+
+    for row in rows {
+        let path: String = row.get(1);
+        let size: i64 = row.get(2);
+        let mime: String = row.get(3);
+        let mtime: i64 = row.get(4);
+        let tag_list: Option<String> = row.get(5);
+        let merge_planned: bool = row.get(6);
+        let reclaim = if merge_planned { size } else { 0 };
+
+        report.total_files += 1;
+        report.total_bytes += size;
+        report.reclaimable_bytes += reclaim;
+        add(
+            dirs.entry(top_directory(&path, roots)).or_default(),
+            size,
+            reclaim,
+        );
+        add(mimes.entry(mime).or_default(), size, reclaim);
+        for tag in tag_list.iter().flat_map(|t| t.split('\u{1f}')) {
+            add(tags.entry(tag.to_string()).or_default(), size, reclaim);
+        }
+        let age_days = (now - mtime).max(0) / DAY;
+        if let Some(bucket) = AGE_BUCKETS
+            .iter()
+            .position(|(_, max)| age_days < *max)
+            .and_then(|i| report.by_age.get_mut(i))
+        {
+            add(bucket, size, reclaim);
+        }
+        if age_days >= STALE_DOWNLOAD_DAYS && in_downloads(&path) {
+            add(&mut report.stale_downloads, size, reclaim);
+        }
+        report.largest_files.push(FileSpace {
+            path,
+            bytes: size,
+            mtime,
+        });
+    }
+
+    report.by_directory = ranked(dirs, top);
+    report.by_tag = ranked(tags, top);
+    report.by_mime = ranked(mimes, top);
+    report
+        .largest_files
+        .sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.path.cmp(&b.path)));
+    report.largest_files.truncate(top);
+    report.duplicate_groups = duplicate_space(pool, top).await?;
+    Ok(report)
+}
+
+async fn duplicate_space(pool: &SqlitePool, top: usize) -> Result<Vec<DuplicateSpace>> {
+    let groups = sqlx::query(
+        "SELECT g.id, s.path, COALESCE(g.size, 0),
+            (SELECT COUNT(*) FROM duplicate_group_members m WHERE m.group_id = g.id)
+         FROM duplicate_groups g LEFT JOIN files s ON s.id = g.survivor_file_id",
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut out = Vec::new();
+    for g in groups {
+        let group_id: i64 = g.get(0);
+        let merges: Vec<MergeSpace> = sqlx::query(
+            "SELECT a.id, f.path, f.size FROM actions a JOIN files f ON f.id = a.file_id
+             WHERE a.kind = 'merge_duplicate' AND a.status = 'planned'
+               AND json_extract(a.payload_json, '$.group_id') = ?
+             ORDER BY a.id",
+        )
+        .bind(group_id)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|r| MergeSpace {
+            action_id: r.get(0),
+            path: r.get(1),
+            bytes: r.get(2),
+        })
+        .collect();
+        out.push(DuplicateSpace {
+            group_id,
+            survivor: g.get(1),
+            size: g.get(2),
+            copies: g.get::<i64, _>(3) as u64,
+            reclaimable: merges.iter().map(|m| m.bytes).sum(),
+            merges,
+        });
+    }
+    out.sort_by(|a, b| {
+        b.reclaimable
+            .cmp(&a.reclaimable)
+            .then(a.group_id.cmp(&b.group_id))
+    });
+    out.truncate(top);
+    Ok(out)
+}
+
+fn add(bucket: &mut SpaceBucket, size: i64, reclaim: i64) {
+    bucket.files += 1;
+    bucket.bytes += size;
+    bucket.reclaimable += reclaim;
+}
+
+fn ranked(buckets: HashMap<String, SpaceBucket>, top: usize) -> Vec<SpaceBucket> {
+    let mut list: Vec<SpaceBucket> = buckets
+        .into_iter()
+        .map(|(key, b)| SpaceBucket { key, ..b })
+        .collect();
+    list.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.key.cmp(&b.key)));
+    list.truncate(top);
+    list
+}
+
+/// `root/first-level` for files under a scan root, otherwise the file's parent directory.
+pub fn top_directory(path: &str, roots: &[String]) -> String {
+    let p = Path::new(path);
+    for root in roots {
+        if let Ok(rest) = p.strip_prefix(root) {
+            let mut parts = rest.components();
+            return match (parts.next(), parts.next()) {
+                (Some(first), Some(_)) => {
+                    Path::new(root).join(first).to_string_lossy().into_owned()
+                }
+                _ => root.clone(),
+            };
+        }
+    }
+    p.parent()
+        .map(|d| d.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn in_downloads(path: &str) -> bool {
+    Path::new(path).components().any(|c| {
+        c.as_os_str()
+            .to_string_lossy()
+            .eq_ignore_ascii_case("downloads")
+    })
+}
+
+/// `1536` -> `1.5 KiB`.
+pub fn human_bytes(bytes: i64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value.abs() >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
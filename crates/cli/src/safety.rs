@@ -0,0 +1,47 @@
+//! Delete and dry-run policy for `apply`: operations that remove bytes from the user's tree
+//! (merging a duplicate away, overwriting a conflicting destination) need `safety.allow_delete`,
+//! and `safety.dry_run` holds unless the run is explicitly executed.
+
+use anyhow::{bail, Result};
+use organizer_core::config::SafetyConfig;
+use serde_json::Value;
+
+/// Whether a run only previews: `--execute` wins, then `--dry-run`, then `safety.dry_run`.
+pub fn resolve_dry_run(safety: &SafetyConfig, dry_run_flag: bool, execute: bool) -> bool {
+    if execute {
+        false
+    } else {
+        dry_run_flag || safety.dry_run
+    }
+}
+
+/// The `overwrite` conflict policy replaces whatever sits at a destination, so it is refused
+/// up front unless deletes are allowed.
+pub fn check_conflict_policy(safety: &SafetyConfig, conflict: &str) -> Result<()> {
+    if conflict == "overwrite" && !safety.allow_delete {
+        bail!("conflict policy 'overwrite' replaces existing files; set safety.allow_delete to use it");
+    }
+    Ok(())
+}
+
+/// Returns why an action may not run when it would remove bytes and deletes are not allowed.
+///
+/// Every merge strategy removes a copy: trash strategies delete the duplicate, `replace` and
+/// `keep_duplicate` overwrite the survivor, and link strategies swap the duplicate for a link.
+pub fn check_action(safety: &SafetyConfig, kind: &str, payload: &str) -> Result<(), String> {
+    if safety.allow_delete || kind != "merge_duplicate" {
+        return Ok(());
+    }
+    let strategy = serde_json::from_str::<Value>(payload)
+        .ok()
+        .and_then(|v| {
+            v.get("strategy")
+                .and_then(|s| s.as_str())
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| "trash_duplicate".to_string());
+    Err(format!(
+        "merge_duplicate ({}) removes a file; set safety.allow_delete to allow it",
+        strategy
+    ))
+}
@@ -0,0 +1,177 @@
+//! Apply sessions: every `apply` run that touches actions gets an `apply_sessions` row, and its
+//! actions point back at it so the run can be listed, inspected and undone as a unit.
+
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::{Row, SqlitePool};
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SessionSummary {
+    pub id: i64,
+    pub started_at: i64,
+    pub finished_at: Option<i64>,
+    /// `running` while apply is in progress (or if it died), then `finished` (or `failed` when
+    /// the run stopped on an error), then `undone`.
+    pub status: String,
+    /// Actions executed and failed when the run finished.
+    pub succeeded: i64,
+    pub failed: i64,
+    /// Actions of the session still executed now, i.e. what `undo --session` would revert.
+    pub executed: i64,
+    pub undone_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SessionAction {
+    pub id: i64,
+    pub kind: String,
+    pub path: Option<String>,
+    pub status: String,
+    pub executed_at: Option<i64>,
+    pub backup: Option<String>,
+    pub payload: String,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SessionDetail {
+    pub session: SessionSummary,
+    pub actions: Vec<SessionAction>,
+}
+
+const SUMMARY_SELECT: &str =
+    "SELECT s.id, s.started_at, s.finished_at, s.status, s.succeeded, s.failed,
+        (SELECT COUNT(*) FROM actions a WHERE a.session_id = s.id AND a.status = 'executed')
+            + (SELECT COUNT(*) FROM merged_files m WHERE m.session_id = s.id),
+        s.undone_at
+     FROM apply_sessions s";
+
+pub async fn start(pool: &SqlitePool) -> Result<i64> {
+    let id =
+        sqlx::query_scalar("INSERT INTO apply_sessions (status) VALUES ('running') RETURNING id")
+            .fetch_one(pool)
+            .await?;
+    Ok(id)
+}
+
+/// Closes a session with the executed/failed counts of its actions.
+pub async fn finish(pool: &SqlitePool, session_id: i64) -> Result<()> {
+    close(pool, session_id, "finished").await
+}
+
+/// Closes a session whose run stopped on an error; what did execute stays undoable.
+pub async fn fail(pool: &SqlitePool, session_id: i64) -> Result<()> {
+    close(pool, session_id, "failed").await
+}
+
+async fn close(pool: &SqlitePool, session_id: i64, status: &str) -> Result<()> {
+    sqlx::query(
+        "UPDATE apply_sessions SET status = ?2, finished_at = strftime('%s','now'),
+            succeeded = (SELECT COUNT(*) FROM actions WHERE session_id = ?1 AND status = 'executed')
+                + (SELECT COUNT(*) FROM merged_files WHERE session_id = ?1),
+            failed = (SELECT COUNT(*) FROM actions WHERE session_id = ?1 AND status = 'error')
+         WHERE id = ?1",
+    )
+    .bind(session_id)
+    .bind(status)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Marks the session undone once none of its actions is executed any more.
+pub async fn mark_undone_if_reverted(pool: &SqlitePool, session_id: i64) -> Result<bool> {
+    let res = sqlx::query(
+        "UPDATE apply_sessions SET status = 'undone', undone_at = strftime('%s','now')
+         WHERE id = ?1 AND NOT EXISTS(SELECT 1 FROM actions WHERE session_id = ?1 AND status = 'executed')
+           AND NOT EXISTS(SELECT 1 FROM merged_files WHERE session_id = ?1)",
+    )
+    .bind(session_id)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Newest first.
+pub async fn list_sessions(pool: &SqlitePool, limit: usize) -> Result<Vec<SessionSummary>> {
+    let rows = sqlx::query(&format!("{} ORDER BY s.id DESC LIMIT ?1", SUMMARY_SELECT))
+        .bind(limit as i64)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.iter().map(summary_from_row).collect())
+}
+
+pub async fn show_session(pool: &SqlitePool, session_id: i64) -> Result<Option<SessionDetail>> {
+    let Some(row) = sqlx::query(&format!("{} WHERE s.id = ?1", SUMMARY_SELECT))
+        .bind(session_id)
+        .fetch_optional(pool)
+        .await?
+    else {
+        return Ok(None);
+    };
+    let actions = sqlx::query(
+        "SELECT a.id, a.kind, COALESCE(f.path, d.path), a.status, a.executed_at, a.backup_path, a.payload_json
+         FROM actions a LEFT JOIN files f ON f.id = a.file_id LEFT JOIN directories d ON d.id = a.dir_id
+         WHERE a.session_id = ?1
+         UNION ALL
+         SELECT action_id, kind, path, 'executed', executed_at, backup_path, payload_json
+         FROM merged_files WHERE session_id = ?1
+         ORDER BY 1",
+    )
+    .bind(session_id)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| SessionAction {
+        id: r.get(0),
+        kind: r.get(1),
+        path: r.get(2),
+        status: r.get(3),
+        executed_at: r.get(4),
+        backup: r.get(5),
+        payload: r.get(6),
+    })
+    .collect();
+    Ok(Some(SessionDetail {
+        session: summary_from_row(&row),
+        actions,
+    }))
+}
+
+/// The most recent session that still has executed actions.
+pub async fn last_undoable(pool: &SqlitePool) -> Result<Option<i64>> {
+    let id = sqlx::query_scalar(
+        "SELECT s.id FROM apply_sessions s
+         WHERE EXISTS(SELECT 1 FROM actions a WHERE a.session_id = s.id AND a.status = 'executed')
+            OR EXISTS(SELECT 1 FROM merged_files m WHERE m.session_id = s.id)
+         ORDER BY s.id DESC LIMIT 1",
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(id)
+}
+
+/// Executed actions of a session, most recently executed first: the order they are undone in.
+pub async fn executed_actions_newest_first(pool: &SqlitePool, session_id: i64) -> Result<Vec<i64>> {
+    let ids = sqlx::query_scalar(
+        "SELECT id, executed_at FROM actions WHERE session_id = ?1 AND status = 'executed'
+         UNION ALL SELECT action_id, executed_at FROM merged_files WHERE session_id = ?1
+         ORDER BY 2 DESC, 1 DESC",
+    )
+    .bind(session_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(ids)
+}
+
+fn summary_from_row(r: &sqlx::sqlite::SqliteRow) -> SessionSummary {
+    SessionSummary {
+        id: r.get(0),
+        started_at: r.get(1),
+        finished_at: r.get(2),
+        status: r.get(3),
+        succeeded: r.get(4),
+        failed: r.get(5),
+        executed: r.get(6),
+        undone_at: r.get(7),
+    }
+}
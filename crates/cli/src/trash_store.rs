@@ -0,0 +1,263 @@
+//! Trash manifest: every backup `apply` writes to `safety.trash_dir` is recorded in
+//! `trash_entries` with its original path, action and content hash, and can be listed, restored
+//! or purged by age and total size.
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use sqlx::{Row, SqlitePool};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DAY: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TrashEntry {
+    pub id: i64,
+    pub action_id: Option<i64>,
+    pub original_path: String,
+    pub hash: String,
+    pub object_path: String,
+    pub size: i64,
+    pub created_at: i64,
+    pub restored_at: Option<i64>,
+    /// Other entries stored in the same object.
+    pub shared_with: i64,
+}
+
+/// What `purge` removes: everything, entries older than `max_age_days`, and then the oldest
+/// entries until the objects still referenced total at most `max_bytes`.
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    pub all: bool,
+    pub max_age_days: Option<u64>,
+    pub max_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct PurgeReport {
+    pub entries: usize,
+    pub objects: usize,
+    pub bytes_freed: i64,
+    /// Executed actions (live or merged away) whose backup was among the deleted objects. Their
+    /// `backup_path` is cleared, so undo reports the backup as gone.
+    pub backups_cleared: usize,
+}
+
+/// Adds the manifest entry for a backup `apply` just wrote for `action_id`.
+pub async fn record<'e, E: sqlx::Executor<'e, Database = sqlx::Sqlite>>(
+    executor: E,
+    action_id: i64,
+    original_path: &str,
+    object: &Path,
+) -> Result<()> {
+    let hash = object
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let size = fs::metadata(object)?.len() as i64;
+    sqlx::query(
+        "INSERT INTO trash_entries(action_id, original_path, hash, object_path, size) VALUES (?1, ?2, ?3, ?4, ?5)",
+    )
+    .bind(action_id)
+    .bind(original_path)
+    .bind(hash)
+    .bind(object.to_string_lossy())
+    .bind(size)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Newest first.
+pub async fn list_entries(pool: &SqlitePool) -> Result<Vec<TrashEntry>> {
+    let rows = sqlx::query(
+        "SELECT e.id, e.action_id, e.original_path, e.hash, e.object_path, e.size, e.created_at, e.restored_at,
+            (SELECT COUNT(*) FROM trash_entries o WHERE o.hash = e.hash AND o.id != e.id)
+         FROM trash_entries e ORDER BY e.created_at DESC, e.id DESC",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|r| TrashEntry {
+            id: r.get(0),
+            action_id: r.get(1),
+            original_path: r.get(2),
+            hash: r.get(3),
+            object_path: r.get(4),
+            size: r.get(5),
+            created_at: r.get(6),
+            restored_at: r.get(7),
+            shared_with: r.get(8),
+        })
+        .collect())
+}
+
+/// Copies an entry's bytes back to its original path (or `to`), never over an existing file.
+/// The entry stays in the trash, marked restored, until purged.
+pub async fn restore(pool: &SqlitePool, entry_id: i64, to: Option<&Path>) -> Result<PathBuf> {
+    let Some(row) =
+        sqlx::query("SELECT original_path, object_path FROM trash_entries WHERE id = ?1")
+            .bind(entry_id)
+            .fetch_optional(pool)
+            .await?
+    else {
+        bail!("no trash entry {}", entry_id);
+    };
+    let original: String = row.get(0);
+    let object: String = row.get(1);
+    let dest = to
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from(&original));
+    if dest.exists() {
+        bail!("{} already exists", dest.display());
+    }
+    if !Path::new(&object).exists() {
+        bail!("backup {} is missing from the trash", object);
+    }
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(&object, &dest)?;
+    sqlx::query("UPDATE trash_entries SET restored_at = strftime('%s','now') WHERE id = ?1")
+        .bind(entry_id)
+        .execute(pool)
+        .await?;
+    let _ = sqlx::query("INSERT OR REPLACE INTO dirty(path, reason, updated_at) VALUES (?1,'trash_restore', strftime('%s','now'))")
+        .bind(dest.to_string_lossy())
+        .execute(pool)
+        .await;
+    Ok(dest)
+}
+
+/// Marks the entries `action_id` recorded for `object` restored, once undo has copied the
+/// object back.
+pub async fn mark_restored(pool: &SqlitePool, action_id: i64, object: &Path) -> Result<()> {
+    sqlx::query(
+        "UPDATE trash_entries SET restored_at = strftime('%s','now')
+         WHERE action_id = ?1 AND object_path = ?2 AND restored_at IS NULL",
+    )
+    .bind(action_id)
+    .bind(object.to_string_lossy())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Drops entries selected by `policy` and deletes objects no remaining entry refers to, clearing
+/// the backup of any executed action that pointed at one. With `dry_run` only the report is
+/// computed.
+pub async fn purge(
+    pool: &SqlitePool,
+    policy: &RetentionPolicy,
+    now: i64,
+    dry_run: bool,
+) -> Result<PurgeReport> {
+    // Oldest first, so the size limit evicts from the front.
+    let rows = sqlx::query(
+        "SELECT id, hash, object_path, size, created_at FROM trash_entries ORDER BY created_at, id",
+    )
+    .fetch_all(pool)
+    .await?;
+    let entries: Vec<(i64, String, String, i64, i64)> = rows
+        .iter()
+        .map(|r| (r.get(0), r.get(1), r.get(2), r.get(3), r.get(4)))
+        .collect();
+
+    let cutoff = policy
+        .max_age_days
+        .map(|d| now - (d as i64).saturating_mul(DAY));
+    let mut drop: Vec<bool> = entries
+        .iter()
+        .map(|(_, _, _, _, created)| policy.all || cutoff.is_some_and(|c| *created < c))
+        .collect();
+
+    if let Some(max) = policy.max_bytes {
+        // An object's bytes are only freed once its last entry goes.
+        let mut refs: HashMap<&str, usize> = HashMap::new();
+        let mut stored = 0i64;
+        for (e, _) in entries.iter().zip(&drop).filter(|(_, d)| !**d) {
+            let r = refs.entry(e.1.as_str()).or_insert(0);
+            if *r == 0 {
+                stored += e.3;
+            }
+            *r += 1;
+        }
+        for (e, d) in entries.iter().zip(drop.iter_mut()) {
+            if stored <= max as i64 {
+                break;
+            }
+            if *d {
+                continue;
+            }
+            *d = true;
+            if let Some(r) = refs.get_mut(e.1.as_str()) {
+                *r -= 1;
+                if *r == 0 {
+                    stored -= e.3;
+                }
+            }
+        }
+    }
+
+    let kept: HashSet<&str> = entries
+        .iter()
+        .zip(&drop)
+        .filter(|(_, d)| !**d)
+        .map(|(e, _)| e.1.as_str())
+        .collect();
+    let mut report = PurgeReport::default();
+    let mut freed: HashSet<&str> = HashSet::new();
+    for (e, _) in entries.iter().zip(&drop).filter(|(_, d)| **d) {
+        report.entries += 1;
+        if kept.contains(e.1.as_str()) || !freed.insert(e.1.as_str()) {
+            continue;
+        }
+        report.objects += 1;
+        report.bytes_freed += e.3;
+        report.backups_cleared += clear_backup_references(pool, &e.2, dry_run).await?;
+        if !dry_run {
+            let _ = fs::remove_file(&e.2);
+        }
+    }
+    if !dry_run {
+        for (e, _) in entries.iter().zip(&drop).filter(|(_, d)| **d) {
+            sqlx::query("DELETE FROM trash_entries WHERE id = ?1")
+                .bind(e.0)
+                .execute(pool)
+                .await?;
+        }
+    }
+    Ok(report)
+}
+
+/// Counts, and unless `dry_run` clears, the undo references to `object` held by executed actions
+/// and merge snapshots.
+async fn clear_backup_references(pool: &SqlitePool, object: &str, dry_run: bool) -> Result<usize> {
+    let mut cleared = 0;
+    for (count, clear) in [
+        (
+            "SELECT COUNT(*) FROM actions WHERE status = 'executed' AND backup_path = ?1",
+            "UPDATE actions SET backup_path = NULL WHERE status = 'executed' AND backup_path = ?1",
+        ),
+        (
+            "SELECT COUNT(*) FROM merged_files WHERE backup_path = ?1",
+            "UPDATE merged_files SET backup_path = NULL WHERE backup_path = ?1",
+        ),
+    ] {
+        cleared += if dry_run {
+            sqlx::query_scalar::<_, i64>(count)
+                .bind(object)
+                .fetch_one(pool)
+                .await? as usize
+        } else {
+            sqlx::query(clear)
+                .bind(object)
+                .execute(pool)
+                .await?
+                .rows_affected() as usize
+        };
+    }
+    Ok(cleared)
+}
@@ -1,68 +1,526 @@
+//! Undo of executed actions, kind by kind: moved files go back, applied tags come off and merged
+//! duplicates are restored from their backup together with their `files` row and tags. Every
+//! attempt leaves an `undo` audit entry.
+
+use crate::apply;
+use crate::dirs::{self, DirMove};
+use crate::fs_apply;
+use crate::journal;
+use crate::sessions;
+use crate::trash_store;
 use anyhow::Result;
-use sqlx::Row;
+use organizer_core::fts;
+use sqlx::{Row, SqliteConnection, SqlitePool};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use storage;
 
+/// Reverts the executed actions of one apply session, newest first, and marks the session undone
+/// once nothing of it remains executed. Returns how many actions were actually reverted.
+pub async fn undo_session(
+    db_path: &str,
+    session_id: i64,
+    backup_override: Option<&str>,
+) -> Result<usize> {
+    let pool = storage::connect(db_path).await?;
+    let ids = sessions::executed_actions_newest_first(&pool, session_id).await?;
+    let mut reverted = 0;
+    if !ids.is_empty() {
+        let list: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+        reverted = undo_actions(db_path, Some(&list.join(",")), backup_override).await?;
+    }
+    sessions::mark_undone_if_reverted(&pool, session_id).await?;
+    Ok(reverted)
+}
+
+struct Undoable {
+    id: i64,
+    path: String,
+    kind: String,
+    payload: String,
+    executed_at: Option<i64>,
+    backup: Option<String>,
+    undo_token: Option<String>,
+    /// The action only survives as a `merged_files` snapshot (its files row was deleted).
+    merged: bool,
+}
+
+enum Outcome {
+    Reverted { dirty: Vec<String>, detail: String },
+    Skipped(String),
+}
+
+/// Undoes executed actions (all of them when `ids` is `None`), most recently executed first.
+/// Returns how many were reverted; skipped ones are only audited.
 pub async fn undo_actions(
     db_path: &str,
     ids: Option<&str>,
     backup_override: Option<&str>,
-) -> Result<()> {
+) -> Result<usize> {
     let pool = storage::connect(db_path).await?;
-    let rows = if let Some(id_list) = ids {
-        let placeholders: Vec<String> = id_list.split(',').map(|_| "?".into()).collect();
-        let sql = format!(
-            "SELECT actions.id, files.path, actions.kind, actions.payload_json, actions.status, actions.executed_at, actions.backup_path FROM actions JOIN files ON files.id = actions.file_id WHERE actions.status = 'executed' AND actions.id IN ({})",
-            placeholders.join(",")
-        );
+    let mut items = load_undoable(&pool, ids).await?;
+    items.sort_by(|a, b| b.executed_at.cmp(&a.executed_at).then(b.id.cmp(&a.id)));
+    let mut reverted = 0;
+
+    for item in items {
+        let backup = backup_override
+            .map(|s| s.to_string())
+            .or_else(|| item.backup.clone())
+            .or_else(|| extract_backup(&item.payload).map(|p| p.to_string_lossy().into_owned()));
+        let outcome = match item.kind.as_str() {
+            "move" | "rename" => undo_move(&pool, &item, backup.as_deref()).await,
+            "move_dir" => undo_move_dir(&pool, &item).await,
+            "tag" | "dedupe" => undo_tag(&pool, &item).await,
+            "merge_duplicate" if item.merged => undo_merge(&pool, &item, backup.as_deref()).await,
+            "merge_duplicate" if is_link_strategy(&item.payload) => {
+                undo_link_merge(&pool, &item, backup.as_deref()).await
+            }
+            _ => restore_copy(&pool, &item, backup.as_deref()).await,
+        }
+        .unwrap_or_else(|e| Outcome::Skipped(e.to_string()));
+
+        let detail = match &outcome {
+            Outcome::Reverted { dirty, detail } => {
+                reverted += 1;
+                sqlx::query("UPDATE actions SET status='planned', executed_at=NULL WHERE id = ?1")
+                    .bind(item.id)
+                    .execute(&pool)
+                    .await?;
+                for d in dirty {
+                    let _ = sqlx::query("INSERT OR REPLACE INTO dirty(path, reason, updated_at) VALUES (?1,'undo', strftime('%s','now'))")
+                        .bind(d)
+                        .execute(&pool)
+                        .await;
+                }
+                detail.clone()
+            }
+            Outcome::Skipped(reason) => format!("skipped: {}", reason),
+        };
+        // A merge whose snapshot could not be restored has no action row to point at.
+        sqlx::query("INSERT INTO audit(action_id, event, detail) VALUES ((SELECT id FROM actions WHERE id = ?1), 'undo', ?2)")
+            .bind(item.id)
+            .bind(format!("{} #{}: {}", item.kind, item.id, detail))
+            .execute(&pool)
+            .await?;
+    }
+
+    Ok(reverted)
+}
+
+async fn load_undoable(pool: &SqlitePool, ids: Option<&str>) -> Result<Vec<Undoable>> {
+    let id_list: Vec<&str> = ids
+        .map(|l| {
+            l.split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    let filter = |column: &str| {
+        if ids.is_some() {
+            format!(
+                " AND {} IN ({})",
+                column,
+                vec!["?"; id_list.len().max(1)].join(",")
+            )
+        } else {
+            String::new()
+        }
+    };
+    let live_sql = format!(
+        "SELECT actions.id, COALESCE(files.path, directories.path), actions.kind, actions.payload_json, actions.executed_at, actions.backup_path, actions.undo_token FROM actions LEFT JOIN files ON files.id = actions.file_id LEFT JOIN directories ON directories.id = actions.dir_id WHERE actions.status = 'executed'{}",
+        filter("actions.id")
+    );
+    let merged_sql = format!(
+        "SELECT action_id, path, kind, payload_json, executed_at, backup_path, NULL FROM merged_files WHERE 1 = 1{}",
+        filter("action_id")
+    );
+    let mut items = Vec::new();
+    for (sql, merged) in [(live_sql, false), (merged_sql, true)] {
         let mut query = sqlx::query(&sql);
-        for id in id_list.split(',') {
-            query = query.bind(id.trim());
+        if ids.is_some() {
+            if id_list.is_empty() {
+                query = query.bind("");
+            }
+            for id in &id_list {
+                query = query.bind(*id);
+            }
+        }
+        for row in query.fetch_all(pool).await? {
+            items.push(Undoable {
+                id: row.get(0),
+                path: row.get(1),
+                kind: row.get(2),
+                payload: row.get(3),
+                executed_at: row.get(4),
+                backup: row.get(5),
+                undo_token: row.get(6),
+                merged,
+            });
         }
-        query.fetch_all(&pool).await?
+    }
+    Ok(items)
+}
+
+/// Moves the file back from where the action put it. The backup is only used when the moved
+/// file has gone missing since.
+async fn undo_move(pool: &SqlitePool, item: &Undoable, backup: Option<&str>) -> Result<Outcome> {
+    // Journalled moves updated the row to the new location and remember the old one; older
+    // actions still point at the original path and only the payload knows the destination.
+    let original = journal::original_path(pool, item.id).await?;
+    let (current, back_to) = match &original {
+        Some(orig) => (item.path.clone(), orig.clone()),
+        None => (
+            extract_to(&item.payload).unwrap_or_else(|| item.path.clone()),
+            item.path.clone(),
+        ),
+    };
+    if current == back_to {
+        return Ok(Outcome::Skipped("nothing to move back".to_string()));
+    }
+    let target = Path::new(&back_to);
+    if target.exists() {
+        return Ok(Outcome::Skipped(format!("{} already exists", back_to)));
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let detail = if Path::new(&current).exists() {
+        fs_apply::move_across(Path::new(&current), target)?;
+        format!("moved {} back to {}", current, back_to)
+    } else if let Some(b) = backup {
+        restore_backup(pool, item.id, Path::new(b), target).await?;
+        format!("{} missing; restored {} from {}", current, back_to, b)
     } else {
-        sqlx::query("SELECT actions.id, files.path, actions.kind, actions.payload_json, actions.status, actions.executed_at, actions.backup_path FROM actions JOIN files ON files.id = actions.file_id WHERE actions.status = 'executed'")
-            .fetch_all(&pool)
-            .await?
+        return Ok(Outcome::Skipped(format!(
+            "{} missing and no backup",
+            current
+        )));
     };
+    if original.is_some() {
+        let mut tx = pool.begin().await?;
+        journal::record_move(&mut tx, &current, &back_to).await?;
+        tx.commit().await?;
+    }
+    Ok(Outcome::Reverted {
+        dirty: vec![current, back_to],
+        detail,
+    })
+}
 
-    for row in rows {
-        let id: i64 = row.get(0);
-        let path: String = row.get(1);
-        let payload: String = row.get(3);
-        let backup_col: Option<String> = row.try_get(6).ok();
-        let mut restored = false;
-        if let Some(backup) = backup_override.map(|s| s.to_string()).or_else(|| {
-            backup_col
-                .or_else(|| extract_backup(&payload).map(|p| p.to_string_lossy().into_owned()))
-        }) {
-            let dest = PathBuf::from(&path);
-            if dest.exists() {
-                continue; // skip to avoid overwrite
-            }
-            if let Some(parent) = dest.parent() {
-                let _ = fs::create_dir_all(parent);
+/// Moves a directory back: a renamed one in one step, a merged one file by file, leaving files
+/// that reappeared at their old path or vanished from the new one where they are.
+async fn undo_move_dir(pool: &SqlitePool, item: &Undoable) -> Result<Outcome> {
+    let Some(original) = journal::original_path(pool, item.id).await? else {
+        return Ok(Outcome::Skipped(
+            "no journal entry for the move".to_string(),
+        ));
+    };
+    let moved: DirMove = item
+        .undo_token
+        .as_deref()
+        .and_then(|t| serde_json::from_str(t).ok())
+        .unwrap_or_default();
+    let (current, target) = (Path::new(&item.path), Path::new(&original));
+    let back = if moved.renamed {
+        if target.exists() {
+            return Ok(Outcome::Skipped(format!("{} already exists", original)));
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(current, target)?;
+        moved
+    } else {
+        fs::create_dir_all(target)?;
+        let mut back = DirMove::default();
+        for (src, dest) in moved.files.iter().rev() {
+            let (src_path, dest_path) = (Path::new(src), Path::new(dest));
+            if src_path.exists() || !dest_path.exists() {
+                continue;
             }
-            if fs::copy(&backup, &dest).is_ok() {
-                restored = true;
+            if let Some(parent) = src_path.parent() {
+                fs::create_dir_all(parent)?;
             }
+            fs_apply::move_across(dest_path, src_path)?;
+            back.files.push((dest.clone(), src.clone()));
         }
-        if restored {
-            sqlx::query("UPDATE actions SET status='planned', executed_at=NULL WHERE id = ?1")
-                .bind(id)
-                .execute(&pool)
-                .await?;
-            let _ = sqlx::query("INSERT OR REPLACE INTO dirty(path, reason, updated_at) VALUES (?1,'undo', strftime('%s','now'))")
-                .bind(path)
-                .execute(&pool)
-                .await;
-        }
+        back
+    };
+    let mut tx = pool.begin().await?;
+    // After a merge this also hands the action back to the folder at its old path.
+    let files = dirs::record_dir_move(&mut tx, Some(item.id), &item.path, &original, &back).await?;
+    tx.commit().await?;
+    let mut dirty = vec![item.path.clone(), original.clone()];
+    dirty.extend(files.into_iter().flat_map(|(src, dest)| [src, dest]));
+    Ok(Outcome::Reverted {
+        dirty,
+        detail: format!("moved {} back to {}", item.path, original),
+    })
+}
+
+/// Removes the tag a `tag` or `dedupe` action attached. Tags the file already had from another
+/// source were never touched by the action and stay.
+async fn undo_tag(pool: &SqlitePool, item: &Undoable) -> Result<Outcome> {
+    let payload: serde_json::Value = serde_json::from_str(&item.payload).unwrap_or_default();
+    let field = |k: &str| {
+        payload
+            .get(k)
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+    };
+    let (tag, source) = match item.kind.as_str() {
+        "tag" => (field("tag"), "apply"),
+        _ => (
+            apply::extract_duplicate_of(&item.payload).map(|d| format!("duplicate_of:{}", d)),
+            "dedupe",
+        ),
+    };
+    let Some(tag) = tag else {
+        return Ok(Outcome::Skipped("payload names no tag".to_string()));
+    };
+    let mut conn = pool.acquire().await?;
+    let removed = sqlx::query(
+        "DELETE FROM file_tags WHERE file_id = (SELECT id FROM files WHERE path = ?1)
+           AND tag_id = (SELECT id FROM tags WHERE name = ?2) AND source = ?3",
+    )
+    .bind(&item.path)
+    .bind(&tag)
+    .bind(source)
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+    fts::refresh_path(&mut conn, &item.path).await?;
+    Ok(Outcome::Reverted {
+        dirty: vec![item.path.clone()],
+        detail: if removed > 0 {
+            format!("removed tag {}", tag)
+        } else {
+            format!("tag {} was not added by this action", tag)
+        },
+    })
+}
+
+/// Restores a merged duplicate from its backup, then re-inserts its `files` row (under the old id
+/// unless the path was rescanned meanwhile), its tags and the action itself.
+async fn undo_merge(pool: &SqlitePool, item: &Undoable, backup: Option<&str>) -> Result<Outcome> {
+    let target = Path::new(&item.path);
+    if target.exists() {
+        return Ok(Outcome::Skipped(format!("{} already exists", item.path)));
+    }
+    let Some(b) = backup else {
+        return Ok(Outcome::Skipped("no backup to restore from".to_string()));
+    };
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    restore_backup(pool, item.id, Path::new(b), target).await?;
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT OR IGNORE INTO files(id, path, size, mtime, ctime, hash, fast_hash, full_hash, mime, ext, status, first_seen, last_seen)
+         SELECT json_extract(f, '$.id'), path, json_extract(f, '$.size'), json_extract(f, '$.mtime'),
+                json_extract(f, '$.ctime'), json_extract(f, '$.hash'), json_extract(f, '$.fast_hash'),
+                json_extract(f, '$.full_hash'), json_extract(f, '$.mime'), json_extract(f, '$.ext'),
+                json_extract(f, '$.status'), json_extract(f, '$.first_seen'), json_extract(f, '$.last_seen')
+         FROM (SELECT path, file_json AS f FROM merged_files WHERE action_id = ?1)",
+    )
+    .bind(item.id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "INSERT OR IGNORE INTO tags(name)
+         SELECT json_extract(j.value, '$.tag') FROM merged_files m, json_each(m.tags_json) j WHERE m.action_id = ?1",
+    )
+    .bind(item.id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "INSERT OR IGNORE INTO file_tags(file_id, tag_id, confidence, source)
+         SELECT (SELECT id FROM files WHERE path = m.path),
+                (SELECT id FROM tags WHERE name = json_extract(j.value, '$.tag')),
+                json_extract(j.value, '$.confidence'), json_extract(j.value, '$.source')
+         FROM merged_files m, json_each(m.tags_json) j WHERE m.action_id = ?1",
+    )
+    .bind(item.id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "INSERT OR IGNORE INTO actions(id, file_id, kind, payload_json, status, created_at, backup_path, session_id)
+         SELECT action_id, (SELECT id FROM files WHERE path = m.path), kind, payload_json, 'planned',
+                COALESCE(action_created_at, strftime('%s','now')), backup_path, session_id
+         FROM merged_files m WHERE m.action_id = ?1",
+    )
+    .bind(item.id)
+    .execute(&mut *tx)
+    .await?;
+    let tags: Vec<String> = sqlx::query_scalar(
+        "SELECT json_extract(j.value, '$.tag') FROM merged_files m, json_each(m.tags_json) j WHERE m.action_id = ?1",
+    )
+    .bind(item.id)
+    .fetch_all(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM merged_files WHERE action_id = ?1")
+        .bind(item.id)
+        .execute(&mut *tx)
+        .await?;
+    let mut dirty = vec![item.path.clone()];
+    if let Some(survivor) = apply::extract_duplicate_of(&item.payload) {
+        remove_merge_tags(&mut tx, &survivor, &tags).await?;
+        dirty.push(survivor);
+    }
+    fts::refresh_path(&mut tx, &item.path).await?;
+    tx.commit().await?;
+    Ok(Outcome::Reverted {
+        dirty,
+        detail: format!("restored {} from {}", item.path, b),
+    })
+}
+
+/// Swaps the backup back in for a linked duplicate. The path is a link to the survivor, so the
+/// backup replaces the link rather than being written through it.
+async fn undo_link_merge(
+    pool: &SqlitePool,
+    item: &Undoable,
+    backup: Option<&str>,
+) -> Result<Outcome> {
+    let Some(b) = backup else {
+        return Ok(Outcome::Skipped("no backup to restore from".to_string()));
+    };
+    replace_link_with_backup(Path::new(&item.path), Path::new(b))?;
+    trash_store::mark_restored(pool, item.id, Path::new(b)).await?;
+    let mut dirty = vec![item.path.clone()];
+    if let Some(survivor) = apply::extract_duplicate_of(&item.payload) {
+        let mut tx = pool.begin().await?;
+        let tags: Vec<String> = sqlx::query_scalar(
+            "SELECT t.name FROM file_tags ft JOIN tags t ON t.id = ft.tag_id
+             WHERE ft.file_id = (SELECT id FROM files WHERE path = ?1)",
+        )
+        .bind(&item.path)
+        .fetch_all(&mut *tx)
+        .await?;
+        remove_merge_tags(&mut tx, &survivor, &tags).await?;
+        tx.commit().await?;
+        dirty.push(survivor);
+    }
+    Ok(Outcome::Reverted {
+        dirty,
+        detail: format!("restored {} from {}", item.path, b),
+    })
+}
+
+/// Takes back the tags a merge copied from its duplicate onto `survivor`. Only copies (source
+/// `merge`) come off, and a tag stays while another merged duplicate of the survivor carried it.
+async fn remove_merge_tags(
+    conn: &mut SqliteConnection,
+    survivor: &str,
+    tags: &[String],
+) -> Result<()> {
+    for tag in tags {
+        sqlx::query(
+            "DELETE FROM file_tags WHERE file_id = (SELECT id FROM files WHERE path = ?1)
+               AND tag_id = (SELECT id FROM tags WHERE name = ?2) AND source = 'merge'
+               AND NOT EXISTS (
+                 SELECT 1 FROM merged_files m, json_each(m.tags_json) j
+                 WHERE json_extract(j.value, '$.tag') = ?2
+                   AND COALESCE(json_extract(m.payload_json, '$.duplicate_of'),
+                                json_extract(m.payload_json, '$.payload.duplicate_of')) = ?1)",
+        )
+        .bind(survivor)
+        .bind(tag)
+        .execute(&mut *conn)
+        .await?;
+    }
+    fts::refresh_path(conn, survivor).await?;
+    Ok(())
+}
+
+/// Snapshots a merge action with its duplicate's `files` row and tags into `merged_files`; call
+/// it right before that row is deleted.
+pub async fn snapshot_merged(conn: &mut SqliteConnection, action_id: i64) -> Result<()> {
+    sqlx::query(
+        "INSERT OR REPLACE INTO merged_files(action_id, session_id, kind, payload_json, action_created_at, executed_at, backup_path, path, file_json, tags_json)
+         SELECT a.id, a.session_id, a.kind, a.payload_json, a.created_at, a.executed_at, a.backup_path, f.path,
+                json_object('id', f.id, 'size', f.size, 'mtime', f.mtime, 'ctime', f.ctime, 'hash', f.hash,
+                            'fast_hash', f.fast_hash, 'full_hash', f.full_hash, 'mime', f.mime, 'ext', f.ext,
+                            'status', f.status, 'first_seen', f.first_seen, 'last_seen', f.last_seen),
+                (SELECT json_group_array(json_object('tag', t.name, 'confidence', ft.confidence, 'source', ft.source))
+                 FROM file_tags ft JOIN tags t ON t.id = ft.tag_id WHERE ft.file_id = f.id)
+         FROM actions a JOIN files f ON f.id = a.file_id WHERE a.id = ?1",
+    )
+    .bind(action_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Fallback for kinds without a specific undo: copy the backup back if the path is free.
+async fn restore_copy(pool: &SqlitePool, item: &Undoable, backup: Option<&str>) -> Result<Outcome> {
+    let path = item.path.as_str();
+    let Some(b) = backup else {
+        return Ok(Outcome::Skipped("no backup to restore from".to_string()));
+    };
+    let dest = PathBuf::from(path);
+    if dest.exists() {
+        return Ok(Outcome::Skipped(format!("{} already exists", path)));
     }
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    restore_backup(pool, item.id, Path::new(b), &dest).await?;
+    Ok(Outcome::Reverted {
+        dirty: vec![path.to_string()],
+        detail: format!("restored {} from {}", path, b),
+    })
+}
 
+/// Puts a backup's bytes at `dest`: files in the OS trash are moved back out of it (dropping
+/// their `.trashinfo`), backups in our own trash are copied so the object stays shared and
+/// the action's manifest entry is marked restored.
+async fn restore_backup(
+    pool: &SqlitePool,
+    action_id: i64,
+    backup: &Path,
+    dest: &Path,
+) -> Result<()> {
+    if fs_apply::os_trash_info_path(backup).is_some() {
+        return fs_apply::restore_from_os_trash(backup, dest);
+    }
+    fs::copy(backup, dest)?;
+    trash_store::mark_restored(pool, action_id, backup).await
+}
+
+fn is_link_strategy(payload: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(payload)
+        .ok()
+        .and_then(|v| {
+            v.get("strategy")
+                .and_then(|s| s.as_str())
+                .map(fs_apply::is_link_strategy)
+        })
+        .unwrap_or(false)
+}
+
+fn replace_link_with_backup(dest: &Path, backup: &Path) -> Result<()> {
+    let name = dest
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "restore".to_string());
+    let tmp = dest.with_file_name(format!(".{}.organizer-restore", name));
+    fs::copy(backup, &tmp)?;
+    if let Err(e) = fs::rename(&tmp, dest) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e.into());
+    }
     Ok(())
 }
 
+fn extract_to(payload: &str) -> Option<String> {
+    serde_json::from_str::<serde_json::Value>(payload)
+        .ok()
+        .and_then(|v| v.get("to").and_then(|t| t.as_str()).map(|s| s.to_string()))
+}
+
 fn extract_backup(payload: &str) -> Option<PathBuf> {
     serde_json::from_str::<serde_json::Value>(payload)
         .ok()
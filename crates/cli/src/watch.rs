@@ -1,15 +1,16 @@
-use crate::keyword_index;
+use crate::keyword_index::{self, IndexState, KeywordDoc};
 use anyhow::Result;
 use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use organizer_core::classifier;
 use organizer_core::config::{AppConfig, SafetyConfig};
 use organizer_core::embeddings;
 use organizer_core::extractor;
+use organizer_core::fts;
 use organizer_core::pipeline;
 use organizer_core::scanner::{self, HashMode};
 use organizer_core::vectorstore::AsQdrant;
 use sqlx::{QueryBuilder, Row};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::channel;
 use std::time::{Duration, Instant};
@@ -22,7 +23,7 @@ pub async fn watch_paths(
     quiet: bool,
 ) -> Result<()> {
     let mut watch_list: Vec<PathBuf> = if paths.is_empty() {
-        cfg.scan.include.iter().map(|p| PathBuf::from(p)).collect()
+        cfg.scan.include.iter().map(PathBuf::from).collect()
     } else {
         paths.into_iter().map(PathBuf::from).collect()
     };
@@ -55,15 +56,10 @@ pub async fn watch_paths(
     let mut attempted_vectors_last: usize = 0;
 
     loop {
-        match rx.recv_timeout(Duration::from_millis(500)) {
-            Ok(event) => {
-                if let Ok(ev) = event {
-                    for path in ev.paths {
-                        pending.insert(path);
-                    }
-                }
+        if let Ok(Ok(ev)) = rx.recv_timeout(Duration::from_millis(500)) {
+            for path in ev.paths {
+                pending.insert(path);
             }
-            Err(_) => {}
         }
 
         if !pending.is_empty() && last_flush.elapsed() >= debounce {
@@ -103,7 +99,7 @@ pub async fn watch_paths(
             if !removed.is_empty() {
                 if cfg!(feature = "keyword-index") {
                     let _ = keyword_index::enabled::delete_docs(
-                        &keyword_index_dir(&cfg.database.path),
+                        &keyword_index::index_dir(&cfg.database.path),
                         &removed,
                     );
                 }
@@ -143,7 +139,7 @@ pub async fn watch_paths(
                 }
             }
             if cfg!(feature = "keyword-index") {
-                let _ = refresh_keyword_index_if_dirty(&cfg, None).await;
+                let _ = refresh_keyword_index_if_dirty(&cfg.database.path).await;
             }
             if !quiet {
                 println!(
@@ -170,7 +166,7 @@ pub async fn watch_paths(
     }
 }
 
-async fn process_file(cfg: &AppConfig, path: &PathBuf) -> Result<()> {
+async fn process_file(cfg: &AppConfig, path: &Path) -> Result<()> {
     // Re-scan just this path, then run extractor/embed/classify.
     let pool = storage::connect(&cfg.database.path).await?;
     // Capture existing hash to detect change.
@@ -184,8 +180,9 @@ async fn process_file(cfg: &AppConfig, path: &PathBuf) -> Result<()> {
 
     let hash_mode = HashMode::from(cfg.scan.hash_mode.as_deref().unwrap_or(""));
     let excludes = cfg.scan.exclude.clone();
-    let roots = vec![path.clone()];
-    scanner::scan(&roots, &excludes, &hash_mode, &pool).await?;
+    let roots = vec![path.to_path_buf()];
+    let options = scanner::ScanOptions::from_config(&cfg.scan);
+    scanner::scan_with_options(&roots, &excludes, &hash_mode, &options, &pool).await?;
 
     extractor::run_extractor(&pool, &cfg.parsers).await?;
 
@@ -251,42 +248,46 @@ async fn files_for_ids(pool: &sqlx::SqlitePool, ids: &[i64]) -> Result<Vec<stora
     Ok(rows)
 }
 
-// Copied from main.rs until shared helpers are extracted.
-fn keyword_index_dir(db_path: &str) -> PathBuf {
-    let stripped = db_path.strip_prefix("sqlite://").unwrap_or(db_path);
-    let db = PathBuf::from(stripped);
-    let base = if stripped == ":memory:" {
-        std::env::temp_dir()
-    } else {
-        db.parent().unwrap_or_else(|| Path::new(".")).to_path_buf()
-    };
-    let dir = base.join(".organizer_keyword_index");
-    let _ = std::fs::create_dir_all(&dir);
-    dir
+/// Wipes the keyword index and re-adds every file in the DB; returns the document count.
+pub async fn rebuild_keyword_index(db_path: &str) -> Result<usize> {
+    let pool = storage::connect(db_path).await?;
+    let paths: Vec<String> = sqlx::query_scalar("SELECT path FROM files")
+        .fetch_all(&pool)
+        .await?;
+    let (docs, _) = keyword_index_docs_for_paths(db_path, &paths, None).await?;
+    keyword_index::enabled::build_index(&keyword_index::index_dir(db_path), &docs)?;
+    Ok(docs.len())
+}
+
+/// Re-indexes `paths` (dropping those no longer in the DB) and returns the doc/missing counts.
+/// A missing index, or one written with another schema version, is rebuilt from the DB
+/// instead of being patched.
+pub async fn update_keyword_index(db_path: &str, paths: &[String]) -> Result<(usize, usize)> {
+    let dir = keyword_index::index_dir(db_path);
+    if keyword_index::index_state(&dir) != IndexState::Current {
+        return Ok((rebuild_keyword_index(db_path).await?, 0));
+    }
+    let (docs, missing) = keyword_index_docs_for_paths(db_path, paths, None).await?;
+    keyword_index::enabled::upsert_docs(&dir, &docs)?;
+    if !missing.is_empty() {
+        keyword_index::enabled::delete_docs(&dir, &missing)?;
+    }
+    Ok((docs.len(), missing.len()))
 }
 
-async fn refresh_keyword_index_if_dirty(cfg: &AppConfig, tags: Option<&[String]>) -> Result<()> {
+/// Applies the paths queued in `dirty` to the keyword index, then clears them.
+pub async fn refresh_keyword_index_if_dirty(db_path: &str) -> Result<()> {
     if !cfg!(feature = "keyword-index") {
         return Ok(());
     }
-    let pool = storage::connect(&cfg.database.path).await?;
-    let rows = sqlx::query("SELECT path FROM dirty")
+    let pool = storage::connect(db_path).await?;
+    let paths: Vec<String> = sqlx::query_scalar("SELECT path FROM dirty")
         .fetch_all(&pool)
         .await?;
-    if rows.is_empty() {
+    if paths.is_empty() {
         return Ok(());
     }
-    let paths: Vec<String> = rows.into_iter().filter_map(|r| r.try_get(0).ok()).collect();
-    let dir = keyword_index_dir(&cfg.database.path);
-    let (docs, missing) = keyword_index_docs_for_paths(&cfg.database.path, &paths, tags).await?;
-    if dir.join("meta.json").exists() {
-        let _ = keyword_index::enabled::upsert_docs(&dir, &docs);
-        if !missing.is_empty() {
-            let _ = keyword_index::enabled::delete_docs(&dir, &missing);
-        }
-    } else {
-        let _ = keyword_index::enabled::build_index(&dir, &docs);
-    }
+    update_keyword_index(db_path, &paths).await?;
     let mut qb = sqlx::QueryBuilder::new("DELETE FROM dirty WHERE path IN (");
     let mut separated = qb.separated(", ");
     for p in &paths {
@@ -300,53 +301,129 @@ async fn refresh_keyword_index_if_dirty(cfg: &AppConfig, tags: Option<&[String]>
 pub async fn keyword_index_docs_for_paths(
     db_path: &str,
     paths: &[String],
-    _tags: Option<&[String]>,
-) -> Result<(Vec<(String, String)>, Vec<String>)> {
+    tags: Option<&[String]>,
+) -> Result<(Vec<KeywordDoc>, Vec<String>)> {
     if paths.is_empty() {
         return Ok((Vec::new(), Vec::new()));
     }
     let pool = storage::connect(db_path).await?;
-    let mut qb = QueryBuilder::new("SELECT path, mime, id FROM files WHERE path IN (");
+    let mut qb = QueryBuilder::new(
+        "SELECT path, mime, id, ext, mtime, size FROM files WHERE status != 'missing' AND path IN (",
+    );
     let mut separated = qb.separated(", ");
     for p in paths {
         separated.push_bind(p);
     }
     separated.push_unseparated(")");
+    if let Some(tag_list) = tags {
+        if !tag_list.is_empty() {
+            qb.push(" AND EXISTS (SELECT 1 FROM file_tags ft JOIN tags t ON t.id = ft.tag_id WHERE ft.file_id = files.id AND t.name IN (");
+            let mut sep = qb.separated(", ");
+            for t in tag_list {
+                sep.push_bind(t);
+            }
+            sep.push_unseparated("))");
+        }
+    }
     let rows = qb.build().fetch_all(&pool).await?;
-    let mut docs = Vec::new();
     let mut found = HashSet::new();
-    let mut file_ids = Vec::new();
+    let mut files: Vec<KeywordDoc> = Vec::new();
     for row in rows {
         let path: String = row.get(0);
         let mime: Option<String> = row.try_get(1).ok();
         let file_id: i64 = row.try_get(2).unwrap_or_default();
-        file_ids.push((file_id, path.clone(), mime.clone()));
-        let doc_text = format!("{} {}", path, mime.clone().unwrap_or_default());
-        docs.push((path.clone(), doc_text));
-        found.insert(path);
+        let ext: Option<String> = row.try_get(3).ok();
+        let mtime: i64 = row.try_get(4).unwrap_or_default();
+        let size: i64 = row.try_get(5).unwrap_or_default();
+        let name = Path::new(&path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        found.insert(path.clone());
+        files.push(KeywordDoc {
+            path,
+            file_id,
+            name,
+            ext: ext.unwrap_or_default(),
+            mime: mime.unwrap_or_default(),
+            mtime,
+            size,
+            ..Default::default()
+        });
     }
-    // Append first chunk preview text for richer keyword search
-    if !file_ids.is_empty() {
-        let mut qb_chunks =
-            QueryBuilder::new("SELECT file_id, text_preview FROM chunks WHERE file_id IN (");
-        let mut sep = qb_chunks.separated(", ");
-        for (id, _, _) in &file_ids {
-            sep.push_bind(id);
+    if files.is_empty() {
+        let missing = paths
+            .iter()
+            .filter(|p| !found.contains(*p))
+            .cloned()
+            .collect();
+        return Ok((Vec::new(), missing));
+    }
+
+    // File-level fields: tag names and metadata values.
+    let mut tag_map: HashMap<i64, Vec<String>> = HashMap::new();
+    let mut qb_tags = QueryBuilder::new(
+        "SELECT ft.file_id, t.name FROM file_tags ft JOIN tags t ON t.id = ft.tag_id WHERE ft.file_id IN (",
+    );
+    let mut sep = qb_tags.separated(", ");
+    for f in &files {
+        sep.push_bind(f.file_id);
+    }
+    sep.push_unseparated(")");
+    for row in qb_tags.build().fetch_all(&pool).await? {
+        let fid: i64 = row.try_get(0).unwrap_or_default();
+        if let Ok(name) = row.try_get::<String, _>(1) {
+            tag_map.entry(fid).or_default().push(name);
         }
-        sep.push_unseparated(")");
-        let chunk_rows = qb_chunks.build().fetch_all(&pool).await?;
-        let mut chunk_map: std::collections::HashMap<i64, String> = std::collections::HashMap::new();
-        for row in chunk_rows {
-            let fid: i64 = row.try_get(0).unwrap_or_default();
-            if let Ok(Some(text)) = row.try_get::<Option<String>, _>(1) {
-                chunk_map.entry(fid).or_insert(text);
-            }
+    }
+    let mut meta_map: HashMap<i64, Vec<String>> = HashMap::new();
+    let mut qb_meta = QueryBuilder::new(
+        "SELECT file_id, value FROM metadata WHERE value IS NOT NULL AND file_id IN (",
+    );
+    let mut sep = qb_meta.separated(", ");
+    for f in &files {
+        sep.push_bind(f.file_id);
+    }
+    sep.push_unseparated(")");
+    for row in qb_meta.build().fetch_all(&pool).await? {
+        let fid: i64 = row.try_get(0).unwrap_or_default();
+        if let Ok(value) = row.try_get::<String, _>(1) {
+            meta_map.entry(fid).or_default().push(value);
         }
-        for (fid, path, mime) in file_ids {
-            if let Some(text) = chunk_map.get(&fid) {
-                let doc_text = format!("{} {} {}", path, mime.clone().unwrap_or_default(), text);
-                docs.push((path.clone(), doc_text));
+    }
+
+    // One document per chunk so matches point at (and highlight) the right passage.
+    let mut chunk_map: HashMap<i64, Vec<(i64, String)>> = HashMap::new();
+    let mut qb_chunks =
+        QueryBuilder::new("SELECT file_id, id, text_preview FROM chunks WHERE file_id IN (");
+    let mut sep = qb_chunks.separated(", ");
+    for f in &files {
+        sep.push_bind(f.file_id);
+    }
+    sep.push_unseparated(") ORDER BY file_id, start");
+    for row in qb_chunks.build().fetch_all(&pool).await? {
+        let fid: i64 = row.try_get(0).unwrap_or_default();
+        let cid: i64 = row.try_get(1).unwrap_or_default();
+        if let Ok(Some(text)) = row.try_get::<Option<String>, _>(2) {
+            chunk_map.entry(fid).or_default().push((cid, text));
+        }
+    }
+
+    let mut docs = Vec::new();
+    for mut file in files {
+        file.tags = tag_map.remove(&file.file_id).unwrap_or_default();
+        file.meta = meta_map.remove(&file.file_id).unwrap_or_default();
+        match chunk_map.remove(&file.file_id) {
+            Some(chunks) if !chunks.is_empty() => {
+                for (cid, text) in chunks {
+                    docs.push(KeywordDoc {
+                        chunk_id: Some(cid),
+                        content: text,
+                        ..file.clone()
+                    });
+                }
             }
+            _ => docs.push(file),
         }
     }
     let missing: Vec<String> = paths
@@ -357,7 +434,7 @@ pub async fn keyword_index_docs_for_paths(
     Ok((docs, missing))
 }
 
-async fn mark_dirty(db_path: &str, path: &PathBuf) -> Result<()> {
+async fn mark_dirty(db_path: &str, path: &Path) -> Result<()> {
     let pool = storage::connect(db_path).await?;
     let _ = sqlx::query(
         "INSERT OR REPLACE INTO dirty(path, reason, updated_at) VALUES (?1,'watch', strftime('%s','now'))",
@@ -368,7 +445,7 @@ async fn mark_dirty(db_path: &str, path: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-async fn purge_file(db_path: &str, path: &PathBuf) -> Result<()> {
+async fn purge_file(db_path: &str, path: &Path) -> Result<()> {
     let pool = storage::connect(db_path).await?;
     // Mark actions for this file as errored, then delete (covers cases where FK cascade is not enforced).
     let err_count: i64 = sqlx::query_scalar(
@@ -391,6 +468,9 @@ async fn purge_file(db_path: &str, path: &PathBuf) -> Result<()> {
         .bind(path.to_string_lossy())
         .execute(&pool)
         .await;
+    let mut conn = pool.acquire().await?;
+    fts::remove_path(&mut conn, &path.to_string_lossy()).await?;
+    drop(conn);
     let _ = sqlx::query("DELETE FROM files WHERE path = ?1")
         .bind(path.to_string_lossy())
         .execute(&pool)
@@ -403,7 +483,7 @@ async fn purge_file(db_path: &str, path: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-async fn collect_hashes(db_path: &str, path: &PathBuf) -> Result<(Vec<String>, Option<String>)> {
+async fn collect_hashes(db_path: &str, path: &Path) -> Result<(Vec<String>, Option<String>)> {
     let pool = storage::connect(db_path).await?;
     let rows = sqlx::query(
         "SELECT c.hash, f.hash as file_hash FROM chunks c JOIN files f ON f.id = c.file_id WHERE f.path = ?1 AND c.hash IS NOT NULL",
@@ -0,0 +1,272 @@
+use organizer_core::config::SafetyConfig;
+use std::fs;
+use std::path::Path;
+
+fn safety(root: &Path) -> SafetyConfig {
+    SafetyConfig {
+        allow_paths: vec![root.to_string_lossy().into_owned()],
+        immediate_vector_delete: true,
+        ..Default::default()
+    }
+}
+
+async fn plan_move(pool: &sqlx::SqlitePool, from: &Path, to: &Path) -> i64 {
+    sqlx::query("INSERT INTO files(path, size, mtime, ctime, status) VALUES (?1, 5, 0, 0, 'new')")
+        .bind(from.to_string_lossy())
+        .execute(pool)
+        .await
+        .unwrap();
+    sqlx::query_scalar(
+        "INSERT INTO actions(file_id, kind, payload_json, status) VALUES ((SELECT id FROM files WHERE path = ?1), 'move', ?2, 'planned') RETURNING id",
+    )
+    .bind(from.to_string_lossy())
+    .bind(serde_json::json!({ "to": to.to_string_lossy() }).to_string())
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+async fn pending(pool: &sqlx::SqlitePool, action_id: i64, src: &Path, dest: &Path) {
+    sqlx::query(
+        "INSERT INTO apply_journal(action_id, kind, src, dest) VALUES (?1, 'move', ?2, ?3)",
+    )
+    .bind(action_id)
+    .bind(src.to_string_lossy())
+    .bind(dest.to_string_lossy())
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+async fn action_status(pool: &sqlx::SqlitePool, id: i64) -> String {
+    sqlx::query_scalar("SELECT status FROM actions WHERE id = ?1")
+        .bind(id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn move_updates_file_row_and_journals_conflict_resolved_destination() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:apply_journal_move?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let src = temp.path().join("report.txt");
+    let taken = temp.path().join("docs/report.txt");
+    fs::create_dir_all(taken.parent().unwrap()).unwrap();
+    fs::write(&src, "hello").unwrap();
+    fs::write(&taken, "other").unwrap();
+    let id = plan_move(&pool, &src, &taken).await;
+
+    let views =
+        cli::apply::apply_actions(db_url, false, true, None, &safety(temp.path()), "rename")
+            .await
+            .unwrap();
+    assert_eq!(views[0].status, "executed");
+    let landed = temp.path().join("docs/report_1.txt");
+    assert_eq!(fs::read_to_string(&landed).unwrap(), "hello");
+
+    let row_path: String = sqlx::query_scalar("SELECT path FROM files")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(row_path, landed.to_string_lossy());
+    let (state, dest): (String, String) =
+        sqlx::query_as("SELECT state, dest FROM apply_journal WHERE action_id = ?1")
+            .bind(id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        (state.as_str(), dest),
+        ("done", landed.to_string_lossy().into_owned())
+    );
+    assert_eq!(
+        cli::journal::original_path(&pool, id)
+            .await
+            .unwrap()
+            .as_deref(),
+        Some(src.to_string_lossy().as_ref())
+    );
+}
+
+#[tokio::test]
+async fn recovery_completes_finished_moves_and_rolls_back_untouched_ones() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:apply_journal_recover?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    // Crashed after the rename: the file is at its destination, the DB still says planned.
+    let moved_src = temp.path().join("a.txt");
+    let moved_dest = temp.path().join("sorted/a.txt");
+    fs::create_dir_all(moved_dest.parent().unwrap()).unwrap();
+    fs::write(&moved_dest, "a").unwrap();
+    let moved = plan_move(&pool, &moved_src, &moved_dest).await;
+    pending(&pool, moved, &moved_src, &moved_dest).await;
+
+    // Crashed before the rename.
+    let kept_src = temp.path().join("b.txt");
+    let kept_dest = temp.path().join("sorted/b.txt");
+    fs::write(&kept_src, "b").unwrap();
+    let kept = plan_move(&pool, &kept_src, &kept_dest).await;
+    pending(&pool, kept, &kept_src, &kept_dest).await;
+
+    // Both copies exist: nothing safe to decide automatically.
+    let both_src = temp.path().join("c.txt");
+    let both_dest = temp.path().join("sorted/c.txt");
+    fs::write(&both_src, "c").unwrap();
+    fs::write(&both_dest, "c").unwrap();
+    let both = plan_move(&pool, &both_src, &both_dest).await;
+    pending(&pool, both, &both_src, &both_dest).await;
+
+    let report = cli::journal::recover(&pool, None).await.unwrap();
+    assert_eq!(
+        report,
+        cli::journal::RecoveryReport {
+            completed: 1,
+            rolled_back: 1,
+            failed: 1
+        }
+    );
+    assert_eq!(action_status(&pool, moved).await, "executed");
+    assert_eq!(action_status(&pool, kept).await, "planned");
+    assert_eq!(action_status(&pool, both).await, "error");
+    let tracked: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM files WHERE path = ?1")
+        .bind(moved_dest.to_string_lossy())
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(tracked, 1);
+    let audits: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM audit WHERE event = 'recover'")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(audits, 3);
+
+    // Nothing is left pending, so a second pass is a no-op and apply picks up the rolled-back move.
+    assert_eq!(cli::journal::recover(&pool, None).await.unwrap().total(), 0);
+    cli::apply::apply_actions(
+        db_url,
+        false,
+        true,
+        Some(&kept.to_string()),
+        &safety(temp.path()),
+        "rename",
+    )
+    .await
+    .unwrap();
+    assert_eq!(fs::read_to_string(&kept_dest).unwrap(), "b");
+}
+
+#[tokio::test]
+async fn recovery_restores_the_backup_of_a_finished_trash_merge() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:apply_journal_merge?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    // Crashed after the duplicate was backed up and deleted, before the DB caught up.
+    let survivor = temp.path().join("keep.txt");
+    let dup = temp.path().join("copy.txt");
+    fs::write(&survivor, "same").unwrap();
+    let trash = temp.path().join("trash");
+    let hash = blake3::hash(b"same").to_hex().to_string();
+    let object = cli::fs_apply::trash_object_path(&trash, &hash);
+    fs::create_dir_all(object.parent().unwrap()).unwrap();
+    fs::write(&object, "same").unwrap();
+    for p in [&survivor, &dup] {
+        sqlx::query(
+            "INSERT INTO files(path, size, mtime, ctime, status) VALUES (?1, 4, 0, 0, 'new')",
+        )
+        .bind(p.to_string_lossy())
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+    let id: i64 = sqlx::query_scalar(
+        "INSERT INTO actions(file_id, kind, payload_json, status) VALUES ((SELECT id FROM files WHERE path = ?1), 'merge_duplicate', ?2, 'planned') RETURNING id",
+    )
+    .bind(dup.to_string_lossy())
+    .bind(serde_json::json!({ "duplicate_of": survivor.to_string_lossy() }).to_string())
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    sqlx::query("INSERT INTO apply_journal(action_id, kind, src, dest, strategy) VALUES (?1, 'merge_duplicate', ?2, ?3, 'trash')")
+        .bind(id)
+        .bind(dup.to_string_lossy())
+        .bind(survivor.to_string_lossy())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let report = cli::journal::recover(&pool, Some(&trash)).await.unwrap();
+    assert_eq!(report.completed, 1);
+    let backup: Option<String> =
+        sqlx::query_scalar("SELECT backup_path FROM merged_files WHERE action_id = ?1")
+            .bind(id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(backup, Some(object.to_string_lossy().into_owned()));
+    let entries: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM trash_entries WHERE action_id = ?1")
+            .bind(id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(entries, 1);
+}
+
+#[tokio::test]
+async fn apply_refuses_a_live_lock_and_takes_over_a_stale_one() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:apply_journal_lock?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+    let src = temp.path().join("a.txt");
+    fs::write(&src, "a").unwrap();
+    plan_move(&pool, &src, &temp.path().join("sorted/a.txt")).await;
+
+    // Held by this very process, which is certainly alive.
+    sqlx::query("INSERT INTO apply_lock(id, pid) VALUES (1, ?1)")
+        .bind(std::process::id() as i64)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let err = cli::apply::apply_actions(db_url, false, true, None, &safety(temp.path()), "rename")
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("another apply"), "{}", err);
+    assert!(src.exists());
+
+    // A pid no process can have: the lock is stale.
+    sqlx::query("UPDATE apply_lock SET pid = 999999999, acquired_at = 0")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let views =
+        cli::apply::apply_actions(db_url, false, true, None, &safety(temp.path()), "rename")
+            .await
+            .unwrap();
+    assert_eq!(views[0].status, "executed");
+    let held: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM apply_lock")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(held, 0, "released after the run");
+}
+
+#[test]
+fn resolved_destinations_are_never_clobbered() {
+    let temp = tempfile::tempdir().unwrap();
+    let taken = temp.path().join("taken.txt");
+    fs::write(&taken, "x").unwrap();
+    assert!(cli::fs_apply::resolve_destination(&taken, "fail").is_err());
+    assert_eq!(
+        cli::fs_apply::resolve_destination(&taken, "overwrite").unwrap(),
+        Some(taken.clone())
+    );
+}
@@ -0,0 +1,207 @@
+use organizer_core::config::SafetyConfig;
+use std::fs;
+use std::path::Path;
+
+fn safety(root: &Path) -> SafetyConfig {
+    SafetyConfig {
+        dry_run: true,
+        allow_delete: true,
+        allow_paths: vec![root.to_string_lossy().into_owned()],
+        trash_dir: Some(root.join("trash").to_string_lossy().into_owned()),
+        immediate_vector_delete: true,
+        ..Default::default()
+    }
+}
+
+async fn plan_action(pool: &sqlx::SqlitePool, path: &Path, kind: &str, payload: serde_json::Value) {
+    sqlx::query(
+        "INSERT OR IGNORE INTO files(path, size, mtime, ctime, status) VALUES (?1, 0, 0, 0, 'new')",
+    )
+    .bind(path.to_string_lossy())
+    .execute(pool)
+    .await
+    .unwrap();
+    sqlx::query("INSERT INTO actions(file_id, kind, payload_json, status) VALUES ((SELECT id FROM files WHERE path = ?1), ?2, ?3, 'planned')")
+        .bind(path.to_string_lossy())
+        .bind(kind)
+        .bind(payload.to_string())
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn plan_simulates_conflicts_in_order_and_matches_apply() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:apply_plan?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let root = temp.path();
+    let a = root.join("inbox/a/report.txt");
+    let b = root.join("inbox/b/report.txt");
+    let survivor = root.join("photo.jpg");
+    let dup = root.join("photo copy.jpg");
+    for (p, body) in [(&a, "aaaa"), (&b, "bb"), (&survivor, "pix"), (&dup, "pix")] {
+        fs::create_dir_all(p.parent().unwrap()).unwrap();
+        fs::write(p, body).unwrap();
+    }
+    let dest = root.join("docs/report.txt");
+    plan_action(
+        &pool,
+        &a,
+        "move",
+        serde_json::json!({ "to": dest.to_string_lossy() }),
+    )
+    .await;
+    plan_action(
+        &pool,
+        &b,
+        "move",
+        serde_json::json!({ "to": dest.to_string_lossy() }),
+    )
+    .await;
+    plan_action(
+        &pool,
+        &dup,
+        "merge_duplicate",
+        serde_json::json!({ "duplicate_of": survivor.to_string_lossy() }),
+    )
+    .await;
+    plan_action(&pool, &a, "tag", serde_json::json!({ "tag": "work" })).await;
+
+    let plan = cli::plan::simulate(&pool, None, &safety(root), "rename")
+        .await
+        .unwrap();
+    let renamed = root.join("docs/report_1.txt");
+    let effects: Vec<&str> = plan.steps.iter().map(|s| s.effect.as_str()).collect();
+    assert_eq!(effects, vec!["move", "move", "remove", "tag"]);
+    assert_eq!(
+        plan.steps[0].destination.as_deref(),
+        Some(dest.to_string_lossy().as_ref())
+    );
+    assert_eq!(
+        plan.steps[1].destination.as_deref(),
+        Some(renamed.to_string_lossy().as_ref())
+    );
+    assert_eq!(
+        plan.steps[1].requested.as_deref(),
+        Some(dest.to_string_lossy().as_ref())
+    );
+    assert_eq!(plan.collisions.len(), 1);
+    assert_eq!(plan.collisions[0].action_ids, vec![1, 2]);
+    assert_eq!((plan.bytes_moved, plan.bytes_removed), (6, 3));
+    let changes: Vec<(String, &str)> = plan
+        .changes
+        .iter()
+        .map(|c| (c.path.clone(), c.change.as_str()))
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            (dest.to_string_lossy().into_owned(), "added"),
+            (renamed.to_string_lossy().into_owned(), "added"),
+            (a.to_string_lossy().into_owned(), "removed"),
+            (b.to_string_lossy().into_owned(), "removed"),
+            (dup.to_string_lossy().into_owned(), "removed"),
+        ]
+    );
+
+    let text = cli::plan::render_text(&plan);
+    assert!(text.starts_with(
+        "dry-run plan: 4 actions (move=2, remove=1, tag=1), 6 B moved, 3 B removed\n"
+    ));
+    assert!(
+        text.contains("+   docs/\n+     report.txt\n+     report_1.txt\n"),
+        "{text}"
+    );
+    assert!(
+        text.contains("    inbox/\n      a/\n-       report.txt\n"),
+        "{text}"
+    );
+    let script = cli::plan::render_shell(&plan);
+    assert!(script.contains(&format!(
+        "mv -n -- '{}' '{}'",
+        b.display(),
+        renamed.display()
+    )));
+    assert!(script.contains(&format!("rm -- '{}'", dup.display())));
+
+    // The simulation left the tree alone, and apply lands files where it predicted.
+    assert!(a.exists() && b.exists() && dup.exists());
+    let mut run = safety(root);
+    run.dry_run = false;
+    cli::apply::apply_actions(db_url, false, true, None, &run, "rename")
+        .await
+        .unwrap();
+    assert_eq!(fs::read_to_string(&dest).unwrap(), "aaaa");
+    assert_eq!(fs::read_to_string(&renamed).unwrap(), "bb");
+    assert!(!dup.exists());
+}
+
+#[tokio::test]
+async fn paths_under_a_moved_folder_are_free_and_the_script_stays_posix() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:apply_plan_moved_dir?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let root = temp.path();
+    let old = root.join("old");
+    let loose = root.join("inbox/notes.txt");
+    let (survivor, dup) = (root.join("raw.cr2"), root.join("raw copy.cr2"));
+    for p in [
+        old.join("notes.txt"),
+        loose.clone(),
+        survivor.clone(),
+        dup.clone(),
+    ] {
+        fs::create_dir_all(p.parent().unwrap()).unwrap();
+        fs::write(&p, "x").unwrap();
+    }
+    sqlx::query("INSERT INTO directories(path, mtime) VALUES (?1, 0)")
+        .bind(old.to_string_lossy())
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO actions(dir_id, kind, payload_json, status) VALUES (1, 'move_dir', ?1, 'planned')")
+        .bind(serde_json::json!({ "to": root.join("archive").to_string_lossy() }).to_string())
+        .execute(&pool)
+        .await
+        .unwrap();
+    let freed = old.join("notes.txt");
+    plan_action(
+        &pool,
+        &loose,
+        "move",
+        serde_json::json!({ "to": freed.to_string_lossy() }),
+    )
+    .await;
+    plan_action(
+        &pool,
+        &dup,
+        "merge_duplicate",
+        serde_json::json!({ "duplicate_of": survivor.to_string_lossy(), "strategy": "reflink" }),
+    )
+    .await;
+
+    let plan = cli::plan::simulate(&pool, None, &safety(root), "rename")
+        .await
+        .unwrap();
+    assert_eq!(plan.steps[1].effect, "move");
+    assert_eq!(
+        plan.steps[1].destination.as_deref(),
+        Some(freed.to_string_lossy().as_ref())
+    );
+    assert_eq!(
+        plan.steps[1].requested, None,
+        "the folder's old file moved away with it"
+    );
+    let script = cli::plan::render_shell(&plan);
+    assert!(!script.contains("--reflink"), "{script}");
+    assert!(script.contains(&format!(
+        "ln -f -- '{}' '{}'",
+        survivor.display(),
+        dup.display()
+    )));
+}
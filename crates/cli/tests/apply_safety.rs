@@ -0,0 +1,78 @@
+use cli::safety;
+use organizer_core::config::SafetyConfig;
+use std::fs;
+use std::path::Path;
+
+fn config(root: &Path, allow_delete: bool) -> SafetyConfig {
+    SafetyConfig {
+        dry_run: true,
+        allow_delete,
+        allow_paths: vec![root.to_string_lossy().into_owned()],
+        trash_dir: Some(root.join("trash").to_string_lossy().into_owned()),
+        immediate_vector_delete: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn config_dry_run_holds_unless_executed() {
+    let temp = tempfile::tempdir().unwrap();
+    let mut cfg = config(temp.path(), false);
+    assert!(safety::resolve_dry_run(&cfg, false, false));
+    assert!(!safety::resolve_dry_run(&cfg, false, true));
+    cfg.dry_run = false;
+    assert!(!safety::resolve_dry_run(&cfg, false, false));
+    assert!(safety::resolve_dry_run(&cfg, true, false));
+}
+
+#[tokio::test]
+async fn merges_and_overwrites_need_allow_delete() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:apply_safety_merge?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let survivor = temp.path().join("a.txt");
+    let dup = temp.path().join("b.txt");
+    fs::write(&survivor, "same").unwrap();
+    fs::write(&dup, "same").unwrap();
+    for p in [&survivor, &dup] {
+        sqlx::query(
+            "INSERT INTO files(path, size, mtime, ctime, status) VALUES (?1, 4, 0, 0, 'new')",
+        )
+        .bind(p.to_string_lossy())
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+    sqlx::query("INSERT INTO actions(file_id, kind, payload_json, status) VALUES (2, 'merge_duplicate', ?1, 'planned')")
+        .bind(serde_json::json!({ "duplicate_of": survivor.to_string_lossy(), "strategy": "hardlink" }).to_string())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let mut cfg = config(temp.path(), false);
+    cfg.dry_run = false;
+    let err = cli::apply::apply_actions(db_url, false, true, None, &cfg, "overwrite")
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("allow_delete"), "{err}");
+
+    let views = cli::apply::apply_actions(db_url, false, true, None, &cfg, "rename")
+        .await
+        .unwrap();
+    assert_eq!(
+        views[0].error.as_deref(),
+        Some("merge_duplicate (hardlink) removes a file; set safety.allow_delete to allow it")
+    );
+    assert_eq!(fs::read_to_string(&dup).unwrap(), "same");
+    assert!(
+        !temp.path().join("trash").exists(),
+        "nothing was backed up or removed"
+    );
+    let status: String = sqlx::query_scalar("SELECT status FROM actions WHERE id = 1")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "error");
+}
@@ -0,0 +1,163 @@
+use organizer_core::config::SafetyConfig;
+use std::fs;
+use std::path::Path;
+
+fn safety(root: &Path) -> SafetyConfig {
+    SafetyConfig {
+        allow_paths: vec![root.to_string_lossy().into_owned()],
+        trash_dir: Some(root.join("trash").to_string_lossy().into_owned()),
+        immediate_vector_delete: true,
+        ..Default::default()
+    }
+}
+
+async fn plan_move(pool: &sqlx::SqlitePool, from: &Path, to: &Path) -> i64 {
+    sqlx::query("INSERT INTO files(path, size, mtime, ctime, status) VALUES (?1, 1, 0, 0, 'new')")
+        .bind(from.to_string_lossy())
+        .execute(pool)
+        .await
+        .unwrap();
+    sqlx::query_scalar(
+        "INSERT INTO actions(file_id, kind, payload_json, status) VALUES ((SELECT id FROM files WHERE path = ?1), 'move', ?2, 'planned') RETURNING id",
+    )
+    .bind(from.to_string_lossy())
+    .bind(serde_json::json!({ "to": to.to_string_lossy() }).to_string())
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn each_apply_run_is_a_session_that_undoes_as_a_unit() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:apply_sessions?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+    let safety = safety(temp.path());
+
+    let a = temp.path().join("a.txt");
+    let b = temp.path().join("b.txt");
+    fs::write(&a, "a").unwrap();
+    fs::write(&b, "b").unwrap();
+    let first = plan_move(&pool, &a, &temp.path().join("one/a.txt")).await;
+    let views = cli::apply::apply_actions(db_url, false, true, None, &safety, "rename")
+        .await
+        .unwrap();
+    let s1 = views[0].session_id.expect("apply run opens a session");
+
+    let second = plan_move(&pool, &b, &temp.path().join("two/b.txt")).await;
+    let views = cli::apply::apply_actions(db_url, false, true, None, &safety, "rename")
+        .await
+        .unwrap();
+    let s2 = views[0].session_id.unwrap();
+    assert_ne!(s1, s2);
+
+    // Dry runs and empty runs leave no session behind.
+    let views = cli::apply::apply_actions(db_url, true, true, None, &safety, "rename")
+        .await
+        .unwrap();
+    assert!(views.is_empty());
+
+    let list = cli::sessions::list_sessions(&pool, 10).await.unwrap();
+    assert_eq!(list.iter().map(|s| s.id).collect::<Vec<_>>(), vec![s2, s1]);
+    assert_eq!(
+        (list[0].status.as_str(), list[0].succeeded, list[0].executed),
+        ("finished", 1, 1)
+    );
+
+    let detail = cli::sessions::show_session(&pool, s1)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(detail.actions.len(), 1);
+    assert_eq!(detail.actions[0].id, first);
+    assert!(cli::sessions::show_session(&pool, 999)
+        .await
+        .unwrap()
+        .is_none());
+
+    // `--last` picks the newest session; undoing it leaves the first one alone.
+    assert_eq!(cli::sessions::last_undoable(&pool).await.unwrap(), Some(s2));
+    let reverted = cli::undo::undo_session(db_url, s2, None).await.unwrap();
+    assert_eq!(reverted, 1);
+    assert!(b.exists());
+    let status: String = sqlx::query_scalar("SELECT status FROM actions WHERE id = ?1")
+        .bind(second)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "planned");
+    let undone = cli::sessions::show_session(&pool, s2)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(undone.session.status, "undone");
+    assert!(undone.session.undone_at.is_some());
+    assert_eq!(cli::sessions::last_undoable(&pool).await.unwrap(), Some(s1));
+}
+
+#[tokio::test]
+async fn session_actions_are_undone_newest_first() {
+    let db_url = "sqlite://file:apply_sessions_order?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+    let sid = cli::sessions::start(&pool).await.unwrap();
+    sqlx::query(
+        "INSERT INTO files(path, size, mtime, ctime, status) VALUES ('/x', 1, 0, 0, 'new')",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    for executed_at in [100, 300, 200] {
+        sqlx::query("INSERT INTO actions(file_id, kind, payload_json, status, executed_at, session_id) VALUES (1, 'tag', '{}', 'executed', ?1, ?2)")
+            .bind(executed_at)
+            .bind(sid)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+    cli::sessions::finish(&pool, sid).await.unwrap();
+    let order = cli::sessions::executed_actions_newest_first(&pool, sid)
+        .await
+        .unwrap();
+    assert_eq!(order, vec![2, 3, 1]);
+}
+
+#[tokio::test]
+async fn failed_runs_close_their_session_and_undo_counts_only_reverted_actions() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:apply_sessions_failed?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let a = temp.path().join("a.txt");
+    fs::write(&a, "a").unwrap();
+    plan_move(&pool, &a, &temp.path().join("one/a.txt")).await;
+    let broken = SafetyConfig {
+        deny_paths: vec!["/tmp/[".to_string()],
+        ..safety(temp.path())
+    };
+    assert!(
+        cli::apply::apply_actions(db_url, false, true, None, &broken, "rename")
+            .await
+            .is_err()
+    );
+    let status: String =
+        sqlx::query_scalar("SELECT status FROM apply_sessions ORDER BY id DESC LIMIT 1")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(status, "failed");
+
+    let b = temp.path().join("b.txt");
+    fs::write(&b, "b").unwrap();
+    plan_move(&pool, &b, &temp.path().join("two/b.txt")).await;
+    let views =
+        cli::apply::apply_actions(db_url, false, true, None, &safety(temp.path()), "rename")
+            .await
+            .unwrap();
+    let sid = views[0].session_id.unwrap();
+    // Something new took the original place of `a`, so only `b` can go back.
+    fs::write(&a, "new").unwrap();
+    assert_eq!(cli::undo::undo_session(db_url, sid, None).await.unwrap(), 1);
+}
@@ -32,13 +32,10 @@ async fn apply_and_undo_moves_with_backup() {
         .unwrap();
 
     let safety = SafetyConfig {
-        dry_run: false,
-        allow_delete: false,
         allow_paths: vec![temp.path().to_string_lossy().into_owned()],
-        deny_paths: vec![],
         trash_dir: Some(temp.path().join("trash").to_string_lossy().into_owned()),
-        copy_then_delete: false,
         immediate_vector_delete: true,
+        ..Default::default()
     };
 
     let actions = cli::apply::apply_actions(&db_url, false, true, None, &safety, "rename")
@@ -0,0 +1,214 @@
+use organizer_core::bundles::{self, BundleKind};
+use organizer_core::rules::{Action, Condition};
+use organizer_core::{scanner, suggester};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn tree(root: &Path) {
+    for (path, body) in [
+        ("repo/src/lib.rs", "pub fn f() {}"),
+        ("repo/.git/HEAD", "ref: refs/heads/main"),
+        ("tool/Cargo.toml", "[package]"),
+        ("tool/src/main.rs", "fn main() {}"),
+        ("Viewer.app/Contents/Info.plist", "<plist/>"),
+        ("page.html", "<html/>"),
+        ("page_files/style.css", "body {}"),
+        ("IMG_1.CR2", "raw"),
+        ("IMG_1.xmp", "<x/>"),
+        ("img_1.JPG", "jpeg"),
+        ("lone.jpg", "jpeg"),
+        ("orphan.xmp", "<x/>"),
+    ] {
+        let p = root.join(path);
+        fs::create_dir_all(p.parent().unwrap()).unwrap();
+        fs::write(p, body).unwrap();
+    }
+}
+
+#[test]
+fn recognises_folders_pages_and_sidecars() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path();
+    tree(root);
+    let files: Vec<PathBuf> = [
+        "repo/src/lib.rs",
+        "tool/Cargo.toml",
+        "tool/src/main.rs",
+        "Viewer.app/Contents/Info.plist",
+        "page.html",
+        "page_files/style.css",
+        "IMG_1.CR2",
+        "IMG_1.xmp",
+        "img_1.JPG",
+        "lone.jpg",
+        "orphan.xmp",
+    ]
+    .iter()
+    .map(|p| root.join(p))
+    .collect();
+    let dirs: Vec<PathBuf> = [
+        "repo",
+        "repo/src",
+        "tool",
+        "tool/src",
+        "Viewer.app",
+        "Viewer.app/Contents",
+        "page_files",
+    ]
+    .iter()
+    .map(|p| root.join(p))
+    .collect();
+
+    let found = bundles::detect(&files, &dirs);
+    let summary: Vec<(BundleKind, PathBuf, String, usize)> = found
+        .iter()
+        .map(|b| {
+            (
+                b.kind,
+                b.root.strip_prefix(root).unwrap().to_path_buf(),
+                b.marker.clone(),
+                b.members.len(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (
+                BundleKind::Sidecar,
+                PathBuf::from("IMG_1.CR2"),
+                "IMG_1.xmp".to_string(),
+                3
+            ),
+            (
+                BundleKind::Package,
+                PathBuf::from("Viewer.app"),
+                "app".to_string(),
+                1
+            ),
+            (
+                BundleKind::WebPage,
+                PathBuf::from("page.html"),
+                "page_files".to_string(),
+                2
+            ),
+            (
+                BundleKind::Vcs,
+                PathBuf::from("repo"),
+                ".git".to_string(),
+                1
+            ),
+            (
+                BundleKind::Project,
+                PathBuf::from("tool"),
+                "Cargo.toml".to_string(),
+                2
+            ),
+        ]
+    );
+
+    assert_eq!(
+        bundles::companion_destination(
+            &root.join("IMG_1.CR2"),
+            &root.join("IMG_1.xmp"),
+            &root.join("2024/trip.CR2")
+        ),
+        Some(root.join("2024/trip.xmp"))
+    );
+    assert_eq!(
+        bundles::companion_destination(
+            &root.join("IMG_1.CR2"),
+            &root.join("img_1.XMP"),
+            &root.join("2024/trip.CR2")
+        ),
+        Some(root.join("2024/trip.XMP")),
+        "sidecars are matched case-insensitively"
+    );
+    assert_eq!(
+        bundles::companion_destination(
+            &root.join("page.html"),
+            &root.join("page_files/style.css"),
+            &root.join("web/saved.html")
+        ),
+        Some(root.join("web/saved_files/style.css"))
+    );
+}
+
+#[tokio::test]
+async fn members_move_with_their_primary_or_not_at_all() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:bundles_suggest?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+    let root = temp.path().join("inbox");
+    tree(&root);
+    scanner::scan(
+        std::slice::from_ref(&root),
+        &[],
+        &scanner::HashMode::None,
+        &pool,
+    )
+    .await
+    .unwrap();
+    assert_eq!(bundles::detect_bundles(&pool).await.unwrap(), 5);
+
+    let sorted = temp.path().join("sorted");
+    for (name, ext, to) in [
+        ("pages", "html", sorted.join("web/saved.html")),
+        ("raw", "CR2", sorted.join("raw/IMG_1.CR2")),
+        ("code", "rs", sorted.join("code/lib.rs")),
+        ("sidecars", "xmp", sorted.join("xmp/file.xmp")),
+    ] {
+        let actions = vec![Action::Move {
+            to: to.to_string_lossy().into_owned(),
+        }];
+        sqlx::query("INSERT INTO rules (name, priority, enabled, condition_json, action_json) VALUES (?, 0, 1, ?, ?)")
+            .bind(name)
+            .bind(serde_json::to_string(&Condition::Extension { ext: ext.into() }).unwrap())
+            .bind(serde_json::to_string(&actions).unwrap())
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+    suggester::run_suggester(&pool).await.unwrap();
+
+    let mut planned: Vec<(String, String)> = sqlx::query_as(
+        "SELECT f.path, json_extract(a.payload_json, '$.to') FROM actions a JOIN files f ON f.id = a.file_id",
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    planned.sort();
+    let s = |p: &Path| p.to_string_lossy().into_owned();
+    assert_eq!(
+        planned,
+        vec![
+            (s(&root.join("IMG_1.CR2")), s(&sorted.join("raw/IMG_1.CR2"))),
+            (s(&root.join("IMG_1.xmp")), s(&sorted.join("raw/IMG_1.xmp"))),
+            (s(&root.join("img_1.JPG")), s(&sorted.join("raw/IMG_1.JPG"))),
+            // The sidecar rule only catches the xmp without a photo.
+            (s(&root.join("orphan.xmp")), s(&sorted.join("xmp/file.xmp"))),
+            (
+                s(&root.join("page.html")),
+                s(&sorted.join("web/saved.html"))
+            ),
+            (
+                s(&root.join("page_files/style.css")),
+                s(&sorted.join("web/saved_files/style.css"))
+            ),
+        ]
+    );
+
+    let info = bundles::bundles_for_paths(
+        &pool,
+        &[s(&root.join("repo/src/lib.rs")), s(&root.join("lone.jpg"))],
+    )
+    .await
+    .unwrap();
+    assert_eq!(info.len(), 1);
+    let repo = &info[&s(&root.join("repo/src/lib.rs"))];
+    assert_eq!(
+        (repo.kind, repo.root.as_str(), repo.members),
+        (BundleKind::Vcs, s(&root.join("repo")).as_str(), 1)
+    );
+}
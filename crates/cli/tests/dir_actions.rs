@@ -0,0 +1,265 @@
+use organizer_core::config::SafetyConfig;
+use organizer_core::rules::{Action, Condition};
+use organizer_core::{scanner, suggester};
+use std::fs;
+use std::path::Path;
+
+fn safety(root: &Path) -> SafetyConfig {
+    SafetyConfig {
+        allow_paths: vec![root.to_string_lossy().into_owned()],
+        immediate_vector_delete: true,
+        cleanup_empty_dirs: true,
+        ..Default::default()
+    }
+}
+
+async fn add_rule(pool: &sqlx::SqlitePool, name: &str, condition: Condition, to: &Path) {
+    let actions = vec![Action::Move {
+        to: to.to_string_lossy().into_owned(),
+    }];
+    sqlx::query("INSERT INTO rules (name, priority, enabled, condition_json, action_json) VALUES (?, 0, 1, ?, ?)")
+        .bind(name)
+        .bind(serde_json::to_string(&condition).unwrap())
+        .bind(serde_json::to_string(&actions).unwrap())
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn project_folder_moves_whole_and_undo_puts_it_back() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:dir_actions_move?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let root = temp.path().join("inbox");
+    let project = root.join("tool");
+    fs::create_dir_all(project.join("src")).unwrap();
+    fs::write(project.join("Cargo.toml"), "[package]").unwrap();
+    fs::write(project.join("src/main.rs"), "fn main() {}").unwrap();
+    fs::write(root.join("notes.txt"), "notes").unwrap();
+    scanner::scan(
+        std::slice::from_ref(&root),
+        &[],
+        &scanner::HashMode::Fast,
+        &pool,
+    )
+    .await
+    .unwrap();
+
+    let projects = temp.path().join("Projects");
+    add_rule(
+        &pool,
+        "projects",
+        Condition::Contains {
+            name: "Cargo.toml".into(),
+        },
+        &projects,
+    )
+    .await;
+    // A plain prefix rule never picks up folders, and files of a moving folder are left alone.
+    let prefix = Condition::PathPrefix {
+        prefix: root.to_string_lossy().into_owned(),
+    };
+    add_rule(
+        &pool,
+        "everything",
+        prefix,
+        &temp.path().join("sorted/file"),
+    )
+    .await;
+    suggester::run_suggester(&pool).await.unwrap();
+
+    let planned: Vec<(String, String)> = sqlx::query_as(
+        "SELECT kind, COALESCE(f.path, d.path) FROM actions a LEFT JOIN files f ON f.id = a.file_id
+         LEFT JOIN directories d ON d.id = a.dir_id ORDER BY a.id",
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        planned,
+        vec![
+            (
+                "move_dir".to_string(),
+                project.to_string_lossy().into_owned()
+            ),
+            (
+                "move".to_string(),
+                root.join("notes.txt").to_string_lossy().into_owned()
+            ),
+        ]
+    );
+
+    let views = cli::apply::apply_actions(
+        db_url,
+        false,
+        true,
+        Some("1"),
+        &safety(temp.path()),
+        "rename",
+    )
+    .await
+    .unwrap();
+    assert_eq!(views[0].status, "executed", "{:?}", views[0].error);
+    let moved = projects.join("tool");
+    assert!(moved.join("src/main.rs").exists());
+    assert!(!project.exists());
+    assert!(root.exists(), "scan roots are never cleaned up");
+    let main_rs: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM files WHERE path = ?1")
+        .bind(moved.join("src/main.rs").to_string_lossy())
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(main_rs, 1, "file rows follow the folder");
+    let dirty = |path: std::path::PathBuf| {
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM dirty WHERE path = ?1")
+            .bind(path.to_string_lossy().into_owned())
+            .fetch_one(&pool)
+    };
+    assert_eq!(
+        dirty(project.join("src/main.rs")).await.unwrap(),
+        1,
+        "old paths leave the indexes"
+    );
+    assert_eq!(
+        dirty(moved.join("src/main.rs")).await.unwrap(),
+        1,
+        "new paths are indexed"
+    );
+
+    sqlx::query("DELETE FROM dirty")
+        .execute(&pool)
+        .await
+        .unwrap();
+    cli::undo::undo_actions(db_url, Some("1"), None)
+        .await
+        .unwrap();
+    assert!(project.join("src/main.rs").exists());
+    assert!(!moved.exists());
+    assert_eq!(dirty(project.join("Cargo.toml")).await.unwrap(), 1);
+    assert_eq!(dirty(moved.join("Cargo.toml")).await.unwrap(), 1);
+    let dir_path: String = sqlx::query_scalar(
+        "SELECT d.path FROM actions a JOIN directories d ON d.id = a.dir_id WHERE a.id = 1",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(dir_path, project.to_string_lossy());
+}
+
+#[tokio::test]
+async fn folder_merges_into_existing_one_and_emptied_dirs_are_cleaned() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:dir_actions_merge?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let root = temp.path().join("root");
+    let old = root.join("old");
+    let target = root.join("photos");
+    let kept = root.join("kept/deep");
+    for (p, body) in [
+        (old.join("a.jpg"), "new a"),
+        (old.join("2020/b.jpg"), "b"),
+        (target.join("a.jpg"), "old a"),
+        (kept.join("c.txt"), "c"),
+    ] {
+        fs::create_dir_all(p.parent().unwrap()).unwrap();
+        fs::write(&p, body).unwrap();
+    }
+    scanner::scan(
+        std::slice::from_ref(&root),
+        &[],
+        &scanner::HashMode::None,
+        &pool,
+    )
+    .await
+    .unwrap();
+    sqlx::query("INSERT INTO actions(dir_id, kind, payload_json, status) VALUES ((SELECT id FROM directories WHERE path = ?1), 'move_dir', ?2, 'planned')")
+        .bind(old.to_string_lossy())
+        .bind(serde_json::json!({ "to": target.to_string_lossy() }).to_string())
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO actions(file_id, kind, payload_json, status) VALUES ((SELECT id FROM files WHERE path = ?1), 'move', ?2, 'planned')")
+        .bind(kept.join("c.txt").to_string_lossy())
+        .bind(serde_json::json!({ "to": root.join("c.txt").to_string_lossy() }).to_string())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let plan = cli::plan::simulate(&pool, None, &safety(temp.path()), "rename")
+        .await
+        .unwrap();
+    assert_eq!(plan.steps[0].effect, "merge");
+    assert_eq!(plan.steps[0].bytes, 6);
+
+    let views =
+        cli::apply::apply_actions(db_url, false, true, None, &safety(temp.path()), "rename")
+            .await
+            .unwrap();
+    assert!(views.iter().all(|v| v.status == "executed"), "{:?}", views);
+    assert_eq!(fs::read_to_string(target.join("a.jpg")).unwrap(), "old a");
+    assert_eq!(fs::read_to_string(target.join("a_1.jpg")).unwrap(), "new a");
+    assert_eq!(fs::read_to_string(target.join("2020/b.jpg")).unwrap(), "b");
+    assert!(!old.exists());
+    assert!(
+        !root.join("kept").exists(),
+        "emptied folders are removed up to the root"
+    );
+    assert!(root.exists());
+    let cleaned: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM audit WHERE event = 'cleanup_empty_dirs'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(cleaned, 2);
+
+    cli::undo::undo_actions(db_url, Some("1"), None)
+        .await
+        .unwrap();
+    assert_eq!(fs::read_to_string(old.join("a.jpg")).unwrap(), "new a");
+    assert_eq!(fs::read_to_string(old.join("2020/b.jpg")).unwrap(), "b");
+    assert_eq!(fs::read_to_string(target.join("a.jpg")).unwrap(), "old a");
+    assert!(!target.join("a_1.jpg").exists());
+}
+
+#[tokio::test]
+async fn cleanup_stays_inside_allowed_paths() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:dir_actions_cleanup?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let root = temp.path().join("root");
+    let empty = root.join("keep/empty");
+    fs::create_dir_all(&empty).unwrap();
+    scanner::scan(
+        std::slice::from_ref(&root),
+        &[],
+        &scanner::HashMode::None,
+        &pool,
+    )
+    .await
+    .unwrap();
+    let policy = cli::paths::PathPolicy::new(
+        &[temp.path().to_string_lossy().into_owned()],
+        &[root.join("keep").to_string_lossy().into_owned()],
+    )
+    .unwrap();
+    let removed = cli::dirs::cleanup_empty_dirs(&pool, std::slice::from_ref(&empty), &policy)
+        .await
+        .unwrap();
+    assert!(removed.is_empty());
+    assert!(empty.exists());
+
+    let policy =
+        cli::paths::PathPolicy::new(&[temp.path().to_string_lossy().into_owned()], &[]).unwrap();
+    let removed = cli::dirs::cleanup_empty_dirs(&pool, std::slice::from_ref(&empty), &policy)
+        .await
+        .unwrap();
+    assert_eq!(removed, vec![empty.clone(), root.join("keep")]);
+    assert!(root.exists());
+}
@@ -0,0 +1,134 @@
+use organizer_core::config::DedupeConfig;
+use organizer_core::dedupe::{self, GroupMember, SurvivorPolicy};
+use storage::{connect, migrate};
+
+fn member(file_id: i64, path: &str, mtime: i64, tags: i64, pixels: i64) -> GroupMember {
+    GroupMember {
+        file_id,
+        path: path.to_string(),
+        mtime,
+        tags,
+        pixels,
+    }
+}
+
+#[test]
+fn survivor_policies_pick_expected_copy() {
+    let members = vec![
+        member(1, "/inbox/deep/nested/photo.jpg", 300, 0, 100),
+        member(2, "/photos/photo.jpg", 200, 3, 400),
+        member(3, "/backup/p.jpg", 100, 1, 400),
+    ];
+    let pick = |policy: SurvivorPolicy, roots: &[String]| {
+        dedupe::choose_survivor(&members, policy, roots).map(|i| members[i].file_id)
+    };
+    assert_eq!(pick(SurvivorPolicy::Oldest, &[]), Some(3));
+    assert_eq!(pick(SurvivorPolicy::Newest, &[]), Some(1));
+    assert_eq!(pick(SurvivorPolicy::ShortestPath, &[]), Some(3));
+    assert_eq!(pick(SurvivorPolicy::MostTags, &[]), Some(2));
+    // Equal resolution falls back to the older file.
+    assert_eq!(pick(SurvivorPolicy::HighestResolution, &[]), Some(3));
+    let roots = vec!["/photos".to_string(), "/backup".to_string()];
+    assert_eq!(pick(SurvivorPolicy::PreferredRoot, &roots), Some(2));
+    assert_eq!(
+        dedupe::choose_survivor(&[], SurvivorPolicy::Oldest, &[]),
+        None
+    );
+
+    assert_eq!(
+        SurvivorPolicy::from("shortest-path"),
+        SurvivorPolicy::ShortestPath
+    );
+    assert_eq!(SurvivorPolicy::from("bogus"), SurvivorPolicy::Oldest);
+}
+
+#[tokio::test]
+async fn groups_plan_one_merge_per_copy() {
+    let db = "sqlite://file:duplicate_groups?mode=memory&cache=shared";
+    let pool = connect(db).await.unwrap();
+    migrate(&pool).await.unwrap();
+
+    for (path, mtime, hash) in [
+        ("/inbox/a-very-long-name-copy.pdf", 300, "h1"),
+        ("/docs/a.pdf", 100, "h1"),
+        ("/backup/old/a.pdf", 200, "h1"),
+        ("/docs/unique.pdf", 100, "h2"),
+    ] {
+        sqlx::query("INSERT INTO files(path, size, mtime, ctime, full_hash, status) VALUES (?1, 42, ?2, 0, ?3, 'new')")
+            .bind(path)
+            .bind(mtime)
+            .bind(hash)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    let cfg = DedupeConfig::default();
+    assert_eq!(dedupe::plan_duplicate_groups(&pool, &cfg).await.unwrap(), 2);
+    assert_eq!(dedupe::plan_duplicate_groups(&pool, &cfg).await.unwrap(), 0);
+
+    let groups = dedupe::list_duplicate_groups(&pool).await.unwrap();
+    assert_eq!(groups.len(), 1);
+    let group = &groups[0];
+    assert_eq!(group.survivor.as_deref(), Some("/docs/a.pdf"));
+    assert_eq!(group.policy, "oldest");
+    assert_eq!(group.members.len(), 3);
+    assert!(group.members[0].survivor && group.members[0].action_id.is_none());
+    assert!(group.members[1..]
+        .iter()
+        .all(|m| !m.survivor && m.action_status.as_deref() == Some("planned")));
+
+    // Switching policy replaces the planned merges instead of chaining them.
+    let newest = DedupeConfig {
+        survivor_policy: "newest".to_string(),
+        ..DedupeConfig::default()
+    };
+    assert_eq!(
+        dedupe::plan_duplicate_groups(&pool, &newest).await.unwrap(),
+        2
+    );
+    let targets: Vec<(String, String)> = sqlx::query_as(
+        "SELECT f.path, json_extract(a.payload_json, '$.duplicate_of') FROM actions a JOIN files f ON f.id = a.file_id
+         WHERE a.kind = 'merge_duplicate' AND a.status = 'planned' ORDER BY f.path",
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        targets,
+        vec![
+            (
+                "/backup/old/a.pdf".to_string(),
+                "/inbox/a-very-long-name-copy.pdf".to_string()
+            ),
+            (
+                "/docs/a.pdf".to_string(),
+                "/inbox/a-very-long-name-copy.pdf".to_string()
+            ),
+        ]
+    );
+
+    // Once only one copy is left the group dissolves and its planned merges go with it.
+    sqlx::query(
+        "UPDATE files SET full_hash = 'other' WHERE path != '/docs/a.pdf' AND full_hash = 'h1'",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query("UPDATE files SET full_hash = 'h3' WHERE path = '/backup/old/a.pdf'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    dedupe::plan_duplicate_groups(&pool, &cfg).await.unwrap();
+    assert!(dedupe::list_duplicate_groups(&pool)
+        .await
+        .unwrap()
+        .is_empty());
+    let planned: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM actions WHERE kind = 'merge_duplicate' AND status = 'planned'",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(planned, 0);
+}
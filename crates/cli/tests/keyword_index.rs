@@ -0,0 +1,60 @@
+use cli::keyword_index::{ensure_current, index_state, write_version, IndexState, SCHEMA_VERSION};
+
+#[test]
+fn index_state_tracks_schema_version() {
+    let dir = tempfile::tempdir().unwrap();
+    assert_eq!(index_state(dir.path()), IndexState::Missing);
+
+    // An index from before versioning has Tantivy's meta.json but no version file.
+    std::fs::write(dir.path().join("meta.json"), "{}").unwrap();
+    assert_eq!(index_state(dir.path()), IndexState::Stale(None));
+    let err = ensure_current(dir.path()).unwrap_err().to_string();
+    assert!(err.contains("rebuild-keyword-index --full"), "{}", err);
+
+    std::fs::write(dir.path().join("organizer_schema_version"), "1").unwrap();
+    assert_eq!(index_state(dir.path()), IndexState::Stale(Some(1)));
+
+    write_version(dir.path()).unwrap();
+    assert_eq!(index_state(dir.path()), IndexState::Current);
+    assert!(ensure_current(dir.path()).is_ok());
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("organizer_schema_version")).unwrap(),
+        SCHEMA_VERSION.to_string()
+    );
+}
+
+#[cfg(feature = "keyword-index")]
+#[tokio::test]
+async fn stale_index_is_refused_then_rebuilt_from_db() {
+    use cli::keyword_index::{enabled, index_dir};
+    use cli::watch::update_keyword_index;
+    use storage::{connect, migrate};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let db_file = tmp.path().join("org.db");
+    std::fs::File::create(&db_file).unwrap();
+    // The index lives next to the DB file, i.e. inside the temp dir.
+    let db = db_file.to_string_lossy().into_owned();
+    let pool = connect(&db).await.unwrap();
+    migrate(&pool).await.unwrap();
+    sqlx::query("INSERT INTO files(path, size, mtime, ctime, status) VALUES ('/docs/report.txt', 1, 0, 0, 'new')")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let dir = index_dir(&db);
+    enabled::build_index(&dir, &[]).unwrap();
+    assert_eq!(index_state(&dir), IndexState::Current);
+
+    // Simulate an index written by an older build.
+    std::fs::write(dir.join("organizer_schema_version"), "1").unwrap();
+    assert!(enabled::search(&dir, "report", 5).is_err());
+
+    let (docs, missing) = update_keyword_index(&db, &["/docs/report.txt".to_string()])
+        .await
+        .unwrap();
+    assert_eq!((docs, missing), (1, 0));
+    assert_eq!(index_state(&dir), IndexState::Current);
+    let hits = enabled::search(&dir, "report", 5).unwrap();
+    assert_eq!(hits.len(), 1);
+}
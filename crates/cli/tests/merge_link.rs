@@ -0,0 +1,142 @@
+#![cfg(unix)]
+
+use organizer_core::config::SafetyConfig;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+fn safety(root: &Path) -> SafetyConfig {
+    SafetyConfig {
+        allow_delete: true,
+        allow_paths: vec![root.to_string_lossy().into_owned()],
+        trash_dir: Some(root.join("trash").to_string_lossy().into_owned()),
+        immediate_vector_delete: true,
+        ..Default::default()
+    }
+}
+
+async fn plan_merge(pool: &sqlx::SqlitePool, dup: &Path, survivor: &Path, strategy: &str) -> i64 {
+    for p in [survivor, dup] {
+        sqlx::query("INSERT OR IGNORE INTO files(path, size, mtime, ctime, status) VALUES (?1, 6, 0, 0, 'new')")
+            .bind(p.to_string_lossy())
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+    sqlx::query_scalar(
+        "INSERT INTO actions(file_id, kind, payload_json, status) VALUES ((SELECT id FROM files WHERE path = ?1), 'merge_duplicate', ?2, 'planned') RETURNING id",
+    )
+    .bind(dup.to_string_lossy())
+    .bind(
+        serde_json::json!({ "duplicate_of": survivor.to_string_lossy(), "strategy": strategy })
+            .to_string(),
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn hardlink_merge_keeps_both_paths_and_undoes() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:merge_link_hard?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let survivor = temp.path().join("photo.jpg");
+    let dup = temp.path().join("photo (1).jpg");
+    fs::write(&survivor, "pixels").unwrap();
+    fs::write(&dup, "pixels").unwrap();
+    let id = plan_merge(&pool, &dup, &survivor, "hardlink").await;
+
+    let views =
+        cli::apply::apply_actions(db_url, false, true, None, &safety(temp.path()), "rename")
+            .await
+            .unwrap();
+    assert_eq!(views[0].error, None);
+    assert_eq!(
+        fs::metadata(&dup).unwrap().ino(),
+        fs::metadata(&survivor).unwrap().ino()
+    );
+    let backup = views[0]
+        .backup
+        .clone()
+        .expect("duplicate backed up to trash");
+    assert_eq!(fs::read_to_string(&backup).unwrap(), "pixels");
+    let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM files WHERE path = ?1")
+        .bind(dup.to_string_lossy())
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(rows, 1, "linked duplicate keeps its row");
+
+    cli::undo::undo_actions(db_url, Some(&id.to_string()), None)
+        .await
+        .unwrap();
+    assert_ne!(
+        fs::metadata(&dup).unwrap().ino(),
+        fs::metadata(&survivor).unwrap().ino()
+    );
+    assert_eq!(fs::read_to_string(&dup).unwrap(), "pixels");
+    assert_eq!(fs::read_to_string(&survivor).unwrap(), "pixels");
+}
+
+#[tokio::test]
+async fn reflink_merge_falls_back_to_a_valid_path() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:merge_link_reflink?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let survivor = temp.path().join("a.raw");
+    let dup = temp.path().join("b.raw");
+    fs::write(&survivor, "raw-01").unwrap();
+    fs::write(&dup, "raw-01").unwrap();
+    plan_merge(&pool, &dup, &survivor, "reflink").await;
+
+    let views =
+        cli::apply::apply_actions(db_url, false, true, None, &safety(temp.path()), "rename")
+            .await
+            .unwrap();
+    assert_eq!(views[0].error, None);
+    // A clone on reflink-capable filesystems, a hardlink elsewhere; either way both paths read.
+    assert_eq!(fs::read_to_string(&dup).unwrap(), "raw-01");
+    assert_eq!(fs::read_to_string(&survivor).unwrap(), "raw-01");
+    let leftovers: Vec<_> = fs::read_dir(temp.path())
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().ends_with(".organizer-link"))
+        .collect();
+    assert!(leftovers.is_empty());
+}
+
+#[tokio::test]
+async fn link_merge_without_trash_dir_is_refused() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:merge_link_no_trash?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let survivor = temp.path().join("a.txt");
+    let dup = temp.path().join("b.txt");
+    fs::write(&survivor, "same-1").unwrap();
+    fs::write(&dup, "same-1").unwrap();
+    plan_merge(&pool, &dup, &survivor, "hardlink").await;
+
+    let safety = SafetyConfig {
+        trash_dir: None,
+        ..safety(temp.path())
+    };
+    let views = cli::apply::apply_actions(db_url, false, true, None, &safety, "rename")
+        .await
+        .unwrap();
+    assert!(views[0]
+        .error
+        .as_deref()
+        .unwrap_or_default()
+        .contains("without a trash directory"));
+    assert_ne!(
+        fs::metadata(&dup).unwrap().ino(),
+        fs::metadata(&survivor).unwrap().ino()
+    );
+}
@@ -0,0 +1,163 @@
+use organizer_core::config::SafetyConfig;
+use std::fs;
+use std::path::Path;
+
+fn safety(root: &Path, byte_compare: bool) -> SafetyConfig {
+    SafetyConfig {
+        allow_delete: true,
+        allow_paths: vec![root.to_string_lossy().into_owned()],
+        trash_dir: Some(root.join("trash").to_string_lossy().into_owned()),
+        immediate_vector_delete: true,
+        byte_compare_duplicates: byte_compare,
+        ..Default::default()
+    }
+}
+
+async fn plan_merge(
+    pool: &sqlx::SqlitePool,
+    path: &Path,
+    survivor: &Path,
+    full_hash: Option<&str>,
+) -> i64 {
+    for (p, h) in [(survivor, full_hash), (path, full_hash)] {
+        let meta = fs::metadata(p).unwrap();
+        sqlx::query(
+            "INSERT OR IGNORE INTO files(path, size, mtime, ctime, hash, full_hash, status) VALUES (?1, ?3, ?4, 0, 'fast-prefix', ?2, 'new')",
+        )
+        .bind(p.to_string_lossy())
+        .bind(h)
+        .bind(meta.len() as i64)
+        .bind(organizer_core::scanner::mtime(&meta))
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+    sqlx::query_scalar(
+        "INSERT INTO actions(file_id, kind, payload_json, status) VALUES ((SELECT id FROM files WHERE path = ?1), 'merge_duplicate', ?2, 'planned') RETURNING id",
+    )
+    .bind(path.to_string_lossy())
+    .bind(serde_json::json!({ "duplicate_of": survivor.to_string_lossy() }).to_string())
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+async fn status(pool: &sqlx::SqlitePool, id: i64) -> Option<String> {
+    sqlx::query_scalar("SELECT status FROM actions WHERE id = ?1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn merge_requires_matching_full_hash() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:merge_verify_hash?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let original = temp.path().join("original.txt");
+    let copy = temp.path().join("copy.txt");
+    let lookalike = temp.path().join("lookalike.txt");
+    fs::write(&original, "same-one").unwrap();
+    fs::write(&copy, "same-one").unwrap();
+    // Same size and (fast) hash column, different content.
+    fs::write(&lookalike, "same-two").unwrap();
+
+    let true_copy = plan_merge(&pool, &copy, &original, None).await;
+    let false_copy = plan_merge(&pool, &lookalike, &original, None).await;
+
+    let views = cli::apply::apply_actions(
+        db_url,
+        false,
+        true,
+        None,
+        &safety(temp.path(), false),
+        "rename",
+    )
+    .await
+    .unwrap();
+    let error_of = |id: i64| {
+        views
+            .iter()
+            .find(|v| v.id == id)
+            .and_then(|v| v.error.clone())
+    };
+    assert_eq!(error_of(true_copy), None);
+    assert_eq!(
+        error_of(false_copy).as_deref(),
+        Some("not a true duplicate")
+    );
+
+    assert!(!copy.exists(), "verified copy is merged away");
+    assert!(lookalike.exists(), "look-alike file is kept");
+    assert_eq!(status(&pool, false_copy).await.as_deref(), Some("error"));
+
+    // Full hashes were computed on demand and stored.
+    let stored: Option<String> = sqlx::query_scalar("SELECT full_hash FROM files WHERE path = ?1")
+        .bind(lookalike.to_string_lossy())
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(stored, Some(blake3::hash(b"same-two").to_hex().to_string()));
+}
+
+#[tokio::test]
+async fn byte_compare_catches_stale_hashes() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:merge_verify_bytes?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let original = temp.path().join("original.txt");
+    let edited = temp.path().join("edited.txt");
+    fs::write(&original, "abcdefgh").unwrap();
+    // Edited after the scan recorded a matching full hash.
+    fs::write(&edited, "abcdefgX").unwrap();
+
+    let id = plan_merge(&pool, &edited, &original, Some("stale")).await;
+    let views = cli::apply::apply_actions(
+        db_url,
+        false,
+        true,
+        None,
+        &safety(temp.path(), true),
+        "rename",
+    )
+    .await
+    .unwrap();
+    assert_eq!(views[0].id, id);
+    assert_eq!(views[0].error.as_deref(), Some("not a true duplicate"));
+    assert!(edited.exists());
+}
+
+#[tokio::test]
+async fn stored_full_hash_is_ignored_once_the_file_changed() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:merge_verify_changed?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let original = temp.path().join("original.txt");
+    let edited = temp.path().join("edited.txt");
+    fs::write(&original, "abcdefgh").unwrap();
+    fs::write(&edited, "abcdefgh").unwrap();
+    let id = plan_merge(&pool, &edited, &original, Some("scanned")).await;
+    // Grown since the scan: the stored hash no longer describes it.
+    fs::write(&edited, "abcdefgh, edited").unwrap();
+
+    let views = cli::apply::apply_actions(
+        db_url,
+        false,
+        true,
+        None,
+        &safety(temp.path(), false),
+        "rename",
+    )
+    .await
+    .unwrap();
+    assert_eq!(views[0].id, id);
+    assert_eq!(views[0].error.as_deref(), Some("not a true duplicate"));
+    assert!(edited.exists());
+}
@@ -0,0 +1,149 @@
+use organizer_core::config::DedupeConfig;
+use organizer_core::dedupe;
+use organizer_core::fingerprint::{self, Fingerprint};
+use storage::{connect, migrate};
+
+const REPORT: &str = "Quarterly report for the northern region. Revenue grew by twelve percent \
+    compared with the previous quarter, driven by strong demand for consulting services and \
+    renewed maintenance contracts. Operating costs stayed flat while headcount increased by \
+    three engineers. The outlook for the next quarter remains positive, with two large \
+    projects scheduled to start in early spring and a pipeline of smaller engagements.";
+
+fn gradient(width: usize, height: usize, shift: u8) -> Vec<u8> {
+    (0..width * height)
+        .map(|i| {
+            let (x, y) = (i % width, i / width);
+            ((x * 255 / width + y * 64 / height) as u8).saturating_add(shift)
+        })
+        .collect()
+}
+
+#[test]
+fn minhash_scores_edited_text_above_unrelated_text() {
+    let original = fingerprint::minhash(REPORT).unwrap();
+    let edited = fingerprint::minhash(&REPORT.replace("twelve", "thirteen")).unwrap();
+    let other = fingerprint::minhash(
+        "Shopping list for the weekend: apples, bread, milk, eggs, coffee beans, rice and a bag of lemons.",
+    )
+    .unwrap();
+
+    assert_eq!(fingerprint::minhash_similarity(&original, &original), 1.0);
+    assert!(fingerprint::minhash_similarity(&original, &edited) > 0.75);
+    assert!(fingerprint::minhash_similarity(&original, &other) < 0.2);
+    assert!(fingerprint::minhash("  ... ").is_none());
+    assert!(
+        fingerprint::minhash("Shopping list: apples, bread, milk, eggs and coffee beans.")
+            .is_none(),
+        "too few shingles to compare"
+    );
+    assert_eq!(
+        fingerprint::minhash_from_bytes(&fingerprint::minhash_to_bytes(&original)),
+        original
+    );
+}
+
+#[test]
+fn image_hashes_survive_resizing_and_brightness() {
+    let base = fingerprint::image_hashes(&gradient(64, 48, 0), 64, 48).unwrap();
+    let resized = fingerprint::image_hashes(&gradient(128, 96, 10), 128, 96).unwrap();
+    let flipped: Vec<u8> = gradient(64, 48, 0).into_iter().rev().collect();
+    let mirrored = fingerprint::image_hashes(&flipped, 64, 48).unwrap();
+
+    assert!(fingerprint::image_similarity(&base, &resized) > 0.9);
+    assert!(fingerprint::image_similarity(&base, &mirrored) < 0.6);
+    assert!(fingerprint::image_hashes(&[], 0, 0).is_none());
+}
+
+#[tokio::test]
+async fn near_duplicates_become_dedupe_actions_once() {
+    let db = "sqlite://file:near_duplicates?mode=memory&cache=shared";
+    let pool = connect(db).await.unwrap();
+    migrate(&pool).await.unwrap();
+
+    let docs = [
+        ("/docs/report.txt", REPORT.to_string()),
+        (
+            "/docs/report-copy.txt",
+            REPORT.replace("three engineers", "four engineers"),
+        ),
+        (
+            "/docs/list.txt",
+            "Shopping list: apples, bread, milk, eggs and coffee beans.".to_string(),
+        ),
+    ];
+    let mut conn = pool.acquire().await.unwrap();
+    for (path, text) in &docs {
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO files(path, size, mtime, ctime, status) VALUES (?1, 1, 0, 0, 'new') RETURNING id",
+        )
+        .bind(path)
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap();
+        let fp = fingerprint::minhash(text).map(Fingerprint::Text);
+        dedupe::store_fingerprint(&mut conn, id, fp.as_ref())
+            .await
+            .unwrap();
+    }
+    for (path, shift) in [("/photos/a.png", 0u8), ("/photos/a-small.png", 5)] {
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO files(path, size, mtime, ctime, status) VALUES (?1, 1, 0, 0, 'new') RETURNING id",
+        )
+        .bind(path)
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap();
+        let hashes = fingerprint::image_hashes(&gradient(64, 48, shift), 64, 48).unwrap();
+        dedupe::store_fingerprint(&mut conn, id, Some(&Fingerprint::Image(hashes)))
+            .await
+            .unwrap();
+    }
+    // In-memory pools hold a single connection.
+    drop(conn);
+
+    let cfg = DedupeConfig::default();
+    let found = dedupe::find_near_duplicates(&pool, &cfg).await.unwrap();
+    let pairs: Vec<(&str, &str)> = found
+        .iter()
+        .map(|d| (d.duplicate_of_path.as_str(), d.method))
+        .collect();
+    assert_eq!(
+        pairs,
+        vec![
+            ("/docs/report.txt", "minhash"),
+            ("/photos/a.png", "image_hash")
+        ]
+    );
+
+    assert_eq!(
+        dedupe::suggest_near_duplicates(&pool, &cfg).await.unwrap(),
+        2
+    );
+    // Re-running does not plan the same suggestion twice.
+    assert_eq!(
+        dedupe::suggest_near_duplicates(&pool, &cfg).await.unwrap(),
+        0
+    );
+
+    let (path, payload): (String, String) = sqlx::query_as(
+        "SELECT f.path, a.payload_json FROM actions a JOIN files f ON f.id = a.file_id WHERE a.kind = 'dedupe' ORDER BY a.id LIMIT 1",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    let payload: serde_json::Value = serde_json::from_str(&payload).unwrap();
+    assert_eq!(path, "/docs/report-copy.txt");
+    assert_eq!(payload["duplicate_of"], "/docs/report.txt");
+    assert!(payload["score"].as_f64().unwrap() >= 0.9);
+
+    let strict = DedupeConfig {
+        text_threshold: 1.0,
+        image_threshold: 1.0,
+        ..DedupeConfig::default()
+    };
+    assert!(dedupe::find_near_duplicates(&pool, &strict)
+        .await
+        .unwrap()
+        .iter()
+        .all(|d| d.score >= 1.0));
+}
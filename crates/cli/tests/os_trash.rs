@@ -0,0 +1,90 @@
+use organizer_core::config::SafetyConfig;
+use std::fs;
+use std::path::Path;
+
+fn safety(root: &Path) -> SafetyConfig {
+    SafetyConfig {
+        allow_delete: true,
+        allow_paths: vec![root.to_string_lossy().into_owned()],
+        trash_dir: Some(root.join("trash").to_string_lossy().into_owned()),
+        immediate_vector_delete: true,
+        use_os_trash: true,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn merged_duplicate_goes_to_xdg_trash_and_undo_restores_it() {
+    let temp = tempfile::tempdir().unwrap();
+    let data_home = tempfile::tempdir().unwrap();
+    // The only test in this binary, so nothing else observes the variable.
+    std::env::set_var("XDG_DATA_HOME", data_home.path());
+    let db_url = "sqlite://file:os_trash_merge?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let survivor = temp.path().join("a.txt");
+    let dup = temp.path().join("copy of a.txt");
+    fs::write(&survivor, "pixels").unwrap();
+    fs::write(&dup, "pixels").unwrap();
+    for p in [&survivor, &dup] {
+        sqlx::query(
+            "INSERT INTO files(path, size, mtime, ctime, status) VALUES (?1, 6, 0, 0, 'new')",
+        )
+        .bind(p.to_string_lossy())
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+    let action: i64 = sqlx::query_scalar(
+        "INSERT INTO actions(file_id, kind, payload_json, status) VALUES ((SELECT id FROM files WHERE path = ?1), 'merge_duplicate', ?2, 'planned') RETURNING id",
+    )
+    .bind(dup.to_string_lossy())
+    .bind(serde_json::json!({ "duplicate_of": survivor.to_string_lossy() }).to_string())
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    let views =
+        cli::apply::apply_actions(db_url, false, true, None, &safety(temp.path()), "rename")
+            .await
+            .unwrap();
+    assert_eq!(views[0].error, None);
+    assert!(!dup.exists());
+    let trash = data_home.path().join("Trash");
+    let trashed = trash.join("files/copy of a.txt");
+    assert_eq!(
+        views[0].backup.as_deref(),
+        Some(trashed.to_string_lossy().as_ref())
+    );
+    assert_eq!(fs::read_to_string(&trashed).unwrap(), "pixels");
+    let info_path = trash.join("info/copy of a.txt.trashinfo");
+    let info = fs::read_to_string(&info_path).unwrap();
+    assert!(info.starts_with("[Trash Info]\n"));
+    assert!(info.contains(&format!(
+        "Path={}/copy%20of%20a.txt\n",
+        temp.path().display()
+    )));
+    assert!(info.contains("DeletionDate="));
+    assert!(
+        !temp.path().join("trash").exists(),
+        "the app trash is bypassed"
+    );
+    assert!(cli::trash_store::list_entries(&pool)
+        .await
+        .unwrap()
+        .is_empty());
+
+    cli::undo::undo_session(db_url, views[0].session_id.unwrap(), None)
+        .await
+        .unwrap();
+    assert_eq!(fs::read_to_string(&dup).unwrap(), "pixels");
+    assert!(!trashed.exists());
+    assert!(!info_path.exists());
+    let status: String = sqlx::query_scalar("SELECT status FROM actions WHERE id = ?1")
+        .bind(action)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "planned");
+}
@@ -0,0 +1,113 @@
+use cli::paths::PathPolicy;
+use organizer_core::config::SafetyConfig;
+use std::fs;
+use std::path::Path;
+
+fn owned(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn entries_match_whole_components_after_resolving_dot_dot() {
+    let temp = tempfile::tempdir().unwrap();
+    let home = temp.path().join("home/a");
+    fs::create_dir_all(&home).unwrap();
+    let policy = PathPolicy::new(&owned(&[&home.to_string_lossy()]), &[]).unwrap();
+
+    assert!(policy.check("source", &home.join("notes.txt")).is_ok());
+    assert!(policy
+        .check("source", &temp.path().join("home/abc/x"))
+        .is_err());
+    let err = policy
+        .check("destination", &home.join("../../etc/passwd"))
+        .unwrap_err();
+    assert!(err.contains("outside allowed paths"), "{err}");
+}
+
+#[test]
+fn globs_cover_matching_directories_and_deny_wins() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path().to_string_lossy().into_owned();
+    let deny = format!("{}/*/private", root);
+    let policy = PathPolicy::new(&owned(&[&format!("{}/**", root)]), &owned(&[&deny])).unwrap();
+
+    assert!(policy
+        .check("source", &temp.path().join("alice/docs/a.txt"))
+        .is_ok());
+    let err = policy
+        .check("destination", &temp.path().join("alice/private/deep/a.txt"))
+        .unwrap_err();
+    assert_eq!(
+        err,
+        format!(
+            "destination {}/alice/private/deep/a.txt is under denied path {}",
+            root, deny
+        )
+    );
+    // `*` does not cross directories.
+    assert!(policy
+        .check("source", &temp.path().join("a/b/private/x"))
+        .is_ok());
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_out_of_an_allowed_root_are_reported() {
+    let temp = tempfile::tempdir().unwrap();
+    let allowed = temp.path().join("allowed");
+    let outside = temp.path().join("outside");
+    fs::create_dir_all(&allowed).unwrap();
+    fs::create_dir_all(&outside).unwrap();
+    std::os::unix::fs::symlink(&outside, allowed.join("escape")).unwrap();
+    let policy = PathPolicy::new(&owned(&[&allowed.to_string_lossy()]), &[]).unwrap();
+
+    let err = policy
+        .check("destination", &allowed.join("escape/new/file.txt"))
+        .unwrap_err();
+    assert!(err.contains("through a symlink"), "{err}");
+}
+
+#[tokio::test]
+async fn apply_refuses_moves_into_denied_destinations() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:path_policy_apply?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let src = temp.path().join("inbox/report.txt");
+    let dest = temp.path().join("vault/report.txt");
+    fs::create_dir_all(src.parent().unwrap()).unwrap();
+    fs::write(&src, "hello").unwrap();
+    sqlx::query("INSERT INTO files(path, size, mtime, ctime, status) VALUES (?1, 5, 0, 0, 'new')")
+        .bind(src.to_string_lossy())
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO actions(file_id, kind, payload_json, status) VALUES (1, 'move', ?1, 'planned')")
+        .bind(serde_json::json!({ "to": dest.to_string_lossy() }).to_string())
+        .execute(&pool)
+        .await
+        .unwrap();
+    let safety = SafetyConfig {
+        allow_paths: vec![temp.path().to_string_lossy().into_owned()],
+        deny_paths: vec![temp.path().join("vault").to_string_lossy().into_owned()],
+        immediate_vector_delete: true,
+        ..Default::default()
+    };
+
+    let views = cli::apply::apply_actions(db_url, false, true, None, &safety, "rename")
+        .await
+        .unwrap();
+    let error = views[0].error.as_deref().unwrap();
+    assert!(
+        error.starts_with("destination ") && error.contains("denied path"),
+        "{error}"
+    );
+    assert!(src.exists());
+    assert!(!Path::new(&dest).exists());
+    let status: String = sqlx::query_scalar("SELECT status FROM actions WHERE id = 1")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "error");
+}
@@ -1,11 +1,14 @@
-use organizer_core::config::{AppConfig, DatabaseConfig, RuleConfig, SafetyConfig, ScanPaths, EmbeddingConfig, VectorConfig, ClassificationConfig, Thresholds};
-use organizer_core::scanner;
-use organizer_core::extractor;
 use organizer_core::classifier;
+use organizer_core::config::{
+    AppConfig, ClassificationConfig, DatabaseConfig, EmbeddingConfig, RuleConfig, SafetyConfig,
+    ScanPaths, Thresholds, VectorConfig,
+};
+use organizer_core::extractor;
+use organizer_core::scanner;
 use organizer_core::suggester;
+use sqlx::Row;
 use std::fs;
 use tempfile::tempdir;
-use sqlx::Row;
 
 #[tokio::test]
 async fn test_full_pipeline() {
@@ -52,25 +55,25 @@ async fn test_full_pipeline() {
         database: DatabaseConfig { path: db_url.clone() },
         scan: ScanPaths {
             include: vec![src_dir.to_string_lossy().into_owned()],
-            exclude: vec![],
             hash_mode: Some("fast".to_string()),
+            ..Default::default()
         },
         rules: RuleConfig {
             path: Some(rules_dir.to_string_lossy().into_owned()),
         },
         safety: SafetyConfig {
-            dry_run: false,
             allow_delete: true,
             allow_paths: vec![temp.path().to_string_lossy().into_owned()],
-            deny_paths: vec![],
             trash_dir: Some(trash_dir.to_string_lossy().into_owned()),
-            copy_then_delete: false,
             immediate_vector_delete: true,
+            ..Default::default()
         },
         embeddings: EmbeddingConfig { provider: "noop".to_string(), model: "".to_string(), batch_size: 1 },
         vectors: VectorConfig { provider: "noop".to_string(), url: None, collection: "".to_string() },
         classification: ClassificationConfig { thresholds: Thresholds { accept: 0.5, review: 0.1 } },
         parsers: organizer_core::config::ParserConfig::default(),
+        dedupe: Default::default(),
+        trash: Default::default(),
     };
 
     let pool = storage::connect(&cfg.database.path).await.unwrap();
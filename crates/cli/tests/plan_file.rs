@@ -0,0 +1,175 @@
+use organizer_core::config::SafetyConfig;
+use std::fs;
+use std::path::Path;
+
+fn safety(root: &Path) -> SafetyConfig {
+    SafetyConfig {
+        allow_paths: vec![root.to_string_lossy().into_owned()],
+        immediate_vector_delete: true,
+        ..Default::default()
+    }
+}
+
+async fn plan_move(pool: &sqlx::SqlitePool, from: &Path, to: &Path) {
+    sqlx::query("INSERT INTO files(path, size, mtime, ctime, status) VALUES (?1, 0, 0, 0, 'new')")
+        .bind(from.to_string_lossy())
+        .execute(pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO actions(file_id, kind, payload_json, status) VALUES ((SELECT id FROM files WHERE path = ?1), 'move', ?2, 'planned')")
+        .bind(from.to_string_lossy())
+        .bind(serde_json::json!({ "to": to.to_string_lossy(), "rule": "docs" }).to_string())
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn reviewed_plan_runs_only_kept_unchanged_actions_with_edits() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:plan_file_review?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let root = temp.path();
+    let names = ["a.txt", "b \"quoted\".txt", "c.txt"];
+    for name in names {
+        fs::write(root.join(name), name).unwrap();
+        plan_move(&pool, &root.join(name), &root.join("sorted").join(name)).await;
+    }
+
+    let exported = cli::plan_file::export(&pool, None).await.unwrap();
+    let lines: Vec<&str> = exported
+        .lines()
+        .filter(|l| l.trim_start().starts_with('{'))
+        .collect();
+    assert_eq!(lines.len(), 3, "one line per action:\n{exported}");
+    assert!(lines[0].contains("hash = \""), "{exported}");
+
+    // The reviewer drops c, redirects a, and b changes on disk afterwards.
+    let elsewhere = root.join("elsewhere/a.txt");
+    let edited: String = exported
+        .lines()
+        .filter(|l| !l.contains("c.txt"))
+        .map(|l| {
+            l.replace(
+                &root.join("sorted/a.txt").to_string_lossy().to_string(),
+                &elsewhere.to_string_lossy(),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let reviewed = cli::plan_file::parse(&edited).unwrap();
+    assert_eq!(reviewed.actions.len(), 2);
+    assert_eq!(
+        reviewed.actions[1].path,
+        root.join(names[1]).to_string_lossy()
+    );
+    fs::write(root.join(names[1]), "rewritten").unwrap();
+
+    let (ready, refused) = cli::plan_file::prepare(&pool, &reviewed).await.unwrap();
+    assert_eq!(ready, vec![1]);
+    assert_eq!(refused.len(), 1);
+    assert_eq!(refused[0].id, 2);
+    assert!(
+        refused[0]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("changed size"),
+        "{:?}",
+        refused[0].error
+    );
+    let payload: String = sqlx::query_scalar("SELECT payload_json FROM actions WHERE id = 1")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let payload: serde_json::Value = serde_json::from_str(&payload).unwrap();
+    assert_eq!(payload["to"], elsewhere.to_string_lossy().as_ref());
+    assert_eq!(payload["rule"], "docs", "other payload keys are kept");
+    let audit: Vec<String> =
+        sqlx::query_scalar("SELECT detail FROM audit WHERE action_id = 1 AND event = 'plan_edit'")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        audit,
+        vec![format!(
+            "to: {} -> {}",
+            root.join("sorted/a.txt").to_string_lossy(),
+            elsewhere.to_string_lossy()
+        )],
+        "each edit is audited"
+    );
+
+    let views = cli::apply::apply_actions(db_url, false, true, Some("1"), &safety(root), "rename")
+        .await
+        .unwrap();
+    assert_eq!(views.len(), 1);
+    assert_eq!(fs::read_to_string(&elsewhere).unwrap(), "a.txt");
+    assert!(
+        root.join("c.txt").exists(),
+        "dropped from the plan, so untouched"
+    );
+    assert!(root.join(names[1]).exists());
+
+    // An already applied action is refused on a second run of the same plan.
+    let (ready, refused) = cli::plan_file::prepare(&pool, &reviewed).await.unwrap();
+    assert!(ready.is_empty());
+    assert_eq!(
+        refused[0].error.as_deref(),
+        Some("action is executed, not planned")
+    );
+}
+
+#[tokio::test]
+async fn editing_a_merge_survivor_is_refused() {
+    let temp = tempfile::tempdir().unwrap();
+    let pool = storage::connect("sqlite://file:plan_file_survivor?mode=memory&cache=shared")
+        .await
+        .unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let root = temp.path();
+    let (survivor, dup) = (root.join("a.jpg"), root.join("a copy.jpg"));
+    fs::write(&survivor, "pix").unwrap();
+    fs::write(&dup, "pix").unwrap();
+    sqlx::query("INSERT INTO files(path, size, mtime, ctime, status) VALUES (?1, 3, 0, 0, 'new')")
+        .bind(dup.to_string_lossy())
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO actions(file_id, kind, payload_json, status) VALUES (1, 'merge_duplicate', ?1, 'planned')")
+        .bind(serde_json::json!({ "payload": { "duplicate_of": survivor.to_string_lossy() } }).to_string())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let exported = cli::plan_file::export(&pool, None).await.unwrap();
+    let plan = cli::plan_file::parse(&exported).unwrap();
+    assert_eq!(
+        plan.actions[0].duplicate_of.as_deref(),
+        Some(survivor.to_string_lossy().as_ref())
+    );
+    let (ready, _) = cli::plan_file::prepare(&pool, &plan).await.unwrap();
+    assert_eq!(ready, vec![1]);
+
+    let other = root.join("b.jpg");
+    let edited = exported.replace(
+        &survivor.to_string_lossy().to_string(),
+        &other.to_string_lossy(),
+    );
+    let (ready, refused) = cli::plan_file::prepare(&pool, &cli::plan_file::parse(&edited).unwrap())
+        .await
+        .unwrap();
+    assert!(ready.is_empty());
+    assert!(
+        refused[0]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("duplicate_of"),
+        "{:?}",
+        refused[0].error
+    );
+}
@@ -0,0 +1,132 @@
+use cli::preflight::{self, Scanned};
+use organizer_core::config::SafetyConfig;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+fn safety(root: &Path) -> SafetyConfig {
+    SafetyConfig {
+        allow_paths: vec![root.to_string_lossy().into_owned()],
+        immediate_vector_delete: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn checks_source_is_present_and_unchanged() {
+    let temp = tempfile::tempdir().unwrap();
+    let src = temp.path().join("a.txt");
+    let dest = temp.path().join("new/dir/a.txt");
+    fs::write(&src, "scanned").unwrap();
+    let scanned = Scanned {
+        fast_hash: Some(organizer_core::scanner::fast_hash(&src).unwrap()),
+        full_hash: None,
+    };
+    assert_eq!(preflight::check(&src, Some(&dest), None, &scanned), Ok(()));
+
+    fs::write(&src, "edited").unwrap();
+    let err = preflight::check(&src, Some(&dest), None, &scanned).unwrap_err();
+    assert!(err.ends_with("changed since it was scanned"), "{err}");
+    let by_full = Scanned {
+        fast_hash: None,
+        full_hash: Some(cli::fs_apply::full_hash(&src).unwrap()),
+    };
+    assert_eq!(
+        preflight::check(&src, None, Some(temp.path()), &by_full),
+        Ok(())
+    );
+
+    fs::remove_file(&src).unwrap();
+    let err = preflight::check(&src, Some(&dest), None, &Scanned::default()).unwrap_err();
+    assert_eq!(err, format!("source {} no longer exists", src.display()));
+}
+
+#[test]
+fn verified_copy_keeps_times_and_permissions() {
+    let temp = tempfile::tempdir().unwrap();
+    let src = temp.path().join("photo.jpg");
+    let dest = temp.path().join("other/photo.jpg");
+    fs::create_dir_all(dest.parent().unwrap()).unwrap();
+    fs::write(&src, "pixels").unwrap();
+    let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    fs::File::options()
+        .write(true)
+        .open(&src)
+        .unwrap()
+        .set_modified(mtime)
+        .unwrap();
+    let mut perms = fs::metadata(&src).unwrap().permissions();
+    perms.set_readonly(true);
+    fs::set_permissions(&src, perms).unwrap();
+
+    cli::fs_apply::copy_verified_then_delete(&src, &dest).unwrap();
+    assert!(!src.exists());
+    let meta = fs::metadata(&dest).unwrap();
+    assert_eq!(fs::read_to_string(&dest).unwrap(), "pixels");
+    assert_eq!(meta.modified().unwrap(), mtime);
+    assert!(meta.permissions().readonly());
+    assert!(!dest.with_file_name(".photo.jpg.partial").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn verified_copy_of_a_read_only_source_as_a_regular_user() {
+    use std::os::unix::fs::PermissionsExt;
+    // Root ignores file modes, so only a regular user sees the copy refuse writes.
+    // SAFETY: geteuid has no preconditions and cannot fail.
+    if unsafe { libc::geteuid() } == 0 {
+        eprintln!("skipped: running as root");
+        return;
+    }
+    let temp = tempfile::tempdir().unwrap();
+    let src = temp.path().join("notes.txt");
+    let dest = temp.path().join("moved/notes.txt");
+    fs::create_dir_all(dest.parent().unwrap()).unwrap();
+    fs::write(&src, "read only").unwrap();
+    fs::set_permissions(&src, fs::Permissions::from_mode(0o444)).unwrap();
+
+    cli::fs_apply::copy_verified_then_delete(&src, &dest).unwrap();
+    assert!(!src.exists());
+    assert_eq!(fs::read_to_string(&dest).unwrap(), "read only");
+    let mode = fs::metadata(&dest).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o444);
+}
+
+#[tokio::test]
+async fn apply_refuses_moves_of_files_changed_since_scan() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:preflight_apply?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let src = temp.path().join("notes.txt");
+    fs::write(&src, "old").unwrap();
+    let stale = organizer_core::scanner::fast_hash(&src).unwrap();
+    fs::write(&src, "new contents").unwrap();
+    sqlx::query("INSERT INTO files(path, size, mtime, ctime, fast_hash, status) VALUES (?1, 3, 0, 0, ?2, 'new')")
+        .bind(src.to_string_lossy())
+        .bind(&stale)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO actions(file_id, kind, payload_json, status) VALUES (1, 'move', ?1, 'planned')")
+        .bind(serde_json::json!({ "to": temp.path().join("sorted/notes.txt").to_string_lossy() }).to_string())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let views =
+        cli::apply::apply_actions(db_url, false, true, None, &safety(temp.path()), "rename")
+            .await
+            .unwrap();
+    assert_eq!(
+        views[0].error.as_deref(),
+        Some(format!("source {} changed since it was scanned", src.display()).as_str())
+    );
+    assert!(src.exists());
+    let journalled: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM apply_journal")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(journalled, 0, "refused before anything was started");
+}
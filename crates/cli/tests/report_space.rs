@@ -0,0 +1,123 @@
+use cli::report::{self, human_bytes, top_directory};
+use organizer_core::config::DedupeConfig;
+use organizer_core::dedupe;
+use storage::{connect, migrate};
+
+const NOW: i64 = 1_750_000_000;
+const DAY: i64 = 24 * 60 * 60;
+
+#[test]
+fn directories_are_first_level_below_a_root() {
+    let roots = vec!["/home/me".to_string()];
+    assert_eq!(
+        top_directory("/home/me/Photos/2024/a.jpg", &roots),
+        "/home/me/Photos"
+    );
+    assert_eq!(top_directory("/home/me/notes.txt", &roots), "/home/me");
+    assert_eq!(top_directory("/srv/data/x.bin", &roots), "/srv/data");
+    assert_eq!(human_bytes(512), "512 B");
+    assert_eq!(human_bytes(1536), "1.5 KiB");
+    assert_eq!(human_bytes(3 * 1024 * 1024 * 1024), "3.0 GiB");
+}
+
+#[tokio::test]
+async fn space_report_groups_bytes_and_reclaimable() {
+    let db = "sqlite://file:report_space?mode=memory&cache=shared";
+    let pool = connect(db).await.unwrap();
+    migrate(&pool).await.unwrap();
+
+    for (path, size, age_days, mime, hash) in [
+        ("/home/me/Photos/a.jpg", 4_000, 10, "image/jpeg", Some("h1")),
+        (
+            "/home/me/Photos/copy/a.jpg",
+            4_000,
+            5,
+            "image/jpeg",
+            Some("h1"),
+        ),
+        (
+            "/home/me/Downloads/a.jpg",
+            4_000,
+            400,
+            "image/jpeg",
+            Some("h1"),
+        ),
+        (
+            "/home/me/Downloads/setup.iso",
+            900_000,
+            200,
+            "application/x-iso9660-image",
+            None,
+        ),
+        (
+            "/home/me/Documents/cv.pdf",
+            2_000,
+            2_000,
+            "application/pdf",
+            None,
+        ),
+    ] {
+        sqlx::query("INSERT INTO files(path, size, mtime, ctime, mime, full_hash, status) VALUES (?1, ?2, ?3, 0, ?4, ?5, 'new')")
+            .bind(path)
+            .bind(size)
+            .bind(NOW - age_days * DAY)
+            .bind(mime)
+            .bind(hash)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+    sqlx::query("INSERT INTO tags(name) VALUES ('photos')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO file_tags(file_id, tag_id, confidence) SELECT id, (SELECT id FROM tags WHERE name = 'photos'), 1.0 FROM files WHERE mime = 'image/jpeg'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    // Oldest copy (the download) survives; the two Photos copies get merges.
+    dedupe::plan_duplicate_groups(&pool, &DedupeConfig::default())
+        .await
+        .unwrap();
+
+    let roots = vec!["/home/me".to_string()];
+    let r = report::space_report(&pool, &roots, NOW, 10).await.unwrap();
+    assert_eq!(r.total_files, 5);
+    assert_eq!(r.total_bytes, 914_000);
+    assert_eq!(r.reclaimable_bytes, 8_000);
+
+    assert_eq!(r.duplicate_groups.len(), 1);
+    let g = &r.duplicate_groups[0];
+    assert_eq!(g.copies, 3);
+    assert_eq!(g.reclaimable, 8_000);
+    assert_eq!(g.survivor.as_deref(), Some("/home/me/Downloads/a.jpg"));
+    assert_eq!(g.merges.len(), 2);
+
+    let dir = |key: &str| {
+        r.by_directory
+            .iter()
+            .find(|b| b.key == key)
+            .cloned()
+            .unwrap()
+    };
+    assert_eq!(dir("/home/me/Downloads").bytes, 904_000);
+    assert_eq!(dir("/home/me/Photos").reclaimable, 8_000);
+    assert_eq!(r.by_directory[0].key, "/home/me/Downloads");
+
+    assert_eq!(r.by_tag.len(), 1);
+    assert_eq!((r.by_tag[0].files, r.by_tag[0].reclaimable), (3, 8_000));
+    assert_eq!(r.by_mime[0].key, "application/x-iso9660-image");
+
+    let ages: Vec<u64> = r.by_age.iter().map(|b| b.files).collect();
+    assert_eq!(ages, vec![2, 0, 1, 1, 1]);
+    assert_eq!(
+        (r.stale_downloads.files, r.stale_downloads.bytes),
+        (2, 904_000)
+    );
+    assert_eq!(r.largest_files[0].path, "/home/me/Downloads/setup.iso");
+
+    let limited = report::space_report(&pool, &roots, NOW, 1).await.unwrap();
+    assert_eq!(limited.by_directory.len(), 1);
+    assert_eq!(limited.largest_files.len(), 1);
+    assert_eq!(limited.by_age.len(), 5, "age buckets are fixed");
+}
@@ -0,0 +1,185 @@
+use organizer_core::scanner::{self, HashMode, ScanOptions, SymlinkPolicy};
+use sqlx::SqlitePool;
+use std::fs;
+use std::path::Path;
+
+fn tree(root: &Path, files: &[(&str, &str)]) {
+    for (path, body) in files {
+        let p = root.join(path);
+        fs::create_dir_all(p.parent().unwrap()).unwrap();
+        fs::write(p, body).unwrap();
+    }
+}
+
+/// Scans `root` into a fresh DB and returns the stored file paths relative to it.
+async fn scanned(name: &str, root: &Path, options: &ScanOptions) -> (SqlitePool, Vec<String>) {
+    let pool = storage::connect(&format!("sqlite://file:{}?mode=memory&cache=shared", name))
+        .await
+        .unwrap();
+    storage::migrate(&pool).await.unwrap();
+    scanner::scan_with_options(
+        std::slice::from_ref(&root.to_path_buf()),
+        &[],
+        &HashMode::Fast,
+        options,
+        &pool,
+    )
+    .await
+    .unwrap();
+    let paths: Vec<String> = sqlx::query_scalar("SELECT path FROM files ORDER BY path")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    let rel = paths
+        .iter()
+        .map(|p| {
+            Path::new(p)
+                .strip_prefix(root)
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    (pool, rel)
+}
+
+#[tokio::test]
+async fn ignore_files_follow_gitignore_rules() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path().join("root");
+    tree(
+        &root,
+        &[
+            (
+                ".organizerignore",
+                "# logs\n*.log\n!keep.log\nbuild/\n/top.txt\n",
+            ),
+            (".gitignore", "*.tmp\n"),
+            ("sub/.organizerignore", "!debug.log\n"),
+            ("a.log", "x"),
+            ("keep.log", "x"),
+            ("top.txt", "x"),
+            ("notes.txt", "x"),
+            ("x.tmp", "x"),
+            ("build/out.txt", "x"),
+            ("sub/top.txt", "x"),
+            ("sub/debug.log", "x"),
+            ("sub/other.log", "x"),
+            ("sub/build", "a file, not a folder"),
+        ],
+    );
+
+    let (_, files) = scanned("scan_filters_ignore", &root, &ScanOptions::default()).await;
+    assert_eq!(
+        files,
+        vec![
+            "keep.log",
+            "notes.txt",
+            "sub/build",
+            "sub/debug.log",
+            "sub/top.txt",
+            "x.tmp"
+        ]
+    );
+
+    let options = ScanOptions {
+        respect_gitignore: true,
+        ..ScanOptions::default()
+    };
+    let (_, files) = scanned("scan_filters_gitignore", &root, &options).await;
+    assert!(!files.contains(&"x.tmp".to_string()));
+    assert!(files.contains(&"keep.log".to_string()));
+}
+
+#[tokio::test]
+async fn hidden_depth_and_size_limits() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path().join("root");
+    tree(
+        &root,
+        &[
+            (".hidden/h.txt", "hidden"),
+            ("d1/d2/deep.txt", "deep"),
+            ("d1/mid.txt", "mid"),
+            ("tiny.txt", "1"),
+            ("big.bin", &"b".repeat(100)),
+        ],
+    );
+
+    let options = ScanOptions {
+        include_hidden: true,
+        max_depth: Some(2),
+        min_size: Some(2),
+        max_size: Some(50),
+        ..ScanOptions::default()
+    };
+    let (pool, files) = scanned("scan_filters_limits", &root, &options).await;
+    assert_eq!(files, vec![".hidden/h.txt", "d1/mid.txt"]);
+    let dirs: Vec<String> = sqlx::query_scalar("SELECT path FROM directories ORDER BY path")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        dirs,
+        vec![
+            root.join(".hidden").to_string_lossy().into_owned(),
+            root.join("d1").to_string_lossy().into_owned(),
+            root.join("d1/d2").to_string_lossy().into_owned(),
+        ],
+        "folders at the last level are recorded but not read"
+    );
+
+    let (_, files) = scanned("scan_filters_defaults", &root, &ScanOptions::default()).await;
+    assert_eq!(
+        files,
+        vec!["big.bin", "d1/d2/deep.txt", "d1/mid.txt", "tiny.txt"]
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn symlink_policies() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path().join("root");
+    let outside = temp.path().join("outside");
+    tree(&outside, &[("f.txt", "linked")]);
+    tree(&root, &[("own.txt", "own")]);
+    std::os::unix::fs::symlink(&outside, root.join("dir_link")).unwrap();
+    std::os::unix::fs::symlink(outside.join("f.txt"), root.join("file_link.txt")).unwrap();
+    std::os::unix::fs::symlink(&root, root.join("loop")).unwrap();
+
+    let policy = |symlinks| ScanOptions {
+        symlinks,
+        ..ScanOptions::default()
+    };
+    let (_, files) = scanned("scan_filters_follow", &root, &policy(SymlinkPolicy::Follow)).await;
+    assert_eq!(
+        files,
+        vec!["dir_link/f.txt", "file_link.txt", "own.txt"],
+        "the loop is read once"
+    );
+
+    let (_, files) = scanned("scan_filters_skip", &root, &policy(SymlinkPolicy::Skip)).await;
+    assert_eq!(files, vec!["own.txt"]);
+
+    let (pool, files) = scanned("scan_filters_record", &root, &policy(SymlinkPolicy::Record)).await;
+    assert_eq!(files, vec!["file_link.txt", "own.txt"]);
+    let link_hash: Option<String> =
+        sqlx::query_scalar("SELECT fast_hash FROM files WHERE path = ?")
+            .bind(root.join("file_link.txt").to_string_lossy())
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(link_hash, None);
+    let dirs: Vec<String> = sqlx::query_scalar("SELECT path FROM directories ORDER BY path")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        dirs,
+        vec![
+            root.join("dir_link").to_string_lossy().into_owned(),
+            root.join("loop").to_string_lossy().into_owned(),
+        ]
+    );
+}
@@ -0,0 +1,211 @@
+use organizer_core::scanner::{self, HashMode, MissingPolicy, ScanOptions, ScanReport};
+use sqlx::SqlitePool;
+use std::fs;
+use std::path::Path;
+
+async fn rescan(
+    pool: &SqlitePool,
+    root: &Path,
+    missing: MissingPolicy,
+    hash: HashMode,
+) -> ScanReport {
+    let options = ScanOptions {
+        missing,
+        ..ScanOptions::default()
+    };
+    scanner::scan_with_options(
+        std::slice::from_ref(&root.to_path_buf()),
+        &[],
+        &hash,
+        &options,
+        pool,
+    )
+    .await
+    .unwrap()
+}
+
+fn s(p: &Path) -> String {
+    p.to_string_lossy().into_owned()
+}
+
+#[tokio::test]
+async fn moved_files_keep_their_row_and_deleted_ones_are_purged() {
+    let temp = tempfile::tempdir().unwrap();
+    let pool = storage::connect("sqlite://file:scan_missing_purge?mode=memory&cache=shared")
+        .await
+        .unwrap();
+    storage::migrate(&pool).await.unwrap();
+    let root = temp.path().join("root");
+    fs::create_dir_all(root.join("sub")).unwrap();
+    fs::write(root.join("a.txt"), "alpha").unwrap();
+    fs::write(root.join("b.txt"), "beta").unwrap();
+    fs::write(root.join("sub/c.txt"), "gamma").unwrap();
+    fs::write(root.join("x.txt"), "kept").unwrap();
+    // Moves are told apart by full hash; later scans only hash the first block.
+    let first = rescan(&pool, &root, MissingPolicy::Purge, HashMode::Full).await;
+    assert_eq!(
+        (first.files, first.moved.len(), first.missing.len()),
+        (4, 0, 0)
+    );
+
+    let a_id: i64 = sqlx::query_scalar("SELECT id FROM files WHERE path = ?")
+        .bind(s(&root.join("a.txt")))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO tags (name) VALUES ('invoice')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO file_tags (file_id, tag_id, confidence) VALUES (?, 1, 1.0)")
+        .bind(a_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("DELETE FROM dirty")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    fs::create_dir_all(root.join("moved")).unwrap();
+    fs::rename(root.join("a.txt"), root.join("moved/a.txt")).unwrap();
+    fs::remove_file(root.join("b.txt")).unwrap();
+    fs::remove_dir_all(root.join("sub")).unwrap();
+    let report = rescan(&pool, &root, MissingPolicy::Purge, HashMode::Fast).await;
+
+    assert_eq!(report.files, 2);
+    assert_eq!(
+        report.moved,
+        vec![(s(&root.join("a.txt")), s(&root.join("moved/a.txt")))]
+    );
+    assert_eq!(
+        report.missing,
+        vec![s(&root.join("b.txt")), s(&root.join("sub/c.txt"))]
+    );
+
+    let (id, tag): (i64, String) = sqlx::query_as(
+        "SELECT f.id, t.name FROM files f JOIN file_tags ft ON ft.file_id = f.id JOIN tags t ON t.id = ft.tag_id
+         WHERE f.path = ?",
+    )
+    .bind(s(&root.join("moved/a.txt")))
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        (id, tag.as_str()),
+        (a_id, "invoice"),
+        "the row and its tags followed the file"
+    );
+    let paths: Vec<String> = sqlx::query_scalar("SELECT path FROM files ORDER BY path")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        paths,
+        vec![s(&root.join("moved/a.txt")), s(&root.join("x.txt"))]
+    );
+    let dirs: Vec<String> = sqlx::query_scalar("SELECT path FROM directories ORDER BY path")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(dirs, vec![s(&root.join("moved"))]);
+    let moved_dirty: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM dirty WHERE reason = 'moved'")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        moved_dirty, 2,
+        "both paths are queued for the keyword index and vector payloads"
+    );
+}
+
+#[tokio::test]
+async fn tombstones_come_back_and_empty_roots_are_left_alone() {
+    let temp = tempfile::tempdir().unwrap();
+    let pool = storage::connect("sqlite://file:scan_missing_tombstone?mode=memory&cache=shared")
+        .await
+        .unwrap();
+    storage::migrate(&pool).await.unwrap();
+    let root = temp.path().join("root");
+    fs::create_dir_all(&root).unwrap();
+    let gone = root.join("gone.txt");
+    fs::write(&gone, "bye").unwrap();
+    fs::write(root.join("stays.txt"), "hi").unwrap();
+    rescan(&pool, &root, MissingPolicy::Tombstone, HashMode::Fast).await;
+
+    fs::remove_file(&gone).unwrap();
+    let report = rescan(&pool, &root, MissingPolicy::Tombstone, HashMode::Fast).await;
+    assert_eq!(report.missing, vec![s(&gone)]);
+    let status: String = sqlx::query_scalar("SELECT status FROM files WHERE path = ?")
+        .bind(s(&gone))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "missing");
+    let space = cli::report::space_report(&pool, &[s(&root)], chrono::Utc::now().timestamp(), 10)
+        .await
+        .unwrap();
+    assert_eq!(space.total_files, 1, "tombstones take no space");
+    let again = rescan(&pool, &root, MissingPolicy::Tombstone, HashMode::Fast).await;
+    assert!(
+        again.missing.is_empty(),
+        "a tombstone is only reported once"
+    );
+
+    fs::write(&gone, "bye").unwrap();
+    rescan(&pool, &root, MissingPolicy::Tombstone, HashMode::Fast).await;
+    let status: String = sqlx::query_scalar("SELECT status FROM files WHERE path = ?")
+        .bind(s(&gone))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "seen");
+
+    // An empty root looks like an unmounted drive: nothing is purged.
+    fs::remove_file(&gone).unwrap();
+    fs::remove_file(root.join("stays.txt")).unwrap();
+    let report = rescan(&pool, &root, MissingPolicy::Purge, HashMode::Fast).await;
+    assert!(report.missing.is_empty());
+    let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM files")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(rows, 2);
+}
+
+#[tokio::test]
+async fn a_file_sharing_only_the_first_block_is_not_a_move() {
+    let temp = tempfile::tempdir().unwrap();
+    let pool = storage::connect("sqlite://file:scan_missing_full_hash?mode=memory&cache=shared")
+        .await
+        .unwrap();
+    storage::migrate(&pool).await.unwrap();
+    let root = temp.path().join("root");
+    fs::create_dir_all(&root).unwrap();
+    let head = "h".repeat(64 * 1024);
+    fs::write(root.join("log.txt"), format!("{}old", head)).unwrap();
+    fs::write(root.join("fast.txt"), "no full hash on record").unwrap();
+    rescan(&pool, &root, MissingPolicy::Purge, HashMode::Full).await;
+    sqlx::query("UPDATE files SET full_hash = NULL WHERE path = ?")
+        .bind(s(&root.join("fast.txt")))
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    fs::remove_file(root.join("log.txt")).unwrap();
+    fs::write(root.join("other.txt"), format!("{}new", head)).unwrap();
+    fs::rename(root.join("fast.txt"), root.join("renamed.txt")).unwrap();
+    let report = rescan(&pool, &root, MissingPolicy::Purge, HashMode::Fast).await;
+    assert!(report.moved.is_empty(), "{:?}", report.moved);
+    assert_eq!(
+        report.missing,
+        vec![s(&root.join("fast.txt")), s(&root.join("log.txt"))]
+    );
+    let hashed: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM files WHERE path = ? AND full_hash IS NOT NULL")
+            .bind(s(&root.join("other.txt")))
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(hashed, 1, "the candidate was hashed in full to compare");
+}
@@ -0,0 +1,116 @@
+use organizer_core::scanner::{self, HashMode, ScanOptions, ScanProgress};
+use std::fs;
+use std::time::Duration;
+
+#[tokio::test]
+async fn parallel_scan_stores_everything_and_reports_progress() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:scan_progress?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let root = temp.path().join("root");
+    let mut bytes = 0u64;
+    for d in 0..5 {
+        for s in 0..3 {
+            let dir = root.join(format!("d{}/s{}", d, s));
+            fs::create_dir_all(&dir).unwrap();
+            for f in 0..4 {
+                let body = "x".repeat(d * 100 + s * 10 + f);
+                bytes += body.len() as u64;
+                fs::write(dir.join(format!("f{}.txt", f)), body).unwrap();
+            }
+        }
+    }
+    fs::write(root.join(".hidden"), "skip").unwrap();
+    fs::create_dir_all(root.join("cache")).unwrap();
+    fs::write(root.join("cache/skip.bin"), "skip").unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&root, root.join("d0/loop")).unwrap();
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let options = ScanOptions {
+        walk_threads: 4,
+        hash_threads: 3,
+        batch_size: 7,
+        progress: Some(tx),
+        progress_interval: Duration::from_millis(1),
+        ..ScanOptions::default()
+    };
+    let excludes = vec!["**/cache".to_string()];
+    let report = scanner::scan_with_options(
+        std::slice::from_ref(&root),
+        &excludes,
+        &HashMode::Fast,
+        &options,
+        &pool,
+    )
+    .await
+    .unwrap();
+    drop(options);
+    assert_eq!(report.files, 60);
+
+    let (files, hashed): (i64, i64) =
+        sqlx::query_as("SELECT COUNT(*), COUNT(fast_hash) FROM files")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!((files, hashed), (60, 60));
+    let dirs: Vec<String> = sqlx::query_scalar("SELECT path FROM directories ORDER BY path")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(dirs.len(), 20, "{:?}", dirs);
+    assert!(!dirs.contains(&root.to_string_lossy().into_owned()));
+
+    let mut events: Vec<ScanProgress> = Vec::new();
+    while let Some(p) = rx.recv().await {
+        events.push(p);
+    }
+    let last = events.last().unwrap();
+    assert!(last.done && last.walk_done);
+    assert_eq!(
+        (last.files_found, last.files_stored, last.files_hashed),
+        (60, 60, 60)
+    );
+    assert_eq!((last.bytes_found, last.bytes_hashed), (bytes, bytes));
+    assert_eq!(last.dirs_found, 20);
+    assert_eq!(last.eta_secs, Some(0));
+    assert!(events
+        .windows(2)
+        .all(|w| w[0].files_stored <= w[1].files_stored));
+    assert!(events[..events.len() - 1].iter().all(|p| !p.done));
+}
+
+#[test]
+fn progress_line_shows_bar_once_the_total_is_known() {
+    let walking = ScanProgress {
+        files_found: 40,
+        files_stored: 10,
+        files_per_sec: 100.0,
+        ..Default::default()
+    };
+    assert_eq!(
+        cli::progress::render(&walking),
+        "10/40 files, 100 files/s, walking..."
+    );
+
+    let hashing = ScanProgress {
+        files_found: 40,
+        files_stored: 20,
+        files_hashed: 20,
+        bytes_found: 4096,
+        bytes_hashed: 1024,
+        files_per_sec: 20.0,
+        eta_secs: Some(75),
+        walk_done: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        cli::progress::render(&hashing),
+        format!(
+            "[######------------------]  25% 20/40 files, {} hashed, 20 files/s, ETA 1m15s",
+            cli::report::human_bytes(1024)
+        )
+    );
+}
@@ -0,0 +1,292 @@
+use cli::query::SearchQuery;
+use sqlx::{QueryBuilder, Row};
+use storage::{connect, migrate};
+
+#[test]
+fn parses_fielded_query() {
+    let q = SearchQuery::parse(
+        r#"invoice "due date" tag:finance ext:.PDF modified:>2024-01-01 size:>1MB path:/docs -tag:archive -draft"#,
+    )
+    .unwrap();
+    assert_eq!(q.text, vec!["invoice".to_string(), "due date".to_string()]);
+    assert_eq!(q.exclude_text, vec!["draft".to_string()]);
+    assert_eq!(q.tag_groups, vec![vec!["finance".to_string()]]);
+    assert_eq!(q.exclude_tags, vec!["archive".to_string()]);
+    assert_eq!(q.exts, vec!["pdf".to_string()]);
+    assert_eq!(q.path_prefixes, vec!["/docs".to_string()]);
+    // `>` a date means after the whole day.
+    assert_eq!(q.modified_after, Some(1_704_153_600));
+    assert_eq!(q.modified_before, None);
+    assert_eq!(q.size_min, Some(1024 * 1024 + 1));
+    assert_eq!(q.size_max, None);
+
+    let day = SearchQuery::parse("modified:2024-01-01 size:<=2KB").unwrap();
+    assert_eq!(day.modified_after, Some(1_704_067_200));
+    assert_eq!(day.modified_before, Some(1_704_153_599));
+    assert_eq!(day.size_max, Some(2048));
+
+    assert!(SearchQuery::parse("size:>lots").is_err());
+    assert!(SearchQuery::parse("modified:>yesterday").is_err());
+}
+
+#[test]
+fn translates_to_qdrant_and_tantivy() {
+    let q = SearchQuery::parse("invoice tag:finance ext:pdf path:/Docs size:<10KB -mime:image/png")
+        .unwrap();
+    let filter = q.to_qdrant_filter().unwrap();
+    let must = filter["must"].as_array().unwrap();
+    assert!(must
+        .iter()
+        .any(|c| c["key"] == "path_prefixes" && c["match"]["any"][0] == "/docs"));
+    assert!(must
+        .iter()
+        .any(|c| c["key"] == "ext" && c["match"]["any"][0] == "pdf"));
+    assert!(must
+        .iter()
+        .any(|c| c["key"] == "size" && c["range"]["lte"] == 10 * 1024 - 1));
+    assert_eq!(filter["must_not"][0]["key"], "mime");
+
+    let tantivy = q.to_tantivy_query();
+    assert_eq!(
+        tantivy,
+        r#""invoice" +(tags:"finance") +(ext:"pdf") -mime:"image/png" +size:[* TO 10239]"#
+    );
+    // Words that look like query syntax are quoted rather than parsed.
+    let syntax = SearchQuery::parse("error:timeout https://example.com/a?b=(1) -c++").unwrap();
+    assert_eq!(
+        syntax.to_tantivy_query(),
+        r#""error:timeout" "https://example.com/a?b=(1)" -"c++""#
+    );
+    assert!(SearchQuery::default().to_qdrant_filter().is_none());
+}
+
+#[tokio::test]
+async fn sql_filters_match_fields_and_tags() {
+    let db = "sqlite://file:search_query_sql?mode=memory&cache=shared";
+    let pool = connect(db).await.unwrap();
+    migrate(&pool).await.unwrap();
+
+    for (path, size, mtime, ext) in [
+        ("/docs/invoice-jan.pdf", 2_000_000, 1_710_000_000, "PDF"),
+        ("/docs/invoice-old.pdf", 2_000_000, 1_600_000_000, "pdf"),
+        ("/docs/invoice-small.pdf", 10, 1_710_000_000, "pdf"),
+        (
+            "/docs/invoice-archived.pdf",
+            2_000_000,
+            1_710_000_000,
+            "pdf",
+        ),
+        ("/other/invoice.pdf", 2_000_000, 1_710_000_000, "pdf"),
+        ("/docs-2024/invoice.pdf", 2_000_000, 1_710_000_000, "pdf"),
+    ] {
+        sqlx::query("INSERT INTO files(path, size, mtime, ctime, ext, status) VALUES (?1, ?2, ?3, 0, ?4, 'new')")
+            .bind(path)
+            .bind(size)
+            .bind(mtime)
+            .bind(ext)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+    sqlx::query("INSERT INTO tags(name) VALUES ('finance'), ('archive')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO file_tags(file_id, tag_id, confidence) SELECT f.id, t.id, 1.0 FROM files f, tags t WHERE t.name = 'finance'",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO file_tags(file_id, tag_id, confidence) SELECT f.id, t.id, 1.0 FROM files f, tags t WHERE t.name = 'archive' AND f.path LIKE '%archived%'",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let q = SearchQuery::parse(
+        "invoice tag:finance ext:pdf modified:>2024-01-01 size:>1MB path:/docs -tag:archive",
+    )
+    .unwrap();
+    let mut qb = QueryBuilder::new("SELECT files.path FROM files WHERE 1 = 1");
+    q.push_sql_filters(&mut qb, true);
+    let paths: Vec<String> = qb
+        .build()
+        .fetch_all(&pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.get(0))
+        .collect();
+    assert_eq!(paths, vec!["/docs/invoice-jan.pdf".to_string()]);
+
+    // `path:` stops at folder boundaries and treats LIKE wildcards literally.
+    let under = |q: &'static str| {
+        let pool = pool.clone();
+        async move {
+            let mut qb = QueryBuilder::new("SELECT files.path FROM files WHERE 1 = 1");
+            SearchQuery::parse(q)
+                .unwrap()
+                .push_sql_filters(&mut qb, false);
+            let mut paths: Vec<String> = qb
+                .build()
+                .fetch_all(&pool)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|r| r.get(0))
+                .collect();
+            paths.sort();
+            paths
+        }
+    };
+    assert_eq!(
+        under("path:/docs-2024/").await,
+        vec!["/docs-2024/invoice.pdf".to_string()]
+    );
+    assert_eq!(under("path:/docs_2024").await, Vec::<String>::new());
+    assert_eq!(
+        under("path:/other/invoice.pdf").await,
+        vec!["/other/invoice.pdf".to_string()]
+    );
+    assert_eq!(under("path:/docs").await.len(), 4);
+    assert_eq!(
+        under("-path:/docs").await,
+        vec![
+            "/docs-2024/invoice.pdf".to_string(),
+            "/other/invoice.pdf".to_string()
+        ]
+    );
+
+    sqlx::query("UPDATE files SET status = 'missing' WHERE path = '/other/invoice.pdf'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        under("path:/other").await,
+        Vec::<String>::new(),
+        "tombstones never match"
+    );
+}
+
+#[tokio::test]
+async fn fts_rows_follow_chunks_and_tags() {
+    use organizer_core::fts;
+
+    let db = "sqlite://file:search_query_fts?mode=memory&cache=shared";
+    let pool = connect(db).await.unwrap();
+    migrate(&pool).await.unwrap();
+
+    let mut ids = Vec::new();
+    for path in [
+        "/docs/march.txt",
+        "/docs/invoice-april.txt",
+        "/photos/beach.jpg",
+    ] {
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO files(path, size, mtime, ctime, status) VALUES (?1, 1, 0, 0, 'new') RETURNING id",
+        )
+        .bind(path)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        ids.push(id);
+    }
+    for (file_id, start, text) in [
+        (ids[0], 0, "Invoice for consulting services"),
+        (ids[0], 100, "Payment terms: thirty days"),
+        (ids[1], 0, "Draft notes"),
+    ] {
+        sqlx::query("INSERT INTO chunks(file_id, hash, start, end, text_preview) VALUES (?1, ?2, ?3, ?3, ?4)")
+            .bind(file_id)
+            .bind(format!("h{}-{}", file_id, start))
+            .bind(start)
+            .bind(text)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+    sqlx::query("INSERT INTO tags(name) VALUES ('vacation')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO file_tags(file_id, tag_id, confidence) VALUES (?1, (SELECT id FROM tags WHERE name = 'vacation'), 1.0)")
+        .bind(ids[2])
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let mut conn = pool.acquire().await.unwrap();
+    for path in [
+        "/docs/march.txt",
+        "/docs/invoice-april.txt",
+        "/photos/beach.jpg",
+    ] {
+        fts::refresh_path(&mut conn, path).await.unwrap();
+    }
+    let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM search_fts")
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap();
+    assert_eq!(rows, 4, "one row per chunk, one for the chunk-less image");
+    fts::refresh_path(&mut conn, "/docs/march.txt")
+        .await
+        .unwrap();
+    let march: Vec<i64> =
+        sqlx::query_scalar("SELECT rowid FROM search_fts WHERE file_id = ? ORDER BY rowid")
+            .bind(ids[0])
+            .fetch_all(&mut *conn)
+            .await
+            .unwrap();
+    assert_eq!(
+        march,
+        vec![ids[0] << 20, (ids[0] << 20) + 1],
+        "refreshing replaces the file's rowid range"
+    );
+
+    // In-memory pools hold a single connection.
+    drop(conn);
+    let matches = |q: &'static str| fts_paths(&pool, q);
+    // Name match outranks the body mention.
+    assert_eq!(
+        matches("invoice").await,
+        vec![
+            "/docs/invoice-april.txt".to_string(),
+            "/docs/march.txt".to_string()
+        ]
+    );
+    assert_eq!(
+        matches("vacat").await,
+        vec!["/photos/beach.jpg".to_string()]
+    );
+    assert_eq!(
+        matches("\"payment terms\"").await,
+        vec!["/docs/march.txt".to_string()]
+    );
+    assert_eq!(
+        matches("invoice -draft").await,
+        vec!["/docs/march.txt".to_string()]
+    );
+
+    let mut conn = pool.acquire().await.unwrap();
+    fts::remove_path(&mut conn, "/docs/march.txt")
+        .await
+        .unwrap();
+    drop(conn);
+    assert_eq!(
+        matches("invoice").await,
+        vec!["/docs/invoice-april.txt".to_string()]
+    );
+}
+
+async fn fts_paths(pool: &sqlx::SqlitePool, q: &str) -> Vec<String> {
+    let expr = SearchQuery::parse(q).unwrap().to_fts_query().unwrap();
+    sqlx::query_scalar(
+        "SELECT path FROM search_fts WHERE search_fts MATCH ?1 ORDER BY bm25(search_fts, 3.0, 2.0, 1.5, 1.0)",
+    )
+    .bind(expr)
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
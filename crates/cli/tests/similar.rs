@@ -0,0 +1,51 @@
+use organizer_core::search::{aggregate_by_file, average_vector, SimilarMode};
+use providers::qdrant::SearchResult;
+
+fn hit(file_id: i64, chunk_id: i64, score: f32) -> SearchResult {
+    SearchResult {
+        id: serde_json::json!(format!("c{}", chunk_id)),
+        score,
+        payload: Some(serde_json::json!({
+            "file_id": file_id,
+            "chunk_id": chunk_id,
+            "path": format!("/docs/{}.txt", file_id),
+        })),
+    }
+}
+
+#[test]
+fn average_vector_is_normalised_mean() {
+    let avg = average_vector(&[vec![1.0, 0.0], vec![0.0, 1.0]]).unwrap();
+    let expected = std::f32::consts::FRAC_1_SQRT_2;
+    assert!((avg[0] - expected).abs() < 1e-6 && (avg[1] - expected).abs() < 1e-6);
+    assert!(average_vector(&[]).is_none());
+    assert!(average_vector(&[vec![1.0], vec![1.0, 2.0]]).is_none());
+}
+
+#[test]
+fn neighbours_collapse_to_best_chunk_per_file() {
+    let files = aggregate_by_file(vec![
+        hit(2, 20, 0.50),
+        hit(3, 30, 0.70),
+        hit(2, 21, 0.90),
+        hit(3, 31, 0.60),
+        SearchResult {
+            id: serde_json::json!("orphan"),
+            score: 0.99,
+            payload: None,
+        },
+    ]);
+    let summary: Vec<(i64, Option<i64>, f32)> = files
+        .iter()
+        .map(|f| (f.file_id, f.chunk_id, f.score))
+        .collect();
+    assert_eq!(summary, vec![(2, Some(21), 0.90), (3, Some(30), 0.70)]);
+    assert_eq!(files[0].path, "/docs/2.txt");
+}
+
+#[test]
+fn similar_mode_parses_aliases() {
+    assert_eq!(SimilarMode::from("multi"), SimilarMode::MultiQuery);
+    assert_eq!(SimilarMode::from("Multi-Query"), SimilarMode::MultiQuery);
+    assert_eq!(SimilarMode::from("average"), SimilarMode::Average);
+}
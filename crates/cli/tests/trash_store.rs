@@ -0,0 +1,142 @@
+use cli::trash_store::{self, RetentionPolicy};
+use organizer_core::config::SafetyConfig;
+use std::fs;
+use std::path::Path;
+
+const DAY: i64 = 24 * 60 * 60;
+
+fn safety(root: &Path) -> SafetyConfig {
+    SafetyConfig {
+        allow_paths: vec![root.to_string_lossy().into_owned()],
+        trash_dir: Some(root.join("trash").to_string_lossy().into_owned()),
+        immediate_vector_delete: true,
+        ..Default::default()
+    }
+}
+
+async fn plan_move(pool: &sqlx::SqlitePool, from: &Path, to: &Path) {
+    sqlx::query("INSERT INTO files(path, size, mtime, ctime, status) VALUES (?1, 5, 0, 0, 'new')")
+        .bind(from.to_string_lossy())
+        .execute(pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO actions(file_id, kind, payload_json, status) VALUES ((SELECT id FROM files WHERE path = ?1), 'move', ?2, 'planned')")
+        .bind(from.to_string_lossy())
+        .bind(serde_json::json!({ "to": to.to_string_lossy() }).to_string())
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn backups_share_objects_and_are_listed_restored_and_purged() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:trash_store?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let a = temp.path().join("a.txt");
+    let b = temp.path().join("b.txt");
+    let c = temp.path().join("c.txt");
+    fs::write(&a, "same!").unwrap();
+    fs::write(&b, "same!").unwrap();
+    fs::write(&c, "other").unwrap();
+    for p in [&a, &b, &c] {
+        plan_move(
+            &pool,
+            p,
+            &temp.path().join("sorted").join(p.file_name().unwrap()),
+        )
+        .await;
+    }
+    let views =
+        cli::apply::apply_actions(db_url, false, true, None, &safety(temp.path()), "rename")
+            .await
+            .unwrap();
+    assert!(views.iter().all(|v| v.error.is_none()));
+    assert_eq!(
+        views[0].backup, views[1].backup,
+        "identical bytes are stored once"
+    );
+    assert_ne!(views[0].backup, views[2].backup);
+    let objects = fs::read_dir(temp.path().join("trash/objects"))
+        .unwrap()
+        .flat_map(|d| fs::read_dir(d.unwrap().path()).unwrap())
+        .count();
+    assert_eq!(objects, 2);
+
+    let entries = trash_store::list_entries(&pool).await.unwrap();
+    assert_eq!(entries.len(), 3);
+    let entry_a = entries
+        .iter()
+        .find(|e| e.original_path == a.to_string_lossy())
+        .unwrap();
+    assert_eq!(entry_a.shared_with, 1);
+    assert_eq!(entry_a.action_id, Some(views[0].id));
+    assert_eq!(entry_a.hash.len(), 64);
+
+    let restored = trash_store::restore(&pool, entry_a.id, None).await.unwrap();
+    assert_eq!(fs::read_to_string(&restored).unwrap(), "same!");
+    assert!(
+        trash_store::restore(&pool, entry_a.id, None).await.is_err(),
+        "never overwrites"
+    );
+    let elsewhere = temp.path().join("restored/a.txt");
+    trash_store::restore(&pool, entry_a.id, Some(&elsewhere))
+        .await
+        .unwrap();
+    assert!(elsewhere.exists());
+
+    // Age the first two entries; only the shared object's entries go, the object with them.
+    sqlx::query(
+        "UPDATE trash_entries SET created_at = created_at - 40 * 86400 WHERE original_path != ?1",
+    )
+    .bind(c.to_string_lossy())
+    .execute(&pool)
+    .await
+    .unwrap();
+    let now = chrono::Utc::now().timestamp();
+    let by_age = RetentionPolicy {
+        max_age_days: Some(30),
+        ..Default::default()
+    };
+    let preview = trash_store::purge(&pool, &by_age, now, true).await.unwrap();
+    assert_eq!(
+        (preview.entries, preview.objects, preview.bytes_freed),
+        (2, 1, 5)
+    );
+    assert_eq!(
+        preview.backups_cleared, 2,
+        "both moves backed up to the shared object"
+    );
+    assert_eq!(
+        trash_store::list_entries(&pool).await.unwrap().len(),
+        3,
+        "dry run keeps entries"
+    );
+    trash_store::purge(&pool, &by_age, now, false)
+        .await
+        .unwrap();
+    assert!(!Path::new(views[0].backup.as_ref().unwrap()).exists());
+    assert!(Path::new(views[2].backup.as_ref().unwrap()).exists());
+    let backups: Vec<Option<String>> =
+        sqlx::query_scalar("SELECT backup_path FROM actions ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        backups,
+        vec![None, None, views[2].backup.clone()],
+        "undo no longer points at purged objects"
+    );
+
+    let by_size = RetentionPolicy {
+        max_bytes: Some(0),
+        ..Default::default()
+    };
+    let r = trash_store::purge(&pool, &by_size, now + DAY, false)
+        .await
+        .unwrap();
+    assert_eq!((r.entries, r.bytes_freed), (1, 5));
+    assert!(trash_store::list_entries(&pool).await.unwrap().is_empty());
+}
@@ -0,0 +1,223 @@
+use organizer_core::config::SafetyConfig;
+use std::fs;
+use std::path::Path;
+
+fn safety(root: &Path) -> SafetyConfig {
+    SafetyConfig {
+        allow_delete: true,
+        allow_paths: vec![root.to_string_lossy().into_owned()],
+        trash_dir: Some(root.join("trash").to_string_lossy().into_owned()),
+        immediate_vector_delete: true,
+        ..Default::default()
+    }
+}
+
+async fn add_file(pool: &sqlx::SqlitePool, path: &Path) -> i64 {
+    sqlx::query_scalar("INSERT INTO files(path, size, mtime, ctime, mime, status) VALUES (?1, 6, 0, 0, 'text/plain', 'new') RETURNING id")
+        .bind(path.to_string_lossy())
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn plan(
+    pool: &sqlx::SqlitePool,
+    file_id: i64,
+    kind: &str,
+    payload: serde_json::Value,
+) -> i64 {
+    sqlx::query_scalar("INSERT INTO actions(file_id, kind, payload_json, status) VALUES (?1, ?2, ?3, 'planned') RETURNING id")
+        .bind(file_id)
+        .bind(kind)
+        .bind(payload.to_string())
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn tags_of(pool: &sqlx::SqlitePool, path: &Path) -> Vec<String> {
+    sqlx::query_scalar(
+        "SELECT t.name FROM file_tags ft JOIN tags t ON t.id = ft.tag_id
+         WHERE ft.file_id = (SELECT id FROM files WHERE path = ?1) ORDER BY t.name",
+    )
+    .bind(path.to_string_lossy())
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn tag_and_dedupe_undo_remove_only_what_apply_added() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:undo_kinds_tags?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let doc = temp.path().join("doc.txt");
+    fs::write(&doc, "report").unwrap();
+    let file_id = add_file(&pool, &doc).await;
+    // "finance" was already there from the classifier; applying it again must not lose it.
+    sqlx::query("INSERT INTO tags(name) VALUES ('finance')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO file_tags(file_id, tag_id, confidence, source) VALUES (?1, 1, 0.8, 'classifier')")
+        .bind(file_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    plan(
+        &pool,
+        file_id,
+        "tag",
+        serde_json::json!({ "tag": "finance" }),
+    )
+    .await;
+    plan(&pool, file_id, "tag", serde_json::json!({ "tag": "2024" })).await;
+    plan(
+        &pool,
+        file_id,
+        "dedupe",
+        serde_json::json!({ "duplicate_of": "/elsewhere/doc.txt" }),
+    )
+    .await;
+    plan(
+        &pool,
+        file_id,
+        "dedupe",
+        serde_json::json!({ "payload": { "duplicate_of": "/old/doc.txt" } }),
+    )
+    .await;
+
+    cli::apply::apply_actions(db_url, false, true, None, &safety(temp.path()), "rename")
+        .await
+        .unwrap();
+    assert_eq!(
+        tags_of(&pool, &doc).await,
+        vec![
+            "2024",
+            "duplicate_of:/elsewhere/doc.txt",
+            "duplicate_of:/old/doc.txt",
+            "finance"
+        ]
+    );
+
+    cli::undo::undo_actions(db_url, None, None).await.unwrap();
+    assert_eq!(tags_of(&pool, &doc).await, vec!["finance"]);
+    let executed: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM actions WHERE status = 'executed'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(executed, 0);
+    let audits: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM audit WHERE event = 'undo'")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(audits, 4, "one audit entry per undone action");
+}
+
+#[tokio::test]
+async fn merge_undo_restores_file_row_tags_and_action() {
+    let temp = tempfile::tempdir().unwrap();
+    let db_url = "sqlite://file:undo_kinds_merge?mode=memory&cache=shared";
+    let pool = storage::connect(db_url).await.unwrap();
+    storage::migrate(&pool).await.unwrap();
+
+    let survivor = temp.path().join("a.txt");
+    let dup = temp.path().join("copy of a.txt");
+    fs::write(&survivor, "pixels").unwrap();
+    fs::write(&dup, "pixels").unwrap();
+    add_file(&pool, &survivor).await;
+    let dup_id = add_file(&pool, &dup).await;
+    sqlx::query("INSERT INTO tags(name) VALUES ('holiday')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO file_tags(file_id, tag_id, confidence, source) VALUES (?1, 1, 0.7, 'classifier')")
+        .bind(dup_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let action = plan(
+        &pool,
+        dup_id,
+        "merge_duplicate",
+        serde_json::json!({ "duplicate_of": survivor.to_string_lossy() }),
+    )
+    .await;
+
+    let views =
+        cli::apply::apply_actions(db_url, false, true, None, &safety(temp.path()), "rename")
+            .await
+            .unwrap();
+    assert_eq!(views[0].error, None);
+    assert!(!dup.exists());
+    let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM files WHERE id = ?1")
+        .bind(dup_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(rows, 0, "merged duplicate's row is gone");
+    let session = views[0].session_id.unwrap();
+    let detail = cli::sessions::show_session(&pool, session)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        detail.actions.len(),
+        1,
+        "the merge stays listed in its session"
+    );
+    assert_eq!(
+        tags_of(&pool, &survivor).await,
+        vec!["holiday"],
+        "the survivor took over the tags"
+    );
+
+    cli::undo::undo_session(db_url, session, None)
+        .await
+        .unwrap();
+    assert_eq!(fs::read_to_string(&dup).unwrap(), "pixels");
+    let (id, path): (i64, String) = sqlx::query_as("SELECT id, path FROM files WHERE id = ?1")
+        .bind(dup_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!((id, path), (dup_id, dup.to_string_lossy().into_owned()));
+    assert_eq!(tags_of(&pool, &dup).await, vec!["holiday"]);
+    assert!(
+        tags_of(&pool, &survivor).await.is_empty(),
+        "the copied tags came off the survivor"
+    );
+    let (status, file_id): (String, i64) =
+        sqlx::query_as("SELECT status, file_id FROM actions WHERE id = ?1")
+            .bind(action)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!((status.as_str(), file_id), ("planned", dup_id));
+    let undo_audit: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM audit WHERE event = 'undo' AND action_id = ?1")
+            .bind(action)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(undo_audit, 1);
+    let snapshots: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM merged_files")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(snapshots, 0);
+    let entries = cli::trash_store::list_entries(&pool).await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert!(
+        entries[0].restored_at.is_some(),
+        "the trash entry undo restored from is marked restored"
+    );
+    let s = cli::sessions::show_session(&pool, session)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(s.session.status, "undone");
+}
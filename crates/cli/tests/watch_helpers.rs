@@ -35,8 +35,8 @@ async fn keyword_index_docs_include_snippets() {
     assert!(missing.is_empty());
     let combined = docs
         .iter()
-        .filter(|(p, _)| p == "C:/tmp/test.txt")
-        .map(|(_, d)| d.clone())
+        .filter(|d| d.path == "C:/tmp/test.txt")
+        .map(|d| d.content.clone())
         .collect::<Vec<_>>()
         .join(" ");
     assert!(
@@ -44,3 +44,63 @@ async fn keyword_index_docs_include_snippets() {
         "docs should include chunk text"
     );
 }
+
+#[tokio::test]
+async fn keyword_index_docs_split_chunks_and_carry_file_fields() {
+    let db = "sqlite://file:watch_helpers_fields?mode=memory&cache=shared";
+    let pool = connect(db).await.unwrap();
+    migrate(&pool).await.unwrap();
+
+    let path = "/docs/invoices/march.txt";
+    let file_id: i64 = sqlx::query_scalar(
+        "INSERT INTO files(path, size, mtime, ctime, mime, ext, status) VALUES (?1, 1, 0, 0, 'text/plain', 'txt', 'new') RETURNING id",
+    )
+    .bind(path)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    for (hash, start, text) in [
+        ("c1", 0, "first invoice page"),
+        ("c2", 20, "totals and vat"),
+    ] {
+        sqlx::query("INSERT INTO chunks(file_id, hash, start, end, text_preview) VALUES (?1, ?2, ?3, ?3, ?4)")
+            .bind(file_id)
+            .bind(hash)
+            .bind(start)
+            .bind(text)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+    sqlx::query("INSERT INTO tags(name) VALUES ('finance')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO file_tags(file_id, tag_id, confidence) VALUES (?1, (SELECT id FROM tags WHERE name='finance'), 1.0)")
+        .bind(file_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO metadata(file_id, key, value) VALUES (?1, 'author', 'Acme Corp')")
+        .bind(file_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let (docs, missing) = keyword_index_docs_for_paths(db, &[path.to_string()], None)
+        .await
+        .unwrap();
+    assert!(missing.is_empty());
+    assert_eq!(docs.len(), 2, "one document per chunk");
+    assert_eq!(docs[0].content, "first invoice page");
+    assert_eq!(docs[1].content, "totals and vat");
+    for d in &docs {
+        assert_eq!(d.file_id, file_id);
+        assert!(d.chunk_id.is_some());
+        assert_eq!(d.name, "march.txt");
+        assert_eq!(d.ext, "txt");
+        assert_eq!(d.mime, "text/plain");
+        assert_eq!(d.tags, vec!["finance".to_string()]);
+        assert_eq!(d.meta, vec!["Acme Corp".to_string()]);
+    }
+}
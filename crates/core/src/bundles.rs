@@ -0,0 +1,336 @@
+//! Bundles: files that only make sense together and must not be split by per-file moves. Folders
+//! are recognised by a VCS directory, a build manifest or a package extension (`Foo.app`); loose
+//! files by a saved web page's `_files` folder or by sidecars sharing a photo's name.
+
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::{Row, SqlitePool};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+const VCS_MARKERS: [&str; 4] = [".git", ".hg", ".svn", ".bzr"];
+const MANIFESTS: [&str; 10] = [
+    "Cargo.toml",
+    "package.json",
+    "pyproject.toml",
+    "setup.py",
+    "go.mod",
+    "pom.xml",
+    "build.gradle",
+    "CMakeLists.txt",
+    "Gemfile",
+    "composer.json",
+];
+/// Folders that macOS shows as a single file.
+const PACKAGE_EXTS: [&str; 11] = [
+    "app",
+    "bundle",
+    "framework",
+    "plugin",
+    "kext",
+    "photoslibrary",
+    "pages",
+    "numbers",
+    "key",
+    "rtfd",
+    "xcodeproj",
+];
+/// Edit and metadata files written next to a photo or video under the same name.
+const SIDECAR_EXTS: [&str; 6] = ["xmp", "aae", "thm", "pp3", "dop", "on1"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BundleKind {
+    Vcs,
+    Project,
+    Package,
+    WebPage,
+    Sidecar,
+}
+
+impl BundleKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BundleKind::Vcs => "vcs",
+            BundleKind::Project => "project",
+            BundleKind::Package => "package",
+            BundleKind::WebPage => "web_page",
+            BundleKind::Sidecar => "sidecar",
+        }
+    }
+
+    /// Folder bundles only ever move as a whole folder (`move_dir`); loose-file bundles move
+    /// along with their primary file.
+    pub fn is_folder(&self) -> bool {
+        matches!(
+            self,
+            BundleKind::Vcs | BundleKind::Project | BundleKind::Package
+        )
+    }
+}
+
+impl From<&str> for BundleKind {
+    fn from(s: &str) -> Self {
+        match s {
+            "vcs" => BundleKind::Vcs,
+            "project" => BundleKind::Project,
+            "package" => BundleKind::Package,
+            "web_page" => BundleKind::WebPage,
+            _ => BundleKind::Sidecar,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Bundle {
+    pub kind: BundleKind,
+    /// The bundle's folder, or the primary file of a web page or sidecar group.
+    pub root: PathBuf,
+    /// What gave it away: `.git`, `Cargo.toml`, `app`, `page_files`, `IMG_1.xmp`.
+    pub marker: String,
+    pub members: Vec<PathBuf>,
+}
+
+/// Groups `files` into bundles. `dirs` are the scanned folders (never a scan root), checked
+/// outermost first; a folder inside a bundle folder is part of it. Markers the scanner skips,
+/// like `.git`, are looked up on disk.
+pub fn detect(files: &[PathBuf], dirs: &[PathBuf]) -> Vec<Bundle> {
+    let mut dirs: Vec<&PathBuf> = dirs.iter().collect();
+    dirs.sort();
+    let mut folders: Vec<(BundleKind, &Path, String)> = Vec::new();
+    for dir in dirs {
+        if folders.iter().any(|(_, f, _)| dir.starts_with(f)) {
+            continue;
+        }
+        if let Some((kind, marker)) = folder_kind(dir) {
+            folders.push((kind, dir, marker));
+        }
+    }
+
+    let mut bundles: Vec<Bundle> = folders
+        .iter()
+        .map(|(kind, dir, marker)| Bundle {
+            kind: *kind,
+            root: dir.to_path_buf(),
+            marker: marker.clone(),
+            members: Vec::new(),
+        })
+        .collect();
+    let mut loose: Vec<&PathBuf> = Vec::new();
+    for file in files {
+        match bundles.iter_mut().find(|b| file.starts_with(&b.root)) {
+            Some(bundle) => bundle.members.push(file.clone()),
+            None => loose.push(file),
+        }
+    }
+
+    // Saved web pages: `page.html` plus everything in `page_files/`.
+    let mut taken: HashSet<&PathBuf> = HashSet::new();
+    for file in &loose {
+        let ext = extension(file);
+        if ext != "html" && ext != "htm" {
+            continue;
+        }
+        let (Some(parent), Some(stem)) = (file.parent(), file.file_stem()) else {
+            continue;
+        };
+        let assets = parent.join(format!("{}_files", stem.to_string_lossy()));
+        let members: Vec<&PathBuf> = loose
+            .iter()
+            .copied()
+            .filter(|f| f.starts_with(&assets))
+            .collect();
+        if members.is_empty() && !assets.is_dir() {
+            continue;
+        }
+        taken.insert(file);
+        taken.extend(members.iter().copied());
+        bundles.push(Bundle {
+            kind: BundleKind::WebPage,
+            root: (*file).clone(),
+            marker: assets
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            members: std::iter::once(*file).chain(members).cloned().collect(),
+        });
+    }
+
+    // Sidecars: `IMG_1.CR2` with `IMG_1.xmp` or `IMG_1.CR2.xmp`, matched case-insensitively.
+    let mut by_name: BTreeMap<(PathBuf, String), Vec<&PathBuf>> = BTreeMap::new();
+    for file in loose.into_iter().filter(|f| !taken.contains(f)) {
+        if let (Some(parent), Some(key)) = (file.parent(), sidecar_key(file)) {
+            by_name
+                .entry((parent.to_path_buf(), key))
+                .or_default()
+                .push(file);
+        }
+    }
+    for (_, mut group) in by_name {
+        group.sort();
+        let (sidecars, primaries): (Vec<&PathBuf>, Vec<&PathBuf>) =
+            group.iter().partition(|f| is_sidecar(f));
+        let (Some(primary), Some(sidecar)) = (primaries.first(), sidecars.first()) else {
+            continue;
+        };
+        bundles.push(Bundle {
+            kind: BundleKind::Sidecar,
+            root: (*primary).clone(),
+            marker: sidecar
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            members: group.into_iter().cloned().collect(),
+        });
+    }
+    bundles.sort_by(|a, b| a.root.cmp(&b.root));
+    bundles
+}
+
+fn folder_kind(dir: &Path) -> Option<(BundleKind, String)> {
+    let ext = extension(dir);
+    if PACKAGE_EXTS.contains(&ext.as_str()) {
+        return Some((BundleKind::Package, ext));
+    }
+    if let Some(marker) = VCS_MARKERS.iter().find(|m| dir.join(m).exists()) {
+        return Some((BundleKind::Vcs, marker.to_string()));
+    }
+    MANIFESTS
+        .iter()
+        .find(|m| dir.join(m).is_file())
+        .map(|m| (BundleKind::Project, m.to_string()))
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn is_sidecar(path: &Path) -> bool {
+    SIDECAR_EXTS.contains(&extension(path).as_str())
+}
+
+/// The lowercased name a file shares with its sidecars: the stem, with both extensions dropped
+/// from `IMG_1.CR2.xmp`.
+fn sidecar_key(path: &Path) -> Option<String> {
+    let mut stem = PathBuf::from(path.file_stem()?);
+    if is_sidecar(path) && stem.extension().is_some() {
+        stem = PathBuf::from(stem.file_stem()?);
+    }
+    Some(stem.to_string_lossy().to_lowercase())
+}
+
+/// Where `member` goes when the bundle's primary file moves from `primary` to `to`: the same
+/// place relative to it, with the primary's stem swapped for the new one, so `IMG_1.xmp` follows
+/// `IMG_1.CR2` to `2024/trip.CR2` as `2024/trip.xmp` and `page_files/` follows `page.html`. The
+/// stem is matched case-insensitively, as when the bundle was detected, so `img_1.xmp` follows too.
+pub fn companion_destination(primary: &Path, member: &Path, to: &Path) -> Option<PathBuf> {
+    let rel = member.strip_prefix(primary.parent()?).ok()?;
+    let old_stem = primary.file_stem()?.to_str()?;
+    let new_stem = to.file_stem()?.to_str()?;
+    let mut parts = rel.components();
+    let first = parts.next()?.as_os_str().to_str()?;
+    let first = match strip_prefix_ignore_case(first, old_stem) {
+        Some(rest) => format!("{}{}", new_stem, rest),
+        None => first.to_string(),
+    };
+    let dest = to.parent()?.join(first);
+    let rest = parts.as_path();
+    Some(if rest.as_os_str().is_empty() {
+        dest
+    } else {
+        dest.join(rest)
+    })
+}
+
+fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    let mut chars = s.chars();
+    for p in prefix.chars() {
+        if !chars.next()?.to_lowercase().eq(p.to_lowercase()) {
+            return None;
+        }
+    }
+    Some(chars.as_str())
+}
+
+/// Re-detects bundles over the scanned files and folders and replaces the stored ones. Returns
+/// how many bundles were found.
+pub async fn detect_bundles(pool: &SqlitePool) -> Result<usize> {
+    let rows = sqlx::query("SELECT id, path FROM files WHERE status != 'missing'")
+        .fetch_all(pool)
+        .await?;
+    let ids: HashMap<PathBuf, i64> = rows
+        .iter()
+        .map(|r| (PathBuf::from(r.get::<String, _>(1)), r.get(0)))
+        .collect();
+    let files: Vec<PathBuf> = ids.keys().cloned().collect();
+    let dirs: Vec<PathBuf> = sqlx::query_scalar::<_, String>("SELECT path FROM directories")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(PathBuf::from)
+        .collect();
+    let bundles = detect(&files, &dirs);
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM bundles").execute(&mut *tx).await?;
+    for bundle in &bundles {
+        let bundle_id: i64 = sqlx::query_scalar(
+            "INSERT INTO bundles (root, kind, marker) VALUES (?, ?, ?) RETURNING id",
+        )
+        .bind(bundle.root.to_string_lossy())
+        .bind(bundle.kind.as_str())
+        .bind(&bundle.marker)
+        .fetch_one(&mut *tx)
+        .await?;
+        for member in &bundle.members {
+            sqlx::query("INSERT OR REPLACE INTO bundle_members (file_id, bundle_id) VALUES (?, ?)")
+                .bind(ids[member])
+                .bind(bundle_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+    tx.commit().await?;
+    Ok(bundles.len())
+}
+
+/// A file's bundle, as shown next to search results.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BundleInfo {
+    pub kind: BundleKind,
+    pub root: String,
+    pub members: i64,
+}
+
+/// The stored bundle of each of `paths` that belongs to one.
+pub async fn bundles_for_paths(
+    pool: &SqlitePool,
+    paths: &[String],
+) -> Result<HashMap<String, BundleInfo>> {
+    let mut out = HashMap::new();
+    for path in paths {
+        let row = sqlx::query(
+            "SELECT b.kind, b.root, (SELECT COUNT(*) FROM bundle_members c WHERE c.bundle_id = b.id)
+             FROM files f JOIN bundle_members m ON m.file_id = f.id JOIN bundles b ON b.id = m.bundle_id
+             WHERE f.path = ?",
+        )
+        .bind(path)
+        .fetch_optional(pool)
+        .await?;
+        if let Some(row) = row {
+            out.insert(
+                path.clone(),
+                BundleInfo {
+                    kind: BundleKind::from(row.get::<String, _>(0).as_str()),
+                    root: row.get(1),
+                    members: row.get(2),
+                },
+            );
+        }
+    }
+    Ok(out)
+}
@@ -1,3 +1,4 @@
+use crate::fts;
 use anyhow::Result;
 use providers::qdrant::QdrantClient;
 use providers::ProviderRegistry;
@@ -104,6 +105,7 @@ pub async fn run_classifier_for_files(
             .bind(outcome.confidence)
             .execute(&mut *tx)
             .await?;
+            fts::refresh_path(&mut tx, &file.path).await?;
 
             tx.commit().await?;
         }
@@ -182,6 +184,7 @@ pub async fn run_classifier_no_knn_for_files(
             .bind(outcome.confidence)
             .execute(&mut *tx)
             .await?;
+            fts::refresh_path(&mut tx, &file.path).await?;
 
             tx.commit().await?;
         }
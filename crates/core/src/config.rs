@@ -11,13 +11,49 @@ pub struct AppConfig {
     pub rules: RuleConfig,
     #[serde(default)]
     pub parsers: ParserConfig,
+    #[serde(default)]
+    pub dedupe: DedupeConfig,
+    #[serde(default)]
+    pub trash: TrashConfig,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ScanPaths {
     pub include: Vec<String>,
     pub exclude: Vec<String>,
     pub hash_mode: Option<String>,
+    /// Threads reading directories; defaults to the CPU count, at most 8.
+    #[serde(default)]
+    pub walk_threads: Option<usize>,
+    /// Threads hashing files; defaults to the CPU count.
+    #[serde(default)]
+    pub hash_threads: Option<usize>,
+    /// Rows upserted per transaction; defaults to 500.
+    #[serde(default)]
+    pub batch_size: Option<usize>,
+    /// Files gone since the last scan: "purge" (default) or "tombstone".
+    #[serde(default)]
+    pub missing: Option<String>,
+    /// Walk dot-files and dot-directories too.
+    #[serde(default)]
+    pub include_hidden: bool,
+    /// Honour `.gitignore` files as well as `.organizerignore`.
+    #[serde(default)]
+    pub respect_gitignore: bool,
+    /// Symbolic links below a root: "follow" (default), "skip" or "record".
+    #[serde(default)]
+    pub symlinks: Option<String>,
+    /// Deepest level read below a root; its entries are level 1.
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    /// Files smaller or larger than these sizes (bytes) are left out.
+    #[serde(default)]
+    pub min_size: Option<u64>,
+    #[serde(default)]
+    pub max_size: Option<u64>,
+    /// Stay on each root's filesystem.
+    #[serde(default)]
+    pub one_filesystem: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,7 +81,7 @@ pub struct Thresholds {
     pub review: f32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SafetyConfig {
     pub dry_run: bool,
     pub allow_delete: bool,
@@ -59,6 +95,16 @@ pub struct SafetyConfig {
     pub copy_then_delete: bool,
     #[serde(default)]
     pub immediate_vector_delete: bool,
+    /// Compare duplicate files byte by byte, on top of size and full hash, before a merge.
+    #[serde(default)]
+    pub byte_compare_duplicates: bool,
+    /// Send merged-away duplicates to the desktop trash (freedesktop.org spec) instead of
+    /// backing them up into `trash_dir` and deleting them.
+    #[serde(default)]
+    pub use_os_trash: bool,
+    /// After `apply`, remove the scanned directories it left empty, within the allow/deny paths.
+    #[serde(default)]
+    pub cleanup_empty_dirs: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -87,6 +133,68 @@ pub struct ParserConfig {
     pub max_image_bytes: Option<u64>,
 }
 
+/// Near-duplicate detection; scores are 0..1 similarities (MinHash Jaccard estimate for text,
+/// mean aHash/dHash/pHash agreement for images).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupeConfig {
+    #[serde(default = "default_true")]
+    pub near_duplicates: bool,
+    #[serde(default = "default_near_duplicate_threshold")]
+    pub text_threshold: f32,
+    #[serde(default = "default_near_duplicate_threshold")]
+    pub image_threshold: f32,
+    /// Which copy of an exact-duplicate group is kept: oldest, newest, shortest_path,
+    /// preferred_root, most_tags or highest_resolution.
+    #[serde(default = "default_survivor_policy")]
+    pub survivor_policy: String,
+    /// Roots checked in order by the `preferred_root` policy.
+    #[serde(default)]
+    pub preferred_roots: Vec<String>,
+    /// `strategy` for planned merges: trash_duplicate, hardlink or reflink.
+    #[serde(default = "default_merge_strategy")]
+    pub merge_strategy: String,
+}
+
+impl Default for DedupeConfig {
+    fn default() -> Self {
+        Self {
+            near_duplicates: true,
+            text_threshold: default_near_duplicate_threshold(),
+            image_threshold: default_near_duplicate_threshold(),
+            survivor_policy: default_survivor_policy(),
+            preferred_roots: Vec::new(),
+            merge_strategy: default_merge_strategy(),
+        }
+    }
+}
+
+/// Retention of backups in `safety.trash_dir`; unset limits keep backups forever.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrashConfig {
+    /// Backups older than this many days are purged.
+    #[serde(default)]
+    pub retention_days: Option<u64>,
+    /// Oldest backups are purged until the trash holds at most this many bytes.
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_near_duplicate_threshold() -> f32 {
+    0.9
+}
+
+fn default_survivor_policy() -> String {
+    "oldest".to_string()
+}
+
+fn default_merge_strategy() -> String {
+    "trash_duplicate".to_string()
+}
+
 pub fn load(path: Option<&str>) -> anyhow::Result<AppConfig> {
     let mut settings = config::Config::builder();
     if let Some(p) = path {
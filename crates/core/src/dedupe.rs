@@ -0,0 +1,485 @@
+//! Near-duplicate suggestions built from the fingerprints stored by the extractor.
+
+use crate::config::DedupeConfig;
+use crate::fingerprint::{self, Fingerprint, ImageHashes};
+use anyhow::Result;
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use std::collections::{BTreeSet, HashMap};
+
+/// MinHash LSH banding: 16 bands of 4 rows catches pairs above ~0.6 Jaccard with high probability.
+const TEXT_BANDS: usize = 16;
+/// Image candidates share at least one 16-bit pHash band, so pHashes within 3 bits are always
+/// compared; one-byte bands put unrelated images in the same bucket far too often.
+const IMAGE_BANDS: usize = 4;
+
+/// Replaces the stored fingerprint for `file_id`; `None` clears it.
+pub async fn store_fingerprint(
+    conn: &mut SqliteConnection,
+    file_id: i64,
+    fp: Option<&Fingerprint>,
+) -> Result<()> {
+    sqlx::query("DELETE FROM file_fingerprints WHERE file_id = ?")
+        .bind(file_id)
+        .execute(&mut *conn)
+        .await?;
+    match fp {
+        Some(Fingerprint::Text(sig)) => {
+            sqlx::query(
+                "INSERT INTO file_fingerprints (file_id, kind, minhash) VALUES (?, 'text', ?)",
+            )
+            .bind(file_id)
+            .bind(fingerprint::minhash_to_bytes(sig))
+            .execute(&mut *conn)
+            .await?;
+        }
+        Some(Fingerprint::Image(h)) => {
+            sqlx::query(
+                "INSERT INTO file_fingerprints (file_id, kind, ahash, dhash, phash) VALUES (?, 'image', ?, ?, ?)",
+            )
+            .bind(file_id)
+            .bind(h.ahash as i64)
+            .bind(h.dhash as i64)
+            .bind(h.phash as i64)
+            .execute(&mut *conn)
+            .await?;
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+/// A file that nearly duplicates an older one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NearDuplicate {
+    pub file_id: i64,
+    pub duplicate_of: i64,
+    pub duplicate_of_path: String,
+    pub score: f32,
+    pub method: &'static str,
+}
+
+/// Pairs every fingerprinted file with its most similar older file (lower id) at or above the
+/// configured threshold.
+pub async fn find_near_duplicates(
+    pool: &SqlitePool,
+    cfg: &DedupeConfig,
+) -> Result<Vec<NearDuplicate>> {
+    let rows = sqlx::query(
+        "SELECT fp.file_id, f.path, fp.kind, fp.minhash, fp.ahash, fp.dhash, fp.phash
+         FROM file_fingerprints fp JOIN files f ON f.id = fp.file_id
+         ORDER BY fp.file_id",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut paths: HashMap<i64, String> = HashMap::new();
+    let mut texts: Vec<(i64, Vec<u32>)> = Vec::new();
+    let mut images: Vec<(i64, ImageHashes)> = Vec::new();
+    for row in rows {
+        let id: i64 = row.get(0);
+        paths.insert(id, row.get(1));
+        let kind: String = row.get(2);
+        if kind == "image" {
+            let (a, d, p): (Option<i64>, Option<i64>, Option<i64>) =
+                (row.get(4), row.get(5), row.get(6));
+            if let (Some(a), Some(d), Some(p)) = (a, d, p) {
+                images.push((
+                    id,
+                    ImageHashes {
+                        ahash: a as u64,
+                        dhash: d as u64,
+                        phash: p as u64,
+                    },
+                ));
+            }
+        } else if let Some(blob) = row.get::<Option<Vec<u8>>, _>(3) {
+            let sig = fingerprint::minhash_from_bytes(&blob);
+            if sig.len() == fingerprint::MINHASH_PERMUTATIONS {
+                texts.push((id, sig));
+            }
+        }
+    }
+
+    let mut best: HashMap<i64, NearDuplicate> = HashMap::new();
+    let mut consider = |newer: i64, older: i64, score: f32, method: &'static str| {
+        let candidate = NearDuplicate {
+            file_id: newer,
+            duplicate_of: older,
+            duplicate_of_path: paths.get(&older).cloned().unwrap_or_default(),
+            score,
+            method,
+        };
+        match best.get(&newer) {
+            Some(existing) if existing.score >= score => {}
+            _ => {
+                best.insert(newer, candidate);
+            }
+        }
+    };
+
+    let rows_per_band = fingerprint::MINHASH_PERMUTATIONS / TEXT_BANDS;
+    for (i, j) in candidate_pairs(&texts, TEXT_BANDS, |sig, band| {
+        sig[band * rows_per_band..(band + 1) * rows_per_band]
+            .iter()
+            .fold(0u64, |acc, v| acc.rotate_left(32) ^ *v as u64)
+    }) {
+        let score = fingerprint::minhash_similarity(&texts[i].1, &texts[j].1);
+        if score >= cfg.text_threshold {
+            consider(texts[j].0, texts[i].0, score, "minhash");
+        }
+    }
+    for (i, j) in candidate_pairs(&images, IMAGE_BANDS, |h, band| {
+        (h.phash >> (band * 16)) & 0xffff
+    }) {
+        let score = fingerprint::image_similarity(&images[i].1, &images[j].1);
+        if score >= cfg.image_threshold {
+            consider(images[j].0, images[i].0, score, "image_hash");
+        }
+    }
+
+    let mut found: Vec<NearDuplicate> = best.into_values().collect();
+    found.sort_by_key(|d| d.file_id);
+    Ok(found)
+}
+
+/// Plans a `dedupe` action for each near-duplicate that has never had a dedupe action against
+/// the same original, so rejected or undone suggestions are not re-planned, and that is not in
+/// the same exact-duplicate group. Returns the number of actions inserted.
+pub async fn suggest_near_duplicates(pool: &SqlitePool, cfg: &DedupeConfig) -> Result<usize> {
+    if !cfg.near_duplicates {
+        return Ok(0);
+    }
+    let mut inserted = 0;
+    for dup in find_near_duplicates(pool, cfg).await? {
+        // Exact copies are handled by their duplicate group instead.
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM actions WHERE file_id = ?1 AND kind IN ('dedupe', 'merge_duplicate')
+             AND json_extract(payload_json, '$.duplicate_of') = ?2)
+             OR EXISTS(SELECT 1 FROM duplicate_group_members a JOIN duplicate_group_members b
+             ON a.group_id = b.group_id WHERE a.file_id = ?1 AND b.file_id = ?3)",
+        )
+        .bind(dup.file_id)
+        .bind(&dup.duplicate_of_path)
+        .bind(dup.duplicate_of)
+        .fetch_one(pool)
+        .await?;
+        if exists {
+            continue;
+        }
+        let payload = serde_json::json!({
+            "duplicate_of": dup.duplicate_of_path,
+            "score": dup.score,
+            "method": dup.method,
+        });
+        sqlx::query("INSERT INTO actions (file_id, kind, payload_json, status) VALUES (?, 'dedupe', ?, 'planned')")
+            .bind(dup.file_id)
+            .bind(payload.to_string())
+            .execute(pool)
+            .await?;
+        inserted += 1;
+    }
+    Ok(inserted)
+}
+
+/// Index pairs `(i, j)` with `i < j` that share at least one band key.
+fn candidate_pairs<T>(
+    items: &[(i64, T)],
+    bands: usize,
+    key: impl Fn(&T, usize) -> u64,
+) -> BTreeSet<(usize, usize)> {
+    let mut pairs = BTreeSet::new();
+    for band in 0..bands {
+        let mut buckets: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (idx, (_, item)) in items.iter().enumerate() {
+            buckets.entry(key(item, band)).or_default().push(idx);
+        }
+        for members in buckets.values() {
+            for (n, &i) in members.iter().enumerate() {
+                for &j in &members[n + 1..] {
+                    pairs.insert((i, j));
+                }
+            }
+        }
+    }
+    pairs
+}
+
+/// How the kept copy of an exact-duplicate group is chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SurvivorPolicy {
+    Oldest,
+    Newest,
+    ShortestPath,
+    PreferredRoot,
+    MostTags,
+    HighestResolution,
+}
+
+impl SurvivorPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SurvivorPolicy::Oldest => "oldest",
+            SurvivorPolicy::Newest => "newest",
+            SurvivorPolicy::ShortestPath => "shortest_path",
+            SurvivorPolicy::PreferredRoot => "preferred_root",
+            SurvivorPolicy::MostTags => "most_tags",
+            SurvivorPolicy::HighestResolution => "highest_resolution",
+        }
+    }
+}
+
+impl From<&str> for SurvivorPolicy {
+    fn from(s: &str) -> Self {
+        match s.to_lowercase().replace('-', "_").as_str() {
+            "newest" => SurvivorPolicy::Newest,
+            "shortest_path" | "shortest" => SurvivorPolicy::ShortestPath,
+            "preferred_root" | "root" => SurvivorPolicy::PreferredRoot,
+            "most_tags" | "tags" => SurvivorPolicy::MostTags,
+            "highest_resolution" | "resolution" => SurvivorPolicy::HighestResolution,
+            _ => SurvivorPolicy::Oldest,
+        }
+    }
+}
+
+/// A file in an exact-duplicate group, with the attributes survivor policies look at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupMember {
+    pub file_id: i64,
+    pub path: String,
+    pub mtime: i64,
+    /// Tag count, not counting `duplicate_of:` bookkeeping tags.
+    pub tags: i64,
+    /// Width x height from image metadata; 0 when unknown.
+    pub pixels: i64,
+}
+
+/// Index of the member to keep. Ties, and members the policy cannot tell apart, go to the
+/// oldest file and then the lowest id.
+pub fn choose_survivor(
+    members: &[GroupMember],
+    policy: SurvivorPolicy,
+    preferred_roots: &[String],
+) -> Option<usize> {
+    let root_rank = |path: &str| {
+        preferred_roots
+            .iter()
+            .position(|root| std::path::Path::new(path).starts_with(root))
+            .unwrap_or(preferred_roots.len())
+    };
+    members
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| {
+            let primary = match policy {
+                SurvivorPolicy::Oldest => a.mtime.cmp(&b.mtime),
+                SurvivorPolicy::Newest => b.mtime.cmp(&a.mtime),
+                SurvivorPolicy::ShortestPath => a.path.chars().count().cmp(&b.path.chars().count()),
+                SurvivorPolicy::PreferredRoot => root_rank(&a.path).cmp(&root_rank(&b.path)),
+                SurvivorPolicy::MostTags => b.tags.cmp(&a.tags),
+                SurvivorPolicy::HighestResolution => b.pixels.cmp(&a.pixels),
+            };
+            primary
+                .then(a.mtime.cmp(&b.mtime))
+                .then(a.file_id.cmp(&b.file_id))
+        })
+        .map(|(idx, _)| idx)
+}
+
+/// Regroups files sharing a full hash into `duplicate_groups`, picks each group's survivor with
+/// the configured policy and plans one `merge_duplicate` action per other member. Planned merges
+/// that point at a stale survivor, or belong to a group that dissolved, are dropped. Returns the
+/// number of actions inserted.
+pub async fn plan_duplicate_groups(pool: &SqlitePool, cfg: &DedupeConfig) -> Result<usize> {
+    let policy = SurvivorPolicy::from(cfg.survivor_policy.as_str());
+    let mut tx = pool.begin().await?;
+
+    let hashes: Vec<String> = sqlx::query_scalar(
+        "SELECT full_hash FROM files WHERE full_hash IS NOT NULL AND status != 'missing'
+         GROUP BY full_hash HAVING COUNT(*) > 1",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    // Dissolve groups whose hash is no longer shared.
+    let stale: Vec<i64> = sqlx::query_scalar(
+        "SELECT g.id FROM duplicate_groups g
+         WHERE (SELECT COUNT(*) FROM files f WHERE f.full_hash = g.full_hash AND f.status != 'missing') < 2",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for group_id in stale {
+        sqlx::query(
+            "DELETE FROM actions WHERE status = 'planned' AND kind = 'merge_duplicate'
+             AND json_extract(payload_json, '$.group_id') = ?",
+        )
+        .bind(group_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM duplicate_groups WHERE id = ?")
+            .bind(group_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    let mut inserted = 0;
+    for hash in hashes {
+        let rows = sqlx::query(
+            "SELECT f.id, f.path, f.mtime, f.size,
+                (SELECT COUNT(*) FROM file_tags ft JOIN tags t ON t.id = ft.tag_id
+                 WHERE ft.file_id = f.id AND t.name NOT LIKE 'duplicate_of:%'),
+                COALESCE((SELECT CAST(value AS INTEGER) FROM metadata
+                          WHERE file_id = f.id AND source = 'image' AND key = 'width' LIMIT 1), 0)
+                * COALESCE((SELECT CAST(value AS INTEGER) FROM metadata
+                          WHERE file_id = f.id AND source = 'image' AND key = 'height' LIMIT 1), 0)
+             FROM files f WHERE f.full_hash = ? AND f.status != 'missing' ORDER BY f.id",
+        )
+        .bind(&hash)
+        .fetch_all(&mut *tx)
+        .await?;
+        let size: Option<i64> = rows.first().and_then(|r| r.get(3));
+        let members: Vec<GroupMember> = rows
+            .iter()
+            .map(|r| GroupMember {
+                file_id: r.get(0),
+                path: r.get(1),
+                mtime: r.get(2),
+                tags: r.get(4),
+                pixels: r.get(5),
+            })
+            .collect();
+        let Some(idx) = choose_survivor(&members, policy, &cfg.preferred_roots) else {
+            continue;
+        };
+        let survivor = &members[idx];
+
+        let group_id: i64 = sqlx::query_scalar(
+            "INSERT INTO duplicate_groups (full_hash, size, survivor_file_id, policy) VALUES (?, ?, ?, ?)
+             ON CONFLICT(full_hash) DO UPDATE SET size = excluded.size,
+               survivor_file_id = excluded.survivor_file_id, policy = excluded.policy,
+               updated_at = strftime('%s','now')
+             RETURNING id",
+        )
+        .bind(&hash)
+        .bind(size)
+        .bind(survivor.file_id)
+        .bind(policy.as_str())
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM duplicate_group_members WHERE group_id = ?")
+            .bind(group_id)
+            .execute(&mut *tx)
+            .await?;
+        for m in &members {
+            sqlx::query("INSERT INTO duplicate_group_members (group_id, file_id) VALUES (?, ?)")
+                .bind(group_id)
+                .bind(m.file_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        // Planned merges from an earlier survivor choice no longer apply.
+        sqlx::query(
+            "DELETE FROM actions WHERE status = 'planned' AND kind = 'merge_duplicate'
+             AND json_extract(payload_json, '$.group_id') = ?
+             AND (file_id = ? OR json_extract(payload_json, '$.duplicate_of') != ?)",
+        )
+        .bind(group_id)
+        .bind(survivor.file_id)
+        .bind(&survivor.path)
+        .execute(&mut *tx)
+        .await?;
+
+        for m in members.iter().filter(|m| m.file_id != survivor.file_id) {
+            let exists: bool = sqlx::query_scalar(
+                "SELECT EXISTS(SELECT 1 FROM actions WHERE file_id = ? AND kind = 'merge_duplicate'
+                 AND json_extract(payload_json, '$.duplicate_of') = ?)",
+            )
+            .bind(m.file_id)
+            .bind(&survivor.path)
+            .fetch_one(&mut *tx)
+            .await?;
+            if exists {
+                continue;
+            }
+            let payload = serde_json::json!({
+                "duplicate_of": survivor.path,
+                "group_id": group_id,
+                "policy": policy.as_str(),
+                "strategy": cfg.merge_strategy,
+            });
+            sqlx::query(
+                "INSERT INTO actions (file_id, kind, payload_json, status) VALUES (?, 'merge_duplicate', ?, 'planned')",
+            )
+            .bind(m.file_id)
+            .bind(payload.to_string())
+            .execute(&mut *tx)
+            .await?;
+            inserted += 1;
+        }
+    }
+
+    tx.commit().await?;
+    Ok(inserted)
+}
+
+/// An exact-duplicate group as stored, with each member's latest merge action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateGroup {
+    pub id: i64,
+    pub full_hash: String,
+    pub size: Option<i64>,
+    pub policy: String,
+    pub survivor: Option<String>,
+    pub members: Vec<DuplicateGroupMember>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateGroupMember {
+    pub file_id: i64,
+    pub path: String,
+    pub survivor: bool,
+    pub action_id: Option<i64>,
+    pub action_status: Option<String>,
+}
+
+pub async fn list_duplicate_groups(pool: &SqlitePool) -> Result<Vec<DuplicateGroup>> {
+    let rows = sqlx::query(
+        "SELECT g.id, g.full_hash, g.size, g.policy, s.path, f.id, f.path,
+            (SELECT a.id FROM actions a WHERE a.file_id = f.id AND a.kind = 'merge_duplicate'
+             ORDER BY a.id DESC LIMIT 1),
+            (SELECT a.status FROM actions a WHERE a.file_id = f.id AND a.kind = 'merge_duplicate'
+             ORDER BY a.id DESC LIMIT 1)
+         FROM duplicate_groups g
+         JOIN duplicate_group_members m ON m.group_id = g.id
+         JOIN files f ON f.id = m.file_id
+         LEFT JOIN files s ON s.id = g.survivor_file_id
+         ORDER BY g.id, f.id = g.survivor_file_id DESC, f.id",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+    for row in rows {
+        let id: i64 = row.get(0);
+        if groups.last().map(|g| g.id) != Some(id) {
+            groups.push(DuplicateGroup {
+                id,
+                full_hash: row.get(1),
+                size: row.get(2),
+                policy: row.get(3),
+                survivor: row.get(4),
+                members: Vec::new(),
+            });
+        }
+        let group = groups.last_mut().expect("group pushed above");
+        let path: String = row.get(6);
+        group.members.push(DuplicateGroupMember {
+            file_id: row.get(5),
+            survivor: group.survivor.as_deref() == Some(path.as_str()),
+            path,
+            action_id: row.get(7),
+            action_status: row.get(8),
+        });
+    }
+    Ok(groups)
+}
@@ -12,6 +12,7 @@ struct ChunkWithFile {
     pub mime: Option<String>,
     pub ext: Option<String>,
     pub mtime: Option<i64>,
+    pub size: Option<i64>,
 }
 
 #[derive(Debug, Clone)]
@@ -55,8 +56,8 @@ pub async fn run_embedder_for_files(
     file_ids: Option<&[i64]>,
 ) -> anyhow::Result<usize> {
     let mut query = String::from(
-        "SELECT c.id, c.file_id, c.hash, c.start, c.end, c.text_preview, f.path, f.mime, f.ext, f.mtime \
-         FROM chunks c JOIN files f ON f.id = c.file_id",
+        "SELECT c.id, c.file_id, c.hash, c.start, c.end, c.text_preview, f.path, f.mime, f.ext, f.mtime, f.size \
+         FROM chunks c JOIN files f ON f.id = c.file_id WHERE f.status != 'missing'",
     );
     let mut has_filter = false;
     if let Some(ids) = file_ids {
@@ -65,7 +66,7 @@ pub async fn run_embedder_for_files(
                 .take(ids.len())
                 .collect::<Vec<_>>()
                 .join(",");
-            query.push_str(&format!(" WHERE c.file_id IN ({})", placeholders));
+            query.push_str(&format!(" AND c.file_id IN ({})", placeholders));
             has_filter = true;
         }
     }
@@ -94,6 +95,7 @@ pub async fn run_embedder_for_files(
             mime: row.try_get("mime")?,
             ext: row.try_get("ext")?,
             mtime: row.try_get("mtime").ok(),
+            size: row.try_get("size").ok(),
         });
     }
 
@@ -146,11 +148,15 @@ pub async fn run_embedder_for_files(
                     payload.insert("mime".to_string(), serde_json::json!(m));
                 }
                 if let Some(e) = &chunk_with_file.ext {
-                    payload.insert("ext".to_string(), serde_json::json!(e));
+                    // Lowercased so `ext:` filters match regardless of the file's casing.
+                    payload.insert("ext".to_string(), serde_json::json!(e.to_lowercase()));
                 }
                 if let Some(mt) = chunk_with_file.mtime {
                     payload.insert("mtime".to_string(), serde_json::json!(mt));
                 }
+                if let Some(sz) = chunk_with_file.size {
+                    payload.insert("size".to_string(), serde_json::json!(sz));
+                }
                 let prefixes = path_prefixes(&chunk_with_file.path);
                 payload.insert("path_prefixes".to_string(), serde_json::json!(prefixes));
                 QdrantPoint {
@@ -168,6 +174,26 @@ pub async fn run_embedder_for_files(
     Ok(embedded)
 }
 
+/// Points the vectors of files found at a new path, as (from, to), at that path: `path`,
+/// `path_prefixes` and `ext` are rewritten, the vectors and other payload keys stay.
+pub async fn move_payloads(
+    qdrant: &QdrantClient,
+    moved: &[(String, String)],
+) -> anyhow::Result<()> {
+    for (from, to) in moved {
+        let ext = std::path::Path::new(to)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase());
+        qdrant
+            .set_payload(
+                serde_json::json!({ "must": [{ "key": "path", "match": { "value": from } }] }),
+                serde_json::json!({ "path": to, "path_prefixes": path_prefixes(to), "ext": ext }),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
 fn path_prefixes(path: &str) -> Vec<String> {
     let mut prefixes = Vec::new();
     let normalized = path.replace('\\', "/").to_lowercase();
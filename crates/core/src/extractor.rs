@@ -1,4 +1,6 @@
 use crate::config::ParserConfig;
+use crate::fingerprint::{self, Fingerprint};
+use crate::{dedupe, fts};
 use storage::models::File;
 use sqlx::SqlitePool;
 use std::fs;
@@ -38,10 +40,12 @@ pub async fn run_extractor(pool: &SqlitePool, parsers: &ParserConfig) -> anyhow:
 
     for file in dirty_files {
         let path = PathBuf::from(&file.path);
-        // Short-circuit if file hash matches current fast/full hash (from scanner).
+        // Short-circuit if file hash matches current fast/full hash (from scanner). The scanner
+        // stores the hash before anything is extracted, so only trust it once the file has been
+        // extracted; every extraction sets `mime`, text-less files included.
         let stored_full = file.full_hash.as_ref().or(file.hash.as_ref());
         let stored_fast = file.fast_hash.as_ref().or(file.hash.as_ref());
-        if let Some(stored) = stored_full.or(stored_fast) {
+        if let Some(stored) = stored_full.or(stored_fast).filter(|_| file.mime.is_some()) {
             let current = if stored_full.is_some() {
                 full_hash(&path)
             } else {
@@ -173,6 +177,10 @@ pub async fn run_extractor(pool: &SqlitePool, parsers: &ParserConfig) -> anyhow:
         q.push(" WHERE id = ");
         q.push_bind(file.id);
         let _ = q.build().execute(&mut *tx).await?;
+        // Keep the FTS rows in step with the chunks written above.
+        fts::refresh_path(&mut tx, &file.path).await?;
+        let fp = content_fingerprint(&path, extracted.mime.as_deref(), &extracted.chunks, parsers);
+        dedupe::store_fingerprint(&mut tx, file.id, fp.as_ref()).await?;
 
         // Mark clean after processing; downstream embed/classify already skip unchanged chunks.
         if changed || !extracted.chunks.is_empty() {
@@ -188,6 +196,28 @@ pub async fn run_extractor(pool: &SqlitePool, parsers: &ParserConfig) -> anyhow:
     Ok(())
 }
 
+/// Perceptual hashes for images (when image decoding is enabled), MinHash over chunk text otherwise.
+fn content_fingerprint(
+    path: &std::path::Path,
+    mime: Option<&str>,
+    chunks: &[Chunk],
+    parsers: &ParserConfig,
+) -> Option<Fingerprint> {
+    if mime.is_some_and(|m| m.starts_with("image/")) {
+        if !parsers.image_meta {
+            return None;
+        }
+        return fingerprint::image_hashes_from_path(path, parsers.max_image_bytes)
+            .map(Fingerprint::Image);
+    }
+    let text = chunks
+        .iter()
+        .map(|c| c.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    fingerprint::minhash(&text).map(Fingerprint::Text)
+}
+
 pub async fn extract(path: &PathBuf, parsers: &ParserConfig) -> anyhow::Result<ExtractedMetadata> {
     let meta = fs::metadata(&path)?;
     let size = meta.len();
@@ -281,6 +311,7 @@ fn guess_mime(path: &PathBuf) -> Option<String> {
             "png" => "image/png",
             _ => "application/octet-stream",
         })
+        .or(Some("application/octet-stream"))
         .map(|s| s.to_string())
 }
 
@@ -501,6 +532,7 @@ fn ocr_image_text(path: &PathBuf) -> Option<String> {
 
 #[cfg(feature = "image-meta")]
 fn read_image_meta(path: &PathBuf, max_bytes: Option<u64>) -> Option<ImageMeta> {
+    use image::GenericImageView;
     let meta = std::fs::metadata(path).ok()?;
     let cap = max_bytes.unwrap_or(20 * 1024 * 1024);
     if meta.len() > cap {
@@ -0,0 +1,200 @@
+//! Content fingerprints for near-duplicate detection: MinHash signatures over word shingles
+//! for text, and average/difference/perceptual hashes for images.
+
+/// Signature length; the similarity estimate has a standard error of about 1/sqrt(64).
+pub const MINHASH_PERMUTATIONS: usize = 64;
+const SHINGLE_WORDS: usize = 3;
+/// Fewer shingles than this give a Jaccard estimate too noisy to call anything a near-duplicate.
+const MIN_SHINGLES: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fingerprint {
+    Text(Vec<u32>),
+    Image(ImageHashes),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageHashes {
+    pub ahash: u64,
+    pub dhash: u64,
+    pub phash: u64,
+}
+
+/// MinHash signature of the lowercase word 3-shingles of `text`; `None` when the text is too
+/// short to yield `MIN_SHINGLES` of them.
+pub fn minhash(text: &str) -> Option<Vec<u32>> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect();
+    if words.len() < SHINGLE_WORDS + MIN_SHINGLES - 1 {
+        return None;
+    }
+    let mut signature = vec![u32::MAX; MINHASH_PERMUTATIONS];
+    for shingle in words.windows(SHINGLE_WORDS) {
+        let base = hash64(shingle.join(" ").as_bytes());
+        for (i, slot) in signature.iter_mut().enumerate() {
+            let h = (splitmix64(base ^ splitmix64(i as u64 + 1)) >> 32) as u32;
+            if h < *slot {
+                *slot = h;
+            }
+        }
+    }
+    Some(signature)
+}
+
+/// Estimated Jaccard similarity of the shingle sets behind two signatures.
+pub fn minhash_similarity(a: &[u32], b: &[u32]) -> f32 {
+    if a.is_empty() || a.len() != b.len() {
+        return 0.0;
+    }
+    let equal = a.iter().zip(b).filter(|(x, y)| x == y).count();
+    equal as f32 / a.len() as f32
+}
+
+pub fn minhash_to_bytes(signature: &[u32]) -> Vec<u8> {
+    signature.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+pub fn minhash_from_bytes(bytes: &[u8]) -> Vec<u32> {
+    bytes
+        .chunks_exact(4)
+        .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect()
+}
+
+/// Perceptual hashes of a row-major 8-bit grayscale image.
+pub fn image_hashes(luma: &[u8], width: usize, height: usize) -> Option<ImageHashes> {
+    if width == 0 || height == 0 || luma.len() < width * height {
+        return None;
+    }
+    // aHash: 8x8 thumbnail, bit set where brighter than the mean.
+    let small = resample(luma, width, height, 8, 8);
+    let mean = small.iter().sum::<f32>() / small.len() as f32;
+    let ahash = bits(small.iter().map(|&p| p > mean));
+
+    // dHash: 9x8 thumbnail, bit set where a pixel is brighter than its right neighbour.
+    let wide = resample(luma, width, height, 9, 8);
+    let dhash = bits((0..8).flat_map(|y| {
+        let row = &wide[y * 9..y * 9 + 9];
+        (0..8).map(move |x| row[x] > row[x + 1])
+    }));
+
+    // pHash: low 8x8 frequencies of a 32x32 DCT, bit set above the median (DC excluded).
+    let big = resample(luma, width, height, 32, 32);
+    let dct = dct_2d(&big, 32);
+    let low: Vec<f32> = (0..8)
+        .flat_map(|v| (0..8).map(move |u| (u, v)))
+        .map(|(u, v)| dct[v * 32 + u])
+        .collect();
+    let mut sorted: Vec<f32> = low[1..].to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let median = sorted[sorted.len() / 2];
+    let phash = bits(low.iter().map(|&c| c > median));
+
+    Some(ImageHashes {
+        ahash,
+        dhash,
+        phash,
+    })
+}
+
+/// Mean of the three per-hash similarities (1 - Hamming distance / 64).
+pub fn image_similarity(a: &ImageHashes, b: &ImageHashes) -> f32 {
+    (hamming_similarity(a.ahash, b.ahash)
+        + hamming_similarity(a.dhash, b.dhash)
+        + hamming_similarity(a.phash, b.phash))
+        / 3.0
+}
+
+pub fn hamming_similarity(a: u64, b: u64) -> f32 {
+    1.0 - (a ^ b).count_ones() as f32 / 64.0
+}
+
+#[cfg(feature = "image-meta")]
+pub fn image_hashes_from_path(
+    path: &std::path::Path,
+    max_bytes: Option<u64>,
+) -> Option<ImageHashes> {
+    // Same default cap as the image metadata reader.
+    if std::fs::metadata(path).ok()?.len() > max_bytes.unwrap_or(20 * 1024 * 1024) {
+        return None;
+    }
+    let img = image::open(path).ok()?.to_luma8();
+    let (w, h) = img.dimensions();
+    image_hashes(img.as_raw(), w as usize, h as usize)
+}
+
+#[cfg(not(feature = "image-meta"))]
+pub fn image_hashes_from_path(
+    _path: &std::path::Path,
+    _max_bytes: Option<u64>,
+) -> Option<ImageHashes> {
+    None
+}
+
+fn hash64(data: &[u8]) -> u64 {
+    let digest = blake3::hash(data);
+    let mut first = [0u8; 8];
+    first.copy_from_slice(&digest.as_bytes()[..8]);
+    u64::from_le_bytes(first)
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+fn bits(values: impl Iterator<Item = bool>) -> u64 {
+    values
+        .take(64)
+        .enumerate()
+        .fold(0u64, |acc, (i, set)| if set { acc | (1 << i) } else { acc })
+}
+
+/// Box-filter resize to `tw` x `th`.
+fn resample(luma: &[u8], width: usize, height: usize, tw: usize, th: usize) -> Vec<f32> {
+    let mut out = Vec::with_capacity(tw * th);
+    for ty in 0..th {
+        let y0 = ty * height / th;
+        let y1 = ((ty + 1) * height / th).max(y0 + 1).min(height);
+        for tx in 0..tw {
+            let x0 = tx * width / tw;
+            let x1 = ((tx + 1) * width / tw).max(x0 + 1).min(width);
+            let mut sum = 0u64;
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    sum += luma[y * width + x] as u64;
+                }
+            }
+            out.push(sum as f32 / ((y1 - y0) * (x1 - x0)) as f32);
+        }
+    }
+    out
+}
+
+/// Unnormalised 2-D DCT-II of an `n` x `n` block.
+fn dct_2d(block: &[f32], n: usize) -> Vec<f32> {
+    let cos: Vec<f32> = (0..n * n)
+        .map(|i| {
+            let (k, x) = (i / n, i % n);
+            (std::f32::consts::PI / n as f32 * (x as f32 + 0.5) * k as f32).cos()
+        })
+        .collect();
+    let mut rows = vec![0f32; n * n];
+    for y in 0..n {
+        for k in 0..n {
+            rows[y * n + k] = (0..n).map(|x| block[y * n + x] * cos[k * n + x]).sum();
+        }
+    }
+    let mut out = vec![0f32; n * n];
+    for k in 0..n {
+        for x in 0..n {
+            out[k * n + x] = (0..n).map(|y| rows[y * n + x] * cos[k * n + y]).sum();
+        }
+    }
+    out
+}
@@ -0,0 +1,117 @@
+//! SQLite FTS5 keyword index (`search_fts`) over file names, tags, paths and chunk text.
+//!
+//! Rows are replaced per file whenever its chunks or tags change, so keyword search ranks
+//! content with BM25 even when the Tantivy `keyword-index` feature is not compiled in.
+
+use sqlx::{Row, SqliteConnection, SqlitePool};
+
+/// Rows are keyed by rowid: file `id` owns `id << ROWID_BITS` up to the next file's range, so a
+/// file's rows are found (and deleted) through the rowid b-tree instead of a full-table scan on
+/// the unindexed columns. Matches the backfill in migration 003.
+const ROWID_BITS: u32 = 20;
+
+fn rowid_range(file_id: i64) -> (i64, i64) {
+    let first = file_id << ROWID_BITS;
+    (first, first + (1 << ROWID_BITS) - 1)
+}
+
+/// Replaces the rows of `path`: one per chunk, or a single row with empty content when the
+/// file has no extracted text. Tombstoned files just lose their rows.
+pub async fn refresh_path(conn: &mut SqliteConnection, path: &str) -> anyhow::Result<()> {
+    let Some(row) = sqlx::query("SELECT id, status FROM files WHERE path = ?")
+        .bind(path)
+        .fetch_optional(&mut *conn)
+        .await?
+    else {
+        return Ok(());
+    };
+    let file_id: i64 = row.get(0);
+    remove_file(conn, file_id).await?;
+    if row.get::<String, _>(1) == "missing" {
+        return Ok(());
+    }
+    let tags: Vec<String> = sqlx::query_scalar(
+        "SELECT t.name FROM file_tags ft JOIN tags t ON t.id = ft.tag_id WHERE ft.file_id = ?",
+    )
+    .bind(file_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    let tags = tags.join(" ");
+    let name = file_name(path);
+    let chunks =
+        sqlx::query("SELECT id, text_preview FROM chunks WHERE file_id = ? ORDER BY start")
+            .bind(file_id)
+            .fetch_all(&mut *conn)
+            .await?;
+    let rows: Vec<(Option<i64>, String)> = if chunks.is_empty() {
+        vec![(None, String::new())]
+    } else {
+        chunks
+            .iter()
+            .map(|r| {
+                let text: Option<String> = r.try_get(1).ok().flatten();
+                (r.try_get(0).ok(), text.unwrap_or_default())
+            })
+            .collect()
+    };
+    let (first, last) = rowid_range(file_id);
+    for (rowid, (chunk_id, content)) in (first..=last).zip(rows) {
+        sqlx::query(
+            "INSERT INTO search_fts (rowid, name, tags, path, content, file_id, chunk_id) VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(rowid)
+        .bind(name)
+        .bind(&tags)
+        .bind(path)
+        .bind(content)
+        .bind(file_id)
+        .bind(chunk_id)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Removes the rows of the file stored at `path`; call it before the `files` row goes away.
+pub async fn remove_path(conn: &mut SqliteConnection, path: &str) -> anyhow::Result<()> {
+    let file_id = sqlx::query_scalar::<_, i64>("SELECT id FROM files WHERE path = ?")
+        .bind(path)
+        .fetch_optional(&mut *conn)
+        .await?;
+    match file_id {
+        Some(id) => remove_file(conn, id).await,
+        None => Ok(()),
+    }
+}
+
+/// Removes the rows of file `file_id`.
+pub async fn remove_file(conn: &mut SqliteConnection, file_id: i64) -> anyhow::Result<()> {
+    let (first, last) = rowid_range(file_id);
+    sqlx::query("DELETE FROM search_fts WHERE rowid BETWEEN ? AND ?")
+        .bind(first)
+        .bind(last)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Drops every row and re-indexes all files; returns the number of files indexed.
+pub async fn rebuild(pool: &SqlitePool) -> anyhow::Result<usize> {
+    let paths: Vec<String> = sqlx::query_scalar("SELECT path FROM files")
+        .fetch_all(pool)
+        .await?;
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM search_fts")
+        .execute(&mut *tx)
+        .await?;
+    for p in &paths {
+        refresh_path(&mut tx, p).await?;
+    }
+    tx.commit().await?;
+    Ok(paths.len())
+}
+
+/// Last path component for either separator (matches the migration backfill).
+fn file_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
@@ -0,0 +1,134 @@
+//! Gitignore-style ignore files. Each scanned directory may hold a `.organizerignore` (and, when
+//! enabled, a `.gitignore`); its patterns apply to everything below it, a deeper file overrides
+//! a shallower one, and within a file the last matching pattern wins.
+
+use globset::{GlobBuilder, GlobMatcher};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::warn;
+
+pub const IGNORE_FILE: &str = ".organizerignore";
+pub const GITIGNORE_FILE: &str = ".gitignore";
+
+#[derive(Debug, Clone)]
+struct Pattern {
+    matcher: GlobMatcher,
+    /// `!pattern`: re-includes what an earlier pattern ignored.
+    negated: bool,
+    /// `pattern/`: only matches directories.
+    dir_only: bool,
+}
+
+/// The patterns of one ignore file, relative to the directory holding it.
+#[derive(Debug, Clone)]
+pub struct IgnoreFile {
+    base: PathBuf,
+    patterns: Vec<Pattern>,
+}
+
+impl IgnoreFile {
+    /// Parses gitignore syntax: `#` comments, `!` negation, a trailing `/` for directories only,
+    /// and a `/` at the start or in the middle to anchor the pattern at `base` (otherwise it
+    /// matches at any depth). Invalid patterns are skipped with a warning.
+    pub fn parse(base: &Path, text: &str) -> Self {
+        let mut patterns = Vec::new();
+        for line in text.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.strip_prefix('\\').unwrap_or(line)),
+            };
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let glob = match line.strip_prefix('/') {
+                Some(anchored) => anchored.to_string(),
+                None if line.contains('/') => line.to_string(),
+                None => format!("**/{}", line),
+            };
+            match GlobBuilder::new(&glob).literal_separator(true).build() {
+                Ok(g) => patterns.push(Pattern {
+                    matcher: g.compile_matcher(),
+                    negated,
+                    dir_only,
+                }),
+                Err(e) => warn!(
+                    "Skipping ignore pattern {:?} in {}: {}",
+                    line,
+                    base.display(),
+                    e
+                ),
+            }
+        }
+        Self {
+            base: base.to_path_buf(),
+            patterns,
+        }
+    }
+
+    /// `Some(true)` if the last pattern matching `path` ignores it, `Some(false)` if it
+    /// re-includes it, `None` if no pattern matches.
+    fn verdict(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let rel = path.strip_prefix(&self.base).ok()?;
+        self.patterns
+            .iter()
+            .rev()
+            .find(|p| (is_dir || !p.dir_only) && p.matcher.is_match(rel))
+            .map(|p| !p.negated)
+    }
+}
+
+/// The ignore files in effect for a directory: its own, then its ancestors' up to the scan root.
+#[derive(Debug, Default)]
+pub struct IgnoreStack {
+    files: Vec<IgnoreFile>,
+    parent: Option<Arc<IgnoreStack>>,
+}
+
+impl IgnoreStack {
+    /// The stack for `dir`: `parent` plus the ignore files found in `dir`, or just `parent` when
+    /// there are none.
+    pub fn load(
+        dir: &Path,
+        parent: Option<Arc<IgnoreStack>>,
+        gitignore: bool,
+    ) -> Option<Arc<IgnoreStack>> {
+        let names: &[&str] = if gitignore {
+            &[GITIGNORE_FILE, IGNORE_FILE]
+        } else {
+            &[IGNORE_FILE]
+        };
+        let files: Vec<IgnoreFile> = names
+            .iter()
+            .filter_map(|name| fs::read_to_string(dir.join(name)).ok())
+            .map(|text| IgnoreFile::parse(dir, &text))
+            .collect();
+        if files.is_empty() {
+            return parent;
+        }
+        Some(Arc::new(IgnoreStack { files, parent }))
+    }
+
+    /// Whether `path` is ignored. `.organizerignore` is read after `.gitignore` in the same
+    /// directory, so it has the last word.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut stack = Some(self);
+        while let Some(level) = stack {
+            if let Some(ignored) = level
+                .files
+                .iter()
+                .rev()
+                .find_map(|f| f.verdict(path, is_dir))
+            {
+                return ignored;
+            }
+            stack = level.parent.as_deref();
+        }
+        false
+    }
+}
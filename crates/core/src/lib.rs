@@ -1,9 +1,14 @@
 //! Core library: scanning, extraction, embeddings, classification, suggestions.
 
+pub mod bundles;
 pub mod classifier;
 pub mod config;
+pub mod dedupe;
 pub mod embeddings;
 pub mod extractor;
+pub mod fingerprint;
+pub mod fts;
+pub mod ignore;
 pub mod indexer;
 pub mod models;
 pub mod pipeline;
@@ -1,6 +1,6 @@
 use crate::config::AppConfig;
 use crate::{
-    classifier, embeddings, extractor, scanner, suggester,
+    bundles, classifier, dedupe, embeddings, extractor, scanner, suggester,
     vectorstore::{self, AsQdrant, VectorStore},
 };
 use anyhow::Context;
@@ -25,6 +25,10 @@ pub struct PipelineSummary {
     pub discovered: usize,
     pub processed_files: usize,
     pub embedded_chunks: usize,
+    /// Files the scan found at a new path.
+    pub moved_files: usize,
+    /// Files the scan found gone (purged or tombstoned).
+    pub missing_files: usize,
 }
 
 pub async fn run_with_mode(config: AppConfig, mode: PipelineMode) -> anyhow::Result<()> {
@@ -35,6 +39,15 @@ pub async fn run_with_mode(config: AppConfig, mode: PipelineMode) -> anyhow::Res
 pub async fn run_with_mode_summary(
     config: AppConfig,
     mode: PipelineMode,
+) -> anyhow::Result<PipelineSummary> {
+    run_with_progress(config, mode, None).await
+}
+
+/// Like `run_with_mode_summary`, sending the scan phase's progress to `progress`.
+pub async fn run_with_progress(
+    config: AppConfig,
+    mode: PipelineMode,
+    progress: Option<tokio::sync::mpsc::UnboundedSender<scanner::ScanProgress>>,
 ) -> anyhow::Result<PipelineSummary> {
     // Setup DB
     let pool = connect(&config.database.path).await.context("db connect")?;
@@ -52,9 +65,39 @@ pub async fn run_with_mode_summary(
         let roots: Vec<std::path::PathBuf> =
             config.scan.include.iter().map(std::path::PathBuf::from).collect();
         let hash_mode = scanner::HashMode::from(config.scan.hash_mode.as_deref().unwrap_or(""));
-        summary.discovered =
-            scanner::scan(&roots, &config.scan.exclude, &hash_mode, &pool).await? as usize;
-        info!("Scan complete. Discovered {} files.", summary.discovered);
+        let options = scanner::ScanOptions {
+            progress,
+            ..scanner::ScanOptions::from_config(&config.scan)
+        };
+        let report =
+            scanner::scan_with_options(&roots, &config.scan.exclude, &hash_mode, &options, &pool)
+                .await?;
+        summary.discovered = report.files as usize;
+        summary.moved_files = report.moved.len();
+        summary.missing_files = report.missing.len();
+        info!(
+            "Scan complete. Discovered {} files, {} moved, {} missing.",
+            summary.discovered, summary.moved_files, summary.missing_files
+        );
+        // Moved files keep their vectors, re-pointed at the new path.
+        if let (Some(qdrant), false) = (&qdrant_client, report.moved.is_empty()) {
+            if let Err(e) = embeddings::move_payloads(qdrant, &report.moved).await {
+                warn!("Failed to update vector payloads of moved files: {}", e);
+            }
+        }
+        // Purged and tombstoned files no longer have chunks; drop their vectors too.
+        if !report.missing.is_empty() {
+            if let (Some(qdrant), true) = (&qdrant_client, config.safety.immediate_vector_delete) {
+                if let Err(e) = qdrant
+                    .delete_by_filter(serde_json::json!({
+                        "must": [{ "key": "path", "match": { "any": report.missing } }]
+                    }))
+                    .await
+                {
+                    warn!("Failed to delete vectors of missing files: {}", e);
+                }
+            }
+        }
     }
 
     if matches!(
@@ -116,8 +159,15 @@ pub async fn run_with_mode_summary(
                 }
             }
         }
+        let bundles = bundles::detect_bundles(&pool).await?;
+        info!("Detected {} bundles.", bundles);
         suggester::run_suggester(&pool).await?;
-        info!("Suggestion complete.");
+        let merges = dedupe::plan_duplicate_groups(&pool, &config.dedupe).await?;
+        let near = dedupe::suggest_near_duplicates(&pool, &config.dedupe).await?;
+        info!(
+            "Suggestion complete ({} duplicate merges, {} near-duplicate suggestions).",
+            merges, near
+        );
     }
 
     Ok(summary)
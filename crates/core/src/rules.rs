@@ -10,8 +10,18 @@ pub enum Condition {
     Mime { mime: String },
     Extension { ext: String },
     Tag { tag: String },
-    And { all: Vec<Condition> },
-    Or { any: Vec<Condition> },
+    /// Matches any directory; rules only see directories when their condition requires one.
+    Directory,
+    /// A directory with an entry of this name directly inside, e.g. `Cargo.toml` or `.git`.
+    Contains {
+        name: String,
+    },
+    And {
+        all: Vec<Condition>,
+    },
+    Or {
+        any: Vec<Condition>,
+    },
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -37,6 +47,7 @@ pub struct RuleContext<'a> {
     pub mime: Option<&'a str>,
     pub ext: Option<&'a str>,
     pub tags: &'a [String],
+    pub is_dir: bool,
 }
 
 pub fn matches(condition: &Condition, ctx: &RuleContext<'_>) -> bool {
@@ -49,15 +60,29 @@ pub fn matches(condition: &Condition, ctx: &RuleContext<'_>) -> bool {
         Condition::Mime { mime } => ctx.mime.map(|m| m == mime).unwrap_or(false),
         Condition::Extension { ext } => ctx.ext.map(|e| e == ext).unwrap_or(false),
         Condition::Tag { tag } => ctx.tags.iter().any(|t| t == tag),
+        Condition::Directory => ctx.is_dir,
+        Condition::Contains { name } => ctx.is_dir && ctx.path.join(name).exists(),
         Condition::And { all } => all.iter().all(|c| matches(c, ctx)),
         Condition::Or { any } => any.iter().any(|c| matches(c, ctx)),
     }
 }
 
+/// Whether `condition` can only match directories. Directories are evaluated against these rules
+/// alone, so a plain `path_prefix` rule never moves the folders under its prefix.
+pub fn requires_directory(condition: &Condition) -> bool {
+    match condition {
+        Condition::Directory | Condition::Contains { .. } => true,
+        Condition::And { all } => all.iter().any(requires_directory),
+        Condition::Or { any } => !any.is_empty() && any.iter().all(requires_directory),
+        _ => false,
+    }
+}
+
 pub fn evaluate<'a>(rules: &'a [Rule], ctx: &RuleContext<'a>) -> Vec<&'a Rule> {
     let mut matched: Vec<&Rule> = rules
         .iter()
-        .filter(|r| r.enabled && matches(&r.condition, ctx))
+        .filter(|r| r.enabled && (!ctx.is_dir || requires_directory(&r.condition)))
+        .filter(|r| matches(&r.condition, ctx))
         .collect();
     matched.sort_by_key(|r| r.priority);
     matched
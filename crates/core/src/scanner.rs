@@ -1,13 +1,18 @@
 //! Scans filesystem for items, computes metadata and hashes, and stores in the DB.
 
+use crate::ignore::IgnoreStack;
 use anyhow::Context;
 use globset::{Glob, GlobSet, GlobSetBuilder};
-use sqlx::SqlitePool;
+use serde::Serialize;
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tokio::task;
-use walkdir::WalkDir;
 
 #[derive(Debug, Clone, Default)]
 pub enum HashMode {
@@ -37,83 +42,790 @@ pub struct ScannedItem {
     pub hash_kind: HashMode,
 }
 
+/// How a scan spreads its work, and where it reports progress.
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// Threads reading directories.
+    pub walk_threads: usize,
+    /// Threads hashing files.
+    pub hash_threads: usize,
+    /// Rows upserted per transaction.
+    pub batch_size: usize,
+    /// Receives a `ScanProgress` every `progress_interval` and a last one with `done` set.
+    pub progress: Option<mpsc::UnboundedSender<ScanProgress>>,
+    pub progress_interval: Duration,
+    /// What happens to rows whose file is gone and did not turn up elsewhere.
+    pub missing: MissingPolicy,
+    /// Walk dot-files and dot-directories too.
+    pub include_hidden: bool,
+    /// Honour `.gitignore` files as well as `.organizerignore`.
+    pub respect_gitignore: bool,
+    pub symlinks: SymlinkPolicy,
+    /// Deepest level read below a root (its entries are level 1).
+    pub max_depth: Option<usize>,
+    /// Files outside these sizes are left out.
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    /// Stay on each root's filesystem.
+    pub one_filesystem: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        let cpus = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(4);
+        Self {
+            walk_threads: cpus.min(8),
+            hash_threads: cpus,
+            batch_size: 500,
+            progress: None,
+            progress_interval: Duration::from_millis(250),
+            missing: MissingPolicy::default(),
+            include_hidden: false,
+            respect_gitignore: false,
+            symlinks: SymlinkPolicy::default(),
+            max_depth: None,
+            min_size: None,
+            max_size: None,
+            one_filesystem: false,
+        }
+    }
+}
+
+impl ScanOptions {
+    /// Defaults, overridden by the `[scan]` settings.
+    pub fn from_config(scan: &crate::config::ScanPaths) -> Self {
+        let defaults = Self::default();
+        Self {
+            walk_threads: scan.walk_threads.unwrap_or(defaults.walk_threads).max(1),
+            hash_threads: scan.hash_threads.unwrap_or(defaults.hash_threads).max(1),
+            batch_size: scan.batch_size.unwrap_or(defaults.batch_size).max(1),
+            missing: scan
+                .missing
+                .as_deref()
+                .map(MissingPolicy::from)
+                .unwrap_or_default(),
+            include_hidden: scan.include_hidden,
+            respect_gitignore: scan.respect_gitignore,
+            symlinks: scan
+                .symlinks
+                .as_deref()
+                .map(SymlinkPolicy::from)
+                .unwrap_or_default(),
+            max_depth: scan.max_depth,
+            min_size: scan.min_size,
+            max_size: scan.max_size,
+            one_filesystem: scan.one_filesystem,
+            ..defaults
+        }
+    }
+}
+
+/// What the walk does with a symbolic link below a root (roots themselves are always followed).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Walk into linked directories and hash linked files, once each, so cycles end.
+    #[default]
+    Follow,
+    /// Leave links out.
+    Skip,
+    /// Store the link as what it points to, without reading a directory or hashing a file.
+    Record,
+}
+
+impl From<&str> for SymlinkPolicy {
+    fn from(s: &str) -> Self {
+        match s.to_lowercase().as_str() {
+            "skip" => SymlinkPolicy::Skip,
+            "record" => SymlinkPolicy::Record,
+            _ => SymlinkPolicy::Follow,
+        }
+    }
+}
+
+/// Files that vanished from a scanned root without a matching new file elsewhere.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingPolicy {
+    /// Delete the row, with its tags, metadata, chunks and planned actions.
+    #[default]
+    Purge,
+    /// Keep the row with `status = 'missing'`, hidden from suggestions, duplicates and keyword
+    /// search, so its tags come back if the file does.
+    Tombstone,
+}
+
+impl From<&str> for MissingPolicy {
+    fn from(s: &str) -> Self {
+        match s.to_lowercase().as_str() {
+            "tombstone" => MissingPolicy::Tombstone,
+            _ => MissingPolicy::Purge,
+        }
+    }
+}
+
+/// What a scan stored and reconciled.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ScanReport {
+    /// Files seen.
+    pub files: u64,
+    /// Files found at a new path, as (from, to); their rows followed them.
+    pub moved: Vec<(String, String)>,
+    /// Files gone for good: purged or tombstoned, per `MissingPolicy`.
+    pub missing: Vec<String>,
+}
+
+/// A snapshot of a running scan, for progress bars.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ScanProgress {
+    pub files_found: u64,
+    pub dirs_found: u64,
+    /// Size of the files found so far; the total once `walk_done`.
+    pub bytes_found: u64,
+    pub files_hashed: u64,
+    /// Size of the files hashed so far (a fast hash reads only their first 64 KiB).
+    pub bytes_hashed: u64,
+    /// Files written to the DB.
+    pub files_stored: u64,
+    pub elapsed_ms: u64,
+    pub files_per_sec: f64,
+    pub bytes_per_sec: f64,
+    /// Seconds left; only known once the walk has found everything.
+    pub eta_secs: Option<u64>,
+    pub walk_done: bool,
+    pub done: bool,
+}
+
+#[derive(Default)]
+struct Counters {
+    files_found: AtomicU64,
+    dirs_found: AtomicU64,
+    bytes_found: AtomicU64,
+    files_hashed: AtomicU64,
+    bytes_hashed: AtomicU64,
+    walk_done: AtomicBool,
+}
+
+impl Counters {
+    fn snapshot(
+        &self,
+        hashing: bool,
+        files_stored: u64,
+        started: Instant,
+        done: bool,
+    ) -> ScanProgress {
+        let elapsed = started.elapsed();
+        let secs = elapsed.as_secs_f64().max(0.001);
+        let files_found = self.files_found.load(Ordering::Relaxed);
+        let bytes_found = self.bytes_found.load(Ordering::Relaxed);
+        let bytes_hashed = self.bytes_hashed.load(Ordering::Relaxed);
+        let walk_done = self.walk_done.load(Ordering::Relaxed);
+        let files_per_sec = files_stored as f64 / secs;
+        let bytes_per_sec = bytes_hashed as f64 / secs;
+        let eta_secs = if done {
+            Some(0)
+        } else if !walk_done {
+            None
+        } else if hashing && bytes_per_sec > 0.0 {
+            Some((bytes_found.saturating_sub(bytes_hashed) as f64 / bytes_per_sec).ceil() as u64)
+        } else if files_per_sec > 0.0 {
+            Some((files_found.saturating_sub(files_stored) as f64 / files_per_sec).ceil() as u64)
+        } else {
+            None
+        };
+        ScanProgress {
+            files_found,
+            dirs_found: self.dirs_found.load(Ordering::Relaxed),
+            bytes_found,
+            files_hashed: self.files_hashed.load(Ordering::Relaxed),
+            bytes_hashed,
+            files_stored,
+            elapsed_ms: elapsed.as_millis() as u64,
+            files_per_sec,
+            bytes_per_sec,
+            eta_secs,
+            walk_done,
+            done,
+        }
+    }
+}
+
 pub async fn scan(
     roots: &[PathBuf],
     excludes: &[String],
     hash_mode: &HashMode,
     pool: &SqlitePool,
 ) -> anyhow::Result<u64> {
-    let (tx, mut rx) = mpsc::channel(100);
+    let report =
+        scan_with_options(roots, excludes, hash_mode, &ScanOptions::default(), pool).await?;
+    Ok(report.files)
+}
+
+/// Walks `roots` with a pool of directory readers feeding a pool of hashers, and upserts what
+/// they find in transactions of `batch_size` rows. Every row seen gets this run's `last_seen`
+/// stamp; afterwards rows under the roots that were not seen and whose file is gone are
+/// reconciled (see `reconcile_missing`).
+pub async fn scan_with_options(
+    roots: &[PathBuf],
+    excludes: &[String],
+    hash_mode: &HashMode,
+    options: &ScanOptions,
+    pool: &SqlitePool,
+) -> anyhow::Result<ScanReport> {
+    // Strictly newer than any earlier stamp, even for scans within the same second.
+    let stamp: i64 = sqlx::query_scalar(
+        "SELECT MAX(CAST(strftime('%s','now') AS INTEGER), COALESCE((SELECT MAX(last_seen) FROM files), 0) + 1)",
+    )
+    .fetch_one(pool)
+    .await?;
+    let batch_size = options.batch_size.max(1);
+    let (tx, mut rx) = mpsc::channel(batch_size * 2);
     let exclude_set = build_globset(excludes)?;
-    let hash_mode = hash_mode.clone();
-    let roots = roots.to_vec();
-
-    // Walker task
-    let walker_handle = task::spawn_blocking(move || {
-        for root in roots {
-            for entry in WalkDir::new(root)
-                .follow_links(true)
-                .into_iter()
-                .filter_entry(|e| should_descend(e.path(), false, &exclude_set))
-            {
-                let entry = match entry {
-                    Ok(e) => e,
-                    Err(_) => continue,
-                };
-
-                let path = entry.path();
-                if path.is_dir() || is_excluded(path, &exclude_set) || is_hidden(path) {
-                    continue;
+    let hashing = !matches!(hash_mode, HashMode::None);
+    let counters = Arc::new(Counters::default());
+    let started = Instant::now();
+
+    let walker_handle = {
+        let roots = roots.to_vec();
+        let hash_mode = hash_mode.clone();
+        let counters = counters.clone();
+        let options = options.clone();
+        task::spawn_blocking(move || {
+            let walk = Walk {
+                options: &options,
+                excludes: &exclude_set,
+                hash_mode: &hash_mode,
+                counters: &counters,
+                out: tx,
+                queue: WalkQueue::default(),
+                visited: Mutex::new(HashSet::new()),
+            };
+            walk.run(&roots);
+        })
+    };
+
+    let report = |stored: u64, done: bool| {
+        if let Some(progress) = &options.progress {
+            let _ = progress.send(counters.snapshot(hashing, stored, started, done));
+        }
+    };
+    let mut ticker = tokio::time::interval(options.progress_interval);
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut count = 0u64;
+    loop {
+        tokio::select! {
+            item = rx.recv() => match item {
+                Some(item) => {
+                    batch.push(item);
+                    if batch.len() >= batch_size {
+                        count += store_batch(pool, &mut batch, stamp).await?;
+                    }
                 }
+                None => break,
+            },
+            _ = ticker.tick() => report(count, false),
+        }
+    }
+    count += store_batch(pool, &mut batch, stamp).await?;
 
-                let meta = match fs::metadata(path) {
-                    Ok(m) => m,
-                    Err(_) => continue,
-                };
+    walker_handle.await?;
+    let (moved, missing) = reconcile_missing(pool, roots, stamp, options.missing).await?;
+    report(count, true);
+    Ok(ScanReport {
+        files: count,
+        moved,
+        missing,
+    })
+}
 
-    let mtime = meta
-        .modified()
-        .ok()
-        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-        .map(|d| d.as_secs() as i64)
-        .unwrap_or_default();
+/// Upserts `batch` in one transaction, parents before children. Returns how many were files.
+async fn store_batch(
+    pool: &SqlitePool,
+    batch: &mut Vec<ScannedItem>,
+    stamp: i64,
+) -> anyhow::Result<u64> {
+    if batch.is_empty() {
+        return Ok(0);
+    }
+    batch.sort_by(|a, b| a.path.cmp(&b.path));
+    let mut tx = pool.begin().await?;
+    let mut files = 0;
+    for item in batch.drain(..) {
+        if item.is_dir {
+            upsert_dir_in_db(&mut tx, &item, stamp)
+                .await
+                .with_context(|| format!("Failed to upsert directory in DB: {:?}", item.path))?;
+            continue;
+        }
+        upsert_file_in_db(&mut tx, &item, stamp)
+            .await
+            .with_context(|| format!("Failed to upsert file in DB: {:?}", item.path))?;
+        files += 1;
+    }
+    tx.commit().await?;
+    Ok(files)
+}
 
-    let hash = match hash_mode {
-        HashMode::None => None,
-        HashMode::Fast => fast_hash(path).ok(),
-        HashMode::Full => full_hash(path).ok(),
-    };
+/// Rows under `roots` that this run (`stamp`) did not see and whose file no longer exists.
+/// One whose stored full hash matches a file first seen in this run (hashed now if the scan did
+/// not) was moved: its row takes the new path and the new row is dropped, so tags, metadata,
+/// chunks and vectors stay with the file and nothing is extracted again. Without a full hash on
+/// record a gone file cannot be told from a different one, so it counts as missing. The rest are purged or tombstoned. Roots that are missing or
+/// empty (an unmounted drive) are skipped, and so are files that still exist but were not walked
+/// (excluded, hidden, unreadable). Directories that are gone are dropped. Returns the moves and
+/// the missing paths.
+/// A row this run did not see whose file is gone.
+struct Stale {
+    id: i64,
+    path: String,
+    size: i64,
+    fast_hash: Option<String>,
+    full_hash: Option<String>,
+    tombstoned: bool,
+}
+
+/// A row first seen in this run, as a candidate for where a missing file went.
+struct Arrived {
+    id: i64,
+    path: String,
+    size: i64,
+    fast_hash: Option<String>,
+    full_hash: Option<String>,
+    mtime: i64,
+}
+
+async fn reconcile_missing(
+    pool: &SqlitePool,
+    roots: &[PathBuf],
+    stamp: i64,
+    policy: MissingPolicy,
+) -> anyhow::Result<(Vec<(String, String)>, Vec<String>)> {
+    let mut tx = pool.begin().await?;
+    let mut stale: Vec<Stale> = Vec::new();
+    for root in roots {
+        let present = match fs::read_dir(root) {
+            Ok(mut entries) => entries.next().is_some(),
+            Err(_) => root.is_file(),
+        };
+        if !present {
+            continue;
+        }
+        let root = root.to_string_lossy();
+        let rows = sqlx::query(
+            "SELECT id, path, size, fast_hash, full_hash, status = 'missing' FROM files
+             WHERE last_seen < ?2 AND (path = ?1 OR substr(path, 1, length(?1) + 1) = ?1 || '/')
+             ORDER BY path",
+        )
+        .bind(root.as_ref())
+        .bind(stamp)
+        .fetch_all(&mut *tx)
+        .await?;
+        stale.extend(
+            rows.iter()
+                .map(|r| Stale {
+                    id: r.get(0),
+                    path: r.get(1),
+                    size: r.get(2),
+                    fast_hash: r.get(3),
+                    full_hash: r.get(4),
+                    tombstoned: r.get(5),
+                })
+                .filter(|s| !Path::new(&s.path).exists()),
+        );
+        let dirs: Vec<String> = sqlx::query_scalar(
+            "SELECT path FROM directories WHERE last_seen < ?2 AND substr(path, 1, length(?1) + 1) = ?1 || '/'",
+        )
+        .bind(root.as_ref())
+        .bind(stamp)
+        .fetch_all(&mut *tx)
+        .await?;
+        for dir in dirs.iter().filter(|d| !Path::new(d).exists()) {
+            sqlx::query("DELETE FROM directories WHERE path = ?")
+                .bind(dir)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+    if stale.is_empty() {
+        tx.commit().await?;
+        return Ok((Vec::new(), Vec::new()));
+    }
+
+    let mut arrived: Vec<Arrived> = sqlx::query(
+        "SELECT id, path, size, fast_hash, full_hash, mtime FROM files WHERE first_seen = ?",
+    )
+    .bind(stamp)
+    .fetch_all(&mut *tx)
+    .await?
+    .iter()
+    .map(|r| Arrived {
+        id: r.get(0),
+        path: r.get(1),
+        size: r.get(2),
+        fast_hash: r.get(3),
+        full_hash: r.get(4),
+        mtime: r.get(5),
+    })
+    .collect();
 
-                let item = ScannedItem {
-                    path: path.to_path_buf(),
-                    is_dir: meta.is_dir(),
-                    size: meta.len() as i64,
-                    mtime,
-                    hash,
-                    hash_kind: hash_mode.clone(),
-                };
-
-                if tx.blocking_send(item).is_err() {
-                    // Receiver dropped, stop walking.
+    let mut moved = Vec::new();
+    let mut missing = Vec::new();
+    for Stale {
+        id,
+        path,
+        size,
+        fast_hash: fast,
+        full_hash: full,
+        tombstoned,
+    } in stale
+    {
+        let mut found = None;
+        if let Some(full) = &full {
+            // Prefer a file that kept its name: a move rather than a copy that was renamed.
+            let name = Path::new(&path).file_name();
+            let mut candidates: Vec<usize> = (0..arrived.len())
+                .filter(|&i| {
+                    let a = &arrived[i];
+                    a.size == size
+                        && (fast.is_none() || a.fast_hash.is_none() || a.fast_hash == fast)
+                })
+                .collect();
+            candidates.sort_by_key(|&i| Path::new(&arrived[i].path).file_name() != name);
+            for i in candidates {
+                if arrived[i].full_hash.is_none() {
+                    arrived[i].full_hash = full_hash(Path::new(&arrived[i].path)).ok();
+                    sqlx::query("UPDATE files SET full_hash = ?2 WHERE id = ?1")
+                        .bind(arrived[i].id)
+                        .bind(&arrived[i].full_hash)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                if arrived[i].full_hash.as_ref() == Some(full) {
+                    found = Some(i);
                     break;
                 }
             }
         }
-    });
+        if let Some(i) = found {
+            let Arrived {
+                id: new_id,
+                path: new_path,
+                mtime,
+                ..
+            } = arrived.remove(i);
+            crate::fts::remove_file(&mut tx, new_id).await?;
+            sqlx::query("DELETE FROM files WHERE id = ?")
+                .bind(new_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("UPDATE files SET path = ?2, mtime = ?3, last_seen = ?4, status = 'seen' WHERE id = ?1")
+                .bind(id)
+                .bind(&new_path)
+                .bind(mtime)
+                .bind(stamp)
+                .execute(&mut *tx)
+                .await?;
+            crate::fts::refresh_path(&mut tx, &new_path).await?;
+            mark_dirty(&mut tx, &[&path, &new_path], "moved").await?;
+            moved.push((path, new_path));
+            continue;
+        }
+        crate::fts::remove_file(&mut tx, id).await?;
+        match policy {
+            MissingPolicy::Tombstone if tombstoned => continue,
+            MissingPolicy::Tombstone => {
+                sqlx::query("UPDATE files SET status = 'missing' WHERE id = ?")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            MissingPolicy::Purge => {
+                sqlx::query("DELETE FROM files WHERE id = ?")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+        mark_dirty(&mut tx, &[&path], "missing").await?;
+        missing.push(path);
+    }
+    tx.commit().await?;
+    Ok((moved, missing))
+}
 
-    let mut count = 0u64;
-    while let Some(item) = rx.recv().await {
-        upsert_file_in_db(pool, &item)
-            .await
-            .with_context(|| format!("Failed to upsert file in DB: {:?}", item.path))?;
-        count += 1;
+async fn mark_dirty(
+    conn: &mut SqliteConnection,
+    paths: &[&str],
+    reason: &str,
+) -> anyhow::Result<()> {
+    for path in paths {
+        sqlx::query("INSERT OR REPLACE INTO dirty (path, reason) VALUES (?, ?)")
+            .bind(path)
+            .bind(reason)
+            .execute(&mut *conn)
+            .await?;
     }
+    Ok(())
+}
 
-    walker_handle.await?;
-    Ok(count)
+/// A directory waiting to be read, with what its entries inherit.
+struct PendingDir {
+    path: PathBuf,
+    /// Levels below the root; the root is 0.
+    depth: usize,
+    /// The ignore files of its ancestors.
+    ignores: Option<Arc<IgnoreStack>>,
+    /// The root's filesystem, for `one_filesystem`.
+    device: u64,
 }
 
-async fn upsert_file_in_db(pool: &SqlitePool, item: &ScannedItem) -> anyhow::Result<()> {
+/// Directories waiting to be read. The walk is over once none are pending and no reader is busy
+/// (a busy reader may still add more), or when it is stopped.
+#[derive(Default)]
+struct WalkQueue {
+    state: Mutex<QueueState>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct QueueState {
+    pending: Vec<PendingDir>,
+    busy: usize,
+    stopped: bool,
+}
+
+impl WalkQueue {
+    fn push(&self, dir: PendingDir) {
+        self.state.lock().unwrap().pending.push(dir);
+        self.changed.notify_one();
+    }
+
+    fn next(&self) -> Option<PendingDir> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.stopped {
+                return None;
+            }
+            if let Some(dir) = state.pending.pop() {
+                state.busy += 1;
+                return Some(dir);
+            }
+            if state.busy == 0 {
+                return None;
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    fn done(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.busy -= 1;
+        if state.busy == 0 && state.pending.is_empty() {
+            self.changed.notify_all();
+        }
+    }
+
+    fn stop(&self) {
+        self.state.lock().unwrap().stopped = true;
+        self.changed.notify_all();
+    }
+}
+
+struct Walk<'a> {
+    options: &'a ScanOptions,
+    excludes: &'a GlobSet,
+    hash_mode: &'a HashMode,
+    counters: &'a Counters,
+    out: mpsc::Sender<ScannedItem>,
+    queue: WalkQueue,
+    /// Canonical directories already queued, so each is read once and symlink loops end.
+    visited: Mutex<HashSet<PathBuf>>,
+}
+
+impl Walk<'_> {
+    fn run(&self, roots: &[PathBuf]) {
+        let (files_tx, files_rx) = crossbeam_channel::bounded::<(PathBuf, fs::Metadata)>(1024);
+        std::thread::scope(|s| {
+            for _ in 0..self.options.hash_threads.max(1) {
+                let files_rx = files_rx.clone();
+                s.spawn(move || self.hash_files(files_rx));
+            }
+            for root in roots {
+                if !self.visit(root.clone(), None, &files_tx) {
+                    break;
+                }
+            }
+            let walkers: Vec<_> = (0..self.options.walk_threads.max(1))
+                .map(|_| {
+                    let files_tx = files_tx.clone();
+                    s.spawn(move || self.read_dirs(&files_tx))
+                })
+                .collect();
+            drop(files_tx);
+            for walker in walkers {
+                let _ = walker.join();
+            }
+            self.counters.walk_done.store(true, Ordering::Relaxed);
+        });
+    }
+
+    fn read_dirs(&self, files: &crossbeam_channel::Sender<(PathBuf, fs::Metadata)>) {
+        while let Some(mut dir) = self.queue.next() {
+            dir.ignores = IgnoreStack::load(
+                &dir.path,
+                dir.ignores.take(),
+                self.options.respect_gitignore,
+            );
+            let mut open = true;
+            if let Ok(entries) = fs::read_dir(&dir.path) {
+                for entry in entries.flatten() {
+                    if !self.visit(entry.path(), Some(&dir), files) {
+                        open = false;
+                        break;
+                    }
+                }
+            }
+            self.queue.done();
+            if !open {
+                self.queue.stop();
+            }
+        }
+    }
+
+    /// Queues a directory (and records it, unless it is a scan root, which is never organized)
+    /// or hands a file to the hashers, after the excludes, ignore files and `ScanOptions`
+    /// filters. `parent` is `None` for a root. Returns false once the scan has been abandoned.
+    fn visit(
+        &self,
+        path: PathBuf,
+        parent: Option<&PendingDir>,
+        files: &crossbeam_channel::Sender<(PathBuf, fs::Metadata)>,
+    ) -> bool {
+        let options = self.options;
+        if !should_descend(&path, options.include_hidden, self.excludes) {
+            return true;
+        }
+        let Ok(link_meta) = fs::symlink_metadata(&path) else {
+            return true;
+        };
+        let is_link = parent.is_some() && link_meta.file_type().is_symlink();
+        if is_link && options.symlinks == SymlinkPolicy::Skip {
+            return true;
+        }
+        let Ok(meta) = fs::metadata(&path) else {
+            return true;
+        };
+        if let Some(parent) = parent {
+            if options.one_filesystem && device(&meta) != parent.device {
+                return true;
+            }
+            if let Some(ignores) = &parent.ignores {
+                if ignores.is_ignored(&path, meta.is_dir()) {
+                    return true;
+                }
+            }
+        }
+        let recorded_link = is_link && options.symlinks == SymlinkPolicy::Record;
+
+        if !meta.is_dir() {
+            if options.min_size.is_some_and(|min| meta.len() < min)
+                || options.max_size.is_some_and(|max| meta.len() > max)
+            {
+                return true;
+            }
+            self.counters.files_found.fetch_add(1, Ordering::Relaxed);
+            if recorded_link {
+                return self.send(&path, &meta, None);
+            }
+            self.counters
+                .bytes_found
+                .fetch_add(meta.len(), Ordering::Relaxed);
+            return files.send((path, meta)).is_ok();
+        }
+        if !recorded_link {
+            let key = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+            if !self.visited.lock().unwrap().insert(key) {
+                return true;
+            }
+        }
+        if parent.is_some() {
+            self.counters.dirs_found.fetch_add(1, Ordering::Relaxed);
+            if !self.send(&path, &meta, None) {
+                return false;
+            }
+        }
+        let depth = parent.map_or(0, |p| p.depth + 1);
+        if recorded_link || options.max_depth.is_some_and(|max| depth >= max) {
+            return true;
+        }
+        self.queue.push(PendingDir {
+            path,
+            depth,
+            ignores: parent.and_then(|p| p.ignores.clone()),
+            device: parent.map_or_else(|| device(&meta), |p| p.device),
+        });
+        true
+    }
+
+    /// Sends a directory or an unhashed file to the DB side.
+    fn send(&self, path: &Path, meta: &fs::Metadata, hash: Option<String>) -> bool {
+        let item = ScannedItem {
+            path: path.to_path_buf(),
+            is_dir: meta.is_dir(),
+            size: meta.len() as i64,
+            mtime: mtime(meta),
+            hash,
+            hash_kind: self.hash_mode.clone(),
+        };
+        self.out.blocking_send(item).is_ok()
+    }
+
+    fn hash_files(&self, files: crossbeam_channel::Receiver<(PathBuf, fs::Metadata)>) {
+        for (path, meta) in files {
+            let hash = match self.hash_mode {
+                HashMode::None => None,
+                HashMode::Fast => fast_hash(&path).ok(),
+                HashMode::Full => full_hash(&path).ok(),
+            };
+            if hash.is_some() {
+                self.counters.files_hashed.fetch_add(1, Ordering::Relaxed);
+                self.counters
+                    .bytes_hashed
+                    .fetch_add(meta.len(), Ordering::Relaxed);
+            }
+            if !self.send(&path, &meta, hash) {
+                // Receiver dropped, stop walking.
+                self.queue.stop();
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn device(meta: &fs::Metadata) -> u64 {
+    std::os::unix::fs::MetadataExt::dev(meta)
+}
+
+#[cfg(not(unix))]
+fn device(_meta: &fs::Metadata) -> u64 {
+    0
+}
+
+/// Modification time in whole seconds, as stored in `files.mtime`.
+pub fn mtime(meta: &fs::Metadata) -> i64 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+async fn upsert_file_in_db(
+    conn: &mut SqliteConnection,
+    item: &ScannedItem,
+    stamp: i64,
+) -> anyhow::Result<()> {
     let path_str = item.path.to_string_lossy().to_string();
     let ext = item
         .path
@@ -125,15 +837,15 @@ async fn upsert_file_in_db(pool: &SqlitePool, item: &ScannedItem) -> anyhow::Res
 
     let res = sqlx::query(
         r#"
-        INSERT INTO files (path, size, mtime, ctime, hash, fast_hash, full_hash, ext, last_seen)
-        VALUES (?, ?, ?, ?, ?, ?, ?, ?, strftime('%s','now'))
+        INSERT INTO files (path, size, mtime, ctime, hash, fast_hash, full_hash, ext, first_seen, last_seen)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?9, ?9)
         ON CONFLICT(path) DO UPDATE SET
             size = excluded.size,
             mtime = excluded.mtime,
             hash = excluded.hash,
             fast_hash = COALESCE(excluded.fast_hash, files.fast_hash),
             full_hash = COALESCE(excluded.full_hash, files.full_hash),
-            last_seen = strftime('%s','now'),
+            last_seen = excluded.last_seen,
             status = 'seen'
         WHERE
             files.size != excluded.size OR
@@ -155,19 +867,50 @@ async fn upsert_file_in_db(pool: &SqlitePool, item: &ScannedItem) -> anyhow::Res
         _ => None,
     })
     .bind(&ext)
-    .execute(pool)
+    .bind(stamp)
+    .execute(&mut *conn)
     .await?;
 
     if res.rows_affected() > 0 {
         sqlx::query("INSERT OR REPLACE INTO dirty (path, reason) VALUES (?, 'rescan')")
             .bind(&path_str)
-            .execute(pool)
+            .execute(&mut *conn)
+            .await?;
+    } else {
+        // Unchanged: only mark it seen in this run (and back from a tombstone).
+        sqlx::query("UPDATE files SET last_seen = ?2, status = CASE WHEN status = 'missing' THEN 'seen' ELSE status END WHERE path = ?1")
+            .bind(&path_str)
+            .bind(stamp)
+            .execute(&mut *conn)
             .await?;
     }
 
     Ok(())
 }
 
+/// Records a directory so folder rules and `move_dir` actions can refer to it.
+async fn upsert_dir_in_db(
+    conn: &mut SqliteConnection,
+    item: &ScannedItem,
+    stamp: i64,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO directories (path, mtime, last_seen)
+        VALUES (?, ?, ?)
+        ON CONFLICT(path) DO UPDATE SET
+            mtime = excluded.mtime,
+            last_seen = excluded.last_seen;
+        "#,
+    )
+    .bind(item.path.to_string_lossy().to_string())
+    .bind(item.mtime)
+    .bind(stamp)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
 fn build_globset(patterns: &[String]) -> anyhow::Result<GlobSet> {
     let mut builder = GlobSetBuilder::new();
     for pat in patterns {
@@ -198,7 +941,8 @@ fn is_excluded(path: &Path, excludes: &GlobSet) -> bool {
     excludes.is_match(path)
 }
 
-fn fast_hash(path: &Path) -> anyhow::Result<String> {
+/// blake3 of the first 64 KiB, as stored in `files.fast_hash`.
+pub fn fast_hash(path: &Path) -> anyhow::Result<String> {
     use std::io::Read;
     const BYTES: usize = 64 * 1024;
     let mut file = fs::File::open(path)?;
@@ -1,4 +1,8 @@
 use providers::qdrant::{QdrantClient, SearchResult};
+use std::collections::HashMap;
+
+/// Chunk vectors searched per file in multi-query mode; long files are sampled evenly.
+const MAX_QUERY_CHUNKS: usize = 16;
 
 pub async fn vector_search(
     client: &QdrantClient,
@@ -9,3 +13,141 @@ pub async fn vector_search(
     let resp = client.search(vector, limit, filter).await?;
     Ok(resp.result)
 }
+
+/// How a file's chunk vectors are turned into neighbour queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimilarMode {
+    /// One query with the normalised mean of all chunk vectors.
+    Average,
+    /// One query per chunk; a neighbour file keeps its best chunk score.
+    MultiQuery,
+}
+
+impl From<&str> for SimilarMode {
+    fn from(s: &str) -> Self {
+        match s.to_lowercase().as_str() {
+            "multi" | "multi-query" | "multi_query" => SimilarMode::MultiQuery,
+            _ => SimilarMode::Average,
+        }
+    }
+}
+
+/// A neighbouring file and the chunk that matched best.
+#[derive(Debug, Clone)]
+pub struct SimilarFile {
+    pub file_id: i64,
+    pub path: String,
+    pub score: f32,
+    pub chunk_id: Option<i64>,
+    pub payload: Option<serde_json::Value>,
+}
+
+/// Files similar to `file_id`, ranked by their best-matching chunk. The file itself is
+/// excluded in the Qdrant filter, on top of any caller-supplied `filter`. Enough hits are
+/// fetched for `limit` files, but every distinct file found is returned: callers that
+/// post-filter against the DB truncate to `limit` afterwards.
+pub async fn similar_files(
+    client: &QdrantClient,
+    file_id: i64,
+    vectors: Vec<Vec<f32>>,
+    mode: SimilarMode,
+    limit: u64,
+    filter: Option<serde_json::Value>,
+) -> anyhow::Result<Vec<SimilarFile>> {
+    let filter = exclude_file(filter, file_id);
+    let queries = match mode {
+        SimilarMode::Average => average_vector(&vectors).into_iter().collect(),
+        SimilarMode::MultiQuery => sample_evenly(vectors, MAX_QUERY_CHUNKS),
+    };
+    // Neighbour chunks cluster in a few files; over-fetch so `limit` distinct files survive.
+    let per_query = limit.max(1) * 8;
+    let mut hits = Vec::new();
+    for q in queries {
+        hits.extend(vector_search(client, q, per_query, Some(filter.clone())).await?);
+    }
+    Ok(aggregate_by_file(hits))
+}
+
+/// Normalised mean of `vectors`; `None` when empty or dimensions disagree.
+pub fn average_vector(vectors: &[Vec<f32>]) -> Option<Vec<f32>> {
+    let dim = vectors.first()?.len();
+    if dim == 0 || vectors.iter().any(|v| v.len() != dim) {
+        return None;
+    }
+    let mut mean = vec![0f32; dim];
+    for v in vectors {
+        for (m, x) in mean.iter_mut().zip(v) {
+            *m += x;
+        }
+    }
+    let norm = mean.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        mean.iter_mut().for_each(|x| *x /= norm);
+    }
+    Some(mean)
+}
+
+/// Collapses chunk hits to one entry per file (its best chunk), highest score first.
+pub fn aggregate_by_file(hits: Vec<SearchResult>) -> Vec<SimilarFile> {
+    let mut best: HashMap<i64, SimilarFile> = HashMap::new();
+    for hit in hits {
+        let Some(payload) = hit.payload else {
+            continue;
+        };
+        let Some(file_id) = payload.get("file_id").and_then(|v| v.as_i64()) else {
+            continue;
+        };
+        let path = payload
+            .get("path")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string();
+        let chunk_id = payload.get("chunk_id").and_then(|v| v.as_i64());
+        let candidate = SimilarFile {
+            file_id,
+            path,
+            score: hit.score,
+            chunk_id,
+            payload: Some(payload),
+        };
+        match best.get(&file_id) {
+            Some(existing) if existing.score >= candidate.score => {}
+            _ => {
+                best.insert(file_id, candidate);
+            }
+        }
+    }
+    let mut files: Vec<SimilarFile> = best.into_values().collect();
+    files.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(a.file_id.cmp(&b.file_id))
+    });
+    files
+}
+
+fn sample_evenly(vectors: Vec<Vec<f32>>, max: usize) -> Vec<Vec<f32>> {
+    if vectors.len() <= max {
+        return vectors;
+    }
+    let step = vectors.len() as f64 / max as f64;
+    (0..max)
+        .map(|i| vectors[(i as f64 * step) as usize].clone())
+        .collect()
+}
+
+fn exclude_file(filter: Option<serde_json::Value>, file_id: i64) -> serde_json::Value {
+    let mut filter = match filter {
+        Some(serde_json::Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    let self_match = serde_json::json!({ "key": "file_id", "match": { "value": file_id } });
+    match filter.get_mut("must_not").and_then(|v| v.as_array_mut()) {
+        Some(list) => list.push(self_match),
+        None => {
+            filter.insert("must_not".into(), serde_json::json!([self_match]));
+        }
+    }
+    serde_json::Value::Object(filter)
+}
@@ -1,9 +1,17 @@
 use storage::models::Rule as DbRule;
+use crate::bundles::{self, BundleKind};
 use crate::rules::{self, Rule, RuleContext};
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{FromRow, Row};
 use sqlx::SqlitePool;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, FromRow)]
+struct DirRow {
+    id: i64,
+    path: String,
+}
 
 #[derive(Debug, FromRow)]
 struct FileWithTags {
@@ -35,14 +43,59 @@ pub async fn run_suggester(pool: &SqlitePool) -> anyhow::Result<()> {
         })
         .collect();
 
-    // 2. Fetch files that don't have a planned 'move' or 'rename' action
+    // 2. Directories first: a folder matched as a whole (a project holding a Cargo.toml, say)
+    // moves in one piece, and nothing inside it is planned on its own.
+    let mut moving_dirs: Vec<String> = sqlx::query_scalar(
+        "SELECT d.path FROM actions a JOIN directories d ON d.id = a.dir_id WHERE a.status = 'planned' AND a.kind = 'move_dir'",
+    )
+    .fetch_all(pool)
+    .await?;
+    let dirs = sqlx::query_as::<_, DirRow>("SELECT id, path FROM directories ORDER BY path")
+        .fetch_all(pool)
+        .await?;
+    for dir in dirs {
+        let path = PathBuf::from(&dir.path);
+        if moving_dirs.iter().any(|m| path.starts_with(m)) {
+            continue;
+        }
+        let ctx = RuleContext {
+            path: &path,
+            mime: None,
+            ext: None,
+            tags: &[],
+            is_dir: true,
+        };
+        // Only moves apply to directories; the highest-priority one wins.
+        let to = rules::evaluate(&rules, &ctx)
+            .into_iter()
+            .flat_map(|r| &r.actions)
+            .find_map(|a| match a {
+                rules::Action::Move { to } => Some(to),
+                _ => None,
+            });
+        let (Some(to), Some(name)) = (to, path.file_name()) else {
+            continue;
+        };
+        let target = Path::new(to).join(name);
+        if target == path {
+            continue;
+        }
+        sqlx::query("INSERT INTO actions (dir_id, kind, payload_json, status) VALUES (?, 'move_dir', ?, 'planned')")
+            .bind(dir.id)
+            .bind(serde_json::json!({ "to": target.to_string_lossy() }).to_string())
+            .execute(pool)
+            .await?;
+        moving_dirs.push(dir.path);
+    }
+
+    // 3. Fetch files that don't have a planned 'move' or 'rename' action
     let files_to_process = sqlx::query_as::<_, FileWithTags>(
         r#"
         SELECT f.id, f.path, f.mime, f.ext, GROUP_CONCAT(t.name) as tags
         FROM files f
         LEFT JOIN file_tags ft ON f.id = ft.file_id
         LEFT JOIN tags t ON ft.tag_id = t.id
-        WHERE f.id NOT IN (
+        WHERE f.status != 'missing' AND f.id NOT IN (
             SELECT file_id FROM actions WHERE status = 'planned' AND (kind = 'move' OR kind = 'rename')
         )
         GROUP BY f.id
@@ -51,8 +104,37 @@ pub async fn run_suggester(pool: &SqlitePool) -> anyhow::Result<()> {
     .fetch_all(pool)
     .await?;
 
+    // Bundle members never move on their own: folder bundles only move whole, and the rest of a
+    // web page or sidecar group follows its primary file.
+    let mut bundled: HashMap<String, (BundleKind, String)> = HashMap::new();
+    let mut companions: HashMap<String, Vec<(i64, String)>> = HashMap::new();
+    for row in sqlx::query(
+        "SELECT f.id, f.path, b.kind, b.root FROM bundle_members m
+         JOIN files f ON f.id = m.file_id JOIN bundles b ON b.id = m.bundle_id",
+    )
+    .fetch_all(pool)
+    .await?
+    {
+        let (id, path, root): (i64, String, String) = (row.get(0), row.get(1), row.get(3));
+        let kind = BundleKind::from(row.get::<String, _>(2).as_str());
+        if !kind.is_folder() && path != root {
+            companions
+                .entry(root.clone())
+                .or_default()
+                .push((id, path.clone()));
+        }
+        bundled.insert(path, (kind, root));
+    }
+
     for file in files_to_process {
         let path = PathBuf::from(&file.path);
+        if moving_dirs.iter().any(|m| path.starts_with(m)) {
+            continue;
+        }
+        let moves_alone = match bundled.get(&file.path) {
+            Some((kind, root)) => !kind.is_folder() && *root == file.path,
+            None => true,
+        };
         let tags: Vec<String> = file
             .tags
             .map(|s| s.split(',').map(String::from).collect())
@@ -63,11 +145,20 @@ pub async fn run_suggester(pool: &SqlitePool) -> anyhow::Result<()> {
             mime: file.mime.as_deref(),
             ext: file.ext.as_deref(),
             tags: &tags,
+            is_dir: false,
         };
 
-        // 3. Evaluate rules and get suggestions
+        // 4. Evaluate rules and get suggestions
         for matched_rule in rules::evaluate(&rules, &ctx) {
             for action in &matched_rule.actions {
+                let relocate_to = match action {
+                    rules::Action::Move { to } => Some(to),
+                    rules::Action::Rename { template } => Some(template),
+                    rules::Action::Tag { .. } => None,
+                };
+                if relocate_to.is_some() && !moves_alone {
+                    continue;
+                }
                 let (kind, payload) = match action {
                     rules::Action::Move { to } => ("move", serde_json::json!({ "to": to })),
                     rules::Action::Tag { tag } => ("tag", serde_json::json!({ "tag": tag })),
@@ -76,7 +167,7 @@ pub async fn run_suggester(pool: &SqlitePool) -> anyhow::Result<()> {
                     }
                 };
 
-                // 4. Store action in DB
+                // 5. Store action in DB
                 sqlx::query(
                     r#"
                     INSERT INTO actions (file_id, kind, payload_json, status)
@@ -88,6 +179,28 @@ pub async fn run_suggester(pool: &SqlitePool) -> anyhow::Result<()> {
                 .bind(payload.to_string())
                 .execute(pool)
                 .await?;
+
+                // The rest of a web page or sidecar group follows its primary file.
+                let (Some(to), Some(members)) = (relocate_to, companions.get(&file.path)) else {
+                    continue;
+                };
+                for (member_id, member) in members {
+                    let Some(dest) =
+                        bundles::companion_destination(&path, Path::new(member), Path::new(to))
+                    else {
+                        continue;
+                    };
+                    sqlx::query(
+                        "INSERT INTO actions (file_id, kind, payload_json, status)
+                         SELECT ?1, ?2, ?3, 'planned' WHERE NOT EXISTS (
+                             SELECT 1 FROM actions WHERE file_id = ?1 AND status = 'planned' AND (kind = 'move' OR kind = 'rename'))",
+                    )
+                    .bind(member_id)
+                    .bind(kind)
+                    .bind(serde_json::json!({ "to": dest.to_string_lossy(), "bundle": file.path }).to_string())
+                    .execute(pool)
+                    .await?;
+                }
             }
         }
     }
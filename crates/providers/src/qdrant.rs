@@ -161,6 +161,41 @@ impl QdrantClient {
         Ok(())
     }
 
+    /// Overwrites the given payload keys of every point matching `filter`, keeping the vectors.
+    pub async fn set_payload(
+        &self,
+        filter: serde_json::Value,
+        payload: serde_json::Value,
+    ) -> Result<(), ProviderError> {
+        #[derive(Serialize)]
+        struct SetPayload {
+            payload: serde_json::Value,
+            filter: serde_json::Value,
+        }
+        let url = format!(
+            "{}/collections/{}/points/payload",
+            self.cfg.url, self.cfg.collection
+        );
+        let body = SetPayload { payload, filter };
+        let mut builder = self.client.post(url).json(&body);
+        if let Some(key) = &self.cfg.api_key {
+            builder = builder.header("api-key", key);
+        }
+        let resp = builder
+            .send()
+            .await
+            .map_err(|e| ProviderError::RequestFailed(e.to_string()))?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.bytes().await.unwrap_or(Bytes::from_static(b""));
+            return Err(ProviderError::RequestFailed(format!(
+                "status {} body {:?}",
+                status, body
+            )));
+        }
+        Ok(())
+    }
+
     pub async fn delete_by_ids(&self, ids: &[String]) -> Result<(), ProviderError> {
         #[derive(Serialize)]
         struct DeletePoints {
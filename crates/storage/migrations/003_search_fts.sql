@@ -0,0 +1,26 @@
+-- FTS5 keyword index over file names, tags, paths and chunk text: one row per chunk,
+-- or a single row with empty content for files without extracted text.
+CREATE VIRTUAL TABLE IF NOT EXISTS search_fts USING fts5(
+    name,
+    tags,
+    path,
+    content,
+    file_id UNINDEXED,
+    chunk_id UNINDEXED,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+-- Backfill from existing rows; the name is the path after the last `/` or `\`. A file's rows
+-- take rowids `(file_id << 20) + n` so they can be deleted by rowid range (see `fts.rs`).
+INSERT INTO search_fts (rowid, name, tags, path, content, file_id, chunk_id)
+SELECT
+    (f.id << 20) + ROW_NUMBER() OVER (PARTITION BY f.id ORDER BY c.start) - 1,
+    substr(f.path, length(rtrim(f.path, replace(replace(f.path, '/', ''), '\', ''))) + 1),
+    COALESCE((SELECT group_concat(t.name, ' ') FROM file_tags ft JOIN tags t ON t.id = ft.tag_id WHERE ft.file_id = f.id), ''),
+    f.path,
+    COALESCE(c.text_preview, ''),
+    f.id,
+    c.id
+FROM files f
+LEFT JOIN chunks c ON c.file_id = f.id
+ORDER BY f.id, c.start;
@@ -0,0 +1,12 @@
+-- Per-file fingerprints for near-duplicate detection: a MinHash signature for text
+-- (64 little-endian u32 values) or aHash/dHash/pHash bits for images.
+CREATE TABLE IF NOT EXISTS file_fingerprints (
+    file_id INTEGER PRIMARY KEY,
+    kind TEXT NOT NULL,
+    minhash BLOB,
+    ahash INTEGER,
+    dhash INTEGER,
+    phash INTEGER,
+    updated_at INTEGER NOT NULL DEFAULT (strftime('%s','now')),
+    FOREIGN KEY(file_id) REFERENCES files(id) ON DELETE CASCADE
+);
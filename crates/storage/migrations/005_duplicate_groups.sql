@@ -0,0 +1,21 @@
+-- Exact-duplicate groups: every file sharing a full hash, with the survivor picked by policy.
+CREATE TABLE IF NOT EXISTS duplicate_groups (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    full_hash TEXT NOT NULL UNIQUE,
+    size INTEGER,
+    survivor_file_id INTEGER,
+    policy TEXT NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s','now')),
+    updated_at INTEGER NOT NULL DEFAULT (strftime('%s','now')),
+    FOREIGN KEY(survivor_file_id) REFERENCES files(id) ON DELETE SET NULL
+);
+
+CREATE TABLE IF NOT EXISTS duplicate_group_members (
+    group_id INTEGER NOT NULL,
+    file_id INTEGER NOT NULL,
+    PRIMARY KEY(group_id, file_id),
+    FOREIGN KEY(group_id) REFERENCES duplicate_groups(id) ON DELETE CASCADE,
+    FOREIGN KEY(file_id) REFERENCES files(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_duplicate_group_members_file ON duplicate_group_members(file_id);
@@ -0,0 +1,28 @@
+-- Write-ahead journal for apply: a 'pending' row is written before each filesystem operation and
+-- marked 'done' in the same transaction as the DB changes that follow it. Rows still pending at
+-- startup whose process is gone are reconciled against the filesystem ('done', 'rolled_back' or
+-- 'failed').
+CREATE TABLE IF NOT EXISTS apply_journal (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    action_id INTEGER,
+    kind TEXT NOT NULL,
+    src TEXT NOT NULL,
+    dest TEXT,
+    strategy TEXT,
+    pid INTEGER,
+    state TEXT NOT NULL DEFAULT 'pending',
+    detail TEXT,
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s','now')),
+    updated_at INTEGER NOT NULL DEFAULT (strftime('%s','now')),
+    FOREIGN KEY(action_id) REFERENCES actions(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_apply_journal_state ON apply_journal(state);
+CREATE INDEX IF NOT EXISTS idx_apply_journal_action ON apply_journal(action_id);
+
+-- At most one process applies (or recovers) at a time; a row whose process is gone is stale.
+CREATE TABLE IF NOT EXISTS apply_lock (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    pid INTEGER NOT NULL,
+    acquired_at INTEGER NOT NULL DEFAULT (strftime('%s','now'))
+);
@@ -0,0 +1,15 @@
+-- One row per `apply` run; actions record the session that executed them so a whole run can be
+-- listed and undone together.
+CREATE TABLE IF NOT EXISTS apply_sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    started_at INTEGER NOT NULL DEFAULT (strftime('%s','now')),
+    finished_at INTEGER,
+    status TEXT NOT NULL DEFAULT 'running',
+    succeeded INTEGER NOT NULL DEFAULT 0,
+    failed INTEGER NOT NULL DEFAULT 0,
+    undone_at INTEGER
+);
+
+ALTER TABLE actions ADD COLUMN session_id INTEGER REFERENCES apply_sessions(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_actions_session ON actions(session_id);
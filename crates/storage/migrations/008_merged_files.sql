@@ -0,0 +1,17 @@
+-- Deleting a merged duplicate's `files` row cascades to its action, so the action, the row and its
+-- tags are snapshotted here first; undo re-inserts all three from the snapshot.
+CREATE TABLE IF NOT EXISTS merged_files (
+    action_id INTEGER PRIMARY KEY,
+    session_id INTEGER,
+    kind TEXT NOT NULL,
+    payload_json TEXT NOT NULL,
+    action_created_at INTEGER,
+    executed_at INTEGER,
+    backup_path TEXT,
+    path TEXT NOT NULL,
+    file_json TEXT NOT NULL,
+    tags_json TEXT NOT NULL DEFAULT '[]',
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s','now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_merged_files_session ON merged_files(session_id);
@@ -0,0 +1,16 @@
+-- Manifest of backups in the trash. Bytes live once per content hash under
+-- trash_dir/objects/; several entries may share one object. action_id has no foreign key because
+-- merge actions are deleted and later re-inserted by undo.
+CREATE TABLE IF NOT EXISTS trash_entries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    action_id INTEGER,
+    original_path TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    object_path TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s','now')),
+    restored_at INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS idx_trash_entries_hash ON trash_entries(hash);
+CREATE INDEX IF NOT EXISTS idx_trash_entries_created ON trash_entries(created_at);
@@ -0,0 +1,50 @@
+-- Directories seen by the scanner, so rules can match whole folders and `move_dir` actions can
+-- target them.
+CREATE TABLE IF NOT EXISTS directories (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    path TEXT NOT NULL UNIQUE,
+    mtime INTEGER NOT NULL,
+    first_seen INTEGER NOT NULL DEFAULT (strftime('%s','now')),
+    last_seen INTEGER NOT NULL DEFAULT (strftime('%s','now'))
+);
+
+-- An action now targets either a file or a directory, so actions is rebuilt with a nullable
+-- file_id. Migrations run in a transaction where foreign keys cannot be switched off: dropping
+-- the old table sets audit/apply_journal.action_id to NULL, so those links are saved and restored.
+CREATE TEMP TABLE saved_action_links AS
+    SELECT 'audit' AS tbl, id, action_id FROM audit WHERE action_id IS NOT NULL
+    UNION ALL
+    SELECT 'apply_journal', id, action_id FROM apply_journal WHERE action_id IS NOT NULL;
+
+CREATE TABLE actions_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    file_id INTEGER,
+    dir_id INTEGER,
+    kind TEXT NOT NULL,
+    payload_json TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'planned',
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s','now')),
+    executed_at INTEGER,
+    undo_token TEXT,
+    backup_path TEXT,
+    session_id INTEGER REFERENCES apply_sessions(id) ON DELETE SET NULL,
+    FOREIGN KEY(file_id) REFERENCES files(id) ON DELETE CASCADE,
+    FOREIGN KEY(dir_id) REFERENCES directories(id) ON DELETE CASCADE,
+    CHECK (file_id IS NOT NULL OR dir_id IS NOT NULL)
+);
+
+INSERT INTO actions_new (id, file_id, kind, payload_json, status, created_at, executed_at, undo_token, backup_path, session_id)
+    SELECT id, file_id, kind, payload_json, status, created_at, executed_at, undo_token, backup_path, session_id FROM actions;
+
+DROP TABLE actions;
+ALTER TABLE actions_new RENAME TO actions;
+
+UPDATE audit SET action_id = (SELECT action_id FROM saved_action_links s WHERE s.tbl = 'audit' AND s.id = audit.id)
+    WHERE id IN (SELECT id FROM saved_action_links WHERE tbl = 'audit');
+UPDATE apply_journal SET action_id = (SELECT action_id FROM saved_action_links s WHERE s.tbl = 'apply_journal' AND s.id = apply_journal.id)
+    WHERE id IN (SELECT id FROM saved_action_links WHERE tbl = 'apply_journal');
+DROP TABLE saved_action_links;
+
+CREATE INDEX IF NOT EXISTS idx_actions_status ON actions(status);
+CREATE INDEX IF NOT EXISTS idx_actions_session ON actions(session_id);
+CREATE INDEX IF NOT EXISTS idx_actions_dir ON actions(dir_id);
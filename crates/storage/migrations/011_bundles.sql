@@ -0,0 +1,20 @@
+-- Bundles: files that belong together (a VCS checkout, a project with a build manifest, a
+-- macOS-style package, a saved web page with its `_files` folder, a photo with its sidecars).
+-- `root` is the bundle's folder, or its primary file for web pages and sidecar groups. Recomputed
+-- as a whole by the suggest phase.
+CREATE TABLE IF NOT EXISTS bundles (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    root TEXT NOT NULL UNIQUE,
+    kind TEXT NOT NULL,
+    marker TEXT NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s','now'))
+);
+
+CREATE TABLE IF NOT EXISTS bundle_members (
+    file_id INTEGER PRIMARY KEY,
+    bundle_id INTEGER NOT NULL,
+    FOREIGN KEY(bundle_id) REFERENCES bundles(id) ON DELETE CASCADE,
+    FOREIGN KEY(file_id) REFERENCES files(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_bundle_members_bundle ON bundle_members(bundle_id);
@@ -74,7 +74,9 @@ pub mod models {
     #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
     pub struct Action {
         pub id: i64,
-        pub file_id: i64,
+        /// Set for file actions; `dir_id` is set instead for directory actions like `move_dir`.
+        pub file_id: Option<i64>,
+        pub dir_id: Option<i64>,
         pub kind: String,
         pub payload_json: String,
         pub status: String,
@@ -84,6 +86,15 @@ pub mod models {
         pub backup_path: Option<String>,
     }
 
+    #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+    pub struct Directory {
+        pub id: i64,
+        pub path: String,
+        pub mtime: i64,
+        pub first_seen: i64,
+        pub last_seen: i64,
+    }
+
     #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
     pub struct Audit {
         pub id: i64,